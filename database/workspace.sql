@@ -27,6 +27,12 @@ CREATE TABLE IF NOT EXISTS assets (
     mime text not null
 );
 
+CREATE TABLE IF NOT EXISTS asset_refs (
+    id text unique not null primary key, -- The hash of the URL the asset was fetched from
+    content text not null, -- The hash of the asset contents, used as the ID in the assets table and .assets directory
+    url text not null
+);
+
 CREATE TABLE IF NOT EXISTS files (
     path text unique not null primary key,
     checksum text unique not null,
@@ -34,3 +40,21 @@ CREATE TABLE IF NOT EXISTS files (
     ephemeral integer not null default false,
     buffer blob default null
 );
+
+CREATE TABLE IF NOT EXISTS tasks (
+    note text not null, -- The path of the note containing the task
+    offset integer not null, -- The byte offset of the task marker within the note, used to write changes back
+    line integer not null,
+    text text not null,
+    done integer not null default false,
+    due text default null, -- An ISO 8601 date, from `@due(2026-10-20)`
+    tags text not null default '', -- Space separated, with a leading and trailing space so a single tag can be matched with LIKE
+    primary key (note, offset)
+);
+
+CREATE TABLE IF NOT EXISTS plugin_grants (
+    plugin text not null, -- The name of the plugin, from its manifest
+    version text not null, -- The version of the plugin the scopes were granted to
+    scopes integer not null, -- The bits of the granted `PluginScopes`, which never include `AVOID_SANDBOXES`
+    primary key (plugin, version)
+);
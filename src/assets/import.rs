@@ -0,0 +1,102 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use ::image::{imageops::FilterType, DynamicImage, ImageError, ImageFormat, RgbaImage};
+use ::image::error::{ParameterError, ParameterErrorKind};
+use chrono::Local;
+use crate::storage::workspace::ImageImportFormat;
+use super::{sniff_mime, AssetError, AssetResult};
+
+/// File extensions of images which may be imported into a workspace
+pub const IMPORTABLE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "gif", "webp", "svg"];
+
+/// Returns whether the file at the given path is an image which can be imported
+pub fn is_importable(path: &Path) -> bool {
+    path.is_file() && path.extension()
+        .map(|ext| IMPORTABLE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Resizes and converts an imported image according to the workspace import settings,
+/// returning the resulting image data along with its mime type.
+///
+/// SVGs and GIFs are left untouched, as re-encoding them would lose their vectors or animation.
+pub fn prepare_image(bytes: Vec<u8>, max_width: u32, format: ImageImportFormat) -> AssetResult<(Vec<u8>, String)> {
+    let mime = sniff_mime(&bytes);
+
+    let source_format = match mime {
+        "image/png" => ImageFormat::Png,
+        "image/jpeg" => ImageFormat::Jpeg,
+        "image/webp" => ImageFormat::WebP,
+        _ => return Ok((bytes, mime.to_string())),
+    };
+
+    if max_width == 0 && format == ImageImportFormat::Original {
+        return Ok((bytes, mime.to_string()));
+    }
+
+    let mut img = ::image::load_from_memory_with_format(&bytes, source_format).map_err(AssetError::Image)?;
+    let resize = max_width > 0 && img.width() > max_width;
+
+    let target = match format {
+        // WebP images cannot be encoded, so they are stored as PNGs instead
+        ImageImportFormat::Original if source_format == ImageFormat::WebP && resize => ImageFormat::Png,
+        ImageImportFormat::Original => source_format,
+        ImageImportFormat::Png => ImageFormat::Png,
+        ImageImportFormat::Jpeg => ImageFormat::Jpeg,
+    };
+
+    if !resize && target == source_format {
+        return Ok((bytes, mime.to_string()));
+    }
+
+    if resize {
+        img = img.resize(max_width, u32::MAX, FilterType::Lanczos3);
+    }
+
+    // JPEG has no alpha channel
+    if target == ImageFormat::Jpeg {
+        img = DynamicImage::ImageRgb8(img.to_rgb8());
+    }
+
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, target).map_err(AssetError::Image)?;
+
+    Ok((out.into_inner(), target.to_mime_type().to_string()))
+}
+
+/// Encodes an image copied to the clipboard, which is handed over as raw RGBA pixels, as a PNG
+pub fn encode_clipboard_image(width: usize, height: usize, rgba: Vec<u8>) -> AssetResult<Vec<u8>> {
+    let img = RgbaImage::from_raw(width as u32, height as u32, rgba)
+        .ok_or(AssetError::Image(ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::DimensionMismatch))))?;
+
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, ImageFormat::Png).map_err(AssetError::Image)?;
+
+    Ok(out.into_inner())
+}
+
+/// Returns the directory images pasted from the clipboard are saved to until they have been imported
+pub fn paste_directory() -> PathBuf {
+    std::env::temp_dir().join("noot-paste")
+}
+
+/// Saves the image on the clipboard, eg: a screenshot, to a temporary file so that it can be imported like any other.
+/// Returns `None` if the clipboard does not hold an image
+pub fn save_clipboard_image() -> AssetResult<Option<PathBuf>> {
+    let image = match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_image()) {
+        Ok(image) => image,
+        Err(arboard::Error::ContentNotAvailable) => return Ok(None),
+        Err(err) => return Err(AssetError::Clipboard(err)),
+    };
+
+    let png = encode_clipboard_image(image.width, image.height, image.bytes.into_owned())?;
+
+    let directory = paste_directory();
+    std::fs::create_dir_all(&directory).map_err(AssetError::Io)?;
+
+    // The name of the file becomes the alt text of the image
+    let file = directory.join(format!("Pasted image {}.png", Local::now().format("%Y-%m-%d %H.%M.%S")));
+    std::fs::write(&file, png).map_err(AssetError::Io)?;
+
+    Ok(Some(file))
+}
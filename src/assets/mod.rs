@@ -1,36 +1,330 @@
+use std::cell::{Cell, RefCell};
 use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::{Instant, Duration};
+use crossbeam_queue::ArrayQueue;
+use iced::widget::{image, svg};
+use iced::window::Id;
+use url::Url;
+use rusqlite::{Connection, OptionalExtension};
+use tokio::sync::RwLock;
+use crate::runtime::GLOBAL_STATE;
+use crate::runtime::workers::{Job, JobType};
+use crate::utils::cryptography::hashing::{hash_bytes, hash_str};
+use crate::utils::cryptography::encryption::{decrypt, encrypt, generate_key, EncryptionError};
+
+pub mod import;
+
+pub type AssetResult<T> = Result<T, AssetError>;
+
+#[derive(Debug)]
+pub enum AssetError {
+    Database(rusqlite::Error),
+    Encryption(EncryptionError),
+    Image(::image::ImageError),
+    Clipboard(arboard::Error),
+    Io(std::io::Error),
+}
+
+lazy_static!(
+    static ref ASSET_LOCKS: Mutex<BTreeMap<String, Arc<RwLock<()>>>> = Mutex::new(BTreeMap::new());
+    static ref CACHE_INDEXES: Mutex<BTreeMap<Id, Arc<Mutex<CacheIndex>>>> = Mutex::new(BTreeMap::new());
+);
+
+/// The default lifetime (in seconds) of an asset cached with the `Memory` strategy
+pub const DEFAULT_MEMORY_TTL: u64 = 60 * 60;
+
+/// The default amount of memory (in bytes) that may be used by assets cached with the `Memory` strategy
+pub const DEFAULT_MEMORY_CAPACITY: u64 = 64 * 1024 * 1024;
 
 #[derive(Debug, Clone)]
 pub struct AssetManager {
     assets: BTreeMap<String, CacheEntry>,
+    blocked: BTreeMap<String, AssetRequest>,
+    failed: BTreeMap<String, String>,
+    /// Memory entries which were evicted to make room, kept so that they can be fetched again once they are needed
+    evicted: RefCell<BTreeMap<String, AssetRequest>>,
+    queue: Option<Arc<ArrayQueue<Job>>>,
+    ttl: u64,
+    capacity: u64,
+    /// Shared with the workers, which look assets up here before fetching them
+    index: Arc<Mutex<CacheIndex>>,
+}
+
+/// Counters describing how effective the asset cache has been.
+/// Hits and misses are counted whenever a worker looks an asset up before fetching it, not whenever it is rendered
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub expirations: u64,
+}
+
+impl CacheMetrics {
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+
+        if total == 0 {
+            0.
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
 }
 
 impl AssetManager {
     pub fn new() -> Self {
-        AssetManager { assets: BTreeMap::new() }
+        AssetManager {
+            assets: BTreeMap::new(),
+            blocked: BTreeMap::new(),
+            failed: BTreeMap::new(),
+            evicted: RefCell::new(BTreeMap::new()),
+            queue: None,
+            ttl: DEFAULT_MEMORY_TTL,
+            capacity: DEFAULT_MEMORY_CAPACITY,
+            index: Arc::new(Mutex::new(CacheIndex::default())),
+        }
+    }
+
+    /// Shares the memory entries with the workers fetching assets for the given editor window
+    pub fn attach(&mut self, window: Id) {
+        CACHE_INDEXES.lock().unwrap().insert(window, self.index.clone());
+    }
+
+    /// Sets the queue used to fetch evicted assets again when they are next needed
+    pub fn with_queue(mut self, queue: Arc<ArrayQueue<Job>>) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// Sets the lifetime and size limit applied to memory cached assets
+    pub fn configure(&mut self, ttl: u64, capacity: u64) {
+        self.ttl = ttl;
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Records an asset which was not fetched because of the workspace remote data strategy
+    pub fn block(&mut self, asset: AssetRequest) {
+        self.blocked.insert(asset.id.clone(), asset);
+    }
+
+    pub fn blocked(&self, id: &str) -> Option<&AssetRequest> {
+        self.blocked.get(id)
+    }
+
+    pub fn unblock(&mut self, id: &str) -> Option<AssetRequest> {
+        self.blocked.remove(id)
+    }
+
+    /// Removes and returns every blocked asset hosted on the given domain
+    pub fn unblock_domain(&mut self, domain: &str) -> Vec<AssetRequest> {
+        let ids: Vec<String> = self.blocked.values()
+            .filter(|b| b.domain() == domain)
+            .map(|b| b.id.clone())
+            .collect();
+
+        ids.iter().filter_map(|id| self.blocked.remove(id)).collect()
+    }
+
+    /// Records an asset which could not be loaded, along with the reason why
+    pub fn fail(&mut self, id: impl Into<String>, reason: impl Into<String>) {
+        let id = id.into();
+        self.evicted.get_mut().remove(&id);
+        self.index.lock().unwrap().expires.remove(&id);
+        self.failed.insert(id, reason.into());
+    }
+
+    pub fn failed(&self, id: &str) -> Option<&String> {
+        self.failed.get(id)
+    }
+
+    /// Inserts an asset which can be reloaded from the workspace at any time.
+    /// These entries never expire and are not counted against the memory capacity.
+    pub fn insert(&mut self, id: impl Into<String>, asset: Asset) {
+        let id = id.into();
+        self.record(&id);
+        self.assets.insert(id, CacheEntry::persistent(asset));
+    }
+
+    /// Inserts an asset which only lives in memory, applying the TTL and evicting
+    /// the least recently used memory entries if the capacity has been exceeded.
+    pub fn cache(&mut self, request: AssetRequest, asset: Asset) {
+        let id = request.id.clone();
+        let entry = CacheEntry::new(self.ttl, asset).with_origin(request);
+        self.record(&id);
+        self.index.lock().unwrap().expires.insert(id.clone(), entry.expires);
+        self.assets.insert(id, entry);
+        self.evict();
+    }
+
+    /// Clears any earlier failure or eviction of an asset which has now been delivered
+    fn record(&mut self, id: &str) {
+        self.failed.remove(id);
+        self.evicted.get_mut().remove(id);
+    }
+
+    /// Returns a cached asset. If the asset was evicted from memory, a job is queued to fetch it again
+    pub fn get(&self, id: &str) -> Option<&Asset> {
+        match self.assets.get(id) {
+            Some(entry) if entry.valid() => {
+                entry.touch();
+                Some(entry.extract())
+            }
+            _ => {
+                self.refetch(id);
+                None
+            }
+        }
+    }
+
+    /// Queues a job to fetch an evicted asset again, which only happens once per eviction
+    fn refetch(&self, id: &str) {
+        let Some(queue) = &self.queue else { return };
+        let Some(request) = self.evicted.borrow_mut().remove(id) else { return };
+
+        debug!("Fetching evicted asset '{}' again", id);
+        if queue.push(request.job(false)).is_err() {
+            error!("Failed to queue refetch for asset '{}'", id);
+            self.evicted.borrow_mut().insert(request.id.clone(), request);
+        }
+    }
+
+    pub fn metrics(&self) -> CacheMetrics {
+        self.index.lock().unwrap().metrics
+    }
+
+    /// The number of bytes currently held by memory cached assets
+    pub fn memory_usage(&self) -> u64 {
+        self.assets.values()
+            .filter(|entry| entry.expires.is_some())
+            .map(|entry| entry.extract().size as u64)
+            .sum()
+    }
+
+    /// Removes expired entries, returning the requests needed to fetch them again
+    pub fn purge_expired(&mut self) -> Vec<AssetRequest> {
+        let expired: Vec<String> = self.assets.iter()
+            .filter(|(_, entry)| !entry.valid())
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        if expired.is_empty() {
+            return vec![];
+        }
+
+        let mut index = self.index.lock().unwrap();
+        index.metrics.expirations += expired.len() as u64;
+
+        for id in &expired {
+            index.expires.remove(id);
+        }
+
+        info!("Expired {} cached assets ({:?})", expired.len(), index.metrics);
+        drop(index);
+
+        expired.iter()
+            .filter_map(|id| self.assets.remove(id))
+            .filter_map(|entry| entry.origin)
+            .collect()
+    }
+
+    /// Evicts the least recently used memory entries until the cache fits within its capacity
+    fn evict(&mut self) {
+        let mut usage = self.memory_usage();
+
+        while usage > self.capacity {
+            let lru = self.assets.iter()
+                .filter(|(_, entry)| entry.expires.is_some())
+                .min_by_key(|(_, entry)| entry.last_accessed.get())
+                .map(|(id, _)| id.clone());
+
+            let Some(id) = lru else { break };
+
+            if let Some(entry) = self.assets.remove(&id) {
+                debug!("Evicting asset '{}' from memory", id);
+                usage -= entry.extract().size as u64;
+
+                let mut index = self.index.lock().unwrap();
+                index.expires.remove(&id);
+                index.metrics.evictions += 1;
+                drop(index);
+
+                if let Some(origin) = entry.origin {
+                    self.evicted.get_mut().insert(id, origin);
+                }
+            }
+        }
+    }
+}
+
+/// When each memory entry of an asset manager expires, along with the metrics of its cache
+#[derive(Debug, Default)]
+pub struct CacheIndex {
+    expires: BTreeMap<String, Option<Instant>>,
+    metrics: CacheMetrics,
+}
+
+impl CacheIndex {
+    /// Looks an asset up before it is fetched, counting a hit if it is still cached in memory or a miss if it has to be fetched
+    pub fn lookup(&mut self, id: &str) -> bool {
+        let hit = self.expires.get(id).is_some_and(|expires| expires.is_none_or(|expires| Instant::now() < expires));
+
+        if hit {
+            self.metrics.hits += 1;
+        } else {
+            self.metrics.misses += 1;
+        }
+
+        hit
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct CacheEntry {
-    pub expires: Instant,
+    /// When the entry stops being valid, or `None` if it never expires
+    pub expires: Option<Instant>,
+    last_accessed: Cell<Instant>,
+    origin: Option<AssetRequest>,
     internal: Asset,
 }
 
 impl CacheEntry {
     pub fn new(expires: u64, asset: Asset) -> Self {
         let now = Instant::now();
-        let future = now.checked_add(Duration::from_secs(expires)).unwrap();
+        let future = now.checked_add(Duration::from_secs(expires));
 
         CacheEntry {
             expires: future,
+            last_accessed: Cell::new(now),
+            origin: None,
+            internal: asset
+        }
+    }
+
+    pub fn persistent(asset: Asset) -> Self {
+        CacheEntry {
+            expires: None,
+            last_accessed: Cell::new(Instant::now()),
+            origin: None,
             internal: asset
         }
     }
 
+    /// Records where the asset came from, so that it can be fetched again once it expires
+    pub fn with_origin(mut self, origin: AssetRequest) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
     pub fn valid(&self) -> bool {
-        self.expires <= Instant::now()
+        self.expires.map(|expires| Instant::now() < expires).unwrap_or(true)
+    }
+
+    pub fn touch(&self) {
+        self.last_accessed.set(Instant::now());
     }
 
     pub fn extract(&self) -> &Asset {
@@ -40,16 +334,286 @@ impl CacheEntry {
 
 
 
+/// Everything needed to (re)fetch an asset, used when an asset is blocked or expires from memory
+#[derive(Clone, Debug)]
+pub struct AssetRequest {
+    pub id: String,
+    pub url: Url,
+    pub workspace: String,
+    pub path: PathBuf,
+    pub window: Id,
+    pub buffer: String,
+}
+
+impl AssetRequest {
+    pub fn domain(&self) -> String {
+        self.url.host_str().unwrap_or_default().to_string()
+    }
+
+    /// Builds a job which retries caching this asset.
+    /// When `bypass` is set, the remote data strategy is ignored for this single fetch.
+    pub fn job(&self, bypass: bool) -> Job {
+        Job::new(JobType::CacheAsset(self.workspace.clone(), self.path.clone(), self.url.clone(), self.window, self.buffer.clone(), bypass))
+    }
+}
+
+/// The choices offered to the user when a remote asset has been blocked
+#[derive(Clone, Copy, Debug)]
+pub enum AssetPermission {
+    /// Fetch this asset a single time without changing the workspace settings
+    Once,
+    /// Add the asset's domain to the workspace allowlist
+    Domain,
+}
+
 #[derive(Clone, Debug)]
 pub struct Asset {
     pub kind: String,
     pub name: String,
+    /// The size of the asset in bytes
+    pub size: usize,
     pub data: AssetData
 }
 
+impl Asset {
+    /// Builds a renderable asset from raw bytes, using the MIME type to pick the correct image handle
+    pub fn from_bytes(name: impl Into<String>, mime: impl Into<String>, bytes: Vec<u8>) -> Self {
+        let kind = mime.into();
+        let size = bytes.len();
+
+        let data = if kind == "image/svg+xml" {
+            AssetData::Svg(svg::Handle::from_memory(bytes))
+        } else {
+            AssetData::Image(image::Handle::from_bytes(bytes))
+        };
+
+        Self {
+            kind,
+            name: name.into(),
+            size,
+            data,
+        }
+    }
+}
+
 
 #[derive(Clone, Debug)]
 pub enum AssetData {
     Path(String),
-    Raw(Vec<u8>)
-}
\ No newline at end of file
+    Raw(Vec<u8>),
+    /// A decoded raster image, ready to be rendered
+    Image(image::Handle),
+    /// A decoded vector image, ready to be rendered
+    Svg(svg::Handle),
+}
+
+
+/// Makes a best effort guess at the MIME type of an asset by inspecting its contents
+pub fn sniff_mime(bytes: &[u8]) -> &'static str {
+    if bytes.starts_with(b"\x89PNG") {
+        "image/png"
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        "image/jpeg"
+    } else if bytes.starts_with(b"GIF8") {
+        "image/gif"
+    } else if bytes.len() > 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        "image/webp"
+    } else if String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).contains("<svg") {
+        "image/svg+xml"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+
+/// Returns the key used to encrypt blob assets for the given workspace.
+///
+/// Keys are kept in the process database rather than `workspace.db`, so that copying
+/// a workspace directory does not also copy the means to decrypt its assets.
+pub fn workspace_key(workspace: &str) -> Vec<u8> {
+    let key_name = format!("workspace.{}.asset-key", workspace);
+    let mut state = GLOBAL_STATE.lock().unwrap();
+
+    if let Some(key) = state.store.get_setting::<Vec<u8>>(&key_name) {
+        return key.value;
+    }
+
+    info!("Generating asset encryption key for workspace {}", workspace);
+    let key = generate_key();
+    state.store.set_setting(key_name, key.clone());
+    key
+}
+
+/// Returns the lock guarding the stored assets of a workspace.
+///
+/// Jobs which store an asset hold it for reading until the asset is referenced, both from `asset_refs` and from the
+/// note when importing. Garbage collection holds it for writing, so that it never sees an asset which has been stored
+/// but is not referenced yet.
+pub fn asset_lock(workspace: &str) -> Arc<RwLock<()>> {
+    ASSET_LOCKS.lock().unwrap().entry(workspace.to_string()).or_default().clone()
+}
+
+/// Returns the memory entries of the asset manager belonging to an editor window, if it has one
+pub fn cache_index(window: Id) -> Option<Arc<Mutex<CacheIndex>>> {
+    CACHE_INDEXES.lock().unwrap().get(&window).cloned()
+}
+
+/// Returns the ID used to reference the asset found at the given source.
+/// URLs are normalised first, so that equivalent URLs resolve to the same asset.
+/// Assets imported into the workspace are already addressed by their content, so their ID is taken from the URL.
+pub fn asset_id(src: &str) -> String {
+    match Url::parse(src) {
+        Ok(url) if url.scheme() == "noot" && url.path().starts_with("/assets/") => url.path().trim_start_matches("/assets/").to_string(),
+        Ok(url) => hash_str(url.to_string()),
+        Err(_) => hash_str(src),
+    }
+}
+
+/// Resolves the `src` of an image against the URL of the note it was found in, so that
+/// relative paths such as `./diagram.png` point to files beside the note.
+/// Returns `None` if the source is malformed.
+pub fn resolve_source(src: &str, base: &Url) -> Option<Url> {
+    let src = src.trim();
+
+    if src.is_empty() {
+        return None;
+    }
+
+    match Url::parse(src) {
+        Ok(url) => Some(url),
+        Err(url::ParseError::RelativeUrlWithoutBase) => base.join(src).ok(),
+        Err(_) => None,
+    }
+}
+
+/// Returns the canonical path of a local file referenced by a note, or `None` if it is not within the workspace.
+/// Symbolic links are followed first, so that a link within the workspace cannot reach files outside of it
+pub fn workspace_file(root: &Path, file: &Path) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let file = file.canonicalize().ok()?;
+
+    file.starts_with(root).then_some(file)
+}
+
+/// Returns the `noot://` URL which references an asset stored within a workspace
+pub fn local_asset_url(workspace: &str, id: &str) -> Url {
+    Url::parse(&format!("noot://{}/assets/{}", workspace, id)).unwrap()
+}
+
+/// Returns the content address of an asset, which is used to deduplicate stored assets
+pub fn content_id(data: &[u8]) -> String {
+    hash_bytes(data)
+}
+
+/// Records that the asset with the given ID is stored under the given content address
+pub fn link_asset(db: &Connection, id: &str, content: &str, url: &Url) -> AssetResult<()> {
+    db.execute(
+        "INSERT INTO asset_refs (id, content, url) VALUES (?1, ?2, ?3) ON CONFLICT (id) DO UPDATE SET content = ?2, url = ?3",
+        (id, content, url.as_str())
+    ).map_err(AssetError::Database)?;
+
+    Ok(())
+}
+
+/// Looks up the content address of an asset ID
+pub fn resolve_asset(db: &Connection, id: &str) -> Option<String> {
+    db.query_row("SELECT content FROM asset_refs WHERE id = ?", [id], |r| r.get(0))
+        .optional()
+        .unwrap_or_else(|e| {
+            error!("Failed to resolve asset '{}': {}", id, e);
+            None
+        })
+}
+
+/// Encrypts an asset and stores it within the `assets` table of a workspace database.
+/// Content which is already stored is left untouched.
+pub fn store_blob(db: &Connection, key: &[u8], content: &str, mime: &str, data: &[u8]) -> AssetResult<()> {
+    let exists: bool = db.query_row("SELECT EXISTS(SELECT 1 FROM assets WHERE id = ?)", [content], |r| r.get(0))
+        .map_err(AssetError::Database)?;
+
+    if exists {
+        debug!("Asset content '{}' is already stored", content);
+        return Ok(());
+    }
+
+    let encrypted = encrypt(key, data).map_err(AssetError::Encryption)?;
+
+    db.execute(
+        "INSERT INTO assets (id, data, mime) VALUES (?1, ?2, ?3)",
+        (content, encrypted, mime)
+    ).map_err(AssetError::Database)?;
+
+    Ok(())
+}
+
+/// Loads and decrypts an asset from the `assets` table of a workspace database
+pub fn load_blob(db: &Connection, key: &[u8], id: &str) -> Option<Asset> {
+    let content = resolve_asset(db, id).unwrap_or(id.to_string());
+
+    let row = db.query_row("SELECT data, mime FROM assets WHERE id = ?", [&content], |r| {
+        Ok((r.get::<_, Vec<u8>>(0)?, r.get::<_, String>(1)?))
+    }).optional();
+
+    match row {
+        Ok(Some((data, mime))) => {
+            match decrypt(key, &data) {
+                Ok(bytes) => Some(Asset::from_bytes(id, mime, bytes)),
+                Err(e) => {
+                    error!("Failed to decrypt asset '{}': {:?}", id, e);
+                    None
+                }
+            }
+        }
+        Ok(None) => None,
+        Err(e) => {
+            error!("Failed to load asset '{}': {}", id, e);
+            None
+        }
+    }
+}
+
+/// A summary of the storage used by the cached assets of a workspace
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AssetUsage {
+    pub blob_count: u64,
+    pub blob_bytes: u64,
+    pub disk_count: u64,
+    pub disk_bytes: u64,
+}
+
+impl AssetUsage {
+    pub fn total_bytes(&self) -> u64 {
+        self.blob_bytes + self.disk_bytes
+    }
+}
+
+/// Measures the storage used by the assets of a workspace, both in `workspace.db` and on disk
+pub fn asset_usage(db: &Connection, asset_dir: &Path) -> AssetUsage {
+    let mut usage = AssetUsage::default();
+
+    let blobs = db.query_row("SELECT COUNT(*), COALESCE(SUM(LENGTH(data)), 0) FROM assets", (), |r| {
+        Ok((r.get::<_, u64>(0)?, r.get::<_, u64>(1)?))
+    });
+
+    match blobs {
+        Ok((count, bytes)) => {
+            usage.blob_count = count;
+            usage.blob_bytes = bytes;
+        }
+        Err(e) => error!("Failed to measure blob assets: {}", e)
+    }
+
+    if let Ok(entries) = std::fs::read_dir(asset_dir) {
+        for metadata in entries.flatten().filter_map(|entry| entry.metadata().ok()) {
+            if metadata.is_file() {
+                usage.disk_count += 1;
+                usage.disk_bytes += metadata.len();
+            }
+        }
+    }
+
+    usage
+}
+
+#[cfg(test)]
+mod tests;
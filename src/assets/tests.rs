@@ -0,0 +1,162 @@
+//! Tests for the expiry, eviction and metrics of the asset cache, and for which local files notes may show.
+
+use nanoid::nanoid;
+use super::*;
+
+fn asset(name: &str, size: usize) -> Asset {
+    Asset {
+        kind: "image/png".to_string(),
+        name: name.to_string(),
+        size,
+        data: AssetData::Raw(vec![0; size]),
+    }
+}
+
+fn request(id: &str) -> AssetRequest {
+    AssetRequest {
+        id: id.to_string(),
+        url: Url::parse(&format!("https://example.com/{}.png", id)).unwrap(),
+        workspace: "workspace".to_string(),
+        path: PathBuf::from("note.md"),
+        window: Id::unique(),
+        buffer: "buffer".to_string(),
+    }
+}
+
+#[test]
+fn expired_entries_are_purged_and_fetched_again() {
+    let mut assets = AssetManager::new();
+    assets.configure(0, DEFAULT_MEMORY_CAPACITY);
+    assets.cache(request("a"), asset("a", 4));
+
+    assert!(assets.get("a").is_none());
+
+    let refetch = assets.purge_expired();
+    assert_eq!(refetch.iter().map(|r| r.id.as_str()).collect::<Vec<_>>(), ["a"]);
+    assert_eq!(assets.metrics().expirations, 1);
+    assert!(assets.purge_expired().is_empty());
+}
+
+#[test]
+fn entries_within_their_ttl_are_kept() {
+    let mut assets = AssetManager::new();
+    assets.cache(request("a"), asset("a", 4));
+
+    assert!(assets.get("a").is_some());
+    assert!(assets.purge_expired().is_empty());
+}
+
+#[test]
+fn persistent_entries_never_expire_or_count_towards_capacity() {
+    let mut assets = AssetManager::new();
+    assets.configure(0, 1);
+    assets.insert("a", asset("a", 4));
+
+    assert!(assets.get("a").is_some());
+    assert!(assets.purge_expired().is_empty());
+    assert_eq!(assets.memory_usage(), 0);
+}
+
+#[test]
+fn evicts_the_least_recently_used_entry() {
+    let mut assets = AssetManager::new();
+    assets.configure(DEFAULT_MEMORY_TTL, 8);
+    assets.cache(request("a"), asset("a", 4));
+    std::thread::sleep(Duration::from_millis(2));
+    assets.cache(request("b"), asset("b", 4));
+    std::thread::sleep(Duration::from_millis(2));
+
+    // Rendering `a` makes `b` the least recently used entry
+    assets.get("a");
+    assets.cache(request("c"), asset("c", 4));
+
+    assert!(assets.get("a").is_some());
+    assert!(assets.get("b").is_none());
+    assert!(assets.get("c").is_some());
+    assert_eq!(assets.memory_usage(), 8);
+    assert_eq!(assets.metrics().evictions, 1);
+}
+
+#[test]
+fn evicted_entries_are_queued_again_when_next_needed() {
+    let queue = Arc::new(ArrayQueue::new(8));
+    let mut assets = AssetManager::new().with_queue(queue.clone());
+    assets.configure(DEFAULT_MEMORY_TTL, 4);
+    assets.cache(request("a"), asset("a", 4));
+    assets.cache(request("b"), asset("b", 4));
+
+    assert!(queue.is_empty());
+    assert!(assets.get("a").is_none());
+    assert_eq!(queue.len(), 1);
+
+    // The fetch is already queued, so rendering again does not queue another
+    assert!(assets.get("a").is_none());
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn rendering_does_not_count_towards_metrics() {
+    let mut assets = AssetManager::new();
+    assets.cache(request("a"), asset("a", 4));
+
+    for _ in 0..10 {
+        assets.get("a");
+        assets.get("missing");
+    }
+
+    let metrics = assets.metrics();
+    assert_eq!((metrics.hits, metrics.misses), (0, 0));
+}
+
+#[test]
+fn lookups_count_cached_assets_as_hits() {
+    let window = Id::unique();
+    let mut assets = AssetManager::new();
+    assets.attach(window);
+    assets.cache(request("a"), asset("a", 4));
+
+    let index = cache_index(window).unwrap();
+    assert!(index.lock().unwrap().lookup("a"));
+    assert!(!index.lock().unwrap().lookup("b"));
+
+    let metrics = assets.metrics();
+    assert_eq!((metrics.hits, metrics.misses), (1, 1));
+    assert_eq!(metrics.hit_rate(), 0.5);
+}
+
+#[test]
+fn lookups_miss_once_entries_are_evicted_or_expired() {
+    let window = Id::unique();
+    let mut assets = AssetManager::new();
+    assets.attach(window);
+    assets.configure(DEFAULT_MEMORY_TTL, 4);
+    assets.cache(request("a"), asset("a", 4));
+    assets.cache(request("b"), asset("b", 4));
+
+    let index = cache_index(window).unwrap();
+    assert!(!index.lock().unwrap().lookup("a"));
+    assert!(index.lock().unwrap().lookup("b"));
+
+    assets.configure(0, DEFAULT_MEMORY_CAPACITY);
+    assets.cache(request("c"), asset("c", 4));
+    assert!(!index.lock().unwrap().lookup("c"));
+}
+
+#[test]
+fn local_files_must_be_within_the_workspace() {
+    let root = std::env::temp_dir().join(format!("noot-workspace-{}", nanoid!(5)));
+    std::fs::create_dir_all(root.join("notes")).unwrap();
+    std::fs::write(root.join("image.png"), "image").unwrap();
+    let outside = root.with_extension("png");
+    std::fs::write(&outside, "outside").unwrap();
+
+    assert_eq!(workspace_file(&root, &root.join("notes/../image.png")), Some(root.join("image.png").canonicalize().unwrap()));
+    assert_eq!(workspace_file(&root, &root.join("notes/../../").join(outside.file_name().unwrap())), None);
+    assert_eq!(workspace_file(&root, &outside), None);
+
+    // Links within the workspace cannot reach outside of it
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(&outside, root.join("link.png")).unwrap();
+    #[cfg(unix)]
+    assert_eq!(workspace_file(&root, &root.join("link.png")), None);
+}
@@ -1,5 +1,12 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use clap::Parser;
+use rusqlite::{Connection, OpenFlags};
+use crate::assets::asset_usage;
+use crate::plugins::{install, PluginError, PluginResult, DEFAULT_PLUGIN_DIRECTORY};
+use crate::plugins::install::{InstalledPlugin, PluginUpdate};
+use crate::storage::workspace::{asset_directory, open_database, read_setting};
 
 /// Helper struct to allow the user to pass args to the instance
 #[derive(Parser, Debug)]
@@ -15,7 +22,33 @@ pub struct Args {
 
     /// Command to list the workspaces in a table and then exit
     #[arg(long, default_value_t)]
-    pub list_workspaces: bool
+    pub list_workspaces: bool,
+
+    /// Command to report the disk usage of cached assets for each workspace and then exit
+    #[arg(long, default_value_t)]
+    pub asset_usage: bool,
+
+    /// Command to list the plugins installed in the workspace given by `--load-workspace` and then exit
+    #[arg(long, default_value_t)]
+    pub list_plugins: bool,
+
+    /// Command to install a plugin from a git repository into the workspace given by `--load-workspace` and then exit
+    #[arg(long, value_name = "URL")]
+    pub install_plugin: Option<String>,
+
+    /// Command to load a plugin straight from a local directory in the workspace given by `--load-workspace`, which
+    /// is reloaded whenever its files change, and then exit. Requires the `plugins.allow-unpacked` setting
+    #[arg(long, value_name = "PATH")]
+    pub add_unpacked_plugin: Option<PathBuf>,
+
+    /// Command to update a plugin to the latest commit of its repository, showing any change to the scopes it
+    /// requests before asking to continue, and then exit
+    #[arg(long, value_name = "NAME")]
+    pub update_plugin: Option<String>,
+
+    /// Command to uninstall a plugin from the workspace given by `--load-workspace` and then exit
+    #[arg(long, value_name = "NAME")]
+    pub uninstall_plugin: Option<String>,
 }
 
 
@@ -27,6 +60,16 @@ impl Args {
             Args::list_workspaces();
             exit(0);
         }
+
+        if self.asset_usage {
+            Args::asset_usage();
+            exit(0);
+        }
+
+        if self.list_plugins || self.install_plugin.is_some() || self.add_unpacked_plugin.is_some() || self.update_plugin.is_some() || self.uninstall_plugin.is_some() {
+            self.manage_plugins();
+            exit(0);
+        }
     } 
 
     pub fn list_workspaces() {
@@ -60,4 +103,191 @@ impl Args {
         println!("noot --load-workspace <Id>");
         println!("Eg: noot --load-workspace {}", workspaces.first().unwrap().id);
     }
+
+    pub fn asset_usage() {
+        let store = crate::storage::process::ProcessStorageManager::new();
+
+        let workspaces = store.list_workspaces();
+
+        if workspaces.is_empty() {
+            println!("No workspaces found. Launch noot to generate a workspace");
+            return;
+        }
+
+        let mut builder = tabled::builder::Builder::default();
+
+        builder.push_record(["Id", "Name", "Blob Assets", "Blob Size", "Disk Assets", "Disk Size", "Total"]);
+
+        for workspace in &workspaces {
+            let root = PathBuf::from(&workspace.disk_path);
+            let database = root.join(".noot").join("workspace.db");
+
+            // Reporting usage must not create or migrate the database of a workspace
+            if !database.exists() {
+                println!("Skipping workspace {}, it has no database", workspace.name);
+                continue;
+            }
+
+            let usage = match Connection::open_with_flags(&database, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+                Ok(db) => asset_usage(&db, &asset_directory(root)),
+                Err(err) => {
+                    println!("Failed to open the database for workspace {}: {}", workspace.name, err);
+                    continue;
+                }
+            };
+
+            builder.push_record([
+                workspace.id.clone(),
+                workspace.name.clone(),
+                usage.blob_count.to_string(),
+                format_bytes(usage.blob_bytes),
+                usage.disk_count.to_string(),
+                format_bytes(usage.disk_bytes),
+                format_bytes(usage.total_bytes())
+            ]);
+        }
+
+        let mut table = builder.build();
+
+        table.with(tabled::settings::Style::modern_rounded());
+
+        println!("{}", table);
+    }
+
+    /// Runs the plugin commands against the workspace given by `--load-workspace`
+    fn manage_plugins(&self) {
+        let Some(id) = &self.load_workspace else {
+            println!("Choose the workspace to manage plugins in with --load-workspace <Id>, see --list-workspaces");
+            exit(1);
+        };
+
+        let store = crate::storage::process::ProcessStorageManager::new();
+        let Some(workspace) = store.list_workspaces().into_iter().find(|w| &w.id == id) else {
+            println!("No workspace has the ID {}", id);
+            exit(1);
+        };
+
+        let root = PathBuf::from(&workspace.disk_path);
+        let db = match open_database(&root) {
+            Ok(db) => db,
+            Err(err) => {
+                println!("Failed to open the database for workspace {}: {}", workspace.name, err);
+                exit(1);
+            }
+        };
+
+        let directory = root.join(
+            read_setting::<String>(&db, "plugins.directory").map(|s| s.value).unwrap_or(DEFAULT_PLUGIN_DIRECTORY.to_string())
+        );
+
+        let outcome = if let Some(url) = &self.install_plugin {
+            install::install(&db, &directory, url).map(|plugin| {
+                println!("Installed '{}' at {}", plugin.id, plugin.pin.unwrap_or_default());
+                println!("You will be asked to grant its scopes when the workspace is next opened");
+            })
+        } else if let Some(path) = &self.add_unpacked_plugin {
+            install::add_unpacked(&db, path).map(|plugin| {
+                println!("Added '{}' from {}", plugin.id, plugin.reference);
+                if !read_setting::<bool>(&db, "plugins.allow-unpacked").is_some_and(|s| s.value) {
+                    println!("It will not be loaded until plugins.allow-unpacked is enabled for this workspace");
+                }
+            })
+        } else if let Some(name) = &self.update_plugin {
+            install::check_update(&db, &directory, name).and_then(|update| Args::confirm_update(&db, &directory, update))
+        } else if let Some(name) = &self.uninstall_plugin {
+            install::uninstall(&db, &directory, name).map(|_| println!("Uninstalled '{}'", name))
+        } else {
+            install::installed(&db).map_err(PluginError::from).map(|plugins| Args::list_plugins(&plugins))
+        };
+
+        if let Err(err) = outcome {
+            println!("{}", err);
+            exit(1);
+        }
+    }
+
+    fn list_plugins(plugins: &[InstalledPlugin]) {
+        if plugins.is_empty() {
+            println!("No plugins are installed in this workspace. Install one using --install-plugin <URL>");
+            return;
+        }
+
+        let mut builder = tabled::builder::Builder::default();
+
+        builder.push_record(["Name", "Kind", "Source", "Pinned Commit", "Enabled"]);
+
+        for plugin in plugins {
+            builder.push_record([
+                plugin.id.clone(),
+                format!("{:?}", plugin.kind),
+                plugin.reference.clone(),
+                plugin.pin.clone().unwrap_or_default(),
+                plugin.enable.to_string()
+            ]);
+        }
+
+        let mut table = builder.build();
+
+        table.with(tabled::settings::Style::modern_rounded());
+
+        println!("{}", table);
+    }
+
+    /// Shows how an update changes the scopes a plugin requests, and applies it if the user agrees
+    fn confirm_update(db: &Connection, directory: &Path, update: PluginUpdate) -> PluginResult<()> {
+        if update.from.version == update.to.version && update.added.is_empty() && update.removed.is_empty() {
+            let current = install::find(db, &update.id)?;
+            if current.pin.as_deref() == Some(update.commit.as_str()) {
+                println!("'{}' is already up to date", update.id);
+                return Ok(());
+            }
+        }
+
+        println!("Updating '{}' from v{} to v{} ({})", update.id, update.from.version, update.to.version, update.commit);
+        if update.added.is_empty() && update.removed.is_empty() {
+            println!("The scopes it requests are unchanged");
+        }
+        for scope in update.added.scope_names() {
+            println!("+ {}", scope);
+        }
+        for scope in update.removed.scope_names() {
+            println!("- {}", scope);
+        }
+
+        print!("Continue? [y/N] ");
+        let _ = std::io::stdout().flush();
+        let mut answer = String::new();
+        let _ = std::io::stdin().read_line(&mut answer);
+
+        if answer.trim().eq_ignore_ascii_case("y") {
+            install::update(db, directory, &update)?;
+            println!("Updated '{}'", update.id);
+            if !update.added.is_empty() {
+                println!("You will be asked to grant the new scopes when the workspace is next opened");
+            }
+        } else {
+            println!("Update cancelled");
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a byte count into a human readable size, eg: `1.5 MiB`
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
 }
@@ -1,3 +1,4 @@
+use std::str::FromStr;
 use dark_light::Mode;
 use serde_derive::{Deserialize, Serialize};
 
@@ -37,10 +38,31 @@ pub struct AppearanceSettings {
 }
 
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub enum TTSProvider {
     None,
+    /// The voices of the operating system, eg: speech-dispatcher on Linux
+    System,
+    /// A speech model which runs on this computer, and works offline once downloaded
+    Local,
+    /// Writes WAV files instead of speaking, for testing without a voice or speakers
+    Wav,
+}
+
+impl FromStr for TTSProvider {
+    type Err = String;
+
+    /// Parses the `appearance.tts.provider` setting
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "system" => Ok(Self::System),
+            "local" => Ok(Self::Local),
+            "wav" => Ok(Self::Wav),
+            _ => Err(format!("Unknown TTS provider '{}'", s))
+        }
+    }
 }
 
 pub enum GTTS {
@@ -80,6 +102,19 @@ pub enum ThemeVariant {
     Custom(String)
 }
 
+impl FromStr for ThemeVariant {
+    type Err = String;
+
+    /// Parses the variant a theme is registered with, eg: `"Dark"`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "light" => Ok(Self::Light),
+            "dark" => Ok(Self::Dark),
+            _ => Err(format!("Unknown theme variant '{}', expected Light or Dark", s))
+        }
+    }
+}
+
 // impl AppearanceSettings {
 //     pub fn new() {
 //
@@ -148,7 +148,16 @@ pub const HEADER_SIZE_2: f32 = 28.;
 pub const HEADER_SIZE_3: f32 = 22.;
 pub const HEADER_SIZE_4: f32 = 18.;
 pub const HEADER_SIZE_5: f32 = 14.;
+pub const HEADER_SIZE_6: f32 = 12.;
 pub const TEXT_SIZE: f32 = 12.;
+/// Superscript and subscript text which has no unicode equivalent is drawn at this size instead
+pub const SCRIPT_SIZE: f32 = 9.;
+
+
+// Asset constants
+
+/// How often (in seconds) the editor checks for expired memory cached assets
+pub const ASSET_REFRESH_INTERVAL: u64 = 30;
 
 
 // Window Level Constants
@@ -0,0 +1,30 @@
+use std::path::Path;
+
+/// A plugin which is compiled into the app, so is always available and trusted with the scopes it requests
+#[derive(Debug)]
+pub struct BuiltinPlugin {
+    pub manifest: &'static str,
+    /// The files of the plugin, by their path relative to the plugin root
+    pub files: &'static [(&'static str, &'static str)],
+}
+
+impl BuiltinPlugin {
+    /// Returns the contents of a file of the plugin
+    pub fn file(&self, path: &Path) -> Option<&'static str> {
+        self.files.iter()
+            .find(|(name, _)| Path::new(name) == path)
+            .map(|(_, contents)| *contents)
+    }
+}
+
+/// Every plugin shipped with the app
+pub const BUILTIN_PLUGINS: &[BuiltinPlugin] = &[
+    // The noot-themes collection
+    BuiltinPlugin {
+        manifest: include_str!("../../themes/noot/manifest.yaml"),
+        files: &[
+            ("main.lua", include_str!("../../themes/noot/main.lua")),
+            ("css/molten-core.css", include_str!("../../themes/noot/css/molten-core.css")),
+        ],
+    },
+];
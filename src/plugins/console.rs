@@ -0,0 +1,43 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+use log::Level;
+
+/// The most lines the console keeps, older lines are dropped once it is full
+const CONSOLE_LIMIT: usize = 500;
+
+lazy_static!(
+    /// What plugins have logged, shown in the developer console of the editor
+    static ref CONSOLE: Mutex<VecDeque<ConsoleLine>> = Mutex::new(VecDeque::with_capacity(CONSOLE_LIMIT));
+);
+
+/// A line logged by a plugin, or by the plugin manager about a plugin
+#[derive(Debug, Clone)]
+pub struct ConsoleLine {
+    pub plugin: String,
+    pub level: Level,
+    pub message: String,
+    pub time: DateTime<Local>,
+}
+
+/// Adds a line to the console, and to the application log
+pub fn push(plugin: &str, level: Level, message: impl Into<String>) {
+    let message = message.into();
+    log!(level, "[{}] {}", plugin, message);
+
+    let mut console = CONSOLE.lock().unwrap();
+    if console.len() == CONSOLE_LIMIT {
+        console.pop_front();
+    }
+    console.push_back(ConsoleLine { plugin: plugin.to_string(), level, message, time: Local::now() });
+}
+
+/// Returns every line in the console, oldest first
+pub fn lines() -> Vec<ConsoleLine> {
+    CONSOLE.lock().unwrap().iter().cloned().collect()
+}
+
+pub fn clear() {
+    CONSOLE.lock().unwrap().clear();
+}
@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::plugins::{PluginError, PluginResult};
+
+/// Settings which would let the config of a repository run commands of its choosing, eg: a plugin directory planted
+/// within a shared workspace, overridden for every command
+const SAFE_CONFIG: [&str; 4] = ["-c", "core.fsmonitor=false", "-c", "core.hooksPath=/dev/null"];
+
+/// The settings `git clone` writes into a repository, which are the only ones a plugin repository may have.
+/// Most others could make git run commands, eg: filter drivers or `remote.origin.uploadpack`
+const CLONE_CONFIG: [&str; 10] = [
+    "core.repositoryformatversion", "core.filemode", "core.bare", "core.logallrefupdates", "core.ignorecase",
+    "core.precomposeunicode", "core.symlinks", "extensions.objectformat", "remote.origin.url", "remote.origin.fetch",
+];
+
+/// A file within a commit
+pub struct TreeFile {
+    pub mode: String,
+    pub hash: String,
+    /// The path of the file from the root of the repository, separated with `/`
+    pub path: String,
+}
+
+/// Runs a git command against the repository checked out at `dir`, returning what it printed.
+/// Only the repository is used, the files checked out are never read unless `--work-tree` is given
+fn git(dir: Option<&Path>, args: &[&str]) -> PluginResult<String> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.arg("--git-dir").arg(git_dir(dir));
+    }
+
+    let output = command.env("GIT_CONFIG_NOSYSTEM", "1").args(SAFE_CONFIG).args(args).output()
+        .map_err(|e| PluginError::Git(format!("Unable to run git, is it installed? {}", e)))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(PluginError::Git(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim())))
+    }
+}
+
+fn git_dir(dir: &Path) -> PathBuf {
+    dir.join(".git")
+}
+
+/// Clones a repository, returning the commit which was checked out
+pub fn clone(url: &str, dest: &Path) -> PluginResult<String> {
+    git(None, &["clone", "--quiet", "--", url, &dest.to_string_lossy()])?;
+    head(dest)
+}
+
+/// Checks that a repository only has the settings `git clone` wrote into it, without letting git read them.
+/// This has to pass before any other command is run against a repository which may have been changed since
+pub fn check_config(dir: &Path) -> PluginResult<()> {
+    let git_dir = git_dir(dir);
+
+    // Linked worktrees read their config from another repository
+    if !git_dir.is_dir() || git_dir.join("commondir").exists() {
+        return Err(PluginError::Git(format!("{} is not a repository cloned by noot", dir.display())));
+    }
+
+    let config = git(None, &["config", "--file", &git_dir.join("config").to_string_lossy(), "--no-includes", "--list", "-z"])?;
+
+    for entry in config.split('\0').filter(|entry| !entry.is_empty()) {
+        let key = entry.split_once('\n').map_or(entry, |(key, _)| key);
+        let branch = key.strip_prefix("branch.")
+            .and_then(|key| key.rsplit_once('.'))
+            .is_some_and(|(_, setting)| setting == "remote" || setting == "merge");
+
+        if !branch && !CLONE_CONFIG.contains(&key) {
+            return Err(PluginError::Git(format!("The repository of {} has the setting '{}', which noot did not write", dir.display(), key)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the commit which is checked out
+pub fn head(dir: &Path) -> PluginResult<String> {
+    git(Some(dir), &["rev-parse", "HEAD"])
+}
+
+/// Fetches the default branch of the repository at `url`, returning its latest commit without checking it out.
+/// The URL is given rather than the name of the remote, so that the settings of the remote are not used
+pub fn fetch(dir: &Path, url: &str) -> PluginResult<String> {
+    git(Some(dir), &["fetch", "--quiet", "--", url, "HEAD"])?;
+    git(Some(dir), &["rev-parse", "FETCH_HEAD"])
+}
+
+/// Checks out a commit, leaving the repository detached from any branch so that it stays pinned
+pub fn checkout(dir: &Path, commit: &str) -> PluginResult<()> {
+    git(Some(dir), &["--work-tree", &dir.to_string_lossy(), "checkout", "--quiet", "--detach", commit]).map(|_| ())
+}
+
+/// Returns the contents of a file at a commit
+pub fn show(dir: &Path, commit: &str, path: &str) -> PluginResult<String> {
+    git(Some(dir), &["cat-file", "blob", &format!("{}:{}", commit, path)])
+}
+
+/// Lists every file within a commit
+pub fn files(dir: &Path, commit: &str) -> PluginResult<Vec<TreeFile>> {
+    let tree = git(Some(dir), &["ls-tree", "-r", "-z", "--full-tree", commit])?;
+
+    tree.split('\0').filter(|entry| !entry.is_empty()).map(|entry| {
+        // Each entry is `<mode> <type> <hash>\t<path>`
+        let (info, path) = entry.split_once('\t').ok_or_else(|| PluginError::Git(format!("Unexpected tree entry '{}'", entry)))?;
+        let info: Vec<&str> = info.split(' ').collect();
+
+        match info[..] {
+            [mode, _, hash] => Ok(TreeFile { mode: mode.to_string(), hash: hash.to_string(), path: path.to_string() }),
+            _ => Err(PluginError::Git(format!("Unexpected tree entry '{}'", entry))),
+        }
+    }).collect()
+}
+
+/// Hashes files as they are on disk, in the order they are given. Filters are not applied, as the attributes and
+/// config which choose them could run commands
+pub fn hash_files(dir: &Path, files: &[PathBuf]) -> PluginResult<Vec<String>> {
+    if files.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let paths: Vec<String> = files.iter().map(|file| file.to_string_lossy().to_string()).collect();
+    let mut args = vec!["hash-object", "--no-filters", "--"];
+    args.extend(paths.iter().map(String::as_str));
+
+    git(Some(dir), &args).map(|hashes| hashes.lines().map(str::to_string).collect())
+}
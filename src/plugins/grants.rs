@@ -0,0 +1,50 @@
+use rusqlite::{params, Connection};
+use semver::Version;
+use crate::plugins::scopes::PluginScopes;
+
+/// Returns the scopes the user granted to a plugin, as recorded in `workspace.db`.
+/// If this version of the plugin has not been granted anything, the grant of the newest earlier version is used,
+/// so that updates which ask for nothing new do not prompt the user again
+pub fn granted(db: &Connection, plugin: &str, version: &Version) -> rusqlite::Result<Option<PluginScopes>> {
+    let mut stmt = db.prepare("SELECT version, scopes FROM plugin_grants WHERE plugin = ?")?;
+    let grants = stmt.query_map([plugin], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))?;
+
+    let mut newest: Option<(Version, PluginScopes)> = None;
+    for grant in grants {
+        let (granted_version, scopes) = grant?;
+        let Ok(granted_version) = Version::parse(&granted_version) else { continue };
+        if &granted_version > version || newest.as_ref().is_some_and(|(v, _)| v > &granted_version) {
+            continue;
+        }
+        newest = Some((granted_version, PluginScopes::from_bits_truncate(scopes as u64)));
+    }
+
+    Ok(newest.map(|(_, scopes)| scopes))
+}
+
+/// Records the scopes granted to a version of a plugin.
+/// `AVOID_SANDBOXES` is never recorded, as it must be approved again every session
+pub fn grant(db: &Connection, plugin: &str, version: &Version, scopes: PluginScopes) -> rusqlite::Result<()> {
+    let scopes = scopes.difference(PluginScopes::AVOID_SANDBOXES);
+    db.execute(
+        "INSERT OR REPLACE INTO plugin_grants (plugin, version, scopes) VALUES (?, ?, ?)",
+        params![plugin, version.to_string(), scopes.bits() as i64]
+    )?;
+    Ok(())
+}
+
+/// Forgets every scope granted to a plugin, eg: when it is uninstalled
+pub fn revoke(db: &Connection, plugin: &str) -> rusqlite::Result<()> {
+    db.execute("DELETE FROM plugin_grants WHERE plugin = ?", [plugin])?;
+    Ok(())
+}
+
+/// Returns which of the scopes a plugin requests the user has not yet granted.
+/// `AVOID_SANDBOXES` is only granted by approving it for the current session
+pub fn ungranted(requested: PluginScopes, granted: Option<PluginScopes>, approved_for_session: bool) -> PluginScopes {
+    let mut missing = requested.difference(granted.unwrap_or(PluginScopes::empty()));
+    if approved_for_session {
+        missing.remove(PluginScopes::AVOID_SANDBOXES);
+    }
+    missing
+}
@@ -0,0 +1,240 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use rusqlite::{params, Connection};
+use walkdir::WalkDir;
+use crate::plugins::{git, grants, PluginError, PluginResult};
+use crate::plugins::manifest::{PluginManifest, MANIFEST_FILE};
+use crate::plugins::scopes::PluginScopes;
+
+/// How a plugin was installed, as stored in the `kind` column of the `plugins` table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PluginKind {
+    /// Loaded straight from a local directory, eg: while the plugin is being developed
+    Unpacked = 1,
+    /// Cloned from a git repository into the plugin directory, and pinned to a commit
+    Git = 2,
+}
+
+/// A plugin recorded in the `plugins` table of a workspace
+#[derive(Debug, Clone)]
+pub struct InstalledPlugin {
+    /// The name of the plugin, which git plugins are also cloned into a directory of
+    pub id: String,
+    pub kind: PluginKind,
+    /// The git URL of the plugin, or the path it is loaded from if it is unpacked
+    pub reference: String,
+    /// The commit a git plugin is pinned to
+    pub pin: Option<String>,
+    pub enable: bool,
+}
+
+/// An update to a git plugin, which has been fetched but not yet checked out
+#[derive(Debug, Clone)]
+pub struct PluginUpdate {
+    pub id: String,
+    pub from: PluginManifest,
+    pub to: PluginManifest,
+    /// The commit the plugin would be pinned to
+    pub commit: String,
+    /// The scopes the update asks for which the current version does not
+    pub added: PluginScopes,
+    /// The scopes the current version asks for which the update does not
+    pub removed: PluginScopes,
+}
+
+/// Lists the plugins installed in a workspace
+pub fn installed(db: &Connection) -> rusqlite::Result<Vec<InstalledPlugin>> {
+    let mut stmt = db.prepare("SELECT id, kind, ref, pin, enable FROM plugins ORDER BY id")?;
+    let plugins = stmt.query_map([], |r| {
+        // The kind column has text affinity, so is read back as text even though it holds a number
+        let kind: String = r.get(1)?;
+        Ok(InstalledPlugin {
+            id: r.get(0)?,
+            kind: if kind == "1" { PluginKind::Unpacked } else { PluginKind::Git },
+            reference: r.get(2)?,
+            pin: r.get(3)?,
+            enable: r.get::<_, Option<bool>>(4)?.unwrap_or_default(),
+        })
+    })?;
+
+    plugins.collect()
+}
+
+/// Returns the installed plugin with the given name
+pub fn find(db: &Connection, id: &str) -> PluginResult<InstalledPlugin> {
+    installed(db)?.into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| PluginError::Io(format!("No plugin named '{}' is installed", id)))
+}
+
+/// Clones a plugin from a git repository into the plugin directory, pinning it to the latest commit
+pub fn install(db: &Connection, directory: &Path, url: &str) -> PluginResult<InstalledPlugin> {
+    if let Some(existing) = installed(db)?.into_iter().find(|p| p.reference == url) {
+        return Err(PluginError::Io(format!("{} is already installed as '{}'", url, existing.id)));
+    }
+
+    std::fs::create_dir_all(directory).map_err(|e| PluginError::Io(e.to_string()))?;
+
+    // The name of the plugin is only known once its manifest has been cloned
+    let staging = directory.join(format!(".install-{}", nanoid!(8)));
+    let outcome = clone_into(db, directory, &staging, url);
+    if staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    outcome
+}
+
+fn clone_into(db: &Connection, directory: &Path, staging: &Path, url: &str) -> PluginResult<InstalledPlugin> {
+    let commit = git::clone(url, staging)?;
+    let manifest = PluginManifest::load(staging)?;
+    manifest.requested_scopes()?;
+
+    let id = plugin_name(&manifest)?;
+    let dest = directory.join(&id);
+    if dest.exists() || find(db, &id).is_ok() {
+        return Err(PluginError::Io(format!("A plugin named '{}' is already installed", id)));
+    }
+
+    std::fs::rename(staging, &dest).map_err(|e| PluginError::Io(e.to_string()))?;
+    db.execute(
+        "INSERT INTO plugins (id, kind, ref, pin, enable) VALUES (?, ?, ?, ?, true)",
+        params![id, PluginKind::Git as u8, url, commit]
+    )?;
+
+    info!("Installed plugin '{}' v{} from {} at {}", id, manifest.version, url, commit);
+    find(db, &id)
+}
+
+/// Records a plugin which is loaded straight from a local directory, without copying it into the plugin directory.
+/// Unpacked plugins are only loaded while the `plugins.allow-unpacked` workspace setting is enabled
+pub fn add_unpacked(db: &Connection, path: &Path) -> PluginResult<InstalledPlugin> {
+    let path = path.canonicalize().map_err(|e| PluginError::Io(format!("{}: {}", path.display(), e)))?;
+    let manifest = PluginManifest::load(&path)?;
+    manifest.requested_scopes()?;
+
+    let id = plugin_name(&manifest)?;
+    if find(db, &id).is_ok() {
+        return Err(PluginError::Io(format!("A plugin named '{}' is already installed", id)));
+    }
+
+    db.execute(
+        "INSERT INTO plugins (id, kind, ref, pin, enable) VALUES (?, ?, ?, NULL, true)",
+        params![id, PluginKind::Unpacked as u8, path.to_string_lossy()]
+    )?;
+
+    info!("Added unpacked plugin '{}' v{} from {}", id, manifest.version, path.display());
+    find(db, &id)
+}
+
+/// Checks the name of a plugin can be used as the name of its directory
+fn plugin_name(manifest: &PluginManifest) -> PluginResult<String> {
+    let id = &manifest.name;
+    if id.is_empty() || id.starts_with('.') || id.contains(['/', '\\']) {
+        return Err(PluginError::Manifest(format!("'{}' cannot be used as the name of a plugin", id)));
+    }
+    Ok(id.clone())
+}
+
+/// Fetches the latest commit of a git plugin, comparing its manifest with the installed version
+pub fn check_update(db: &Connection, directory: &Path, id: &str) -> PluginResult<PluginUpdate> {
+    let plugin = find(db, id)?;
+    if plugin.kind != PluginKind::Git {
+        return Err(PluginError::Io(format!("'{}' is not installed from a git repository", id)));
+    }
+
+    let dir = directory.join(id);
+    verify(&plugin, &dir)?;
+
+    let commit = git::fetch(&dir, &plugin.reference)?;
+    let from = PluginManifest::load(&dir)?;
+    let to = PluginManifest::parse(&git::show(&dir, &commit, MANIFEST_FILE)?)?;
+    if to.name != from.name {
+        return Err(PluginError::Manifest(format!("The update renames '{}' to '{}'", from.name, to.name)));
+    }
+
+    let (old, new) = (from.requested_scopes()?, to.requested_scopes()?);
+    Ok(PluginUpdate { id: id.to_string(), commit, added: new.difference(old), removed: old.difference(new), from, to })
+}
+
+/// Checks out a fetched update, and pins the plugin to it.
+/// The user is asked to grant any added scopes the next time the plugin is loaded
+pub fn update(db: &Connection, directory: &Path, update: &PluginUpdate) -> PluginResult<()> {
+    // The repository may have been changed while the user was deciding whether to update
+    let dir = directory.join(&update.id);
+    verify(&find(db, &update.id)?, &dir)?;
+
+    git::checkout(&dir, &update.commit)?;
+    db.execute("UPDATE plugins SET pin = ? WHERE id = ?", params![update.commit, update.id])?;
+    info!("Updated plugin '{}' to v{} at {}", update.id, update.to.version, update.commit);
+    Ok(())
+}
+
+/// Removes a plugin, forgetting the scopes it was granted.
+/// Only git plugins are deleted from disk, as unpacked plugins live outside of the plugin directory
+pub fn uninstall(db: &Connection, directory: &Path, id: &str) -> PluginResult<()> {
+    let plugin = find(db, id)?;
+
+    if plugin.kind == PluginKind::Git {
+        let dir = directory.join(id);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).map_err(|e| PluginError::Io(e.to_string()))?;
+        }
+    }
+
+    db.execute("DELETE FROM plugins WHERE id = ?", [id])?;
+    grants::revoke(db, id)?;
+    info!("Uninstalled plugin '{}'", id);
+    Ok(())
+}
+
+/// Checks that a git plugin has the commit it is pinned to checked out, with none of its files changed.
+/// The files are compared with the commit by hashing them, as asking git whether they have changed would apply the
+/// settings and attributes of the repository, which could run commands
+pub fn verify(plugin: &InstalledPlugin, dir: &Path) -> PluginResult<()> {
+    let Some(pin) = &plugin.pin else {
+        return Err(PluginError::Git(format!("'{}' is not pinned to a commit", plugin.id)));
+    };
+
+    git::check_config(dir)?;
+
+    let head = git::head(dir)?;
+    if &head != pin {
+        return Err(PluginError::Git(format!("'{}' is at {}, but is pinned to {}", plugin.id, head, pin)));
+    }
+
+    let changed = || PluginError::Git(format!("The files of '{}' have been changed since it was installed", plugin.id));
+    let tree = git::files(dir, pin)?;
+
+    if tree.iter().any(|file| file.mode != "100644" && file.mode != "100755") {
+        return Err(PluginError::Git(format!("'{}' holds links or submodules, which plugins cannot", plugin.id)));
+    }
+
+    // Every file checked out has to be part of the commit, including any added since
+    let mut checked_out = BTreeSet::new();
+    for entry in WalkDir::new(dir).min_depth(1).into_iter().filter_entry(|e| e.depth() != 1 || e.file_name() != ".git") {
+        let entry = entry.map_err(|e| PluginError::Io(e.to_string()))?;
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        if !entry.file_type().is_file() {
+            return Err(changed());
+        }
+
+        let relative = entry.path().strip_prefix(dir).map_err(|e| PluginError::Io(e.to_string()))?;
+        let components: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+        checked_out.insert(components.join("/"));
+    }
+
+    if checked_out != tree.iter().map(|file| file.path.clone()).collect::<BTreeSet<_>>() {
+        return Err(changed());
+    }
+
+    let paths: Vec<PathBuf> = tree.iter().map(|file| dir.join(&file.path)).collect();
+    let hashes = git::hash_files(dir, &paths)?;
+
+    if hashes.len() != tree.len() || tree.iter().zip(&hashes).any(|(file, hash)| &file.hash != hash) {
+        return Err(changed());
+    }
+
+    Ok(())
+}
@@ -1,12 +1,41 @@
 use std::collections::BTreeSet;
+use std::path::Path;
 use semver::Version;
 use serde_derive::{Deserialize, Serialize};
+use crate::plugins::{PluginError, PluginResult};
+use crate::plugins::scopes::PluginScopes;
+
+/// The name of the file describing a plugin, found at the root of its directory
+pub const MANIFEST_FILE: &str = "manifest.yaml";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PluginManifest {
     pub name: String,
     pub version: Version,
     pub description: String,
+    /// The Lua file which is run when the plugin is loaded, relative to the plugin directory
+    pub entrypoint: String,
     pub authors: Vec<String>,
     pub scopes: BTreeSet<String>
 }
+
+impl PluginManifest {
+    /// Reads the manifest of the plugin within the given directory
+    pub fn load(dir: &Path) -> PluginResult<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let contents = std::fs::read_to_string(&path)
+            .map_err(|e| PluginError::Io(format!("{}: {}", path.display(), e)))?;
+
+        Self::parse(&contents).map_err(|e| PluginError::Manifest(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Parses the contents of a manifest
+    pub fn parse(contents: &str) -> PluginResult<Self> {
+        serde_yaml::from_str(contents).map_err(|e| PluginError::Manifest(e.to_string()))
+    }
+
+    /// Returns the scopes the plugin asks for, or an error naming any which are not scopes
+    pub fn requested_scopes(&self) -> PluginResult<PluginScopes> {
+        PluginScopes::parse(self.scopes.iter().map(String::as_str))
+    }
+}
@@ -1,14 +1,84 @@
-use std::collections::BTreeSet;
-use mlua::Lua;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Component, Path, PathBuf};
+use iced::Subscription;
+use log::Level;
+use mlua::{ChunkMode, Function, Lua, Table};
+use crate::plugins::builtin::{BuiltinPlugin, BUILTIN_PLUGINS};
+use crate::plugins::install::{InstalledPlugin, PluginKind};
 use crate::plugins::manifest::PluginManifest;
+use crate::plugins::runtime::{build_lua_runtime, limited};
+use crate::plugins::runtime::commands::COMMAND_REGISTRY;
+use crate::plugins::runtime::markdown::RENDER_HOOKS;
 use crate::plugins::scopes::PluginScopes;
+use crate::runtime::Message;
+use crate::runtime::commands::COMMANDS;
+use crate::storage::workspace::open_database;
+use crate::ui::theme::THEMES;
 
 pub mod manifest;
 pub mod scopes;
 pub mod runtime;
+pub mod grants;
+pub mod install;
+pub mod console;
+pub mod builtin;
+mod git;
+mod watch;
+
+#[cfg(test)]
+mod tests;
+
+/// The directory plugins are installed into when the `plugins.directory` workspace setting is not set
+pub const DEFAULT_PLUGIN_DIRECTORY: &str = ".plugins";
+
+pub type PluginResult<T> = Result<T, PluginError>;
+
+#[derive(Debug, Clone)]
+pub enum PluginError {
+    /// The plugin files could not be read
+    Io(String),
+    /// The manifest is missing a field, or a field is invalid
+    Manifest(String),
+    /// The Lua VM could not be built, or the entrypoint raised an error
+    Script(String),
+    /// The plugin could not be installed or updated from its repository, or no longer matches the commit it is pinned to
+    Git(String),
+    /// The installed plugins could not be read from, or written to, the workspace database
+    Database(String),
+    /// The plugin was stopped for running longer than [runtime::TIME_LIMIT]
+    TimedOut,
+}
+
+impl Display for PluginError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PluginError::Io(reason) => write!(f, "Unable to read plugin: {}", reason),
+            PluginError::Manifest(reason) => write!(f, "Invalid plugin manifest: {}", reason),
+            PluginError::Script(reason) => write!(f, "Plugin error: {}", reason),
+            PluginError::Git(reason) => write!(f, "Git error: {}", reason),
+            PluginError::Database(reason) => write!(f, "Unable to access the installed plugins: {}", reason),
+            PluginError::TimedOut => write!(f, "Plugin stopped after running for more than {} seconds", runtime::TIME_LIMIT.as_secs()),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for PluginError {
+    fn from(e: rusqlite::Error) -> Self {
+        PluginError::Database(e.to_string())
+    }
+}
+
+impl From<mlua::Error> for PluginError {
+    fn from(e: mlua::Error) -> Self {
+        PluginError::Script(e.to_string())
+    }
+}
 
 pub enum PluginState {
     NotLoaded,
+    /// The plugin asks for scopes the user has not granted yet, which are held here
+    AwaitingConsent(PluginScopes),
     Loaded,
     Errored(String)
 }
@@ -17,11 +87,42 @@ pub struct Plugin {
     pub state: PluginState,
     pub rt: Lua,
     pub manifest: PluginManifest,
-    pub scope: PluginScopes
+    pub scope: PluginScopes,
+    /// Where the files of the plugin are read from
+    pub source: PluginSource,
+    /// How a git plugin was installed, which its checkout must still match for it to be started
+    pub pinned: Option<InstalledPlugin>,
+}
+
+/// Where the files of a plugin are read from
+#[derive(Debug, Clone)]
+pub enum PluginSource {
+    /// A directory on disk, eg: a git plugin within the plugin directory, or an unpacked plugin
+    Directory(PathBuf),
+    /// A plugin compiled into the app, which is always loaded
+    Builtin(&'static BuiltinPlugin),
 }
 
 pub struct PluginManager {
-    pub plugins: BTreeSet<Plugin>
+    /// Every plugin which has been found, by name
+    pub plugins: BTreeMap<String, Plugin>,
+    /// The root of the workspace the plugins were loaded from, whose database holds the scopes granted to them
+    workspace: PathBuf,
+    /// The plugins, by name and version, which the user has allowed to avoid sandboxes until the app is closed
+    unsandboxed: BTreeSet<String>,
+    /// Whether plugins may be loaded straight from a local directory, from the `plugins.allow-unpacked` setting
+    allow_unpacked: bool,
+    /// The directories of the unpacked plugins which were loaded, by name, which are watched for changes
+    unpacked: BTreeMap<String, PathBuf>,
+}
+
+/// Asks the user to grant a plugin the scopes it requests
+#[derive(Debug, Clone)]
+pub struct ConsentRequest {
+    pub manifest: PluginManifest,
+    pub requested: PluginScopes,
+    /// The scopes which have not been granted before, eg: those added by an update
+    pub added: PluginScopes,
 }
 
 
@@ -29,25 +130,234 @@ pub struct PluginManager {
 impl PluginManager {
     pub fn new() -> Self {
         Self {
-            plugins: Default::default()
+            plugins: Default::default(),
+            workspace: PathBuf::new(),
+            unsandboxed: Default::default(),
+            allow_unpacked: false,
+            unpacked: Default::default(),
         }
     }
 
-    pub fn load_plugins(&mut self, path: &str) {
-        let entries = std::fs::read_dir(path).unwrap();
-        for entry in entries {
-            if let Ok(entry) = entry {
-                let path = entry.path();
-                if path.is_dir() {
+    /// Starts the plugins shipped with the app, which are trusted with every scope they request
+    pub fn load_builtin(&mut self) {
+        for builtin in BUILTIN_PLUGINS {
+            let mut plugin = match Plugin::builtin(builtin) {
+                Ok(plugin) => plugin,
+                Err(e) => {
+                    error!("Unable to read a built in plugin: {}", e);
                     continue;
-                } else {
+                }
+            };
+
+            plugin.start(&self.workspace);
+            if let PluginState::Errored(e) = &plugin.state {
+                console::push(&plugin.manifest.name, Level::Error, format!("Failed to start: {}", e));
+            }
+            self.plugins.insert(plugin.manifest.name.clone(), plugin);
+        }
+    }
+
+    /// Sets whether plugins may be loaded straight from a local directory, which applies the next time they are loaded
+    pub fn allow_unpacked(&mut self, allow: bool) {
+        self.allow_unpacked = allow;
+    }
 
+    /// Loads the plugins installed in a workspace, replacing any workspace plugins which were loaded before.
+    /// Git plugins are cloned into the `plugin-directory`, and are only loaded while they match the commit they are
+    /// pinned to. Unpacked plugins are loaded from wherever they are, if they are allowed. Plugins which cannot be
+    /// loaded are logged and skipped.
+    ///
+    /// Plugins are only started once the user has granted every scope they request, so the plugins still awaiting
+    /// consent are returned for the user to be asked about
+    pub fn load_plugins(&mut self, path: &Path, workspace: &Path) -> Vec<ConsentRequest> {
+        let loaded: Vec<String> = self.plugins.iter()
+            .filter(|(_, plugin)| !plugin.is_builtin())
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in loaded {
+            self.unload(&name);
+        }
+        self.unpacked.clear();
+        self.workspace = workspace.to_path_buf();
+
+        let installed = match open_database(workspace).and_then(|db| install::installed(&db)) {
+            Ok(installed) => installed,
+            Err(e) => {
+                error!("Unable to read the installed plugins: {}", e);
+                return vec![];
+            }
+        };
+
+        let mut requests = vec![];
+        for installed in installed.into_iter().filter(|p| p.enable) {
+            if self.plugins.contains_key(&installed.id) {
+                console::push(&installed.id, Level::Error, "Skipping plugin, as a built in plugin has the same name");
+                continue;
+            }
+
+            let plugin = match installed.kind {
+                PluginKind::Git => {
+                    // The checkout is only verified once the plugin is about to start, as that runs git within it
+                    Plugin::read(&path.join(&installed.id)).map(|mut plugin| {
+                        plugin.pinned = Some(installed.clone());
+                        plugin
+                    })
+                }
+                PluginKind::Unpacked if !self.allow_unpacked => {
+                    warn!("Skipping unpacked plugin '{}', as unpacked plugins are not allowed in this workspace", installed.id);
+                    continue;
+                }
+                PluginKind::Unpacked => {
+                    // Watch the plugin even if it fails to load, so that it is loaded once it is fixed
+                    let dir = PathBuf::from(&installed.reference);
+                    self.unpacked.insert(installed.id.clone(), dir.clone());
+                    Plugin::read(&dir)
                 }
+            };
+
+            match plugin {
+                Ok(plugin) => requests.extend(self.admit(plugin)),
+                Err(e) => console::push(&installed.id, Level::Error, format!("Skipping plugin: {}", e)),
             }
         }
+
+        requests
     }
-}
 
+    /// Tears down the VM of an unpacked plugin and loads it again from its directory, eg: after its files changed.
+    /// Returns a request for consent if the plugin now asks for scopes it has not been granted
+    pub fn reload(&mut self, name: &str) -> Option<ConsentRequest> {
+        let dir = self.unpacked.get(name)?.clone();
+        self.unload(name);
+
+        match Plugin::read(&dir) {
+            Ok(plugin) => {
+                console::push(name, Level::Info, format!("Reloading from {}", dir.display()));
+                self.admit(plugin)
+            }
+            Err(e) => {
+                console::push(name, Level::Error, format!("Unable to reload: {}", e));
+                None
+            }
+        }
+    }
+
+    /// Watches the directories of the unpacked plugins, so that they are reloaded when their files change
+    pub fn subscription(&self) -> Subscription<Message> {
+        watch::subscription(&self.unpacked)
+    }
+
+    /// Runs a command a plugin registered, logging any error it raises to the console.
+    /// A plugin which runs out of time is stopped, as it would likely do so again
+    pub fn run_command(&mut self, name: &str, command: &str) {
+        let Some(plugin) = self.plugins.get(name).filter(|p| matches!(p.state, PluginState::Loaded)) else {
+            console::push(name, Level::Warn, format!("Cannot run '{}', as the plugin is not loaded", command));
+            return;
+        };
+
+        let outcome = limited(|| {
+            plugin.rt.named_registry_value::<Table>(COMMAND_REGISTRY)
+                .and_then(|commands| commands.get::<Function>(command))
+                .and_then(|function| function.call::<()>(()))
+        });
+
+        match outcome {
+            Ok(_) => {}
+            Err(PluginError::TimedOut) => {
+                console::push(name, Level::Error, format!("Command '{}' failed: {}", command, PluginError::TimedOut));
+                self.stop(name, PluginError::TimedOut);
+            }
+            Err(e) => console::push(name, Level::Error, format!("Command '{}' failed: {}", command, e)),
+        }
+    }
+
+    /// Marks a plugin as errored, removing everything it registered while keeping it listed
+    fn stop(&mut self, name: &str, error: PluginError) {
+        let Some(mut plugin) = self.plugins.remove(name) else { return };
+        self.unload(name);
+
+        plugin.state = PluginState::Errored(error.to_string());
+        self.plugins.insert(name.to_string(), plugin);
+    }
+
+    /// Stops a plugin, tearing down its VM and removing the themes, commands, key bindings and render hooks it
+    /// registered
+    fn unload(&mut self, name: &str) {
+        self.plugins.remove(name);
+        THEMES.lock().unwrap().remove_plugin_themes(name);
+        COMMANDS.lock().unwrap().remove_plugin(name);
+        RENDER_HOOKS.lock().unwrap().remove_plugin(name);
+    }
+
+    /// Starts a plugin if it has been granted every scope it requests, otherwise holds it until the user is asked
+    fn admit(&mut self, mut plugin: Plugin) -> Option<ConsentRequest> {
+        let added = grants::ungranted(plugin.scope, self.granted(&plugin.manifest), self.unsandboxed.contains(&plugin.key()));
+        let request = if added.is_empty() {
+            self.start(&mut plugin);
+            None
+        } else {
+            info!("Plugin '{}' is waiting to be granted {}", plugin.manifest.name, added);
+            plugin.state = PluginState::AwaitingConsent(added);
+            Some(ConsentRequest { manifest: plugin.manifest.clone(), requested: plugin.scope, added })
+        };
+
+        self.plugins.insert(plugin.manifest.name.clone(), plugin);
+        request
+    }
+
+    /// Records whether the user granted a plugin the scopes it requests, starting the plugin if they did
+    pub fn consent(&mut self, name: &str, allowed: bool) {
+        let Some(mut plugin) = self.plugins.remove(name) else { return };
+
+        if !allowed {
+            info!("Plugin '{}' was not granted {}, so will not be started", name, plugin.scope);
+            plugin.state = PluginState::NotLoaded;
+        } else {
+            if plugin.scope.contains(PluginScopes::AVOID_SANDBOXES) {
+                self.unsandboxed.insert(plugin.key());
+            }
+            self.start(&mut plugin);
+        }
+
+        self.plugins.insert(name.to_string(), plugin);
+    }
+
+    /// Returns the scopes granted to a plugin, which are stored in the workspace database
+    fn granted(&self, manifest: &PluginManifest) -> Option<PluginScopes> {
+        open_database(&self.workspace)
+            .and_then(|db| grants::granted(&db, &manifest.name, &manifest.version))
+            .unwrap_or_else(|e| {
+                error!("Unable to read the scopes granted to '{}': {}", manifest.name, e);
+                None
+            })
+    }
+
+    /// Starts a plugin whose scopes have all been granted, recording the grant against its current version.
+    /// Git plugins are only started while they still match the commit they are pinned to
+    fn start(&self, plugin: &mut Plugin) {
+        let verified = match (&plugin.pinned, &plugin.source) {
+            (Some(installed), PluginSource::Directory(dir)) => install::verify(installed, dir),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = verified {
+            console::push(&plugin.manifest.name, Level::Error, format!("Skipping plugin: {}", e));
+            plugin.state = PluginState::Errored(e.to_string());
+            return;
+        }
+
+        if let Err(e) = open_database(&self.workspace).and_then(|db| grants::grant(&db, &plugin.manifest.name, &plugin.manifest.version, plugin.scope)) {
+            error!("Unable to record the scopes granted to '{}': {}", plugin.manifest.name, e);
+        }
+
+        plugin.start(&self.workspace);
+        if let PluginState::Errored(e) = &plugin.state {
+            console::push(&plugin.manifest.name, Level::Error, format!("Failed to start: {}", e));
+        } else {
+            info!("Loaded plugin '{}' v{}", plugin.manifest.name, plugin.manifest.version);
+        }
+    }
+}
 
 impl Plugin {
     pub fn new(manifest: PluginManifest) -> Self {
@@ -57,6 +367,107 @@ impl Plugin {
             rt,
             manifest,
             scope: PluginScopes::empty(),
+            source: PluginSource::Directory(PathBuf::new()),
+            pinned: None,
         }
     }
-}
\ No newline at end of file
+
+    /// Reads a plugin from its directory, without starting it
+    pub fn read(dir: &Path) -> PluginResult<Self> {
+        let manifest = PluginManifest::load(dir)?;
+        let scope = manifest.requested_scopes()?;
+
+        let mut plugin = Self::new(manifest);
+        plugin.scope = scope;
+        plugin.source = PluginSource::Directory(dir.to_path_buf());
+        Ok(plugin)
+    }
+
+    /// Reads a plugin compiled into the app, without starting it
+    pub fn builtin(builtin: &'static BuiltinPlugin) -> PluginResult<Self> {
+        let manifest = PluginManifest::parse(builtin.manifest)?;
+        let scope = manifest.requested_scopes()?;
+
+        let mut plugin = Self::new(manifest);
+        plugin.scope = scope;
+        plugin.source = PluginSource::Builtin(builtin);
+        Ok(plugin)
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.source, PluginSource::Builtin(_))
+    }
+
+    /// Runs the entrypoint of the plugin within a sandboxed VM, with every scope it requested.
+    /// Errors raised by the entrypoint are kept in the plugin state
+    pub fn start(&mut self, workspace: &Path) {
+        self.state = match build_lua_runtime(&self.manifest, self.scope, &self.source, workspace) {
+            Ok(rt) => {
+                self.rt = rt;
+                match self.run() {
+                    Ok(_) => PluginState::Loaded,
+                    Err(e) => PluginState::Errored(e.to_string()),
+                }
+            }
+            Err(e) => PluginState::Errored(e.to_string()),
+        };
+    }
+
+    /// Identifies this version of the plugin
+    fn key(&self) -> String {
+        format!("{}@{}", self.manifest.name, self.manifest.version)
+    }
+
+    /// Runs the entrypoint of the plugin
+    fn run(&self) -> PluginResult<()> {
+        if self.source.resolve(Path::new(&self.manifest.entrypoint)).is_none() {
+            return Err(PluginError::Manifest(format!("The entrypoint '{}' is outside of the plugin", self.manifest.entrypoint)));
+        }
+
+        let entrypoint = self.source.read(&self.manifest.entrypoint)?;
+        limited(|| {
+            self.rt.load(entrypoint)
+                .set_name(format!("@{}/{}", self.manifest.name, self.manifest.entrypoint))
+                .set_mode(ChunkMode::Text)
+                .exec()
+        })
+    }
+}
+
+impl PluginSource {
+    /// Resolves a path given by a plugin against its root, returning it relative to the root.
+    /// Paths which would leave the plugin are refused
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut resolved = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => resolved.push(name),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !resolved.pop() {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        Some(resolved)
+    }
+
+    /// Reads a file of the plugin, given its path relative to the root of the plugin
+    pub fn read(&self, path: &str) -> PluginResult<String> {
+        let Some(relative) = self.resolve(Path::new(path)) else {
+            return Err(PluginError::Io(format!("'{}' is outside of the plugin", path)));
+        };
+
+        match self {
+            PluginSource::Directory(root) => {
+                let file = root.join(relative);
+                std::fs::read_to_string(&file).map_err(|e| PluginError::Io(format!("{}: {}", file.display(), e)))
+            }
+            PluginSource::Builtin(plugin) => plugin.file(&relative)
+                .map(str::to_string)
+                .ok_or_else(|| PluginError::Io(format!("'{}' is not part of the plugin", path))),
+        }
+    }
+}
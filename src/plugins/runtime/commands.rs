@@ -0,0 +1,49 @@
+use std::str::FromStr;
+use mlua::{Function, Lua, Table};
+use crate::plugins::runtime::require;
+use crate::plugins::scopes::PluginScopes;
+use crate::runtime::commands::{Command, CommandAction, KeyCombo, COMMANDS};
+
+/// Where the functions a plugin registered its commands with are kept within its VM, by command id
+pub const COMMAND_REGISTRY: &str = "noot.commands";
+
+/// Builds the `noot.commands` table.
+///
+/// - `register(id, title, fn)` adds a command to the command palette, which calls `fn` when run. The id is prefixed
+///   with the name of the plugin, and the full id is returned
+/// - `bind(keys, id)` binds keys such as `"Ctrl+Shift+H"` to a command, either one of the plugin or any other by its
+///   full id. Requires `keybinds.modify`
+/// - `keybinds()` returns every key binding, mapping the keys to the id of the command they run. Requires `keybinds.view`
+pub fn build(lua: &Lua, plugin: &str, scope: PluginScopes) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    lua.set_named_registry_value(COMMAND_REGISTRY, lua.create_table()?)?;
+
+    let name = plugin.to_string();
+    table.set("register", lua.create_function(move |lua, (id, title, function): (String, String, Function)| {
+        let id = format!("{}.{}", name, id);
+        COMMANDS.lock().unwrap()
+            .register(Command { id: id.clone(), title, action: CommandAction::Plugin(name.clone()) })
+            .map_err(mlua::Error::runtime)?;
+
+        lua.named_registry_value::<Table>(COMMAND_REGISTRY)?.set(id.clone(), function)?;
+        Ok(id)
+    })?)?;
+
+    let name = plugin.to_string();
+    table.set("bind", lua.create_function(move |_, (keys, id): (String, String)| {
+        require(scope, PluginScopes::MODIFY_KEYBINDS, "commands.bind")?;
+        let keys = KeyCombo::from_str(&keys).map_err(mlua::Error::runtime)?;
+
+        let mut commands = COMMANDS.lock().unwrap();
+        let own = format!("{}.{}", name, id);
+        let id = if commands.get(&own).is_some() { own } else { id };
+        commands.bind(keys, &id, &name).map_err(mlua::Error::runtime)
+    })?)?;
+
+    table.set("keybinds", lua.create_function(move |lua, ()| {
+        require(scope, PluginScopes::SEE_KEYBINDS, "commands.keybinds")?;
+        lua.create_table_from(COMMANDS.lock().unwrap().bindings().into_iter().map(|(keys, id)| (keys.to_string(), id)))
+    })?)?;
+
+    Ok(table)
+}
@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
+use mlua::{Function, Lua, Table};
+use crate::plugins::runtime::require;
+use crate::plugins::scopes::PluginScopes;
+use crate::ui::diagrams;
+
+lazy_static!(
+    /// The functions plugins have registered to change how notes are rendered
+    pub static ref RENDER_HOOKS: Mutex<RenderHooks> = Mutex::new(RenderHooks::default());
+);
+
+/// A function registered by a plugin, which is called while notes are parsed
+#[derive(Debug, Clone)]
+pub struct RenderHook {
+    pub plugin: String,
+    pub function: Function,
+}
+
+#[derive(Debug, Default)]
+pub struct RenderHooks {
+    /// Called with every top level block of a note once it has been parsed, in the order they were registered
+    transforms: Vec<RenderHook>,
+    /// Render fenced code blocks, by the language they are tagged with
+    code_blocks: BTreeMap<String, RenderHook>,
+}
+
+impl RenderHooks {
+    /// Returns the transforms to run over each block, so that they can be called without holding the lock
+    pub fn transforms(&self) -> Vec<RenderHook> {
+        self.transforms.clone()
+    }
+
+    /// Returns the hook which renders code blocks tagged with a language, if a plugin claimed it
+    pub fn code_block(&self, lang: &str) -> Option<RenderHook> {
+        self.code_blocks.get(lang).cloned()
+    }
+
+    pub fn add_transform(&mut self, hook: RenderHook) {
+        self.transforms.push(hook);
+    }
+
+    /// Claims the code blocks tagged with a language for a plugin.
+    /// Languages rendered by the app, such as diagrams, or claimed by another plugin cannot be claimed
+    pub fn add_code_block(&mut self, lang: &str, hook: RenderHook) -> Result<(), String> {
+        if diagrams::is_diagram(lang) {
+            return Err(format!("Code blocks tagged '{}' are rendered by Noot", lang));
+        }
+        if self.code_blocks.get(lang).is_some_and(|existing| existing.plugin != hook.plugin) {
+            return Err(format!("Code blocks tagged '{}' are already rendered by another plugin", lang));
+        }
+
+        self.code_blocks.insert(lang.to_string(), hook);
+        Ok(())
+    }
+
+    /// Removes the hooks of a plugin, eg: when it is unloaded
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.transforms.retain(|hook| hook.plugin != plugin);
+        self.code_blocks.retain(|_, hook| hook.plugin != plugin);
+    }
+}
+
+/// Builds the `noot.markdown` table. Every function requires `render`.
+///
+/// - `transform(fn)` calls `fn` with each top level block of a note once it has been parsed, as a tree of
+///   `{ name, attributes, children }` tables, with text as `{ name = "TEXT", text }`. Returning a tree replaces the
+///   block, while returning `nil` leaves it as it was
+/// - `code_block(lang, fn)` renders fenced code blocks tagged with `lang` by calling `fn(source, lang)`, which returns
+///   a list of items to draw instead, or `nil` to draw the code block as it is:
+///   - `{ type = "text", text, bold, italic, strike, code, link }`, where consecutive text is joined into a paragraph
+///   - `{ type = "image", src, alt }`, where `src` is resolved against the note
+///   - `{ type = "table", head, rows }`, where each cell is either a string or a list of text items
+pub fn build(lua: &Lua, plugin: &str, scope: PluginScopes) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+
+    let name = plugin.to_string();
+    table.set("transform", lua.create_function(move |_, function: Function| {
+        require(scope, PluginScopes::RENDER, "markdown.transform")?;
+        RENDER_HOOKS.lock().unwrap().add_transform(RenderHook { plugin: name.clone(), function });
+        Ok(())
+    })?)?;
+
+    let name = plugin.to_string();
+    table.set("code_block", lua.create_function(move |_, (lang, function): (String, Function)| {
+        require(scope, PluginScopes::RENDER, "markdown.code_block")?;
+        RENDER_HOOKS.lock().unwrap()
+            .add_code_block(&lang, RenderHook { plugin: name.clone(), function })
+            .map_err(mlua::Error::runtime)
+    })?)?;
+
+    Ok(table)
+}
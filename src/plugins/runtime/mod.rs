@@ -1,31 +1,144 @@
-use mlua::{Lua, Variadic};
+use std::cell::Cell;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use log::Level;
+use mlua::{ffi, Function, Lua, LuaOptions, StdLib, Variadic};
+use crate::consts::APP_VERSION;
+use crate::plugins::{console, PluginError, PluginResult, PluginSource};
 use crate::plugins::manifest::PluginManifest;
+use crate::plugins::scopes::PluginScopes;
 
-pub fn build_lua_runtime(manifest: PluginManifest) -> Lua {
-    let mut lua = Lua::new();
-    let mut globals = lua.globals();
+/// The `noot.workspace` API, for reading and writing the files of the open workspace
+pub mod workspace;
+
+/// The `noot.register_theme` API, for adding themes the user can choose from
+pub mod theme;
+
+/// The `noot.commands` API, for adding commands to the command palette and binding keys to them
+pub mod commands;
+
+/// The `noot.markdown` API, for changing how notes are rendered
+pub mod markdown;
+
+/// The most memory a plugin may use before its VM refuses to allocate any more
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+/// The longest a plugin may run each time the app calls into it, see [limited]
+pub const TIME_LIMIT: Duration = Duration::from_secs(2);
+/// How many instructions a plugin runs between checks of whether it has run out of time
+const CHECK_INTERVAL: i32 = 10_000;
+
+thread_local!(
+    /// When the call being made into a plugin on this thread has to finish by
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+);
+
+/// Builds the VM a plugin runs within, exposing the `noot` host API.
+///
+/// Plugins are sandboxed, so only the standard libraries which cannot reach the host are available, and every host
+/// function checks the plugin holds the scope it needs before acting
+pub fn build_lua_runtime(manifest: &PluginManifest, scope: PluginScopes, source: &PluginSource, workspace: &Path) -> mlua::Result<Lua> {
+    let libraries = if scope.contains(PluginScopes::AVOID_SANDBOXES) {
+        StdLib::ALL_SAFE
+    } else {
+        StdLib::TABLE | StdLib::STRING | StdLib::UTF8 | StdLib::MATH | StdLib::COROUTINE
+    };
+
+    let lua = Lua::new_with(libraries, LuaOptions::default())?;
+    lua.set_memory_limit(MEMORY_LIMIT)?;
+    // Plugins are called from the UI thread and while notes are parsed, so one stuck in a loop would freeze the app.
+    // The hook is set on the VM directly, as coroutines inherit it from there but ignore hooks set through mlua
+    unsafe {
+        lua.exec_raw::<()>((), |state| ffi::lua_sethook(state, Some(time_limit_hook), ffi::LUA_MASKCOUNT, CHECK_INTERVAL))?;
+    }
+    let globals = lua.globals();
+
+    // The base library can still run files from anywhere on the host
+    globals.set("dofile", mlua::Nil)?;
+    globals.set("loadfile", mlua::Nil)?;
+
+    // Precompiled chunks are not checked by the VM, so crafted bytecode could escape the sandbox
+    globals.get::<mlua::Table>("string")?.set("dump", mlua::Nil)?;
+    lua.load(r##"
+        local load = ...
+        _G.load = function(chunk, name, _, ...)
+            -- An environment given as `nil` is still used, so it is only passed on when one was given
+            if select("#", ...) > 0 then return load(chunk, name, "t", ...) end
+            return load(chunk, name, "t")
+        end
+    "##).set_name("=text only").call::<()>(globals.get::<Function>("load")?)?;
+
+    // Running out of time raises an error, which a plugin stuck in a loop could otherwise catch and carry on
+    let timed_out = lua.create_function(|_, ()| Ok(DEADLINE.get().is_some_and(|deadline| Instant::now() > deadline)))?;
+    lua.load(r#"
+        local timed_out, pcall, xpcall, coroutine = ...
+        local function rethrow(ok, ...)
+            if not ok and timed_out() then error((...), 0) end
+            return ok, ...
+        end
+        _G.pcall = function(...) return rethrow(pcall(...)) end
+        _G.xpcall = function(...) return rethrow(xpcall(...)) end
+        if coroutine then
+            local resume = coroutine.resume
+            coroutine.resume = function(...) return rethrow(resume(...)) end
+        end
+    "#).set_name("=time limit").call::<()>((timed_out, globals.get::<Function>("pcall")?, globals.get::<Function>("xpcall")?, globals.get::<mlua::Value>("coroutine")?))?;
 
     // Create basic runtime functions
-    let info = lua.create_function(|_, strings: Variadic<String>| {
-        info!("{}", strings.join("\t"));
-        Ok(())
-    }).unwrap();
+    globals.set("info", log_function(&lua, &manifest.name, Level::Info)?)?;
 
-    globals.set("info", info).unwrap();
+    let noot = lua.create_table()?;
+    noot.set("version", APP_VERSION)?;
+    noot.set("plugin", manifest.name.clone())?;
+    noot.set("info", log_function(&lua, &manifest.name, Level::Info)?)?;
+    noot.set("warn", log_function(&lua, &manifest.name, Level::Warn)?)?;
+    noot.set("error", log_function(&lua, &manifest.name, Level::Error)?)?;
+    noot.set("workspace", workspace::build(&lua, scope, workspace)?)?;
+    noot.set("register_theme", theme::build(&lua, &manifest.name, scope, source)?)?;
+    noot.set("commands", commands::build(&lua, &manifest.name, scope)?)?;
+    noot.set("markdown", markdown::build(&lua, &manifest.name, scope)?)?;
 
-    // if manifest.scopes.contains("theme") {
-    //
-    // } else {
-    //
-    // }
-    // lua.create_function()
+    globals.set("noot", noot)?;
 
-    lua
+    Ok(lua)
 }
 
+/// Raises an error within a plugin once the call being made into it has run out of time
+unsafe extern "C-unwind" fn time_limit_hook(state: *mut ffi::lua_State, _: *mut ffi::lua_Debug) {
+    if DEADLINE.get().is_some_and(|deadline| Instant::now() > deadline) {
+        unsafe { ffi::luaL_error(state, c"The plugin ran out of time".as_ptr()) };
+    }
+}
 
+/// Calls into a plugin, stopping it with [PluginError::TimedOut] if it is still running after [TIME_LIMIT], eg: because
+/// it is stuck in a loop
+pub fn limited<R>(call: impl FnOnce() -> mlua::Result<R>) -> PluginResult<R> {
+    let deadline = Instant::now() + TIME_LIMIT;
+    // A plugin called while another is running has to finish within the time left to the outer call
+    let previous = DEADLINE.replace(Some(DEADLINE.get().map_or(deadline, |outer| outer.min(deadline))));
+    let outcome = call();
+    DEADLINE.set(previous);
 
+    match outcome {
+        Err(_) if Instant::now() > deadline => Err(PluginError::TimedOut),
+        outcome => outcome.map_err(PluginError::from),
+    }
+}
 
-fn register_theme() {
+/// Creates a function which logs its arguments to the developer console, tagged with the name of the plugin
+fn log_function(lua: &Lua, plugin: &str, level: Level) -> mlua::Result<Function> {
+    let plugin = plugin.to_string();
+    lua.create_function(move |_, strings: Variadic<String>| {
+        console::push(&plugin, level, strings.join("\t"));
+        Ok(())
+    })
+}
+
+/// Checks that a plugin holds a scope before a host function acts on its behalf
+pub(crate) fn require(granted: PluginScopes, needed: PluginScopes, function: &str) -> mlua::Result<()> {
+    if granted.contains(needed) {
+        Ok(())
+    } else {
+        Err(mlua::Error::runtime(format!("`noot.{}` requires the `{}` scope, which this plugin has not been granted", function, needed)))
+    }
+}
 
-}
\ No newline at end of file
@@ -0,0 +1,60 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use lightningcss::bundler::{FileProvider, SourceProvider};
+use mlua::{Function, Lua};
+use crate::config::appearance::ThemeVariant;
+use crate::plugins::PluginSource;
+use crate::plugins::runtime::require;
+use crate::plugins::scopes::PluginScopes;
+use crate::ui::theme::{Theme, THEMES};
+
+/// Reads the stylesheets of a theme from the files of the plugin registering it, so that `@import`s cannot reach
+/// anything outside of the plugin. Paths are relative to the root of the plugin
+struct ThemeFiles<'s> {
+    source: &'s PluginSource,
+    files: FileProvider,
+}
+
+impl SourceProvider for ThemeFiles<'_> {
+    type Error = io::Error;
+
+    fn read<'a>(&'a self, file: &Path) -> Result<&'a str, Self::Error> {
+        match self.source {
+            PluginSource::Directory(root) => self.files.read(&root.join(file)),
+            PluginSource::Builtin(plugin) => plugin.file(file)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' is not part of the plugin", file.display()))),
+        }
+    }
+
+    fn resolve(&self, specifier: &str, originating_file: &Path) -> Result<PathBuf, Self::Error> {
+        self.source.resolve(&originating_file.with_file_name(specifier))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::PermissionDenied, format!("'{}' is outside of the plugin", specifier)))
+    }
+}
+
+/// Builds `noot.register_theme(name, variant, path)`, which bundles the stylesheet at `path` within the plugin and
+/// adds it to the themes the user can choose from. `variant` is either `"Light"` or `"Dark"`. Requires `theme`
+pub fn build(lua: &Lua, plugin: &str, scope: PluginScopes, source: &PluginSource) -> mlua::Result<Function> {
+    let plugin = plugin.to_string();
+    let source = source.clone();
+
+    lua.create_function(move |_, (name, variant, path): (String, String, String)| {
+        require(scope, PluginScopes::THEME, "register_theme")?;
+        let variant = ThemeVariant::from_str(&variant).map_err(mlua::Error::runtime)?;
+        let entry = source.resolve(Path::new(&path))
+            .ok_or_else(|| mlua::Error::runtime(format!("'{}' is outside of the plugin", path)))?;
+
+        let mut themes = THEMES.lock().unwrap();
+        if themes.themes.get(name.as_str()).is_some_and(|theme| theme.plugin.as_deref() != Some(plugin.as_str())) {
+            return Err(mlua::Error::runtime(format!("A theme named '{}' is already registered", name)));
+        }
+
+        let files = ThemeFiles { source: &source, files: FileProvider::new() };
+        let mut theme = Theme::bundle(&name, variant, &files, &entry)
+            .map_err(|e| mlua::Error::runtime(format!("Unable to bundle '{}': {}", path, e)))?;
+        theme.plugin = Some(plugin.clone());
+        themes.add_theme(theme);
+        Ok(())
+    })
+}
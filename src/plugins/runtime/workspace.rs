@@ -0,0 +1,61 @@
+use std::path::{Component, Path, PathBuf};
+use mlua::{Lua, Table};
+use walkdir::WalkDir;
+use crate::plugins::runtime::require;
+use crate::plugins::scopes::PluginScopes;
+
+/// Builds the `noot.workspace` table.
+///
+/// - `files()` lists the files of the workspace, relative to its root. Requires `workspace.files.view`
+/// - `read(path)` returns the contents of a file. Requires `workspace.files.view`
+/// - `write(path, contents)` replaces the contents of a file. Requires `workspace.files.modify`
+pub fn build(lua: &Lua, scope: PluginScopes, workspace: &Path) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+
+    let root = workspace.to_path_buf();
+    table.set("files", lua.create_function(move |_, ()| {
+        require(scope, PluginScopes::SEE_WORKSPACE_FILES, "workspace.files")?;
+        Ok(files(&root))
+    })?)?;
+
+    let root = workspace.to_path_buf();
+    table.set("read", lua.create_function(move |_, path: String| {
+        require(scope, PluginScopes::SEE_WORKSPACE_FILES, "workspace.read")?;
+        std::fs::read_to_string(resolve(&root, &path)?).map_err(mlua::Error::external)
+    })?)?;
+
+    let root = workspace.to_path_buf();
+    table.set("write", lua.create_function(move |_, (path, contents): (String, String)| {
+        require(scope, PluginScopes::MODIFY_WORKSPACE_FILES, "workspace.write")?;
+        std::fs::write(resolve(&root, &path)?, contents).map_err(mlua::Error::external)
+    })?)?;
+
+    Ok(table)
+}
+
+/// Lists the files of the workspace, skipping hidden directories such as `.noot`, `.assets` and the plugins themselves
+fn files(root: &Path) -> Vec<String> {
+    WalkDir::new(root).into_iter()
+        .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| e.path().strip_prefix(root).ok().map(|p| p.to_string_lossy().replace('\\', "/")))
+        .collect()
+}
+
+/// Resolves a path given by a plugin against the workspace root.
+/// Paths which would leave the workspace, or reach into hidden directories, are refused
+fn resolve(root: &Path, path: &str) -> mlua::Result<PathBuf> {
+    let relative = Path::new(path);
+    let allowed = relative.components().all(|c| match c {
+        Component::Normal(name) => !name.to_string_lossy().starts_with('.'),
+        Component::CurDir => true,
+        _ => false,
+    });
+
+    if allowed {
+        Ok(root.join(relative))
+    } else {
+        Err(mlua::Error::runtime(format!("'{}' is not within the workspace", path)))
+    }
+}
@@ -1,29 +1,42 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use crate::plugins::PluginError;
+
 bitflags!(
     #[repr(transparent)]
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct PluginScopes: u64 {
-        const THEME = 1;
+        /// Allows a plugin to register themes
+        /// Scope name: `theme`
+        const THEME = 1 << 0;
 
-        /// Allows a plugin to see a list of the files in a workspace
+        /// Allows a plugin to see a list of the files in a workspace, and read them
         /// Scope name: `workspace.files.view`
-        const SEE_WORKSPACE_FILES    = 2;
+        const SEE_WORKSPACE_FILES    = 1 << 1;
 
-        /// Allows a plugin to list the contents of a file in the workspace
+        /// Allows a plugin to change the contents of a file in the workspace
         /// Scope name: `workspace.files.modify`
-        const MODIFY_WORKSPACE_FILES = 3;
+        const MODIFY_WORKSPACE_FILES = 1 << 2;
 
         /// Collector for all workspace file permissions
-        const WORKSPACE_FILES = 4;
+        /// Scope name: `workspace.files`
+        const WORKSPACE_FILES = Self::SEE_WORKSPACE_FILES.bits() | Self::MODIFY_WORKSPACE_FILES.bits();
 
         /// Allows a plugin to see the keybinds currently configured
         /// Scope name: `keybinds.view`
-        const SEE_KEYBINDS           = 5;
+        const SEE_KEYBINDS           = 1 << 3;
 
         /// Allows a plugin to add, remove, or modify keybinds
         /// Scope name: `keybinds.modify`
-        const MODIFY_KEYBINDS        = 6;
+        const MODIFY_KEYBINDS        = 1 << 4;
 
+        /// Collector for all keybind permissions
+        /// Scope name: `keybinds`
+        const KEYBINDS = Self::SEE_KEYBINDS.bits() | Self::MODIFY_KEYBINDS.bits();
 
+        /// Allows a plugin to change how notes are rendered, which lets it read every note as it is rendered
+        /// Scope name: `render`
+        const RENDER                 = 1 << 5;
 
 
 
@@ -32,6 +45,7 @@ bitflags!(
 
         /// Allows a plugin to access methods which may allow it to
         /// escape from sandboxing and affect the host machine
+        /// Scope name: `sandbox.avoid`
         ///
         /// > WARNING: Any plugin using this flag will be required to be enabled
         /// by the user for __every session__. Additionally, this plugin will be
@@ -42,7 +56,77 @@ bitflags!(
         /// this ability from any plugin, at any time, without notice to the maintainers.
         ///
         /// This scope is only available for non-enterprise builds of Noot.
-        const AVOID_SANDBOXES        = 9_223_372_036_854_775_808;
+        const AVOID_SANDBOXES        = 1 << 63;
     }
 );
 
+/// The name each scope is requested by within a plugin manifest.
+/// Collectors are listed after the scopes they contain, so that names are given for single scopes where possible
+const SCOPE_NAMES: [(&str, PluginScopes); 9] = [
+    ("theme", PluginScopes::THEME),
+    ("workspace.files.view", PluginScopes::SEE_WORKSPACE_FILES),
+    ("workspace.files.modify", PluginScopes::MODIFY_WORKSPACE_FILES),
+    ("workspace.files", PluginScopes::WORKSPACE_FILES),
+    ("keybinds.view", PluginScopes::SEE_KEYBINDS),
+    ("keybinds.modify", PluginScopes::MODIFY_KEYBINDS),
+    ("keybinds", PluginScopes::KEYBINDS),
+    ("render", PluginScopes::RENDER),
+    ("sandbox.avoid", PluginScopes::AVOID_SANDBOXES),
+];
+
+impl PluginScopes {
+    /// Returns the scope with the given manifest name, collectors included
+    pub fn from_scope_name(name: &str) -> Option<Self> {
+        SCOPE_NAMES.iter().find(|(n, _)| *n == name).map(|(_, scope)| *scope)
+    }
+
+    /// Returns the manifest names of the single scopes held, collectors being expanded into their members
+    pub fn scope_names(&self) -> Vec<&'static str> {
+        SCOPE_NAMES.iter()
+            .filter(|(_, scope)| scope.bits().is_power_of_two() && self.contains(*scope))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+
+    /// Parses every scope requested by a manifest, rejecting any names which are not scopes
+    pub fn parse<'a>(names: impl IntoIterator<Item = &'a str>) -> Result<Self, PluginError> {
+        let mut scopes = PluginScopes::empty();
+        let mut unknown = vec![];
+
+        for name in names {
+            match PluginScopes::from_scope_name(name) {
+                Some(scope) => scopes |= scope,
+                None => unknown.push(format!("'{}'", name)),
+            }
+        }
+
+        if unknown.is_empty() {
+            Ok(scopes)
+        } else {
+            let known: Vec<&str> = SCOPE_NAMES.iter().map(|(name, _)| *name).collect();
+            Err(PluginError::Manifest(format!(
+                "Unknown scope{} {}, expected one of: {}",
+                if unknown.len() == 1 { "" } else { "s" },
+                unknown.join(", "),
+                known.join(", ")
+            )))
+        }
+    }
+}
+
+impl FromStr for PluginScopes {
+    type Err = PluginError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        PluginScopes::parse([s])
+    }
+}
+
+impl Display for PluginScopes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.scope_names().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,52 @@
+//! Tests for the mapping between the scope names used in manifests and `PluginScopes`.
+
+use std::str::FromStr;
+use super::*;
+
+#[test]
+fn single_scopes_do_not_overlap() {
+    let singles: Vec<PluginScopes> = SCOPE_NAMES.iter()
+        .map(|(_, scope)| *scope)
+        .filter(|scope| scope.bits().is_power_of_two())
+        .collect();
+
+    for (i, scope) in singles.iter().enumerate() {
+        for other in &singles[i + 1..] {
+            assert!(!scope.intersects(*other), "{:?} overlaps {:?}", scope, other);
+        }
+    }
+}
+
+#[test]
+fn names_map_both_ways() {
+    for (name, scope) in SCOPE_NAMES {
+        assert_eq!(PluginScopes::from_scope_name(name), Some(scope));
+        if scope.bits().is_power_of_two() {
+            assert_eq!(scope.scope_names(), vec![name]);
+        }
+    }
+
+    assert_eq!(PluginScopes::from_str("workspace.files.view").unwrap(), PluginScopes::SEE_WORKSPACE_FILES);
+    assert_eq!(PluginScopes::from_scope_name("THEME"), None);
+}
+
+#[test]
+fn collectors_expand_to_their_members() {
+    let scopes = PluginScopes::parse(["workspace.files", "keybinds.view"]).unwrap();
+
+    assert!(scopes.contains(PluginScopes::SEE_WORKSPACE_FILES | PluginScopes::MODIFY_WORKSPACE_FILES));
+    assert!(!scopes.contains(PluginScopes::MODIFY_KEYBINDS));
+    assert_eq!(scopes.scope_names(), vec!["workspace.files.view", "workspace.files.modify", "keybinds.view"]);
+    assert_eq!(scopes.to_string(), "workspace.files.view, workspace.files.modify, keybinds.view");
+}
+
+#[test]
+fn unknown_scopes_are_rejected() {
+    assert_eq!(PluginScopes::parse([]).unwrap(), PluginScopes::empty());
+
+    let error = PluginScopes::parse(["theme", "files.delete", "network"]).unwrap_err().to_string();
+    assert!(error.starts_with("Invalid plugin manifest: Unknown scopes 'files.delete', 'network', expected one of: theme,"), "{}", error);
+
+    let error = PluginScopes::from_str("keybinds.delete").unwrap_err().to_string();
+    assert!(error.contains("Unknown scope 'keybinds.delete'"), "{}", error);
+}
@@ -0,0 +1,537 @@
+//! Tests for installing and loading plugins.
+//! Each test publishes a plugin to a git repository in a temporary directory, and installs it into a workspace beside it.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use nanoid::nanoid;
+use rusqlite::Connection;
+use crate::plugins::install::{PluginKind, PluginUpdate};
+use crate::plugins::manifest::MANIFEST_FILE;
+use crate::storage::workspace::buffer::Buffer;
+use super::*;
+
+/// Creates a workspace holding a note, with the test plugin installed into it
+fn workspace(scopes: &[&str], script: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("noot-plugins-{}", nanoid!(5)));
+    std::fs::create_dir_all(root.join(".noot")).unwrap();
+    std::fs::write(root.join("note.md"), "# Hello").unwrap();
+
+    publish(&root, "0.1.0", scopes, script);
+    install::install(&db(&root), &directory(&root), &source(&root).to_string_lossy()).unwrap();
+    root
+}
+
+/// The repository the test plugin of a workspace is published to
+fn source(root: &Path) -> PathBuf {
+    root.with_extension("source")
+}
+
+fn directory(root: &Path) -> PathBuf {
+    root.join(DEFAULT_PLUGIN_DIRECTORY)
+}
+
+fn db(root: &Path) -> Connection {
+    open_database(root).unwrap()
+}
+
+fn git(dir: &Path, args: &[&str]) {
+    let status = Command::new("git").current_dir(dir)
+        .args(["-c", "user.name=Noot", "-c", "user.email=noot@localhost"])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+/// Commits a version of the test plugin to its repository
+fn publish(root: &Path, version: &str, scopes: &[&str], script: &str) {
+    let plugin = source(root);
+    if !plugin.exists() {
+        std::fs::create_dir_all(&plugin).unwrap();
+        git(&plugin, &["init", "--quiet"]);
+    }
+
+    let scopes: String = scopes.iter().map(|s| format!("\n  - {}", s)).collect();
+    std::fs::write(plugin.join(MANIFEST_FILE), format!(
+        "name: test\nversion: {}\ndescription: A test plugin\nentrypoint: main.lua\nauthors: []\nscopes:{}",
+        version,
+        if scopes.is_empty() { " []".to_string() } else { scopes }
+    )).unwrap();
+    std::fs::write(plugin.join("main.lua"), script).unwrap();
+
+    git(&plugin, &["add", "--all"]);
+    git(&plugin, &["commit", "--quiet", "--message", version]);
+}
+
+/// Updates the installed test plugin to the latest version published
+fn update(root: &Path) -> PluginUpdate {
+    let update = install::check_update(&db(root), &directory(root), "test").unwrap();
+    install::update(&db(root), &directory(root), &update).unwrap();
+    update
+}
+
+/// Loads the plugins of a workspace, granting every scope they ask for
+fn load(root: &Path) -> PluginManager {
+    let mut manager = PluginManager::new();
+    for request in manager.load_plugins(&directory(root), root) {
+        manager.consent(&request.manifest.name, true);
+    }
+    manager
+}
+
+fn error(manager: &PluginManager) -> Option<String> {
+    match &manager.plugins["test"].state {
+        PluginState::Errored(e) => Some(e.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn example_plugin_loads() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut plugin = Plugin::read(&root.join("example-plugin")).unwrap();
+    plugin.start(root);
+
+    assert_eq!(plugin.manifest.entrypoint, "main.lua");
+    assert!(matches!(plugin.state, PluginState::Loaded));
+    assert!(plugin.scope.contains(PluginScopes::SEE_WORKSPACE_FILES));
+}
+
+#[test]
+fn host_functions_check_scopes() {
+    let root = workspace(&[], "noot.workspace.files()");
+    let denied = error(&load(&root)).unwrap();
+    assert!(denied.contains("`noot.workspace.files` requires"), "{}", denied);
+
+    let root = workspace(&["workspace.files.view"], r##"
+        local files = noot.workspace.files()
+        assert(#files == 1 and files[1] == "note.md")
+        assert(noot.workspace.read("note.md") == "# Hello")
+        noot.workspace.write("note.md", "# Changed")
+    "##);
+    let denied = error(&load(&root)).unwrap();
+    assert!(denied.contains("`noot.workspace.write` requires"), "{}", denied);
+    assert_eq!(std::fs::read_to_string(root.join("note.md")).unwrap(), "# Hello");
+}
+
+#[test]
+fn plugins_are_sandboxed() {
+    let root = workspace(&["workspace.files.view"], r#"
+        assert(io == nil and os == nil and require == nil and dofile == nil)
+        assert(not pcall(function() return load(string.dump(function() return 42 end))() end))
+        assert(load("return 42")() == 42 and load("return x", "env", "b", { x = 42 })() == 42)
+        assert(not pcall(noot.workspace.read, "../outside.md"))
+        assert(not pcall(noot.workspace.read, ".noot/workspace.db"))
+    "#);
+    assert_eq!(error(&load(&root)), None);
+}
+
+#[test]
+fn updates_with_unknown_scopes_are_refused() {
+    let root = workspace(&[], "");
+    publish(&root, "0.2.0", &["workspace.files.delete"], "");
+
+    let error = Plugin::read(&source(&root)).err().unwrap();
+    assert!(error.to_string().contains("Unknown scope 'workspace.files.delete'"), "{}", error);
+
+    let error = install::check_update(&db(&root), &directory(&root), "test").unwrap_err();
+    assert!(error.to_string().contains("Unknown scope 'workspace.files.delete'"), "{}", error);
+}
+
+#[test]
+fn plugins_wait_for_consent() {
+    let root = workspace(&["workspace.files.view"], "noot.workspace.write('note.md', 'Started')");
+    let directory = directory(&root);
+
+    let mut manager = PluginManager::new();
+    let requests = manager.load_plugins(&directory, &root);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].added, PluginScopes::SEE_WORKSPACE_FILES);
+    assert!(matches!(manager.plugins["test"].state, PluginState::AwaitingConsent(_)));
+
+    manager.consent("test", false);
+    assert!(matches!(manager.plugins["test"].state, PluginState::NotLoaded));
+    assert_eq!(manager.load_plugins(&directory, &root).len(), 1);
+
+    manager.consent("test", true);
+    assert!(matches!(manager.plugins["test"].state, PluginState::Errored(_)));
+    assert!(PluginManager::new().load_plugins(&directory, &root).is_empty());
+}
+
+#[test]
+fn updates_which_add_scopes_ask_again() {
+    let root = workspace(&["workspace.files.view"], "");
+    let directory = directory(&root);
+    load(&root);
+
+    // Updates asking for nothing new keep the scopes granted to the earlier version
+    publish(&root, "0.2.0", &["workspace.files.view"], "");
+    update(&root);
+    assert!(PluginManager::new().load_plugins(&directory, &root).is_empty());
+
+    publish(&root, "0.3.0", &["workspace.files.view", "workspace.files.modify"], "");
+    update(&root);
+    let requests = PluginManager::new().load_plugins(&directory, &root);
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].added, PluginScopes::MODIFY_WORKSPACE_FILES);
+    assert_eq!(requests[0].requested, PluginScopes::WORKSPACE_FILES);
+}
+
+#[test]
+fn avoiding_sandboxes_is_approved_every_session() {
+    let root = workspace(&["sandbox.avoid"], "assert(io ~= nil)");
+    let directory = directory(&root);
+
+    let mut manager = PluginManager::new();
+    assert_eq!(manager.load_plugins(&directory, &root)[0].added, PluginScopes::AVOID_SANDBOXES);
+    manager.consent("test", true);
+    assert!(matches!(manager.plugins["test"].state, PluginState::Loaded));
+
+    // Reloading within the same session does not ask again, but a new session does
+    assert!(manager.load_plugins(&directory, &root).is_empty());
+    assert_eq!(PluginManager::new().load_plugins(&directory, &root).len(), 1);
+}
+
+#[test]
+fn plugins_are_pinned_to_a_commit() {
+    let root = workspace(&["workspace.files.view"], "");
+    let installed = directory(&root).join("test");
+
+    // Changing the files of a plugin stops it from loading, until they match its commit again
+    std::fs::write(installed.join("main.lua"), "noot.workspace.write('note.md', '')").unwrap();
+    let changed = error(&load(&root)).unwrap();
+    assert!(changed.contains("have been changed since it was installed"), "{}", changed);
+    git(&installed, &["checkout", "--quiet", "--", "."]);
+    assert!(matches!(load(&root).plugins["test"].state, PluginState::Loaded));
+
+    // New commits are not loaded until the plugin is updated
+    publish(&root, "0.2.0", &["workspace.files.modify"], "");
+    assert_eq!(load(&root).plugins["test"].manifest.version.to_string(), "0.1.0");
+
+    let update = update(&root);
+    assert_eq!(update.from.version.to_string(), "0.1.0");
+    assert_eq!(update.added, PluginScopes::MODIFY_WORKSPACE_FILES);
+    assert_eq!(update.removed, PluginScopes::SEE_WORKSPACE_FILES);
+    assert_eq!(install::find(&db(&root), "test").unwrap().pin, Some(update.commit));
+    assert_eq!(load(&root).plugins["test"].manifest.version.to_string(), "0.2.0");
+
+    // Checking out another commit by hand does not move the pin
+    git(&installed, &["checkout", "--quiet", "--detach", "HEAD~1"]);
+    let moved = error(&load(&root)).unwrap();
+    assert!(moved.contains("but is pinned to"), "{}", moved);
+}
+
+#[test]
+fn plugin_repositories_cannot_run_commands() {
+    let root = workspace(&["workspace.files.view"], "");
+    let installed = directory(&root).join("test");
+    let marker = root.join("ran");
+
+    // A plugin directory planted within a workspace could carry a config which runs commands whenever git is used
+    git(&installed, &["config", "core.fsmonitor", &format!("touch '{}'", marker.display())]);
+    let hooks = root.with_extension("hooks");
+    git(&installed, &["config", "core.hooksPath", &hooks.to_string_lossy()]);
+    std::fs::create_dir_all(&hooks).unwrap();
+    std::fs::write(hooks.join("post-checkout"), format!("#!/bin/sh\ntouch '{}'", marker.display())).unwrap();
+    #[cfg(unix)]
+    std::fs::set_permissions(hooks.join("post-checkout"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+    git(&installed, &["config", "remote.origin.uploadpack", &format!("touch '{}'; git-upload-pack", marker.display())]);
+
+    let refused = error(&load(&root)).unwrap();
+    assert!(refused.contains("has the setting 'core.fsmonitor'"), "{}", refused);
+
+    publish(&root, "0.2.0", &["workspace.files.view"], "");
+    let error = install::check_update(&db(&root), &directory(&root), "test").unwrap_err();
+    assert!(error.to_string().contains("which noot did not write"), "{}", error);
+    assert!(!marker.exists());
+}
+
+#[test]
+fn plugin_repositories_cannot_run_filters() {
+    let root = workspace(&["workspace.files.view"], "");
+    let installed = directory(&root).join("test");
+    let marker = root.join("ran");
+
+    git(&installed, &["config", "filter.x.clean", &format!("sh -c 'touch \"{}\"; cat'", marker.display())]);
+    std::fs::write(installed.join(".git/info/attributes"), "*.lua filter=x\n").unwrap();
+    // Files which look changed are hashed again by git, which is when filters run
+    std::fs::write(installed.join("main.lua"), std::fs::read(installed.join("main.lua")).unwrap()).unwrap();
+
+    let refused = error(&load(&root)).unwrap();
+    assert!(refused.contains("has the setting 'filter.x.clean'"), "{}", refused);
+    assert!(!marker.exists());
+
+    // Without the setting, the file still matches the commit it is pinned to
+    git(&installed, &["config", "--unset", "filter.x.clean"]);
+    assert!(matches!(load(&root).plugins["test"].state, PluginState::Loaded));
+    assert!(!marker.exists());
+}
+
+#[test]
+fn plugins_can_be_uninstalled() {
+    let root = workspace(&["workspace.files.view"], "");
+    let url = source(&root).to_string_lossy().to_string();
+    load(&root);
+
+    let error = install::install(&db(&root), &directory(&root), &url).unwrap_err();
+    assert!(error.to_string().contains("already installed as 'test'"), "{}", error);
+
+    install::uninstall(&db(&root), &directory(&root), "test").unwrap();
+    assert!(!directory(&root).join("test").exists());
+    assert!(install::installed(&db(&root)).unwrap().is_empty());
+    assert_eq!(grants::granted(&db(&root), "test", &"0.1.0".parse().unwrap()).unwrap(), None);
+
+    install::install(&db(&root), &directory(&root), &url).unwrap();
+    assert_eq!(PluginManager::new().load_plugins(&directory(&root), &root).len(), 1);
+}
+
+/// Writes an unpacked plugin beside a workspace, returning its directory and its name, which is unique so that its
+/// lines in the shared console can be told apart from those of other tests
+fn unpacked(root: &Path, scopes: &str, script: &str) -> (PathBuf, String) {
+    let name = format!("dev-{}", nanoid!(5));
+    let dir = root.with_extension("unpacked");
+    write_unpacked(&dir, &name, scopes, script);
+    (dir, name)
+}
+
+fn write_unpacked(dir: &Path, name: &str, scopes: &str, script: &str) {
+    std::fs::create_dir_all(dir).unwrap();
+    std::fs::write(dir.join(MANIFEST_FILE), format!(
+        "name: {}\nversion: 0.1.0\ndescription: An unpacked plugin\nentrypoint: main.lua\nauthors: []\nscopes: {}", name, scopes
+    )).unwrap();
+    std::fs::write(dir.join("main.lua"), script).unwrap();
+}
+
+fn logged(name: &str) -> Vec<String> {
+    console::lines().into_iter().filter(|l| l.plugin == name).map(|l| l.message).collect()
+}
+
+#[test]
+fn unpacked_plugins_are_only_loaded_when_allowed() {
+    let root = workspace(&[], "");
+    let (dir, name) = unpacked(&root, "[]", "noot.info('Hello', 'world')");
+    let added = install::add_unpacked(&db(&root), &dir).unwrap();
+    assert_eq!(added.kind, PluginKind::Unpacked);
+    assert_eq!(added.pin, None);
+
+    let mut manager = PluginManager::new();
+    assert!(manager.load_plugins(&directory(&root), &root).is_empty());
+    assert!(!manager.plugins.contains_key(&name));
+    assert!(logged(&name).is_empty());
+
+    manager.allow_unpacked(true);
+    assert!(manager.load_plugins(&directory(&root), &root).is_empty());
+    assert!(matches!(manager.plugins[&name].state, PluginState::Loaded));
+    assert!(matches!(manager.plugins["test"].state, PluginState::Loaded));
+    assert_eq!(logged(&name), vec!["Hello\tworld"]);
+
+    // Uninstalling an unpacked plugin leaves its files alone
+    install::uninstall(&db(&root), &directory(&root), &name).unwrap();
+    assert!(dir.join("main.lua").exists());
+}
+
+#[test]
+fn unpacked_plugins_are_reloaded() {
+    let root = workspace(&[], "");
+    let (dir, name) = unpacked(&root, "[]", "noot.warn('First')");
+    install::add_unpacked(&db(&root), &dir).unwrap();
+
+    let mut manager = PluginManager::new();
+    manager.allow_unpacked(true);
+    manager.load_plugins(&directory(&root), &root);
+    assert_eq!(manager.reload("test").map(|r| r.added), None);
+
+    write_unpacked(&dir, &name, "[]", "error('Second')");
+    assert!(manager.reload(&name).is_none());
+    assert!(matches!(manager.plugins[&name].state, PluginState::Errored(_)));
+
+    // Asking for more scopes while being developed still needs the user to grant them
+    write_unpacked(&dir, &name, "[workspace.files.view]", "noot.info(noot.workspace.read('note.md'))");
+    assert_eq!(manager.reload(&name).unwrap().added, PluginScopes::SEE_WORKSPACE_FILES);
+    manager.consent(&name, true);
+
+    let lines = logged(&name);
+    assert_eq!(lines.first().map(String::as_str), Some("First"));
+    assert!(lines.iter().any(|l| l.starts_with("Failed to start") && l.contains("Second")), "{:?}", lines);
+    assert_eq!(lines.last().map(String::as_str), Some("# Hello"));
+}
+
+#[test]
+fn builtin_themes_are_registered() {
+    let mut manager = PluginManager::new();
+    manager.load_builtin();
+    assert!(matches!(manager.plugins["noot-themes"].state, PluginState::Loaded));
+    assert!(THEMES.lock().unwrap().names().contains(&"Molten Core".to_string()));
+
+    // Built in plugins are kept when the plugins of a workspace are loaded
+    let root = workspace(&[], "");
+    manager.load_plugins(&directory(&root), &root);
+    assert!(manager.plugins.contains_key("noot-themes"));
+}
+
+#[test]
+fn plugins_register_themes_with_the_theme_scope() {
+    let root = workspace(&[], "");
+    let register = "noot.register_theme(noot.plugin, 'Light', 'theme.css')";
+    let (dir, name) = unpacked(&root, "[]", register);
+    std::fs::write(dir.join("theme.css"), "@import 'colours.css';\nbody { color: var(--text); }").unwrap();
+    std::fs::write(dir.join("colours.css"), ":root { --text: #101010; }").unwrap();
+    install::add_unpacked(&db(&root), &dir).unwrap();
+
+    let mut manager = PluginManager::new();
+    manager.allow_unpacked(true);
+    manager.load_plugins(&directory(&root), &root);
+    let PluginState::Errored(denied) = &manager.plugins[&name].state else { panic!("The theme was registered") };
+    assert!(denied.contains("`noot.register_theme` requires the `theme` scope"), "{}", denied);
+    assert!(!THEMES.lock().unwrap().names().contains(&name));
+
+    write_unpacked(&dir, &name, "[theme]", register);
+    manager.reload(&name).unwrap();
+    manager.consent(&name, true);
+    assert!(matches!(manager.plugins[&name].state, PluginState::Loaded));
+    assert!(THEMES.lock().unwrap().names().contains(&name));
+
+    // Themes cannot import files from outside of the plugin, and are removed along with it
+    std::fs::write(dir.join("theme.css"), "@import '../note.md';").unwrap();
+    manager.reload(&name);
+    let PluginState::Errored(denied) = &manager.plugins[&name].state else { panic!("The import was allowed") };
+    assert!(denied.contains("outside of the plugin"), "{}", denied);
+    assert!(!THEMES.lock().unwrap().names().contains(&name));
+}
+
+#[test]
+fn plugins_register_commands_and_bind_keys_with_the_keybind_scopes() {
+    let root = workspace(&[], "");
+    let script = r#"
+        local id = noot.commands.register("hello", "Say hello", function() noot.info("Hello from a command") end)
+        noot.info(id)
+        noot.commands.bind("Ctrl+Alt+H", "hello")
+    "#;
+    let (dir, name) = unpacked(&root, "[]", script);
+    install::add_unpacked(&db(&root), &dir).unwrap();
+    let command = format!("{}.hello", name);
+
+    let mut manager = PluginManager::new();
+    manager.allow_unpacked(true);
+    manager.load_plugins(&directory(&root), &root);
+    let PluginState::Errored(denied) = &manager.plugins[&name].state else { panic!("The keys were bound") };
+    assert!(denied.contains("`noot.commands.bind` requires the `keybinds.modify` scope"), "{}", denied);
+    assert_eq!(logged(&name).first(), Some(&command));
+
+    let script = format!("{}\nlocal binds = noot.commands.keybinds()\nassert(binds['ctrl+alt+h'] == '{}')", script, command);
+    write_unpacked(&dir, &name, "[keybinds]", &script);
+    manager.reload(&name).unwrap();
+    manager.consent(&name, true);
+    assert!(matches!(manager.plugins[&name].state, PluginState::Loaded));
+    assert_eq!(COMMANDS.lock().unwrap().bound(&"ctrl+alt+h".parse().unwrap()), Some(command.as_str()));
+
+    manager.run_command(&name, &command);
+    assert_eq!(logged(&name).last().map(String::as_str), Some("Hello from a command"));
+
+    // Unloading the plugin removes its commands along with the keys bound to them
+    install::uninstall(&db(&root), &directory(&root), &name).unwrap();
+    manager.load_plugins(&directory(&root), &root);
+    assert!(COMMANDS.lock().unwrap().get(&command).is_none());
+    assert_eq!(COMMANDS.lock().unwrap().bound(&"ctrl+alt+h".parse().unwrap()), None);
+}
+
+#[test]
+fn plugins_render_code_blocks_and_transform_blocks_with_the_render_scope() {
+    let root = workspace(&[], "");
+    let script = r#"
+        noot.markdown.code_block(noot.plugin, function(source, lang)
+            if source == "plain\n" then return nil end
+            return {
+                { type = "text", text = "Note: ", bold = true },
+                { type = "text", text = source, link = "https://example.com" },
+                { type = "image", src = "callout.png", alt = lang },
+                { type = "table", head = { "Key" }, rows = { { "Value" }, { { { type = "text", text = "Styled", italic = true } } } } },
+            }
+        end)
+
+        noot.markdown.transform(function(el)
+            local first = el.children[1]
+            if el.name ~= "p" or not first or first.text ~= noot.plugin then return nil end
+            return { name = "p", children = { { name = "a", attributes = { href = "https://tickets/" .. first.text }, children = { first } } } }
+        end)
+    "#;
+    let (dir, name) = unpacked(&root, "[]", script);
+    install::add_unpacked(&db(&root), &dir).unwrap();
+    let note = format!("```{0}\ncallout\n```\n\n{0}\n\n```{0}\nplain\n```", name);
+    let render = || Buffer::render_md("note".to_string(), "workspace".to_string(), format!("file://{}", root.join("note.md").display()), note.clone()).doc;
+
+    let mut manager = PluginManager::new();
+    manager.allow_unpacked(true);
+    manager.load_plugins(&directory(&root), &root);
+    let PluginState::Errored(denied) = &manager.plugins[&name].state else { panic!("The code block was claimed") };
+    assert!(denied.contains("`noot.markdown.code_block` requires the `render` scope"), "{}", denied);
+    assert_eq!(render()[0].name, "pre");
+
+    write_unpacked(&dir, &name, "[render]", script);
+    manager.reload(&name).unwrap();
+    manager.consent(&name, true);
+    assert!(matches!(manager.plugins[&name].state, PluginState::Loaded));
+
+    let doc = render();
+    let block = &doc[0];
+    assert_eq!((block.name.as_str(), block.attribute("plugin")), ("div", name.clone()));
+    let names: Vec<&str> = block.children.iter().map(|el| el.name.as_str()).collect();
+    assert_eq!(names, ["p", "p", "table"]);
+    assert_eq!(block.children[0].children[1].attribute("href"), "https://example.com");
+    let image = &block.children[1].children[0];
+    assert_eq!(image.attribute("src"), format!("file://{}", root.join("callout.png").display()));
+    assert!(image.attributes.contains_key("cached-src"));
+    assert_eq!(block.children[2].children[1].children[1].children[0].children[0].name, "em");
+
+    // Blocks the plugin chooses not to render are drawn as they are
+    assert_eq!(doc[1].children[0].attribute("href"), format!("https://tickets/{}", name));
+    assert_eq!(doc[2].name, "pre");
+
+    // Notes parsed to find what they reference, eg: by garbage collection, are left as they are written
+    let written = Buffer::from_md("note".to_string(), "workspace".to_string(), format!("file://{}", root.join("note.md").display()), note.clone());
+    assert_eq!((written.doc[0].name.as_str(), written.doc[1].children[0].name.as_str()), ("pre", "TEXT"));
+    assert!(written.image_sources().is_empty());
+
+    // Unloading the plugin removes its hooks
+    install::uninstall(&db(&root), &directory(&root), &name).unwrap();
+    manager.load_plugins(&directory(&root), &root);
+    let doc = render();
+    assert_eq!((doc[0].name.as_str(), doc[1].children[0].name.as_str()), ("pre", "TEXT"));
+}
+
+#[test]
+fn plugins_which_run_out_of_time_are_stopped() {
+    let root = workspace(&[], "while true do end");
+    let manager = load(&root);
+    assert_eq!(error(&manager), Some(PluginError::TimedOut.to_string()));
+
+    // Catching the error does not let a plugin keep running
+    let root = workspace(&[], "while true do pcall(function() while true do end end) end");
+    assert_eq!(error(&load(&root)), Some(PluginError::TimedOut.to_string()));
+    let root = workspace(&[], "while true do coroutine.resume(coroutine.create(function() while true do end end)) end");
+    assert_eq!(error(&load(&root)), Some(PluginError::TimedOut.to_string()));
+
+    let script = r#"
+        noot.commands.register("spin", "Spin forever", function() while true do end end)
+        noot.markdown.code_block(noot.plugin, function() while true do end end)
+    "#;
+    let (dir, name) = unpacked(&root, "[render]", script);
+    install::add_unpacked(&db(&root), &dir).unwrap();
+    let mut manager = PluginManager::new();
+    manager.allow_unpacked(true);
+    for request in manager.load_plugins(&directory(&root), &root) {
+        manager.consent(&request.manifest.name, true);
+    }
+    assert!(matches!(manager.plugins[&name].state, PluginState::Loaded));
+
+    // Rendering carries on without the plugin, which cannot hold it up again
+    let note = format!("```{}\nspin\n```", name);
+    let render = || Buffer::render_md("note".to_string(), "workspace".to_string(), format!("file://{}", root.join("note.md").display()), note.clone()).doc;
+    assert_eq!(render()[0].name, "pre");
+    assert!(RENDER_HOOKS.lock().unwrap().code_block(&name).is_none());
+
+    manager.run_command(&name, &format!("{}.spin", name));
+    let PluginState::Errored(stopped) = &manager.plugins[&name].state else { panic!("The plugin is still loaded") };
+    assert_eq!(stopped, &PluginError::TimedOut.to_string());
+    assert!(COMMANDS.lock().unwrap().get(&format!("{}.spin", name)).is_none());
+}
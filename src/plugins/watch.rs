@@ -0,0 +1,82 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream, StreamExt};
+use iced::{stream, Subscription};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use crate::runtime::messaging::{Message, MessageKind};
+
+/// How long to wait for a burst of changes to settle, as editors often write a file several times when saving it
+const SETTLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches the directories of unpacked plugins, sending `PluginChanged` once any of their files change.
+/// The watcher is restarted whenever the plugins being watched change
+pub fn subscription(plugins: &BTreeMap<String, PathBuf>) -> Subscription<Message> {
+    if plugins.is_empty() {
+        return Subscription::none();
+    }
+
+    Subscription::run_with_id(plugins.clone(), watch(plugins.clone()))
+}
+
+fn watch(plugins: BTreeMap<String, PathBuf>) -> impl Stream<Item = Message> {
+    stream::channel(1, |mut output| async move {
+        let (mut tx, mut events) = mpsc::channel::<notify::Result<Event>>(32);
+
+        let mut watcher = match notify::recommended_watcher(move |event| { let _ = tx.try_send(event); }) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!("Unable to watch unpacked plugins: {}", e);
+                return;
+            }
+        };
+
+        for (name, dir) in &plugins {
+            match watcher.watch(dir, RecursiveMode::Recursive) {
+                Ok(_) => info!("Watching unpacked plugin '{}' at {}", name, dir.display()),
+                Err(e) => error!("Unable to watch unpacked plugin '{}': {}", name, e),
+            }
+        }
+
+        while let Some(event) = events.next().await {
+            let mut changed = BTreeSet::new();
+            changed.extend(changed_plugins(&plugins, event));
+
+            tokio::time::sleep(SETTLE_INTERVAL).await;
+            while let Ok(Some(event)) = events.try_next() {
+                changed.extend(changed_plugins(&plugins, event));
+            }
+
+            for name in changed {
+                if let Err(e) = output.send(Message::new(MessageKind::PluginChanged(name), None)).await {
+                    error!("{}", e);
+                }
+            }
+        }
+    })
+}
+
+/// Returns the plugins whose files an event changed, ignoring hidden files such as `.git` or editor swap files
+fn changed_plugins(plugins: &BTreeMap<String, PathBuf>, event: notify::Result<Event>) -> Vec<String> {
+    let event = match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => event,
+        Ok(_) => return vec![],
+        Err(e) => {
+            error!("Unable to watch unpacked plugins: {}", e);
+            return vec![];
+        }
+    };
+
+    plugins.iter()
+        .filter(|(_, dir)| event.paths.iter().any(|path| is_visible_within(path, dir)))
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
+fn is_visible_within(path: &Path, dir: &Path) -> bool {
+    match path.strip_prefix(dir) {
+        Ok(relative) => !relative.components().any(|c| matches!(c, Component::Normal(name) if name.to_string_lossy().starts_with('.'))),
+        Err(_) => false,
+    }
+}
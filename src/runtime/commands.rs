@@ -0,0 +1,280 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::sync::Mutex;
+use iced::keyboard::{self, Key, Modifiers};
+use iced::window::Id;
+use iced::{event, Event};
+use lazy_static::lazy_static;
+use rust_i18n::t;
+use crate::runtime::messaging::{Message, MessageKind};
+
+#[cfg(test)]
+mod tests;
+
+lazy_static!(
+    /// Every command which can be run from the command palette or a key binding
+    pub static ref COMMANDS: Mutex<CommandRegistry> = Mutex::new(CommandRegistry::new());
+);
+
+/// The actions built into the app, which are always registered
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoreCommand {
+    OpenWorkspace,
+    ToggleDyslexia,
+    /// Writes the note shown in the focused editor back to disk
+    Save,
+    CommandPalette,
+}
+
+impl CoreCommand {
+    pub const ALL: [CoreCommand; 4] = [CoreCommand::OpenWorkspace, CoreCommand::ToggleDyslexia, CoreCommand::Save, CoreCommand::CommandPalette];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            CoreCommand::OpenWorkspace => "core.open-workspace",
+            CoreCommand::ToggleDyslexia => "core.toggle-dyslexia",
+            CoreCommand::Save => "core.save",
+            CoreCommand::CommandPalette => "core.command-palette",
+        }
+    }
+
+    fn title(&self) -> String {
+        match self {
+            CoreCommand::OpenWorkspace => t!("commands.open-workspace"),
+            CoreCommand::ToggleDyslexia => t!("commands.toggle-dyslexia"),
+            CoreCommand::Save => t!("commands.save"),
+            CoreCommand::CommandPalette => t!("commands.command-palette"),
+        }.to_string()
+    }
+
+    /// The keys the command is bound to until a plugin binds them to something else.
+    /// Dyslexia mode is toggled by a global hotkey instead, so that it also works while no window is focused
+    fn default_keys(&self) -> Option<&'static str> {
+        match self {
+            CoreCommand::OpenWorkspace => Some("ctrl+o"),
+            CoreCommand::ToggleDyslexia => None,
+            CoreCommand::Save => Some("ctrl+s"),
+            CoreCommand::CommandPalette => Some("ctrl+shift+p"),
+        }
+    }
+}
+
+/// What running a command does
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandAction {
+    Core(CoreCommand),
+    /// Calls the function the plugin with this name registered the command with
+    Plugin(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct Command {
+    /// Identifies the command, plugin commands are prefixed with the name of the plugin, eg: `tickets.open`
+    pub id: String,
+    pub title: String,
+    pub action: CommandAction,
+}
+
+/// A key along with the modifiers held while pressing it, eg: `ctrl+shift+p`.
+/// Combinations are kept in lowercase with their modifiers in a fixed order, so that they compare equal however they
+/// were written
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyCombo(String);
+
+impl KeyCombo {
+    /// Builds the combination for a key pressed within a window.
+    /// Returns `None` for modifiers pressed on their own
+    pub fn from_key(key: &Key, modifiers: Modifiers) -> Option<Self> {
+        let name = match key {
+            Key::Character(c) => c.to_string(),
+            Key::Named(keyboard::key::Named::Control | keyboard::key::Named::Alt | keyboard::key::Named::Shift | keyboard::key::Named::Super) => return None,
+            Key::Named(named) => format!("{:?}", named),
+            Key::Unidentified => return None,
+        };
+
+        let held = [
+            (modifiers.control(), "ctrl"),
+            (modifiers.alt(), "alt"),
+            (modifiers.shift(), "shift"),
+            (modifiers.logo(), "super"),
+        ];
+        let mut parts: Vec<&str> = held.iter().filter(|(pressed, _)| *pressed).map(|(_, name)| *name).collect();
+        parts.push(&name);
+        Some(Self(parts.join("+").to_lowercase()))
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    /// Parses a combination written by a plugin, eg: `Ctrl+Shift+P`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<String> = s.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let Some((key, modifiers)) = parts.split_last().filter(|(key, _)| !key.is_empty()) else {
+            return Err(format!("'{}' does not name a key", s));
+        };
+
+        let mut ordered = vec![];
+        for modifier in ["ctrl", "alt", "shift", "super"] {
+            if modifiers.iter().any(|m| m == modifier || (modifier == "ctrl" && m == "control")) {
+                ordered.push(modifier);
+            }
+        }
+        if let Some(unknown) = modifiers.iter().find(|m| !["ctrl", "control", "alt", "shift", "super"].contains(&m.as_str())) {
+            return Err(format!("Unknown modifier '{}' in '{}', expected one of: Ctrl, Alt, Shift, Super", unknown, s));
+        }
+
+        ordered.push(key);
+        Ok(Self(ordered.join("+")))
+    }
+}
+
+impl Display for KeyCombo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Which command a key combination runs
+#[derive(Debug, Clone)]
+struct Binding {
+    command: String,
+    /// The plugin which bound the keys, so that the binding can be undone when the plugin is unloaded
+    plugin: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct CommandRegistry {
+    commands: BTreeMap<String, Command>,
+    bindings: BTreeMap<KeyCombo, Binding>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self { commands: BTreeMap::new(), bindings: BTreeMap::new() };
+        for core in CoreCommand::ALL {
+            registry.commands.insert(core.id().to_string(), Command {
+                id: core.id().to_string(),
+                title: core.title(),
+                action: CommandAction::Core(core),
+            });
+        }
+        registry.bind_defaults();
+        registry
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Command> {
+        self.commands.get(id)
+    }
+
+    /// Adds a command for a plugin. Commands registered by anything else cannot be replaced
+    pub fn register(&mut self, command: Command) -> Result<(), String> {
+        if self.commands.get(&command.id).is_some_and(|existing| existing.action != command.action) {
+            return Err(format!("A command named '{}' is already registered", command.id));
+        }
+
+        self.commands.insert(command.id.clone(), command);
+        Ok(())
+    }
+
+    /// Binds keys to a command on behalf of a plugin, replacing whatever they were bound to before
+    pub fn bind(&mut self, keys: KeyCombo, command: &str, plugin: &str) -> Result<(), String> {
+        if !self.commands.contains_key(command) {
+            return Err(format!("No command named '{}' is registered", command));
+        }
+
+        self.bindings.insert(keys, Binding { command: command.to_string(), plugin: Some(plugin.to_string()) });
+        Ok(())
+    }
+
+    /// Returns the command the keys are bound to
+    pub fn bound(&self, keys: &KeyCombo) -> Option<&str> {
+        self.bindings.get(keys).map(|binding| binding.command.as_str())
+    }
+
+    /// Lists every key binding, along with the command each runs
+    pub fn bindings(&self) -> Vec<(KeyCombo, String)> {
+        self.bindings.iter().map(|(keys, binding)| (keys.clone(), binding.command.clone())).collect()
+    }
+
+    /// Returns the keys which run a command, if any
+    pub fn keys_for(&self, command: &str) -> Option<&KeyCombo> {
+        self.bindings.iter().find(|(_, binding)| binding.command == command).map(|(keys, _)| keys)
+    }
+
+    /// Removes the commands and key bindings of a plugin, eg: when it is unloaded.
+    /// Keys the plugin had taken from a core command are given back to it
+    pub fn remove_plugin(&mut self, plugin: &str) {
+        self.commands.retain(|_, command| command.action != CommandAction::Plugin(plugin.to_string()));
+
+        let commands = &self.commands;
+        self.bindings.retain(|_, binding| binding.plugin.as_deref() != Some(plugin) && commands.contains_key(&binding.command));
+        self.bind_defaults();
+    }
+
+    /// Finds the commands whose titles fuzzily match a query, best matches first.
+    /// Every command is returned, by title, when the query is empty
+    pub fn search(&self, query: &str) -> Vec<Command> {
+        let mut matches: Vec<(i32, &Command)> = self.commands.values()
+            .filter_map(|command| fuzzy_score(query, &command.title).map(|score| (score, command)))
+            .collect();
+
+        matches.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| x.title.cmp(&y.title)));
+        matches.into_iter().map(|(_, command)| command.clone()).collect()
+    }
+
+    /// Binds the keys of core commands which nothing else is bound to
+    fn bind_defaults(&mut self) {
+        for core in CoreCommand::ALL {
+            let Some(keys) = core.default_keys() else { continue };
+            self.bindings.entry(KeyCombo::from_str(keys).unwrap()).or_insert(Binding { command: core.id().to_string(), plugin: None });
+        }
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Scores how well a query matches some text, where every character of the query must appear in the text in order.
+/// Characters matched one after another, or at the start of a word, score higher. Returns `None` if the text does not
+/// match
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + text[position..].iter().position(|t| *t == c)?;
+
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+
+        previous = Some(found);
+        position = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Listens for key presses within a window which run a command
+pub fn key_event(event: Event, status: event::Status, window: Id) -> Option<Message> {
+    let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = event else { return None };
+    // Keys typed into a text input belong to it
+    if status == event::Status::Captured {
+        return None;
+    }
+
+    let keys = KeyCombo::from_key(&key, modifiers)?;
+    let command = COMMANDS.lock().unwrap().bound(&keys)?.to_string();
+    Some(Message::new(MessageKind::RunCommand(command), Some(window)))
+}
@@ -0,0 +1,69 @@
+//! Tests for the command registry, key combinations and fuzzy matching used by the command palette.
+
+use iced::keyboard::key::Named;
+use super::*;
+
+fn plugin_command(plugin: &str, id: &str, title: &str) -> Command {
+    Command { id: format!("{}.{}", plugin, id), title: title.to_string(), action: CommandAction::Plugin(plugin.to_string()) }
+}
+
+#[test]
+fn key_combos_are_normalised() {
+    let parsed = KeyCombo::from_str("Shift + Ctrl+P").unwrap();
+    assert_eq!(parsed.to_string(), "ctrl+shift+p");
+    assert_eq!(KeyCombo::from_str("control+shift+p").unwrap(), parsed);
+
+    let pressed = KeyCombo::from_key(&Key::Character("P".into()), Modifiers::CTRL | Modifiers::SHIFT).unwrap();
+    assert_eq!(pressed, parsed);
+    assert_eq!(KeyCombo::from_key(&Key::Named(Named::F5), Modifiers::empty()), Some(KeyCombo::from_str("F5").unwrap()));
+    assert_eq!(KeyCombo::from_key(&Key::Named(Named::Shift), Modifiers::SHIFT), None);
+
+    assert!(KeyCombo::from_str("hyper+p").unwrap_err().contains("Unknown modifier 'hyper'"));
+    assert!(KeyCombo::from_str("ctrl+").is_err());
+}
+
+#[test]
+fn fuzzy_matches_prefer_words_and_runs() {
+    assert_eq!(fuzzy_score("", "Save note"), Some(0));
+    assert_eq!(fuzzy_score("xyz", "Save note"), None);
+    assert_eq!(fuzzy_score("ntsv", "Save note"), None);
+    assert!(fuzzy_score("sn", "Save note") > fuzzy_score("sn", "Lesson"));
+    assert!(fuzzy_score("save", "Save note") > fuzzy_score("save", "Show a very extended list"));
+
+    let registry = CommandRegistry::new();
+    let found = registry.search("palette");
+    assert_eq!(found[0].action, CommandAction::Core(CoreCommand::CommandPalette));
+    assert_eq!(registry.search("").len(), CoreCommand::ALL.len());
+}
+
+#[test]
+fn plugins_cannot_replace_other_commands() {
+    let mut registry = CommandRegistry::new();
+    registry.register(plugin_command("tickets", "open", "Open ticket")).unwrap();
+    registry.register(plugin_command("tickets", "open", "Open a ticket")).unwrap();
+    assert_eq!(registry.get("tickets.open").unwrap().title, "Open a ticket");
+
+    let taken = Command { id: "core.save".to_string(), ..plugin_command("tickets", "save", "Save") };
+    assert!(registry.register(taken).unwrap_err().contains("already registered"));
+    assert!(registry.register(plugin_command("other", "open", "Open")).is_ok());
+}
+
+#[test]
+fn unloading_a_plugin_restores_core_bindings() {
+    let mut registry = CommandRegistry::new();
+    let save = KeyCombo::from_str("ctrl+s").unwrap();
+    let custom = KeyCombo::from_str("ctrl+shift+t").unwrap();
+    assert_eq!(registry.bound(&save), Some("core.save"));
+
+    registry.register(plugin_command("tickets", "open", "Open ticket")).unwrap();
+    assert!(registry.bind(custom.clone(), "tickets.missing", "tickets").is_err());
+    registry.bind(save.clone(), "tickets.open", "tickets").unwrap();
+    registry.bind(custom.clone(), "core.save", "tickets").unwrap();
+    assert_eq!(registry.bound(&save), Some("tickets.open"));
+    assert_eq!(registry.keys_for("tickets.open"), Some(&save));
+
+    registry.remove_plugin("tickets");
+    assert!(registry.get("tickets.open").is_none());
+    assert_eq!(registry.bound(&save), Some("core.save"));
+    assert_eq!(registry.bound(&custom), None);
+}
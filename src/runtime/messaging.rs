@@ -1,8 +1,11 @@
 use iced::system;
 use iced::window::Id;
+use tokio::sync::mpsc::Sender;
 use crate::hotkey::Keybind;
+use crate::runtime::speech::Speech;
 use crate::runtime::Task;
-use crate::runtime::windows::editor::messaging::EditorMessage;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::runtime::windows::consent::ConsentWindowMessage;
 use crate::runtime::windows::workspace::WorkspaceWindowMessage;
 use crate::runtime::windows::splash::SplashWindowMessage;
 use crate::runtime::workers::Job;
@@ -21,6 +24,26 @@ pub enum MessageKind {
     Queue(Vec<Job>),
     SysInfo(system::Information),
     Say(String),
+    /// A request for the speech thread, eg: the next segment of a note being read aloud
+    Speak(Speech),
+    /// The speech thread has started, and is ready for requests
+    SpeechReady(Sender<Speech>),
+    /// Keyboard and window input which is only relevant to editor windows
+    EditorInput(EditorMessageKind),
+    /// The user chose whether a plugin may have the scopes it requests
+    /// **Params**
+    /// - String -> The name of the plugin.
+    /// - bool -> Whether the scopes were granted.
+    PluginConsent(String, bool),
+    /// Runs a command, eg: chosen from the command palette or bound to the keys which were pressed.
+    /// Commands which act on an editor act on the window the message came from, or the focused window
+    RunCommand(String),
+    /// The user chose a theme, which is used by every window and remembered for next time
+    SelectTheme(String),
+    /// The files of an unpacked plugin changed, so it needs reloading
+    /// **Params**
+    /// - String -> The name of the plugin.
+    PluginChanged(String),
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +60,12 @@ impl Message {
         }
     }
 
+    /// Records the window the message came from, unless it is already known
+    pub fn from_window(mut self, id: Id) -> Self {
+        self.source_id.get_or_insert(id);
+        self
+    }
+
     pub fn say(text: impl Into<String>) -> Self {
         Self::new(MessageKind::Say(text.into()), None)
     }
@@ -79,6 +108,7 @@ pub enum WindowMessageKind {
     Editor(EditorMessage),
     Component(ComponentMessage),
     Workspace(WorkspaceWindowMessage),
+    Consent(ConsentWindowMessage),
 }
 
 #[derive(Debug, Clone)]
@@ -1,4 +1,5 @@
 use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use iced::{exit, system, Subscription};
 use iced::widget::text;
@@ -7,25 +8,26 @@ use notify_rust::{Notification, Timeout};
 use lazy_static::lazy_static;
 use crossbeam_queue::ArrayQueue;
 use iced::futures::StreamExt;
-use natural_tts::{Model, NaturalTtsBuilder};
-use natural_tts::models::NaturalModelTrait;
-use natural_tts::models::tts_rs::TtsModel;
-use tokio::sync::mpsc::{channel, Sender};
-use tokio::time::Instant;
+use tokio::sync::mpsc::Sender;
 use crate::config::Config;
 use crate::consts::APP_NAME;
 pub(crate) use crate::runtime::messaging::{Message, MessageKind};
 use crate::runtime::windows::{AppWindow, DesktopWindow};
-use crate::runtime::windows::editor::EditorWindow;
+use crate::runtime::windows::consent::ConsentWindow;
+use crate::runtime::windows::editor::{self, EditorWindow};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
 use crate::runtime::windows::workspace::WorkspaceWindow;
 use crate::runtime::windows::splash::SplashWindow;
 use crate::storage::process::ProcessStorageManager;
 use crate::storage::process::structs::setting::Setting;
 use crate::storage::process::structs::workspace::Workspace;
 use crate::hotkey::Keybind;
-use crate::plugins::PluginManager;
+use crate::plugins::{ConsentRequest, PluginManager, DEFAULT_PLUGIN_DIRECTORY};
+use crate::runtime::commands::{CommandAction, CoreCommand, COMMANDS};
+use crate::runtime::speech::Speech;
 use crate::runtime::state::AppState;
 use crate::storage::workspace::WorkspaceManager;
+use crate::config::appearance::ThemeVariant;
 use crate::ui::theme::{ThemeManager, THEMES};
 
 /// Holds all the message passing code for the base layer of the app. All roads lead to `crate::runtime::messaging`.
@@ -40,6 +42,12 @@ pub mod workers;
 /// Holds the definitions for the global app state
 pub mod state;
 
+/// Holds the speech thread, which reads messages and notes aloud
+pub mod speech;
+
+/// Holds the command registry, which the command palette and key bindings run commands from
+pub mod commands;
+
 /// Globally used alias for this applications task type.
 pub type Task = iced::Task<Message>;
 
@@ -73,8 +81,8 @@ pub struct RuntimeState {
     /// A `BTreeMap` containing each window and their respective identifiers for use when rendering or updating.
     pub windows: BTreeMap<Id, AppWindow>,
 
-    /// The TTS message to be played
-    pub tts: Sender<String>,
+    /// Sends requests to the speech thread, once it has started
+    pub tts: Option<Sender<Speech>>,
 
     /// Global plugin manager (used for themes)
     pub plugins: PluginManager,
@@ -82,10 +90,10 @@ pub struct RuntimeState {
 
 impl RuntimeState {
     /// Builds a new `RuntimeState` instance.
-    fn new(tts: Sender<String>) -> RuntimeState {
+    fn new() -> RuntimeState {
         RuntimeState {
             windows: Default::default(),
-            tts,
+            tts: None,
             plugins: PluginManager::new(),
         }
     }
@@ -108,49 +116,21 @@ pub struct Application {
 impl  Application {
     /// Spawn a new Application runtime, returns a trigger task and an instance of the application to run the daemon with.
     pub fn new() -> (Application, Task) {
-        let (tx, mut rx) = channel(1);
-
-        std::thread::spawn(move || {
-            let start = Instant::now();
-            info!("Spawning TTS handling thread");
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            let model = TtsModel::default();
-            let voices = model.0.voices().unwrap();
-
-            info!("Below are available Microsoft voices:");
-            for voice in voices {
-                info!("- {:?}", voice.name());
-            }
-
-            let mut tts = NaturalTtsBuilder::default()
-                .default_model(Model::TTS)
-                .tts_model(TtsModel::default())
-                .build()
-                .unwrap();
-
-            rt.block_on(async move {
-                info!("TTS thread is ready...");
-                let diff = Instant::now() - start;
-                info!("Started in {}ms", diff.as_millis());
-                while let Some(text) = rx.recv().await {
-                    info!("TTS message: '{}'", text);
-                    let x = tts.say(text);
-                    if x.is_err() {
-                        error!("{}", x.unwrap_err());
-                    }
-                }
-            });
-        });
-
         let mut task = system::fetch_information().map(|i| Message::new(MessageKind::SysInfo(i), None)).chain(Task::done(Message::tick()));
         let skip_splash = GLOBAL_STATE.lock().unwrap().skip_splash;
 
         let mut app = Application {
-            rt: RuntimeState::new(tx),
+            rt: RuntimeState::new(),
             state: GLOBAL_STATE.clone(),
             splash_window: None,
         };
 
+        // Choose the theme before the built in plugins start, so that a theme they register can be used straight away
+        if let Some(theme) = app.state.lock().unwrap().store.get_setting::<String>("appearance.theme.name") {
+            THEMES.lock().unwrap().select(&theme.value);
+        }
+        app.rt.plugins.load_builtin();
+
         if !skip_splash {
             let splash = SplashWindow::new();
             let splash_window = splash.0;
@@ -169,13 +149,10 @@ impl  Application {
     }
 
     pub fn theme(&self, _id: Id) -> iced::Theme {
-        let mut binding = THEMES.lock().unwrap();
-        let active_theme = binding.active;
-        let theme = binding.themes.get_mut(active_theme).unwrap();
-
-        theme.evaluate(":root");
-
-        iced::Theme::Dark
+        match THEMES.lock().unwrap().variant() {
+            ThemeVariant::Light => iced::Theme::Light,
+            _ => iced::Theme::Dark
+        }
     }
 
     pub fn update(&mut self, message: Message) -> Task {
@@ -230,23 +207,21 @@ impl  Application {
                         let last_workspace = workspaces.first().unwrap();
                         Task::done(Message::open_workspace(last_workspace.id.clone()))
                     },
-                    Keybind::ToggleDyslexia => {
-                        let mut dyslexia: Setting<bool> = self.state.lock().unwrap().store.get_setting("appearance.font.dyslexic.enable").unwrap();
-                        dyslexia.value = !dyslexia.value;
-                        self.state.lock().unwrap().store.set_setting(dyslexia.key, dyslexia.value);
-                        if dyslexia.value {
-                            Notification::new()
-                                .summary("Dyslexia Mode")
-                                .body("Noot is running in Dyslexia Friendly Mode\nToggle this feature using alt + shift + d")
-                                .appname(APP_NAME)
-                                .timeout(Timeout::Default)
-                                .show().unwrap();
-                        }
-                        Task::none()
-                    }
+                    Keybind::ToggleDyslexia => self.run_command(CoreCommand::ToggleDyslexia.id(), None)
                 }
             }
+            MessageKind::RunCommand(command) => self.run_command(&command, message.source_id),
             MessageKind::OpenWorkspace(workspace_id) => self.open_workspace(workspace_id),
+            MessageKind::EditorInput(kind) => {
+                // Input is listened to across every window, but only editors act upon it
+                match message.source_id.and_then(|id| self.rt.windows.get_mut(&id)) {
+                    Some(AppWindow::EditorWindow(editor)) => {
+                        let id = editor.id;
+                        editor.update(EditorMessage::new(kind, id))
+                    }
+                    _ => Task::none()
+                }
+            }
             MessageKind::WindowClose(id) => {
                 let reference = self.rt.windows.get_mut(&id);
 
@@ -275,6 +250,11 @@ impl  Application {
                     Task::none()
                 }
             }
+            MessageKind::LinkOpened(Some(anchor)) if anchor.starts_with('#') => {
+                // Links within a note, such as footnotes, are handled by the editor which was clicked
+                let anchor = anchor.trim_start_matches('#').to_string();
+                Task::done(Message::new(MessageKind::EditorInput(EditorMessageKind::JumpTo(anchor)), message.source_id))
+            }
             MessageKind::LinkOpened(url) => {
                 if let Some(link) = url {
                     info!("Opening link - {}", link);
@@ -292,12 +272,31 @@ impl  Application {
             }
             MessageKind::Say(message) => {
                 warn!("Saying '{}'", message);
-                let outcome = tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
-                    self.rt.tts.send(message).await
-                });
-                if outcome.is_err() {
-                    error!("{}", outcome.unwrap_err());
-                }
+                self.speak(Speech::Say(message));
+                Task::none()
+            }
+            MessageKind::Speak(speech) => {
+                self.speak(speech);
+                Task::none()
+            }
+            MessageKind::PluginConsent(plugin, allowed) => {
+                self.rt.plugins.consent(&plugin, allowed);
+                if allowed { self.render_notes() } else { Task::none() }
+            }
+            MessageKind::SelectTheme(theme) => {
+                THEMES.lock().unwrap().select(&theme);
+                self.state.lock().unwrap().store.set_setting("appearance.theme.name", theme);
+                Task::none()
+            }
+            MessageKind::PluginChanged(plugin) => {
+                let consent = match self.rt.plugins.reload(&plugin) {
+                    Some(request) => self.ask_consent(request),
+                    None => Task::none()
+                };
+                self.render_notes().chain(consent)
+            }
+            MessageKind::SpeechReady(tts) => {
+                self.rt.tts = Some(tts);
                 Task::none()
             }
             _ => {
@@ -307,6 +306,55 @@ impl  Application {
         }
     }
 
+    /// Runs a command from the registry. Editor commands act on the given window, or the focused window if none is given
+    fn run_command(&mut self, id: &str, window: Option<Id>) -> Task {
+        let Some(command) = COMMANDS.lock().unwrap().get(id).cloned() else {
+            warn!("No command named '{}' is registered", id);
+            return Task::none();
+        };
+
+        info!("Running command '{}'", command.id);
+        match command.action {
+            CommandAction::Core(CoreCommand::OpenWorkspace) => Message::window_open("workspace-manager").into(),
+            CommandAction::Core(CoreCommand::ToggleDyslexia) => {
+                self.toggle_dyslexia();
+                Task::none()
+            }
+            CommandAction::Core(CoreCommand::Save) => Message::new(MessageKind::EditorInput(EditorMessageKind::Save), window).into(),
+            CommandAction::Core(CoreCommand::CommandPalette) => Message::new(MessageKind::EditorInput(EditorMessageKind::ToggleCommandPalette), window).into(),
+            CommandAction::Plugin(plugin) => {
+                self.rt.plugins.run_command(&plugin, &command.id);
+                Task::none()
+            }
+        }
+    }
+
+    fn toggle_dyslexia(&mut self) {
+        let mut dyslexia: Setting<bool> = self.state.lock().unwrap().store.get_setting("appearance.font.dyslexic.enable").unwrap();
+        dyslexia.value = !dyslexia.value;
+        self.state.lock().unwrap().store.set_setting(dyslexia.key, dyslexia.value);
+        if dyslexia.value {
+            Notification::new()
+                .summary("Dyslexia Mode")
+                .body("Noot is running in Dyslexia Friendly Mode\nToggle this feature using alt + shift + d")
+                .appname(APP_NAME)
+                .timeout(Timeout::Default)
+                .show().unwrap();
+        }
+    }
+
+    /// Passes a request on to the speech thread
+    fn speak(&self, speech: Speech) {
+        match &self.rt.tts {
+            Some(tts) => {
+                if let Err(e) = tts.try_send(speech) {
+                    error!("{}", e);
+                }
+            }
+            None => warn!("The speech thread has not started yet, unable to handle {:?}", speech)
+        }
+    }
+
     pub fn tick(&mut self) -> Task {
         // The initial first tick of the app
         let maybe_lock = self.state.try_lock();
@@ -372,15 +420,53 @@ impl  Application {
             "editor" => {
                 let temp_lock = self.state.lock().unwrap();
                 let source = temp_lock.workspaces.get(&temp_lock.open_workspace.clone().unwrap()).cloned().unwrap();
-                let mgr = WorkspaceManager::new(source.clone(), temp_lock).unwrap();
+                let mut mgr = WorkspaceManager::new(source.clone(), temp_lock).unwrap();
+                let consent = self.load_plugins(&mut mgr);
                 let (context, task) = EditorWindow::new(mgr);
                 self.rt.windows.insert(context.id, AppWindow::EditorWindow(context));
-                task.discard()
+                task.discard().chain(consent)
             }
             _ => Task::none()
         }
     }
 
+    /// Loads the plugins installed within a workspace, if the workspace has plugins enabled.
+    /// Returns a task asking the user to grant the scopes of any plugins which need them
+    fn load_plugins(&mut self, mgr: &mut WorkspaceManager) -> Task {
+        let enabled = mgr.get_setting::<bool>("plugins.enable").is_some_and(|s| s.value);
+        if !enabled {
+            return Task::none();
+        }
+
+        let directory = mgr.get_setting::<String>("plugins.directory")
+            .map(|s| s.value)
+            .unwrap_or(DEFAULT_PLUGIN_DIRECTORY.to_string());
+        let allow_unpacked = mgr.get_setting::<bool>("plugins.allow-unpacked").is_some_and(|s| s.value);
+        let root = PathBuf::from(&mgr.source.disk_path);
+        self.rt.plugins.allow_unpacked(allow_unpacked);
+        let requests = self.rt.plugins.load_plugins(&root.join(directory), &root);
+
+        Task::batch(requests.into_iter().map(|request| self.ask_consent(request)))
+    }
+
+    /// Renders the notes open in every editor again, as the plugins changing how they are rendered have changed
+    fn render_notes(&mut self) -> Task {
+        Task::batch(self.rt.windows.values_mut().filter_map(|window| match window {
+            AppWindow::EditorWindow(editor) => {
+                let id = editor.id;
+                Some(editor.update(EditorMessage::new(EditorMessageKind::PluginsChanged, id)))
+            }
+            _ => None
+        }))
+    }
+
+    /// Opens a window asking the user to grant a plugin the scopes it requests
+    fn ask_consent(&mut self, request: ConsentRequest) -> Task {
+        let (context, task) = ConsentWindow::new(request);
+        self.rt.windows.insert(context.id, AppWindow::ConsentWindow(context));
+        task.discard()
+    }
+
     pub fn open_workspace(&mut self, id: String) -> Task {
         info!("Opening workspace {}", id);
         self.state.lock().unwrap().open_workspace = Some(id);
@@ -406,7 +492,11 @@ impl  Application {
             iced::window::close_events().map(|id| Message::window_close(id)),
             #[cfg(feature = "keybinds")]
             Subscription::run(crate::hotkey::start),
-            Subscription::run(workers::spawn)
+            Subscription::run(workers::spawn),
+            Subscription::run(speech::spawn),
+            iced::event::listen_with(editor::input_event),
+            iced::event::listen_with(commands::key_event),
+            self.rt.plugins.subscription()
         ];
 
 
@@ -0,0 +1,257 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+use iced::futures::channel::mpsc;
+use iced::futures::{SinkExt, Stream, StreamExt};
+use iced::stream;
+use iced_core::window::Id;
+use tokio::sync::mpsc::{channel, Receiver};
+use tokio::time::Instant;
+use crate::config::appearance::TTSProvider;
+use crate::runtime::{Message, MessageKind, GLOBAL_STATE};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::storage::process::structs::setting::Setting;
+use crate::utils::audio::{Audio, AudioFormat};
+use self::export::{export, Chapter};
+#[cfg(feature = "tts")]
+use self::local::LocalVoice;
+use self::system::SystemVoice;
+use self::wav::WavVoice;
+
+pub mod system;
+#[cfg(feature = "tts")]
+pub mod local;
+pub mod wav;
+pub mod export;
+
+#[cfg(test)]
+mod tests;
+
+/// How often the speech thread checks whether a segment has finished being spoken
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long a segment may take to begin being spoken, after which it is assumed to have been too short to notice
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A request for the speech thread
+#[derive(Debug, Clone)]
+pub enum Speech {
+    /// Speaks a message, unless something is already being said
+    Say(String),
+    /// Speaks a segment of a note being read aloud, interrupting whatever is being said.
+    /// The window is told once the segment has been spoken, so that it can send the next one
+    Read(String, Id),
+    /// Stops speaking, eg: when reading aloud is paused
+    Stop,
+    /// Changes the speaking rate, as a multiple of the normal rate of the voice
+    Rate(f32),
+    /// Speaks a note into an audio file rather than aloud
+    /// **Params**
+    /// - String -> The title of the note.
+    /// - Vec<Chapter> -> The parts of the note to export, each of which is marked as a chapter.
+    /// - PathBuf -> Where to write the audio file.
+    /// - AudioFormat -> The format to write it in.
+    /// - Id -> The window which is told once the file has been written.
+    Export(String, Vec<Chapter>, PathBuf, AudioFormat, Id),
+}
+
+/// A voice which text can be spoken with, chosen with the `appearance.tts.provider` setting
+pub trait TtsProvider {
+    /// Starts speaking some text without waiting for it to be spoken.
+    /// Unless interrupting, the text is said after whatever is already being said
+    fn speak(&mut self, text: &str, interrupt: bool) -> Result<(), Box<dyn Error>>;
+    fn stop(&mut self) -> Result<(), Box<dyn Error>>;
+    fn is_speaking(&self) -> bool;
+    /// Changes the speaking rate, as a multiple of the normal rate of the voice
+    fn set_rate(&mut self, rate: f32) -> Result<(), Box<dyn Error>>;
+
+    /// Whether the voice can generate speech without playing it, see [TtsProvider::synthesize]
+    fn can_record(&self) -> bool {
+        false
+    }
+
+    /// Generates speech without playing it, eg: to export a note as an audio file
+    fn synthesize(&mut self, _text: &str) -> Result<Audio, Box<dyn Error>> {
+        Err("This voice cannot be recorded, choose the local voice to export audio".into())
+    }
+}
+
+/// Exports a note with the chosen voice, or with the local voice if the chosen one cannot be recorded,
+/// returning where the audio was written
+fn export_audio(voice: Option<&mut Box<dyn TtsProvider>>, title: &str, chapters: &[Chapter], path: PathBuf, format: AudioFormat) -> Result<PathBuf, String> {
+    info!("Exporting {} chapters of '{}' to {}", chapters.len(), title, path.display());
+    let outcome = match voice {
+        Some(voice) if voice.can_record() => export(voice.as_mut(), title, chapters, &path, format),
+        _ => {
+            info!("The chosen voice cannot be recorded, exporting with the local voice instead");
+            local_voice().and_then(|mut local| export(local.as_mut(), title, chapters, &path, format))
+        }
+    };
+    outcome.map(|_| path).map_err(|e| e.to_string())
+}
+
+/// Opens the speech model which runs on this computer
+#[cfg(feature = "tts")]
+fn local_voice() -> Result<Box<dyn TtsProvider>, Box<dyn Error>> {
+    Ok(Box::new(LocalVoice::new()?))
+}
+
+/// The local voice is only built with the `tts` feature, as its speech model pulls in a lot of dependencies
+#[cfg(not(feature = "tts"))]
+fn local_voice() -> Result<Box<dyn TtsProvider>, Box<dyn Error>> {
+    Err("The local voice is not available, as noot was built without the `tts` feature".into())
+}
+
+/// Opens the voice chosen in the settings, or returns `None` if speech is turned off
+fn open_voice() -> Result<Option<Box<dyn TtsProvider>>, Box<dyn Error>> {
+    let (enabled, provider) = {
+        let state = GLOBAL_STATE.lock().unwrap();
+        let enabled: Option<Setting<bool>> = state.store.get_setting("appearance.tts.enable");
+        let provider: Option<Setting<String>> = state.store.get_setting("appearance.tts.provider");
+        (enabled.is_none_or(|s| s.value), provider.map(|s| s.value).unwrap_or_default())
+    };
+
+    let provider = match TTSProvider::from_str(&provider) {
+        Ok(provider) if enabled => provider,
+        Ok(_) => TTSProvider::None,
+        Err(e) => {
+            warn!("{}, falling back to the system voice", e);
+            TTSProvider::System
+        }
+    };
+
+    info!("Using the {:?} TTS provider", provider);
+    Ok(match provider {
+        TTSProvider::None => None,
+        TTSProvider::System => Some(Box::new(SystemVoice::new()?)),
+        TTSProvider::Local => Some(local_voice()?),
+        TTSProvider::Wav => Some(Box::new(WavVoice::new(std::env::temp_dir().join("noot-tts"))?)),
+    })
+}
+
+/// The segment which is currently being read aloud
+struct Reading {
+    window: Id,
+    since: Instant,
+    /// Whether the voice has been heard speaking the segment yet
+    started: bool,
+}
+
+/// Spawns the speech thread, returning a stream which first hands over a sender for requests, and then the
+/// messages sent to windows as they are read aloud to
+pub fn spawn() -> impl Stream<Item = Message> {
+    stream::channel(1, |mut output| async move {
+        let (requests, rx) = channel(8);
+        let (sender, mut messages) = mpsc::channel(1);
+
+        thread::spawn(move || {
+            info!("Spawning TTS handling thread");
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(run(rx, sender));
+        });
+
+        if let Err(e) = output.send(Message::new(MessageKind::SpeechReady(requests), None)).await {
+            error!("{}", e);
+        }
+
+        while let Some(msg) = messages.next().await {
+            if let Err(e) = output.send(msg).await {
+                error!("{}", e);
+            }
+        }
+    })
+}
+
+async fn run(mut rx: Receiver<Speech>, mut sender: mpsc::Sender<Message>) {
+    let start = Instant::now();
+    let mut voice = match open_voice() {
+        Ok(voice) => voice,
+        Err(e) => {
+            error!("Unable to start text to speech: {}", e);
+            None
+        }
+    };
+
+    let mut reading: Option<Reading> = None;
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+
+    info!("TTS thread is ready...");
+    info!("Started in {}ms", start.elapsed().as_millis());
+
+    loop {
+        tokio::select! {
+            request = rx.recv() => {
+                let Some(request) = request else { break };
+
+                let Some(tts) = voice.as_mut() else {
+                    let reply = match request {
+                        // Notes can still be exported while speech is turned off, as nothing is said aloud
+                        Speech::Export(title, chapters, path, format, window) => Some((EditorMessageKind::AudioExported(export_audio(None, &title, &chapters, path, format)), window)),
+                        Speech::Read(_, window) => {
+                            warn!("Text to speech is turned off, cannot read aloud");
+                            Some((EditorMessageKind::StopReading, window))
+                        }
+                        request => {
+                            warn!("Text to speech is turned off, ignoring {:?}", request);
+                            None
+                        }
+                    };
+                    if let Some((kind, window)) = reply {
+                        let _ = sender.send(EditorMessage::new(kind, window).into()).await;
+                    }
+                    continue;
+                };
+
+                match request {
+                    Speech::Say(text) => {
+                        info!("TTS message: '{}'", text);
+                        if tts.is_speaking() {
+                            continue;
+                        }
+                        if let Err(e) = tts.speak(&text, false) {
+                            error!("{}", e);
+                        }
+                    }
+                    Speech::Read(text, window) => {
+                        match tts.speak(&text, true) {
+                            Ok(_) => reading = Some(Reading { window, since: Instant::now(), started: false }),
+                            Err(e) => {
+                                error!("Unable to read aloud: {}", e);
+                                reading = None;
+                                let _ = sender.send(EditorMessage::new(EditorMessageKind::StopReading, window).into()).await;
+                            }
+                        }
+                    }
+                    Speech::Stop => {
+                        reading = None;
+                        if let Err(e) = tts.stop() {
+                            error!("{}", e);
+                        }
+                    }
+                    Speech::Rate(rate) => {
+                        if let Err(e) = tts.set_rate(rate) {
+                            warn!("Unable to change the speaking rate: {}", e);
+                        }
+                    }
+                    Speech::Export(title, chapters, path, format, window) => {
+                        // Exporting can take a while, during which nothing else is spoken
+                        let outcome = export_audio(Some(tts), &title, &chapters, path, format);
+                        let _ = sender.send(EditorMessage::new(EditorMessageKind::AudioExported(outcome), window).into()).await;
+                    }
+                }
+            }
+            _ = poll.tick(), if reading.is_some() => {
+                let (Some(segment), Some(tts)) = (&mut reading, &voice) else { continue };
+
+                if tts.is_speaking() {
+                    segment.started = true;
+                } else if segment.started || segment.since.elapsed() > START_TIMEOUT {
+                    let window = segment.window;
+                    reading = None;
+                    let _ = sender.send(EditorMessage::new(EditorMessageKind::SegmentSpoken, window).into()).await;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use crate::utils::audio::{Audio, AudioFormat, Marker};
+use super::TtsProvider;
+
+/// A part of a note which is exported as a chapter of its own, eg: a section beginning at a heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    /// What is said for the chapter
+    pub text: String,
+}
+
+/// Speaks each chapter with a voice, writing them to a file one after another with a marker where each begins
+pub fn export(voice: &mut dyn TtsProvider, title: &str, chapters: &[Chapter], path: &Path, format: AudioFormat) -> Result<(), Box<dyn Error>> {
+    let mut audio: Option<Audio> = None;
+    let mut markers = vec![];
+
+    for chapter in chapters.iter().filter(|c| !c.text.is_empty()) {
+        let speech = voice.synthesize(&chapter.text)?;
+        let audio = audio.get_or_insert_with(|| Audio::new(speech.sample_rate));
+
+        markers.push(Marker { title: chapter.title.clone(), sample: audio.samples.len() });
+        audio.append(&speech);
+    }
+
+    let audio = audio.ok_or("There is nothing to read aloud")?;
+    // An earlier export is never overwritten, see [unused_path]
+    let mut file = BufWriter::new(File::create_new(path)?);
+
+    match format {
+        AudioFormat::Wav => audio.write_wav(&mut file, &markers)?,
+        AudioFormat::Ogg => audio.write_ogg(&mut file, title, &markers)?,
+    }
+
+    file.flush()?;
+    Ok(())
+}
+
+/// Returns `path` if there is nothing there yet, otherwise the first of `<name> (2).<ext>`, `<name> (3).<ext>`, ...
+/// which is free, so that exporting a note again does not overwrite the audio exported before
+pub fn unused_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+
+    std::iter::once(path.to_path_buf())
+        .chain((2..).map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension))))
+        .find(|candidate| !candidate.exists())
+        .unwrap()
+}
@@ -0,0 +1,92 @@
+use std::error::Error;
+use natural_tts::models::parler::{ParlerModel, ParlerModelOptionsBuilder};
+use natural_tts::models::{NaturalModelTrait, Spec};
+use rodio::buffer::SamplesBuffer;
+use rodio::{OutputStream, OutputStreamHandle, Sink};
+use crate::utils::audio::Audio;
+use super::TtsProvider;
+
+const MODEL: &str = "parler-tts/parler-tts-mini-expresso";
+/// Describes the voice the model speaks with
+const VOICE: &str = "A female speaker in fast calming voice in a quiet environment";
+/// The sample rate of the speech model, for audio which does not say what it was generated at
+const SAMPLE_RATE: u32 = 24_000;
+
+/// Speaks with a speech model which runs on this computer, so that nothing is sent elsewhere.
+/// The model is downloaded the first time it is used, and works offline from then on
+pub struct LocalVoice {
+    /// Loaded when first speaking, as loading takes a while
+    model: Option<ParlerModel>,
+    // The stream has to be kept alive for as long as audio is played through its handle
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+    sink: Option<Sink>,
+    rate: f32,
+}
+
+impl LocalVoice {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let (stream, handle) = OutputStream::try_default()?;
+        Ok(Self { model: None, _stream: stream, handle, sink: None, rate: 1. })
+    }
+}
+
+impl TtsProvider for LocalVoice {
+    fn speak(&mut self, text: &str, interrupt: bool) -> Result<(), Box<dyn Error>> {
+        // Generating speech blocks the speech thread, so requests made in the meantime wait until it is done
+        let audio = self.synthesize(text)?;
+
+        if interrupt {
+            self.stop()?;
+        }
+
+        let sink = match self.sink.take() {
+            Some(sink) => sink,
+            None => Sink::try_new(&self.handle)?,
+        };
+        sink.set_speed(self.rate);
+        sink.append(SamplesBuffer::new(1, audio.sample_rate, audio.samples));
+        self.sink = Some(sink);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(sink) = self.sink.take() {
+            sink.stop();
+        }
+        Ok(())
+    }
+
+    fn is_speaking(&self) -> bool {
+        self.sink.as_ref().is_some_and(|sink| !sink.empty())
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Result<(), Box<dyn Error>> {
+        self.rate = rate;
+        if let Some(sink) = &self.sink {
+            sink.set_speed(rate);
+        }
+        Ok(())
+    }
+
+    fn can_record(&self) -> bool {
+        true
+    }
+
+    fn synthesize(&mut self, text: &str) -> Result<Audio, Box<dyn Error>> {
+        if self.model.is_none() {
+            info!("Loading the local speech model");
+            let options = ParlerModelOptionsBuilder::default().model_name(MODEL).description(VOICE).build()?;
+            self.model = Some(ParlerModel::new(options)?);
+        }
+
+        let audio = self.model.as_mut().unwrap().synthesize(text.to_string())?;
+        let sample_rate = match audio.spec {
+            Spec::Wav(spec) => spec.sample_rate,
+            _ => SAMPLE_RATE,
+        };
+
+        Ok(Audio { samples: audio.data, sample_rate })
+    }
+}
@@ -0,0 +1,44 @@
+use std::error::Error;
+use natural_tts::models::tts_rs::TtsModel;
+use super::TtsProvider;
+
+/// Speaks with the voices of the operating system, eg: speech-dispatcher on Linux
+pub struct SystemVoice {
+    tts: TtsModel,
+    normal_rate: f32,
+}
+
+impl SystemVoice {
+    pub fn new() -> Result<Self, Box<dyn Error>> {
+        let tts = TtsModel::new()?;
+
+        info!("Below are available voices:");
+        for voice in tts.0.voices().unwrap_or_default() {
+            info!("- {:?}", voice.name());
+        }
+
+        Ok(Self { normal_rate: tts.0.normal_rate(), tts })
+    }
+}
+
+impl TtsProvider for SystemVoice {
+    fn speak(&mut self, text: &str, interrupt: bool) -> Result<(), Box<dyn Error>> {
+        self.tts.0.speak(text, interrupt)?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Box<dyn Error>> {
+        self.tts.0.stop()?;
+        Ok(())
+    }
+
+    fn is_speaking(&self) -> bool {
+        self.tts.0.is_speaking().unwrap_or(false)
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Result<(), Box<dyn Error>> {
+        let rate = (self.normal_rate * rate).clamp(self.tts.0.min_rate(), self.tts.0.max_rate());
+        self.tts.0.set_rate(rate)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+//! Tests for the speech providers and audio export, which can run without a voice or speakers.
+
+use std::str::FromStr;
+use std::time::Duration;
+use hound::WavReader;
+use nanoid::nanoid;
+use crate::config::appearance::TTSProvider;
+use crate::utils::audio::AudioFormat;
+use super::TtsProvider;
+use super::export::{export, unused_path, Chapter};
+use super::wav::WavVoice;
+
+#[test]
+fn providers_are_chosen_by_name() {
+    assert_eq!(TTSProvider::from_str("system"), Ok(TTSProvider::System));
+    assert_eq!(TTSProvider::from_str("local"), Ok(TTSProvider::Local));
+    assert_eq!(TTSProvider::from_str("wav"), Ok(TTSProvider::Wav));
+    assert!(TTSProvider::from_str("google").is_err());
+}
+
+#[test]
+fn wav_voice_writes_each_utterance() {
+    let directory = std::env::temp_dir().join(format!("noot-tts-{}", nanoid!(5)));
+    let mut voice = WavVoice::new(directory.clone()).unwrap();
+
+    voice.speak("One two three four five", true).unwrap();
+    voice.set_rate(2.).unwrap();
+    voice.speak("One two three four five", true).unwrap();
+    assert!(voice.is_speaking());
+
+    let first = WavReader::open(directory.join("0001.wav")).unwrap();
+    let second = WavReader::open(directory.join("0002.wav")).unwrap();
+    assert_eq!(first.duration() as f32 / first.spec().sample_rate as f32, 2.);
+    assert_eq!(second.duration() * 2, first.duration());
+    assert_eq!(std::fs::read_to_string(directory.join("0002.txt")).unwrap(), "One two three four five");
+
+    voice.stop().unwrap();
+    assert!(!voice.is_speaking());
+
+    voice.speak("One", true).unwrap();
+    std::thread::sleep(voice.duration("One") + Duration::from_millis(50));
+    assert!(!voice.is_speaking());
+
+    let _ = std::fs::remove_dir_all(directory);
+}
+
+#[test]
+fn exported_audio_has_a_chapter_for_each_part() {
+    let directory = std::env::temp_dir().join(format!("noot-tts-{}", nanoid!(5)));
+    let mut voice = WavVoice::new(directory.clone()).unwrap();
+    let chapters = vec![
+        Chapter { title: "One".to_string(), text: "One two three four five".to_string() },
+        Chapter { title: "Empty".to_string(), text: String::new() },
+        Chapter { title: "Two".to_string(), text: "Six seven".to_string() },
+    ];
+
+    let path = directory.join("note.wav");
+    export(&mut voice, "Note", &chapters, &path, AudioFormat::Wav).unwrap();
+
+    let file = std::fs::read(&path).unwrap();
+    let reader = WavReader::new(file.as_slice()).unwrap();
+    assert_eq!(reader.duration() as f32 / reader.spec().sample_rate as f32, 2.8);
+
+    let cues = file.windows(4).position(|w| w == b"cue ").unwrap();
+    assert_eq!(&file[cues + 8..cues + 12], 2u32.to_le_bytes());
+    assert!(file.windows(4).any(|w| w == b"Two\0"));
+
+    let _ = std::fs::remove_dir_all(directory);
+}
+
+#[test]
+fn exports_do_not_overwrite_earlier_exports() {
+    let directory = std::env::temp_dir().join(format!("noot-tts-{}", nanoid!(5)));
+    let mut voice = WavVoice::new(directory.clone()).unwrap();
+    let chapters = vec![Chapter { title: "One".to_string(), text: "One two".to_string() }];
+    let path = directory.join("note.wav");
+
+    assert_eq!(unused_path(&path), path);
+    export(&mut voice, "Note", &chapters, &path, AudioFormat::Wav).unwrap();
+    assert_eq!(unused_path(&path), directory.join("note (2).wav"));
+    assert!(export(&mut voice, "Note", &chapters, &path, AudioFormat::Wav).is_err());
+
+    export(&mut voice, "Note", &chapters, &unused_path(&path), AudioFormat::Wav).unwrap();
+    assert_eq!(unused_path(&path), directory.join("note (3).wav"));
+
+    let _ = std::fs::remove_dir_all(directory);
+}
@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use crate::utils::audio::Audio;
+use super::TtsProvider;
+
+const SAMPLE_RATE: u32 = 16_000;
+/// Roughly how quickly a voice speaks at its normal rate
+const WORDS_PER_SECOND: f32 = 2.5;
+
+/// Writes what would have been said to WAV files instead of speaking it, so that speech can be tested without a
+/// voice or speakers. Each utterance is written as a numbered WAV file of silence lasting as long as it would take to
+/// say, beside a text file of what was said, and counts as being spoken for that long
+pub struct WavVoice {
+    directory: PathBuf,
+    count: usize,
+    rate: f32,
+    speaking_until: Option<Instant>,
+}
+
+impl WavVoice {
+    pub fn new(directory: PathBuf) -> Result<Self, Box<dyn Error>> {
+        std::fs::create_dir_all(&directory)?;
+        Ok(Self { directory, count: 0, rate: 1., speaking_until: None })
+    }
+
+    /// Returns how long it would take to say some text at the current rate
+    pub fn duration(&self, text: &str) -> Duration {
+        let words = text.split_whitespace().count() as f32;
+        Duration::from_secs_f32(words / (WORDS_PER_SECOND * self.rate))
+    }
+}
+
+impl TtsProvider for WavVoice {
+    fn speak(&mut self, text: &str, interrupt: bool) -> Result<(), Box<dyn Error>> {
+        let duration = self.duration(text);
+        let spec = WavSpec { channels: 1, sample_rate: SAMPLE_RATE, bits_per_sample: 16, sample_format: SampleFormat::Int };
+
+        self.count += 1;
+        let path = self.directory.join(format!("{:04}", self.count));
+        let mut writer = WavWriter::create(path.with_extension("wav"), spec)?;
+        for _ in 0..(duration.as_secs_f32() * SAMPLE_RATE as f32) as usize {
+            writer.write_sample(0i16)?;
+        }
+        writer.finalize()?;
+        std::fs::write(path.with_extension("txt"), text)?;
+
+        // Without interrupting, the utterance is queued after whatever is being said
+        let start = match self.speaking_until {
+            Some(until) if !interrupt && until > Instant::now() => until,
+            _ => Instant::now(),
+        };
+        self.speaking_until = Some(start + duration);
+
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), Box<dyn Error>> {
+        self.speaking_until = None;
+        Ok(())
+    }
+
+    fn is_speaking(&self) -> bool {
+        self.speaking_until.is_some_and(|until| until > Instant::now())
+    }
+
+    fn set_rate(&mut self, rate: f32) -> Result<(), Box<dyn Error>> {
+        self.rate = rate;
+        Ok(())
+    }
+
+    fn can_record(&self) -> bool {
+        true
+    }
+
+    fn synthesize(&mut self, text: &str) -> Result<Audio, Box<dyn Error>> {
+        let length = (self.duration(text).as_secs_f32() * SAMPLE_RATE as f32) as usize;
+        Ok(Audio { samples: vec![0.; length], sample_rate: SAMPLE_RATE })
+    }
+}
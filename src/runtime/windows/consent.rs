@@ -0,0 +1,155 @@
+use iced::{color, window, Alignment, Length, Size, Theme, Task as IcedTask};
+use iced::widget::{column, horizontal_space, row, scrollable, text};
+use iced::window::{icon, Id, Position, Settings};
+use material_icons::Icon;
+use rust_i18n::t;
+use crate::consts::{APP_ICON, BUTTON_DANGER_BACKGROUND, FONT_BOLD};
+use crate::plugins::ConsentRequest;
+use crate::plugins::scopes::PluginScopes;
+use crate::runtime::messaging::{Message, MessageKind, WindowMessage, WindowMessageKind};
+use crate::runtime::{Element, Task};
+use crate::runtime::windows::DesktopWindow;
+use crate::utils::components::buttons::button_with_icon;
+
+#[derive(Debug, Clone)]
+pub enum ConsentWindowMessageKind {
+    Allow,
+    Deny,
+}
+
+#[derive(Debug, Clone)]
+pub struct ConsentWindowMessage {
+    kind: ConsentWindowMessageKind,
+    source_id: Option<Id>,
+}
+
+impl ConsentWindowMessage {
+    pub fn new(kind: ConsentWindowMessageKind, source_id: Id) -> Self {
+        Self {
+            kind,
+            source_id: Some(source_id),
+        }
+    }
+}
+
+impl From<WindowMessageKind> for ConsentWindowMessage {
+    fn from(kind: WindowMessageKind) -> Self {
+        let WindowMessageKind::Consent(message) = kind else { panic!("Somehow got invalid consent event") };
+        message
+    }
+}
+
+impl From<ConsentWindowMessage> for Message {
+    fn from(message: ConsentWindowMessage) -> Self {
+        WindowMessage {
+            source_id: message.source_id,
+            kind: WindowMessageKind::Consent(message),
+        }.into()
+    }
+}
+
+/// Asks the user whether a plugin may have the scopes it requests, listing what each of them allows
+#[derive(Debug)]
+pub struct ConsentWindow {
+    pub id: Id,
+    request: ConsentRequest,
+    /// Whether the user has chosen, so closing the window afterwards does not also deny the plugin
+    answered: bool,
+}
+
+impl ConsentWindow {
+    pub(crate) fn new(request: ConsentRequest) -> (ConsentWindow, IcedTask<Id>) {
+        let (id, task) = window::open(Self::settings());
+        info!("Asking for consent to plugin '{}' in window {}", request.manifest.name, id);
+        (Self { id, request, answered: false }, task)
+    }
+
+    /// Tells the runtime what the user chose for the plugin
+    fn answer(&mut self, allowed: bool) -> Task {
+        self.answered = true;
+        Message::new(MessageKind::PluginConsent(self.request.manifest.name.clone(), allowed), None).into()
+    }
+}
+
+/// Describes what a single scope allows a plugin to do
+fn describe(scope: &str) -> String {
+    match scope {
+        "theme" => t!("windows.plugin-consent.scopes.theme"),
+        "workspace.files.view" => t!("windows.plugin-consent.scopes.workspace-files-view"),
+        "workspace.files.modify" => t!("windows.plugin-consent.scopes.workspace-files-modify"),
+        "keybinds.view" => t!("windows.plugin-consent.scopes.keybinds-view"),
+        "keybinds.modify" => t!("windows.plugin-consent.scopes.keybinds-modify"),
+        "render" => t!("windows.plugin-consent.scopes.render"),
+        "sandbox.avoid" => t!("windows.plugin-consent.scopes.sandbox-avoid"),
+        other => other.into(),
+    }.to_string()
+}
+
+impl DesktopWindow<ConsentWindow, ConsentWindowMessage, Message> for ConsentWindow {
+    fn settings() -> Settings {
+        Settings {
+            size: Size::new(520., 480.),
+            position: Position::Centered,
+            resizable: false,
+            icon: Some(icon::from_file_data(APP_ICON, None).unwrap()),
+            exit_on_close_request: true,
+            ..Default::default()
+        }
+    }
+
+    fn title(&self) -> String {
+        t!("windows.plugin-consent.title", name = self.request.manifest.name).to_string()
+    }
+
+    fn theme(&self) -> Theme {
+        Theme::SolarizedDark
+    }
+
+    fn update(&mut self, message: ConsentWindowMessage) -> Task {
+        let allowed = matches!(message.kind, ConsentWindowMessageKind::Allow);
+        self.answer(allowed).chain(self.close())
+    }
+
+    fn view(&self) -> Element<'_> {
+        let manifest = &self.request.manifest;
+        let emit = |kind: ConsentWindowMessageKind| -> Message { ConsentWindowMessage::new(kind, self.id).into() };
+
+        let scopes = column(self.request.requested.scope_names().into_iter().map(|scope| {
+            let added = PluginScopes::from_scope_name(scope).is_some_and(|s| self.request.added.contains(s));
+            let name = text(scope).font(FONT_BOLD);
+            let name = if added { row!(name, text(t!("windows.plugin-consent.new").to_string()).color(color!(BUTTON_DANGER_BACKGROUND))).spacing(5) } else { row!(name) };
+            column!(name, text(describe(scope)).size(14)).spacing(2).into()
+        })).spacing(10);
+
+        let heading = if self.request.added == self.request.requested {
+            t!("windows.plugin-consent.requested", name = manifest.name, version = manifest.version)
+        } else {
+            t!("windows.plugin-consent.updated", name = manifest.name, version = manifest.version)
+        };
+
+        let mut content = column!(
+            text(heading.to_string()).font(FONT_BOLD).size(20),
+            text(manifest.description.clone()),
+            text(t!("windows.plugin-consent.authors", authors = manifest.authors.join(", ")).to_string()).size(12).color(color!(0x757575)),
+            scrollable(scopes).height(Length::Fill),
+        ).spacing(10);
+
+        if self.request.requested.contains(PluginScopes::AVOID_SANDBOXES) {
+            content = content.push(text(t!("windows.plugin-consent.every-session").to_string()).color(color!(BUTTON_DANGER_BACKGROUND)));
+        }
+
+        content.push(
+            row!(
+                horizontal_space(),
+                button_with_icon(Icon::Block, t!("windows.plugin-consent.deny")).on_press(emit(ConsentWindowMessageKind::Deny)).width(120),
+                button_with_icon(Icon::Check, t!("windows.plugin-consent.allow")).on_press(emit(ConsentWindowMessageKind::Allow)).width(120),
+            ).spacing(5).align_y(Alignment::Center)
+        ).padding(20).into()
+    }
+
+    fn close(&mut self) -> Task {
+        // Closing the window without choosing leaves the plugin stopped, and the user is asked again next time
+        let denied = if self.answered { Task::none() } else { self.answer(false) };
+        denied.chain(window::close(self.id)).chain(Message::window_close(self.id).into())
+    }
+}
@@ -0,0 +1,60 @@
+use iced::widget::{column, container, horizontal_space, row, scrollable, text};
+use iced::window::Id;
+use iced::{color, Alignment, Color, Length};
+use log::Level;
+use material_icons::Icon;
+use rust_i18n::t;
+use crate::consts::FONT_MONO;
+use crate::plugins::console::{self, ConsoleLine};
+use crate::runtime::Element;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::utils::components::buttons::button_with_icon;
+
+/// How tall the console is beneath the current buffer
+const HEIGHT: f32 = 200.;
+
+/// Colours a line of the console by how severe it is
+fn level_color(level: Level) -> Color {
+    match level {
+        Level::Error => color!(0xc34a30),
+        Level::Warn => color!(0xd49b00),
+        Level::Info => color!(0xdddddd),
+        Level::Debug | Level::Trace => color!(0x757575),
+    }
+}
+
+fn line(line: ConsoleLine) -> Element<'static> {
+    text(format!("{} {:<5} [{}] {}", line.time.format("%H:%M:%S"), line.level, line.plugin, line.message))
+        .font(FONT_MONO)
+        .size(13)
+        .color(level_color(line.level))
+        .into()
+}
+
+/// Draws the developer console, which lists what plugins have logged with the newest lines at the bottom
+pub fn view(window: Id) -> Element<'static> {
+    let lines = console::lines();
+    let lines: Element = if lines.is_empty() {
+        text(t!("windows.editor.console.empty").to_string()).color(color!(0x757575)).into()
+    } else {
+        column(lines.into_iter().map(line)).spacing(2).into()
+    };
+
+    container(
+        column!(
+            row!(
+                text(t!("windows.editor.console.title").to_string()).size(18),
+                horizontal_space(),
+                button_with_icon(Icon::ClearAll, t!("windows.editor.console.clear"))
+                    .on_press(EditorMessage::new(EditorMessageKind::ClearConsole, window).into())
+                    .width(120)
+            ).align_y(Alignment::Center),
+            scrollable(lines).anchor_bottom().width(Length::Fill).height(Length::Fill)
+        ).spacing(5)
+    )
+        .padding(10)
+        .width(Length::Fill)
+        .height(HEIGHT)
+        .style(|_| container::Style::default().background(color!(0x1e1e1e)))
+        .into()
+}
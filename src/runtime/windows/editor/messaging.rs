@@ -1,14 +1,85 @@
 use std::path::PathBuf;
 use iced::window::Id;
 use crate::runtime::messaging::{Message, WindowMessage, WindowMessageKind};
+use crate::assets::{AssetPermission, AssetRequest};
 use crate::storage::workspace::buffer::Buffer;
+use crate::storage::workspace::buffer::viewport::Viewport;
+use crate::storage::workspace::tasks::TaskFilter;
+use crate::utils::audio::AudioFormat;
 
 #[derive(Clone, Debug)]
 pub enum EditorMessageKind {
     Tick,
+    /// A note was changed, so its open buffers need updating
     FileChanged(PathBuf),
-    BufferRendered(Buffer),
-    LoadAsset,
+    /// Plugins which change how notes are rendered were loaded or reloaded, so the open buffers are rendered again
+    PluginsChanged,
+    BufferRendered(Box<Buffer>),
+    LoadAsset(String),
+    AssetBlocked(AssetRequest),
+    AssetFetched(AssetRequest, String, Vec<u8>),
+    AssetFailed(String, String),
+    AllowAsset(String, AssetPermission),
+    FileDropped(PathBuf),
+    Paste,
+    PasteText(Option<String>),
+    /// Scrolls the current buffer to the element with the given id, eg: a footnote
+    JumpTo(String),
+    /// Checks or unchecks the task at a byte offset within a note
+    ToggleTask(PathBuf, usize, bool),
+    /// The current buffer was scrolled
+    Scrolled(Viewport),
+    /// A block of the current buffer was drawn at a new height
+    BlockMeasured(usize, f32),
+    /// A top level block of the current buffer was clicked, placing the cursor on it
+    PlaceCursor(usize),
+    /// Opens or closes a `<details>` element in the current buffer
+    ToggleDetails(usize),
+    TasksIndexed,
+    ToggleDashboard,
+    FilterTasks(TaskFilter),
+    ToggleOutline,
+    /// The mouse was pressed on a heading of the outline, which may be the start of dragging its section
+    OutlinePressed(usize),
+    /// The mouse moved over a heading of the outline
+    OutlineHovered(usize),
+    /// The mouse was released on a heading of the outline, either clicking it or dropping a section onto it
+    OutlineReleased(usize),
+    /// The mouse was released elsewhere within the outline
+    OutlineCancelled,
+    /// Starts reading the current buffer aloud from the first block in view, or stops reading it
+    ToggleReadAloud,
+    /// Starts reading the current buffer aloud from a top level block, eg: a heading
+    ReadFrom(usize),
+    /// The speech thread has finished speaking the block being read aloud
+    SegmentSpoken,
+    StopReading,
+    TogglePause,
+    NextSegment,
+    PreviousSegment,
+    /// Moves on to the next speaking rate for reading aloud
+    CycleRate,
+    /// Chooses whether a section of the outline is included when exporting audio
+    ChooseSection(usize, bool),
+    ExportFormat(AudioFormat),
+    /// Speaks the chosen sections of the current buffer into an audio file, or the whole buffer if none are chosen
+    ExportAudio,
+    /// The speech thread has finished exporting audio, either to the given file or with an error
+    AudioExported(Result<PathBuf, String>),
+    /// Shows or hides the developer console, which lists what plugins have logged
+    ToggleConsole,
+    ClearConsole,
+    /// Writes the current buffer back to its note
+    Save,
+    /// Shows or hides the command palette over the editor
+    ToggleCommandPalette,
+    ClosePalette,
+    /// The text the command palette searches for changed
+    PaletteQuery(String),
+    /// Runs the best match for what was typed into the command palette
+    PaletteSubmit,
+    /// Runs a command chosen from the command palette
+    RunPaletteCommand(String),
 }
 
 #[derive(Clone, Debug)]
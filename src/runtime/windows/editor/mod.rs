@@ -1,26 +1,47 @@
 use std::fmt::Debug;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use iced::{window, Size, Task as IcedTask, Theme};
-use iced::widget::{text, container, row, column, scrollable};
+use std::path::{Path, PathBuf};
+use iced::{clipboard, event, keyboard, window, Event, Size, Task as IcedTask, Theme};
+use iced::widget::{text, container, row, column, scrollable, horizontal_space, vertical_space, pick_list, stack, text_input};
+use iced::widget::scrollable::AbsoluteOffset;
 use iced::window::{Id, Position, Settings};
-use iced::{Length, color, Padding};
+use iced::{Length, color, Padding, Alignment};
 use iced::Subscription;
+use material_icons::Icon;
+use notify_rust::{Notification, Timeout};
 use rust_i18n::t;
 use url::Url;
-use crate::consts::{APP_ICON, APP_NAME};
+use crate::assets::Asset;
+use crate::assets::import::{is_importable, save_clipboard_image};
+use crate::consts::{APP_ICON, APP_NAME, ASSET_REFRESH_INTERVAL};
 use crate::runtime::{AppState, Element, Task, GLOBAL_STATE};
-use crate::runtime::messaging::Message;
+use crate::runtime::messaging::{Message, MessageKind};
+use crate::runtime::speech::Speech;
+use crate::runtime::speech::export::unused_path;
 use crate::runtime::windows::DesktopWindow;
 use crate::storage::workspace::WorkspaceManager;
+use crate::ui::theme::THEMES;
+use crate::storage::workspace::buffer::viewport::Viewport;
+use crate::utils::components::buttons::button_with_icon;
 use crate::utils::components::widgets::status_bar::StatusBarWidget;
 
 use self::messaging::{EditorMessage, EditorMessageKind};
+use self::outline::OutlinePanel;
+use self::palette::CommandPalette;
+use self::read_aloud::ReadAloud;
 use self::settings::EditorSettings;
+use self::tasks::TaskDashboard;
 
 pub mod settings;
 pub mod messaging;
+pub mod tasks;
+pub mod outline;
+pub mod read_aloud;
+pub mod console;
+pub mod palette;
     
 
 pub struct EditorWindow {
@@ -31,7 +52,20 @@ pub struct EditorWindow {
     pub settings: EditorSettings,
     pub widgets: Vec<Box<dyn StatusBarWidget>>,
     pub buffers: Vec<String>,
-    pub current_buffer: String
+    pub current_buffer: String,
+    scroll_id: scrollable::Id,
+    viewport: Viewport,
+    /// The task dashboard, which replaces the buffer while it is open
+    tasks: Option<TaskDashboard>,
+    /// The outline of the current buffer, shown beside it while open
+    outline: Option<OutlinePanel>,
+    reading: ReadAloud,
+    /// Whether unpacked plugins are allowed, which is when the developer console is available
+    developer: bool,
+    /// Whether the developer console is shown beneath the buffer
+    console: bool,
+    /// The command palette, which is shown over the editor while open
+    palette: Option<CommandPalette>
 }
 
 impl Debug for EditorWindow {
@@ -41,6 +75,20 @@ impl Debug for EditorWindow {
 }
 
 
+/// Listens for input that the editor acts upon, such as pasting or dropping images onto the window
+pub fn input_event(event: Event, status: event::Status, window: Id) -> Option<Message> {
+    let kind = match event {
+        Event::Window(window::Event::FileDropped(path)) => EditorMessageKind::FileDropped(path),
+        Event::Keyboard(keyboard::Event::KeyPressed { key: keyboard::Key::Character(c), modifiers, .. })
+            if status == event::Status::Ignored && modifiers.command() && c.as_str() == "v" => EditorMessageKind::Paste,
+        // Text inputs stop being focused when escape is pressed, rather than passing it on
+        Event::Keyboard(keyboard::Event::KeyPressed { key: keyboard::Key::Named(keyboard::key::Named::Escape), .. }) => EditorMessageKind::ClosePalette,
+        _ => return None
+    };
+
+    Some(Message::new(MessageKind::EditorInput(kind), Some(window)))
+}
+
 impl EditorWindow {
     pub fn new(mgr: WorkspaceManager) -> (Self, IcedTask<Id>) {
         let (id, task) = window::open(Self::settings());
@@ -53,9 +101,19 @@ impl EditorWindow {
             settings: EditorSettings::new(),
             widgets: vec![],
             buffers,
-            current_buffer: "internal/test".to_string()
+            current_buffer: "internal/test".to_string(),
+            scroll_id: scrollable::Id::unique(),
+            viewport: Viewport::default(),
+            tasks: None,
+            outline: None,
+            reading: ReadAloud::default(),
+            developer: false,
+            console: false,
+            palette: None
         };
 
+        window.developer = window.mgr.get_setting::<bool>("plugins.allow-unpacked").is_some_and(|s| s.value);
+
         if window.buffers.len() == 0 {
             window.buffers.push("noot://internal/test".to_string());
         }
@@ -80,6 +138,60 @@ impl EditorWindow {
         self.widgets.push(Box::new(widget));
     }
 
+    /// Returns the path on disk of the note currently being displayed
+    fn current_note(&self) -> Option<PathBuf> {
+        self.mgr.buffers.get(&self.buffers[0]).and_then(|b| b.url.to_file_path().ok())
+    }
+
+    /// Imports an image into the current note, after the block holding the cursor
+    fn import_image(&mut self, file: PathBuf) {
+        let (Some(note), Some(buffer)) = (self.current_note(), self.mgr.buffers.get(&self.buffers[0])) else {
+            warn!("Cannot import {}, the current buffer is not a file", file.display());
+            return;
+        };
+
+        let (offset, source) = (buffer.insertion_point(), buffer.source_id());
+        self.mgr.import_asset(note, file, offset, source);
+    }
+
+    /// Reloads the outline after the current buffer has changed
+    fn refresh_outline(&mut self) {
+        if let (Some(outline), Some(buffer)) = (&mut self.outline, self.mgr.buffers.get(&self.buffers[0])) {
+            outline.refresh(buffer);
+        }
+    }
+
+    /// Scrolls the current buffer to the top of a block
+    fn scroll_to_block(&self, index: usize) -> Task {
+        match self.mgr.buffers.get(&self.buffers[0]) {
+            Some(buffer) => scrollable::scroll_to(self.scroll_id.clone(), AbsoluteOffset { x: 0., y: buffer.heights.top(index) }),
+            None => Task::none()
+        }
+    }
+
+    /// Moves the reading of the current buffer along, speaking the block it moves to and scrolling to it if it is
+    /// out of view. Reading stops once there is nothing left to read
+    fn read_aloud(&mut self, step: impl FnOnce(&mut ReadAloud, &[String]) -> Option<String>) -> Task {
+        let text = match self.mgr.buffers.get_mut(&self.buffers[0]) {
+            Some(buffer) => step(&mut self.reading, buffer.segments(&self.mgr.assets)),
+            None => None
+        };
+
+        let (Some(text), Some(index)) = (text, self.reading.segment()) else {
+            self.reading.stop();
+            return Message::new(MessageKind::Speak(Speech::Stop), None).into();
+        };
+
+        let speak: Task = Message::new(MessageKind::Speak(Speech::Read(text, self.id)), None).into();
+        let top = self.mgr.buffers.get(&self.buffers[0]).map_or(0., |b| b.heights.top(index));
+
+        if top < self.viewport.offset || top >= self.viewport.offset + self.viewport.height {
+            Task::batch([speak, self.scroll_to_block(index)])
+        } else {
+            speak
+        }
+    }
+
     pub fn emit(&self, kind: EditorMessageKind) -> Message {
         EditorMessage::new(kind, self.id).into()
     }
@@ -92,6 +204,13 @@ impl EditorWindow {
             subscriptions.push(widget.subscribe());
         }
 
+        // Periodically tick so that expired assets can be purged and fetched again
+        subscriptions.push(
+            iced::time::every(Duration::from_secs(ASSET_REFRESH_INTERVAL))
+                .with(self.id)
+                .map(|(id, _)| EditorMessage::new(EditorMessageKind::Tick, id).into())
+        );
+
 
         Subscription::batch(subscriptions)
     }
@@ -125,12 +244,311 @@ impl DesktopWindow<EditorWindow, EditorMessage, Message> for EditorWindow {
 
     fn update(&mut self, message: EditorMessage) -> Task {
         match message.kind {
-            EditorMessageKind::BufferRendered(buffer) => {
+            EditorMessageKind::BufferRendered(mut buffer) => {
                 info!("Adding buffer to list: {}", buffer.id);
-                self.mgr.buffers.insert(buffer.id.clone(), buffer);
+                // Every render creates a new buffer, so an earlier render of the same note is found by its URL and replaced
+                let key = match self.mgr.buffers.iter().find(|(_, previous)| previous.url == buffer.url) {
+                    Some((key, previous)) => {
+                        buffer.heights.carry_over(&previous.heights);
+                        key.clone()
+                    }
+                    None => buffer.id.clone(),
+                };
+                self.mgr.buffers.insert(key, *buffer);
+                self.refresh_outline();
+                Task::none()
+            },
+            EditorMessageKind::FileChanged(note) => {
+                self.mgr.reload_buffer(&note);
+                self.refresh_outline();
+                Task::none()
+            }
+            EditorMessageKind::PluginsChanged => {
+                self.mgr.render_buffers();
+                self.refresh_outline();
+                Task::none()
+            }
+            EditorMessageKind::Scrolled(viewport) => {
+                self.viewport = viewport;
+                if let Some(buffer) = self.mgr.buffers.get_mut(&self.buffers[0]) {
+                    buffer.heights.scrolled(viewport);
+                }
+                Task::none()
+            }
+            EditorMessageKind::BlockMeasured(index, height) => {
+                let shift = self.mgr.buffers.get_mut(&self.buffers[0]).and_then(|b| b.heights.measure(index, height));
+
+                // Keep the blocks in view still when a block above them turns out to be a different height
+                match shift {
+                    Some(y) => scrollable::scroll_by(self.scroll_id.clone(), AbsoluteOffset { x: 0., y }),
+                    None => Task::none()
+                }
+            }
+            EditorMessageKind::PlaceCursor(index) => {
+                if let Some(buffer) = self.mgr.buffers.get_mut(&self.buffers[0]) {
+                    buffer.place_cursor(index);
+                }
+                Task::none()
+            }
+            EditorMessageKind::LoadAsset(id) => {
+                self.mgr.load_asset(id);
+                Task::none()
+            }
+            EditorMessageKind::AssetBlocked(blocked) => {
+                self.mgr.assets.block(blocked);
+                Task::none()
+            }
+            EditorMessageKind::AssetFetched(request, mime, bytes) => {
+                let asset = Asset::from_bytes(request.id.clone(), mime, bytes);
+                self.mgr.assets.cache(request, asset);
+                Task::none()
+            }
+            EditorMessageKind::AssetFailed(id, reason) => {
+                self.mgr.assets.fail(id, reason);
+                Task::none()
+            }
+            EditorMessageKind::AllowAsset(id, permission) => {
+                self.mgr.allow_asset(id, permission);
+                Task::none()
+            }
+            EditorMessageKind::FileDropped(file) => {
+                self.import_image(file);
+                Task::none()
+            }
+            EditorMessageKind::Paste => {
+                let id = self.id;
+                clipboard::read().map(move |text| EditorMessage::new(EditorMessageKind::PasteText(text), id).into())
+            }
+            EditorMessageKind::PasteText(text) => {
+                // Copying files from a file manager places their paths or `file://` URIs on the clipboard
+                let files: Vec<PathBuf> = text.unwrap_or_default().lines()
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| Url::parse(line).ok().and_then(|u| u.to_file_path().ok()).unwrap_or(PathBuf::from(line)))
+                    .filter(|path| is_importable(path))
+                    .collect();
+
+                if files.is_empty() {
+                    // Otherwise the clipboard may hold image data, eg: a screenshot
+                    match save_clipboard_image() {
+                        Ok(Some(file)) => self.import_image(file),
+                        Ok(None) => debug!("Nothing to paste"),
+                        Err(err) => error!("Failed to paste image: {:?}", err),
+                    }
+                }
+
+                for file in files {
+                    self.import_image(file);
+                }
+                Task::none()
+            }
+            EditorMessageKind::JumpTo(anchor) => {
+                match self.mgr.buffers.get(&self.buffers[0]).and_then(|b| b.anchor_offset(&anchor)) {
+                    Some(y) => scrollable::scroll_to(self.scroll_id.clone(), AbsoluteOffset { x: 0., y }),
+                    None => {
+                        warn!("Unable to find '{}' in the current buffer", anchor);
+                        Task::none()
+                    }
+                }
+            }
+            EditorMessageKind::ToggleTask(note, offset, done) => {
+                self.mgr.toggle_task(note, offset, done);
+                Task::none()
+            }
+            EditorMessageKind::ToggleDetails(index) => {
+                if let Some(buffer) = self.mgr.buffers.get_mut(&self.buffers[0]) {
+                    buffer.toggle_details(index);
+                }
                 Task::none()
+            }
+            EditorMessageKind::TasksIndexed => {
+                if let Some(dashboard) = &mut self.tasks {
+                    dashboard.refresh(&self.mgr);
+                }
+                Task::none()
+            }
+            EditorMessageKind::ToggleDashboard => {
+                self.tasks = match self.tasks {
+                    Some(_) => None,
+                    None => Some(TaskDashboard::new(&self.mgr))
+                };
+                Task::none()
+            }
+            EditorMessageKind::FilterTasks(filter) => {
+                if let Some(dashboard) = &mut self.tasks {
+                    dashboard.filter = filter;
+                    dashboard.refresh(&self.mgr);
+                }
+                Task::none()
+            }
+            EditorMessageKind::ToggleOutline => {
+                self.outline = match self.outline {
+                    Some(_) => None,
+                    None => self.mgr.buffers.get(&self.buffers[0]).map(OutlinePanel::new)
+                };
+                Task::none()
+            }
+            EditorMessageKind::OutlinePressed(index) => {
+                if let Some(outline) = &mut self.outline {
+                    outline.press(index);
+                }
+                Task::none()
+            }
+            EditorMessageKind::OutlineHovered(index) => {
+                if let Some(outline) = &mut self.outline {
+                    outline.hover(index);
+                }
+                Task::none()
+            }
+            EditorMessageKind::OutlineReleased(index) => {
+                let Some(outline) = &mut self.outline else { return Task::none() };
+
+                match outline.release() {
+                    // Clicking a heading scrolls to it
+                    Some(pressed) if pressed == index => match outline.block(index) {
+                        Some(block) => self.scroll_to_block(block),
+                        None => Task::none()
+                    },
+                    // Dragging a heading onto another moves its whole section there
+                    Some(pressed) => {
+                        match self.current_note() {
+                            Some(note) => self.mgr.move_section(note, pressed, index),
+                            None => warn!("Cannot reorder sections, the current buffer is not a file")
+                        }
+                        Task::none()
+                    }
+                    None => Task::none()
+                }
+            }
+            EditorMessageKind::OutlineCancelled => {
+                if let Some(outline) = &mut self.outline {
+                    outline.release();
+                }
+                Task::none()
+            }
+            EditorMessageKind::ToggleReadAloud => {
+                if self.reading.segment().is_some() {
+                    self.reading.stop();
+                    return Message::new(MessageKind::Speak(Speech::Stop), None).into();
+                }
+
+                let anchor = self.mgr.buffers.get(&self.buffers[0]).map_or(0, |b| b.heights.anchor());
+                self.read_aloud(|reading, segments| reading.read_from(segments, anchor))
+            }
+            EditorMessageKind::ReadFrom(block) => self.read_aloud(|reading, segments| reading.read_from(segments, block)),
+            EditorMessageKind::SegmentSpoken if !self.reading.paused() => self.read_aloud(ReadAloud::next),
+            EditorMessageKind::NextSegment => self.read_aloud(ReadAloud::next),
+            EditorMessageKind::PreviousSegment => self.read_aloud(ReadAloud::previous),
+            EditorMessageKind::TogglePause if self.reading.paused() => self.read_aloud(ReadAloud::resume),
+            EditorMessageKind::TogglePause => {
+                self.reading.pause();
+                Message::new(MessageKind::Speak(Speech::Stop), None).into()
+            }
+            EditorMessageKind::StopReading => {
+                self.reading.stop();
+                Message::new(MessageKind::Speak(Speech::Stop), None).into()
+            }
+            EditorMessageKind::CycleRate => {
+                let rate = self.reading.cycle_rate();
+                Message::new(MessageKind::Speak(Speech::Rate(rate)), None).into()
+            }
+            EditorMessageKind::ChooseSection(index, chosen) => {
+                if let Some(outline) = &mut self.outline {
+                    outline.choose(index, chosen);
+                }
+                Task::none()
+            }
+            EditorMessageKind::ExportFormat(format) => {
+                if let Some(outline) = &mut self.outline {
+                    outline.set_format(format);
+                }
+                Task::none()
+            }
+            EditorMessageKind::ExportAudio => {
+                let (Some(note), Some(outline)) = (self.current_note(), &mut self.outline) else {
+                    warn!("Cannot export audio, the current buffer is not a file");
+                    return Task::none();
+                };
+                let Some(buffer) = self.mgr.buffers.get_mut(&self.buffers[0]) else { return Task::none() };
+
+                let format = outline.format();
+                let chapters = buffer.chapters(&self.mgr.assets, outline.chosen());
+                let path = unused_path(&note.with_extension(format.extension()));
+                outline.set_exporting(true);
+
+                Message::new(MessageKind::Speak(Speech::Export(buffer.name.clone(), chapters, path, format, self.id)), None).into()
+            }
+            EditorMessageKind::AudioExported(outcome) => {
+                if let Some(outline) = &mut self.outline {
+                    outline.set_exporting(false);
+                }
+
+                let body = match outcome {
+                    Ok(path) => t!("windows.editor.export.done", path = path.display()),
+                    Err(reason) => {
+                        error!("Unable to export audio: {}", reason);
+                        t!("windows.editor.export.failed", reason = reason)
+                    }
+                };
+                let _ = Notification::new()
+                    .summary(&t!("windows.editor.export.title"))
+                    .body(&body)
+                    .appname(APP_NAME)
+                    .timeout(Timeout::Default)
+                    .show();
+                Task::none()
+            }
+            EditorMessageKind::ToggleConsole => {
+                self.console = !self.console;
+                Task::none()
+            }
+            EditorMessageKind::ClearConsole => {
+                crate::plugins::console::clear();
+                Task::none()
+            }
+            EditorMessageKind::Save => {
+                match self.mgr.buffers.get_mut(&self.buffers[0]).map(|buffer| buffer.save()) {
+                    Some(Ok(Some(path))) => info!("Saved {}", path.display()),
+                    Some(Ok(None)) => info!("Nothing to save, the current buffer has no unsaved changes"),
+                    Some(Err(e)) => error!("Unable to save the current buffer: {}", e),
+                    None => warn!("Cannot save, there is no buffer open")
+                }
+                Task::none()
+            }
+            EditorMessageKind::ToggleCommandPalette => match self.palette {
+                Some(_) => {
+                    self.palette = None;
+                    Task::none()
+                }
+                None => {
+                    let palette = CommandPalette::default();
+                    let focus = text_input::focus(palette.input_id.clone());
+                    self.palette = Some(palette);
+                    focus
+                }
             },
+            EditorMessageKind::ClosePalette => {
+                self.palette = None;
+                Task::none()
+            }
+            EditorMessageKind::PaletteQuery(query) => {
+                if let Some(palette) = &mut self.palette {
+                    palette.set_query(query);
+                }
+                Task::none()
+            }
+            EditorMessageKind::PaletteSubmit => {
+                match self.palette.as_ref().and_then(|palette| palette.matches().into_iter().next()) {
+                    Some(command) => self.update(EditorMessage::new(EditorMessageKind::RunPaletteCommand(command.id), self.id)),
+                    None => Task::none()
+                }
+            }
+            EditorMessageKind::RunPaletteCommand(command) => {
+                self.palette = None;
+                Message::new(MessageKind::RunCommand(command), Some(self.id)).into()
+            }
             EditorMessageKind::Tick => {
+                self.mgr.refresh_assets();
                 if self.ticker == 255 {
                     self.ticker = 0;
                 } else {
@@ -143,13 +561,48 @@ impl DesktopWindow<EditorWindow, EditorMessage, Message> for EditorWindow {
     }
 
     fn view(&self) -> Element {
+        let id = self.id;
+        let buffer = self.mgr.buffers.get(&self.buffers[0]).unwrap();
+        let (themes, theme) = {
+            let themes = THEMES.lock().unwrap();
+            (themes.names(), themes.active.clone())
+        };
         let mut status_bar_padding = Padding::new(5.);
         status_bar_padding.left = 10.;
         status_bar_padding.right = 10.;
 
-        column!(
+        let editor = column!(
             container(
-                text("Status Bar")
+                row!(
+                    text("Status Bar"),
+                    horizontal_space(),
+                    pick_list(themes, Some(theme), |theme| Message::new(MessageKind::SelectTheme(theme), None))
+                        .placeholder(t!("windows.editor.theme").to_string())
+                        .width(180),
+                    match self.reading.segment() {
+                        Some(_) => self.reading.view(self.id),
+                        None => horizontal_space().width(0).into()
+                    },
+                    button_with_icon(Icon::RecordVoiceOver, match self.reading.segment() {
+                        Some(_) => t!("windows.editor.read-aloud.stop"),
+                        None => t!("windows.editor.read-aloud.title")
+                    })
+                        .on_press(self.emit(EditorMessageKind::ToggleReadAloud))
+                        .width(180),
+                    button_with_icon(Icon::Toc, t!("windows.editor.outline.title"))
+                        .on_press(self.emit(EditorMessageKind::ToggleOutline))
+                        .width(180),
+                    button_with_icon(Icon::PlaylistAddCheck, t!("windows.editor.tasks.title"))
+                        .on_press(self.emit(EditorMessageKind::ToggleDashboard))
+                        .width(180),
+                    match self.developer {
+                        true => button_with_icon(Icon::DeveloperMode, t!("windows.editor.console.title"))
+                            .on_press(self.emit(EditorMessageKind::ToggleConsole))
+                            .width(180)
+                            .into(),
+                        false => Element::from(horizontal_space().width(0))
+                    }
+                ).spacing(10).align_y(Alignment::Center)
             ).width(Length::Fill).height(30).padding(status_bar_padding).style(|_| {
                     container::Style::default()
                         .background(color!(0xa30000))
@@ -172,15 +625,38 @@ impl DesktopWindow<EditorWindow, EditorMessage, Message> for EditorWindow {
                                 }
                             })
                         ),
-                        scrollable(
-                            column!(
-                                self.mgr.buffers.get(&self.buffers[0]).unwrap().view()
-                            )
-                        )
+                        match &self.tasks {
+                            Some(dashboard) => dashboard.view(self.id, Path::new(&self.mgr.source.disk_path)),
+                            None => row!(
+                                scrollable(
+                                    column!(
+                                        buffer.view(&self.mgr.assets, self.viewport, self.reading.segment(), self.id)
+                                    )
+                                )
+                                .id(self.scroll_id.clone())
+                                .on_scroll(move |viewport| EditorMessage::new(EditorMessageKind::Scrolled(Viewport {
+                                    offset: viewport.absolute_offset().y,
+                                    height: viewport.bounds().height
+                                }), id).into()),
+                                match &self.outline {
+                                    Some(outline) => outline.view(self.id, buffer.heights.anchor()),
+                                    None => horizontal_space().width(0).into()
+                                }
+                            ).into()
+                        }
                     )
                 )
-            )
-        ).into()
+            ).height(Length::Fill),
+            match self.console {
+                true => console::view(self.id),
+                false => vertical_space().height(0).into()
+            }
+        );
+
+        match &self.palette {
+            Some(palette) => stack![editor, palette.view(self.id)].into(),
+            None => editor.into()
+        }
     }
 
     fn close(&mut self) -> Task {
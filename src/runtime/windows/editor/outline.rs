@@ -0,0 +1,158 @@
+use iced::widget::{checkbox, column, container, mouse_area, pick_list, row, scrollable, text};
+use iced::window::Id;
+use iced::{color, Alignment, Border, Length, Padding};
+use material_icons::Icon;
+use rust_i18n::t;
+use crate::runtime::{Element, Message};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::storage::workspace::buffer::Buffer;
+use crate::storage::workspace::buffer::outline::Section;
+use crate::utils::audio::AudioFormat;
+use crate::utils::components::buttons::button_with_icon;
+
+/// How far each level of heading is indented within the outline
+const INDENT: f32 = 12.;
+
+/// Lists the headings of the current buffer, for navigating between its sections and reordering them
+#[derive(Debug, Clone, Default)]
+pub struct OutlinePanel {
+    sections: Vec<Section>,
+    /// The top level block of the buffer each heading was parsed into
+    blocks: Vec<Option<usize>>,
+    /// The heading the mouse was pressed on, whose section is dragged until the mouse is released
+    pressed: Option<usize>,
+    hovered: Option<usize>,
+    /// The headings whose sections are exported as audio, or every section if none are chosen
+    chosen: Vec<usize>,
+    format: AudioFormat,
+    /// Whether audio is being exported, which only one export can do at a time
+    exporting: bool,
+}
+
+impl OutlinePanel {
+    pub fn new(buffer: &Buffer) -> Self {
+        let mut outline = Self::default();
+        outline.refresh(buffer);
+        outline
+    }
+
+    /// Reloads the headings, eg: after the note has changed
+    pub fn refresh(&mut self, buffer: &Buffer) {
+        self.sections = buffer.outline();
+        self.blocks = self.sections.iter().map(|s| buffer.block_at(s.range.start)).collect();
+        self.chosen.retain(|&i| i < self.sections.len());
+    }
+
+    pub fn press(&mut self, index: usize) {
+        self.pressed = Some(index);
+    }
+
+    pub fn hover(&mut self, index: usize) {
+        self.hovered = Some(index);
+    }
+
+    /// Ends a click or a drag, returning the heading the mouse was first pressed on
+    pub fn release(&mut self) -> Option<usize> {
+        self.hovered = None;
+        self.pressed.take()
+    }
+
+    /// Includes or excludes a section when exporting audio
+    pub fn choose(&mut self, index: usize, chosen: bool) {
+        self.chosen.retain(|&i| i != index);
+        if chosen {
+            self.chosen.push(index);
+            self.chosen.sort();
+        }
+    }
+
+    pub fn chosen(&self) -> &[usize] {
+        &self.chosen
+    }
+
+    pub fn set_format(&mut self, format: AudioFormat) {
+        self.format = format;
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    pub fn set_exporting(&mut self, exporting: bool) {
+        self.exporting = exporting;
+    }
+
+    /// Returns the top level block of the buffer a heading was parsed into
+    pub fn block(&self, index: usize) -> Option<usize> {
+        self.blocks.get(index).copied().flatten()
+    }
+
+    /// Returns the heading of the section which contains the given block
+    fn current(&self, block: usize) -> Option<usize> {
+        self.blocks.iter().rposition(|b| b.is_some_and(|b| b <= block))
+    }
+
+    /// Draws the outline, highlighting the section containing the first block in view
+    pub fn view(&self, window: Id, anchor: usize) -> Element<'_> {
+        let emit = move |kind: EditorMessageKind| -> Message { EditorMessage::new(kind, window).into() };
+
+        let current = self.current(anchor);
+        let dragging = self.pressed.filter(|pressed| self.hovered != Some(*pressed));
+        let top = self.sections.iter().map(|s| s.level).min().unwrap_or(1);
+
+        let headings: Element = if self.sections.is_empty() {
+            text(t!("windows.editor.outline.empty").to_string()).color(color!(0x757575)).into()
+        } else {
+            column(self.sections.iter().enumerate().map(|(i, section)| {
+                let highlighted = current == Some(i);
+                let target = dragging.is_some() && self.hovered == Some(i);
+
+                let heading = container(text(section.text.clone()))
+                    .padding(Padding { left: 5. + INDENT * (section.level - top) as f32, ..Padding::new(3.) })
+                    .width(Length::Fill)
+                    .style(move |_| container::Style {
+                        background: highlighted.then(|| color!(0xa30000, 0.25).into()),
+                        border: Border {
+                            color: color!(0xa30000),
+                            width: if target { 1. } else { 0. },
+                            radius: 3.into(),
+                        },
+                        ..Default::default()
+                    });
+
+                row!(
+                    checkbox("", self.chosen.contains(&i)).on_toggle(move |chosen| emit(EditorMessageKind::ChooseSection(i, chosen))),
+                    mouse_area(heading)
+                        .on_press(emit(EditorMessageKind::OutlinePressed(i)))
+                        .on_enter(emit(EditorMessageKind::OutlineHovered(i)))
+                        .on_release(emit(EditorMessageKind::OutlineReleased(i)))
+                ).align_y(Alignment::Center).into()
+            })).spacing(2).into()
+        };
+
+        let label = match (self.exporting, self.chosen.is_empty()) {
+            (true, _) => t!("windows.editor.export.exporting"),
+            (false, true) => t!("windows.editor.export.note"),
+            (false, false) => t!("windows.editor.export.sections"),
+        };
+
+        let export = row!(
+            pick_list(AudioFormat::ALL, Some(self.format), move |format| emit(EditorMessageKind::ExportFormat(format))),
+            button_with_icon(Icon::Audiotrack, label)
+                .on_press_maybe((!self.exporting).then(|| emit(EditorMessageKind::ExportAudio)))
+                .width(Length::Fill)
+        ).spacing(5).align_y(Alignment::Center);
+
+        mouse_area(
+            container(
+                column!(
+                    text(t!("windows.editor.outline.title").to_string()).size(18),
+                    scrollable(headings).height(Length::Fill),
+                    export
+                ).spacing(10)
+            ).padding(10).width(250)
+        )
+        .on_release(emit(EditorMessageKind::OutlineCancelled))
+        .into()
+    }
+}
@@ -0,0 +1,86 @@
+use iced::widget::{button, column, container, mouse_area, opaque, row, scrollable, text, text_input, horizontal_space};
+use iced::window::Id;
+use iced::{color, Border, Length, Padding};
+use rust_i18n::t;
+use crate::consts::FONT_MONO;
+use crate::runtime::commands::{Command, COMMANDS};
+use crate::runtime::{Element, Message};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+
+/// How many matching commands are listed at once
+const RESULT_LIMIT: usize = 12;
+
+/// Finds commands by fuzzily matching their titles against what the user types, and runs the one they choose
+#[derive(Debug, Clone)]
+pub struct CommandPalette {
+    query: String,
+    pub input_id: text_input::Id,
+}
+
+impl Default for CommandPalette {
+    fn default() -> Self {
+        Self { query: String::new(), input_id: text_input::Id::unique() }
+    }
+}
+
+impl CommandPalette {
+    pub fn set_query(&mut self, query: String) {
+        self.query = query;
+    }
+
+    /// Returns the commands matching the query, best first
+    pub fn matches(&self) -> Vec<Command> {
+        let mut matches = COMMANDS.lock().unwrap().search(&self.query);
+        matches.truncate(RESULT_LIMIT);
+        matches
+    }
+
+    /// Draws the palette, which is shown over the top of the editor
+    pub fn view(&self, window: Id) -> Element<'_> {
+        let emit = move |kind: EditorMessageKind| -> Message { EditorMessage::new(kind, window).into() };
+        let matches = self.matches();
+        let commands = COMMANDS.lock().unwrap();
+
+        let results: Element = match matches {
+            matches if matches.is_empty() => text(t!("windows.editor.palette.empty").to_string()).color(color!(0x757575)).into(),
+            matches => column(matches.into_iter().map(|command| {
+                let keys = commands.keys_for(&command.id).map(|keys| keys.to_string()).unwrap_or_default();
+                button(row!(text(command.title), horizontal_space(), text(keys).font(FONT_MONO).size(13).color(color!(0x757575))))
+                    .on_press(emit(EditorMessageKind::RunPaletteCommand(command.id)))
+                    .width(Length::Fill)
+                    .style(button::text)
+                    .into()
+            })).spacing(2).into()
+        };
+
+        // Clicking outside of the palette closes it
+        mouse_area(
+            container(
+                opaque(
+                    container(
+                        column!(
+                            text_input(&t!("windows.editor.palette.placeholder"), &self.query)
+                                .id(self.input_id.clone())
+                                .on_input(move |query| emit(EditorMessageKind::PaletteQuery(query)))
+                                .on_submit(emit(EditorMessageKind::PaletteSubmit))
+                                .padding(8),
+                            scrollable(results).height(Length::Shrink)
+                        ).spacing(8)
+                    )
+                        .padding(10)
+                        .width(500)
+                        .style(|theme: &iced::Theme| container::Style {
+                            background: Some(theme.extended_palette().background.base.color.into()),
+                            border: Border { color: color!(0xa30000), width: 1., radius: 5.into() },
+                            ..Default::default()
+                        })
+                )
+            )
+                .padding(Padding { top: 60., ..Padding::ZERO })
+                .center_x(Length::Fill)
+                .height(Length::Fill)
+        )
+            .on_press(emit(EditorMessageKind::ClosePalette))
+            .into()
+    }
+}
@@ -0,0 +1,109 @@
+use iced::widget::{row, text, tooltip};
+use iced::widget::tooltip::Position;
+use iced::window::Id;
+use material_icons::Icon;
+use rust_i18n::t;
+use crate::runtime::{Element, Message};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::utils::components::buttons::button_with_icon;
+
+#[cfg(test)]
+mod tests;
+
+/// The speaking rates which can be chosen, as multiples of the normal rate of the voice
+const RATES: [f32; 5] = [0.75, 1., 1.25, 1.5, 2.];
+
+/// Reads the current buffer aloud, one top level block at a time
+#[derive(Debug, Clone)]
+pub struct ReadAloud {
+    /// The block being read, while the buffer is being read aloud
+    segment: Option<usize>,
+    paused: bool,
+    rate: f32,
+}
+
+impl Default for ReadAloud {
+    fn default() -> Self {
+        Self { segment: None, paused: false, rate: 1. }
+    }
+}
+
+impl ReadAloud {
+    /// Returns the block being read, if the buffer is being read aloud
+    pub fn segment(&self) -> Option<usize> {
+        self.segment
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Starts reading from a block, or from the first block after it with something to say.
+    /// Returns the text to speak, or `None` if there is nothing left to read
+    pub fn read_from(&mut self, segments: &[String], block: usize) -> Option<String> {
+        self.segment = (block..segments.len()).find(|&i| !segments[i].is_empty());
+        self.paused = false;
+        self.segment.map(|i| segments[i].clone())
+    }
+
+    /// Moves on to the next block with something to say
+    pub fn next(&mut self, segments: &[String]) -> Option<String> {
+        let current = self.segment?;
+        self.read_from(segments, current + 1)
+    }
+
+    /// Moves back to the previous block with something to say, or reads the first block again
+    pub fn previous(&mut self, segments: &[String]) -> Option<String> {
+        let current = self.segment?;
+        let previous = (0..current).rfind(|&i| segments.get(i).is_some_and(|s| !s.is_empty())).unwrap_or(current);
+        self.read_from(segments, previous)
+    }
+
+    /// Reads the current block again from its start, as speech cannot be resumed part way through
+    pub fn resume(&mut self, segments: &[String]) -> Option<String> {
+        let current = self.segment?;
+        self.read_from(segments, current)
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.segment = None;
+        self.paused = false;
+    }
+
+    /// Moves on to the next speaking rate, returning it
+    pub fn cycle_rate(&mut self) -> f32 {
+        let index = RATES.iter().position(|r| *r == self.rate).map_or(0, |i| (i + 1) % RATES.len());
+        self.rate = RATES[index];
+        self.rate
+    }
+
+    /// Draws the controls for reading aloud, which sit in the status bar while the buffer is being read
+    pub fn view(&self, window: Id) -> Element<'_> {
+        let emit = move |kind: EditorMessageKind| -> Message { EditorMessage::new(kind, window).into() };
+        let control = move |icon: Icon, label: String, kind: EditorMessageKind| -> Element<'_> {
+            tooltip(
+                button_with_icon(icon, "").on_press(emit(kind)).width(40),
+                text(label),
+                Position::Bottom
+            ).into()
+        };
+
+        let (pause_icon, pause_label) = match self.paused {
+            true => (Icon::PlayArrow, t!("windows.editor.read-aloud.resume")),
+            false => (Icon::Pause, t!("windows.editor.read-aloud.pause")),
+        };
+
+        row!(
+            control(Icon::SkipPrevious, t!("windows.editor.read-aloud.previous").to_string(), EditorMessageKind::PreviousSegment),
+            control(pause_icon, pause_label.to_string(), EditorMessageKind::TogglePause),
+            control(Icon::SkipNext, t!("windows.editor.read-aloud.next").to_string(), EditorMessageKind::NextSegment),
+            button_with_icon(Icon::FastForward, t!("windows.editor.read-aloud.rate", rate = self.rate))
+                .on_press(emit(EditorMessageKind::CycleRate))
+                .width(100),
+        ).spacing(5).into()
+    }
+}
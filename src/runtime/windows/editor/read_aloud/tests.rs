@@ -0,0 +1,94 @@
+//! Tests for moving between the blocks of a buffer while it is read aloud.
+
+use super::*;
+
+/// The spoken text of each top level block, where empty blocks have nothing to say
+fn segments() -> Vec<String> {
+    ["Title", "", "First", "Second", "", ""].iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn read_from_skips_blocks_with_nothing_to_say() {
+    let mut reading = ReadAloud::default();
+
+    assert_eq!(reading.read_from(&segments(), 1).as_deref(), Some("First"));
+    assert_eq!(reading.segment(), Some(2));
+    assert!(!reading.paused());
+}
+
+#[test]
+fn read_from_stops_past_the_last_block() {
+    let mut reading = ReadAloud::default();
+
+    assert_eq!(reading.read_from(&segments(), 4), None);
+    assert_eq!(reading.segment(), None);
+    assert_eq!(reading.read_from(&segments(), 10), None);
+}
+
+#[test]
+fn next_moves_on_and_finishes_after_the_last_block() {
+    let mut reading = ReadAloud::default();
+    reading.read_from(&segments(), 0);
+
+    assert_eq!(reading.next(&segments()).as_deref(), Some("First"));
+    assert_eq!(reading.next(&segments()).as_deref(), Some("Second"));
+    assert_eq!(reading.next(&segments()), None);
+    assert_eq!(reading.segment(), None);
+}
+
+#[test]
+fn previous_moves_back_over_empty_blocks() {
+    let mut reading = ReadAloud::default();
+    reading.read_from(&segments(), 2);
+
+    assert_eq!(reading.previous(&segments()).as_deref(), Some("Title"));
+    assert_eq!(reading.segment(), Some(0));
+}
+
+#[test]
+fn previous_reads_the_first_block_again() {
+    let mut reading = ReadAloud::default();
+    reading.read_from(&segments(), 0);
+
+    assert_eq!(reading.previous(&segments()).as_deref(), Some("Title"));
+    assert_eq!(reading.segment(), Some(0));
+}
+
+#[test]
+fn resume_reads_the_current_block_again() {
+    let mut reading = ReadAloud::default();
+    reading.read_from(&segments(), 3);
+    reading.pause();
+    assert!(reading.paused());
+
+    assert_eq!(reading.resume(&segments()).as_deref(), Some("Second"));
+    assert!(!reading.paused());
+}
+
+#[test]
+fn moving_does_nothing_when_not_reading() {
+    let mut reading = ReadAloud::default();
+
+    assert_eq!(reading.next(&segments()), None);
+    assert_eq!(reading.previous(&segments()), None);
+    assert_eq!(reading.resume(&segments()), None);
+}
+
+#[test]
+fn stop_clears_the_block_being_read() {
+    let mut reading = ReadAloud::default();
+    reading.read_from(&segments(), 0);
+    reading.pause();
+    reading.stop();
+
+    assert_eq!(reading.segment(), None);
+    assert!(!reading.paused());
+}
+
+#[test]
+fn cycle_rate_wraps_around() {
+    let mut reading = ReadAloud::default();
+    let rates: Vec<f32> = (0..RATES.len()).map(|_| reading.cycle_rate()).collect();
+
+    assert_eq!(rates, vec![1.25, 1.5, 2., 0.75, 1.]);
+}
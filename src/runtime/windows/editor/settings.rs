@@ -1,4 +1,5 @@
-use crate::storage::workspace::{AssetCachingStrategy, RemoteDataStrategy, WorkspaceManager};
+use crate::plugins::DEFAULT_PLUGIN_DIRECTORY;
+use crate::storage::workspace::{AssetCachingStrategy, ImageImportFormat, RemoteDataStrategy, WorkspaceManager};
 
 
 #[derive(Debug)]
@@ -7,16 +8,24 @@ pub struct EditorSettings {
     assets: EditorAssetSettings,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct EditorPluginSettings {
     pub enable: bool,
     pub allow_unpacked: bool,
+    /// Where plugins are installed, relative to the workspace root
+    pub directory: String,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct EditorAssetSettings {
     pub cache_strategy: AssetCachingStrategy,
     pub fetch_remote: RemoteDataStrategy,
+    pub allowed_domains: Vec<String>,
+    /// Whether notes may show local images from outside of the workspace
+    pub allow_external_files: bool,
+    /// The maximum width of imported images, larger images are scaled down. `0` disables resizing
+    pub import_max_width: u32,
+    pub import_format: ImageImportFormat,
 }
 
 
@@ -38,14 +47,19 @@ impl EditorSettings {
 impl EditorPluginSettings {
     pub fn save(&self, mgr: &mut WorkspaceManager) {
         mgr.set_setting("plugins.enable", self.enable)
-            .set_setting("plugins.allow-unpacked", self.allow_unpacked);
+            .set_setting("plugins.allow-unpacked", self.allow_unpacked)
+            .set_setting("plugins.directory", self.directory.clone());
     }
 }
 
 impl EditorAssetSettings {
     pub fn save(&self, mgr: &mut WorkspaceManager) {
         mgr.set_setting("assets.cache-strategy", self.cache_strategy)
-            .set_setting("assets.fetch-remote", self.fetch_remote);
+            .set_setting("assets.fetch-remote", self.fetch_remote)
+            .set_setting("assets.allowed-domains", self.allowed_domains.clone())
+            .set_setting("assets.allow-external-files", self.allow_external_files)
+            .set_setting("assets.import.max-width", self.import_max_width)
+            .set_setting("assets.import.format", self.import_format);
     }
 }
 
@@ -55,6 +69,7 @@ impl Default for EditorPluginSettings {
         Self {
             enable: false,
             allow_unpacked: false,
+            directory: DEFAULT_PLUGIN_DIRECTORY.to_string(),
         }
     }
 }
@@ -63,10 +78,11 @@ impl Default for EditorAssetSettings {
     fn default() -> Self {
         Self {
             cache_strategy: AssetCachingStrategy::Blob,
-            #[cfg(not(feature = "enterprise"))]
-            fetch_remote: RemoteDataStrategy::All,
-            #[cfg(feature = "enterprise")]
-            fetch_remote: RemoteDataStrategy::None,
+            fetch_remote: RemoteDataStrategy::default(),
+            allowed_domains: vec![],
+            allow_external_files: false,
+            import_max_width: 0,
+            import_format: ImageImportFormat::Original,
         }
     }
 }
@@ -0,0 +1,111 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use iced::widget::{checkbox, column, container, horizontal_space, pick_list, row, scrollable, text, text_input};
+use iced::window::Id;
+use iced::{color, Alignment, Length};
+use rust_i18n::t;
+use crate::runtime::{Element, Message};
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::storage::workspace::WorkspaceManager;
+use crate::storage::workspace::tasks::{DueFilter, TaskFilter, TaskItem};
+
+/// A choice within the tag filter, as the pick list needs a value which matches every tag
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TagChoice {
+    Any,
+    Tag(String),
+}
+
+impl Display for TagChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagChoice::Any => write!(f, "{}", t!("windows.editor.tasks.any-tag")),
+            TagChoice::Tag(tag) => write!(f, "#{}", tag),
+        }
+    }
+}
+
+/// Lists the open tasks across every note in the workspace
+#[derive(Debug, Clone, Default)]
+pub struct TaskDashboard {
+    pub filter: TaskFilter,
+    items: Vec<TaskItem>,
+    tags: Vec<String>,
+}
+
+impl TaskDashboard {
+    pub fn new(mgr: &WorkspaceManager) -> Self {
+        let mut dashboard = Self::default();
+        dashboard.refresh(mgr);
+        dashboard
+    }
+
+    /// Reloads the tasks from the index, eg: after a note has changed
+    pub fn refresh(&mut self, mgr: &WorkspaceManager) {
+        self.items = mgr.tasks(&self.filter);
+        self.tags = mgr.task_tags();
+    }
+
+    pub fn view(&self, window: Id, root: &Path) -> Element<'_> {
+        let filter = move |filter: TaskFilter| -> Message {
+            EditorMessage::new(EditorMessageKind::FilterTasks(filter), window).into()
+        };
+
+        let tags: Vec<TagChoice> = [TagChoice::Any].into_iter()
+            .chain(self.tags.iter().cloned().map(TagChoice::Tag))
+            .collect();
+        let selected_tag = self.filter.tag.clone().map(TagChoice::Tag).unwrap_or(TagChoice::Any);
+
+        let note_filter = self.filter.clone();
+        let tag_filter = self.filter.clone();
+        let due_filter = self.filter.clone();
+
+        let filters = row!(
+            text_input(&t!("windows.editor.tasks.filter-note"), &self.filter.note)
+                .on_input(move |note| filter(TaskFilter { note, ..note_filter.clone() }))
+                .width(Length::Fill),
+            pick_list(tags, Some(selected_tag), move |choice| filter(TaskFilter {
+                tag: match choice {
+                    TagChoice::Any => None,
+                    TagChoice::Tag(tag) => Some(tag),
+                },
+                ..tag_filter.clone()
+            })),
+            pick_list(DueFilter::ALL, Some(self.filter.due), move |due| filter(TaskFilter { due, ..due_filter.clone() })),
+        ).spacing(10);
+
+        let items: Element = if self.items.is_empty() {
+            text(t!("windows.editor.tasks.empty").to_string()).into()
+        } else {
+            column(self.items.iter().map(|task| self.task_row(task, window, root))).spacing(5).into()
+        };
+
+        container(
+            column!(
+                text(t!("windows.editor.tasks.title").to_string()).size(24),
+                filters,
+                scrollable(items).height(Length::Fill)
+            ).spacing(10)
+        ).padding(10).into()
+    }
+
+    fn task_row<'a>(&self, task: &'a TaskItem, window: Id, root: &Path) -> Element<'a> {
+        let note = task.note.clone();
+        let offset = task.offset;
+        let location = format!("{}:{}", task.note.strip_prefix(root).unwrap_or(&task.note).display(), task.line);
+
+        let due: Element = match task.due {
+            Some(due) if task.overdue() => text(due.to_string()).color(color!(0xd32f2f)).into(),
+            Some(due) => text(due.to_string()).into(),
+            None => horizontal_space().width(0).into(),
+        };
+
+        row!(
+            checkbox(task.text.as_str(), task.done)
+                .on_toggle(move |done| EditorMessage::new(EditorMessageKind::ToggleTask(note.clone(), offset, done), window).into()),
+            horizontal_space(),
+            text(location).size(12).color(color!(0x757575)),
+            due
+        ).spacing(10).align_y(Alignment::Center).into()
+    }
+}
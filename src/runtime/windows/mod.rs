@@ -3,6 +3,7 @@ use iced::widget::{container, text};
 use iced::window::{Id, Settings};
 use crate::runtime::{Element, Task};
 use crate::runtime::messaging::{Message, WindowMessage};
+use crate::runtime::windows::consent::ConsentWindow;
 use crate::runtime::windows::editor::EditorWindow;
 use crate::runtime::windows::splash::SplashWindow;
 use crate::runtime::windows::workspace::WorkspaceWindow;
@@ -10,6 +11,7 @@ use crate::runtime::windows::workspace::WorkspaceWindow;
 pub mod splash;
 pub mod workspace;
 pub mod editor;
+pub mod consent;
 
 pub mod settings;
 
@@ -33,7 +35,10 @@ pub enum AppWindow {
     EditorWindow(EditorWindow),
 
     /// A window which manages the user settings
-    SettingsWindow
+    SettingsWindow,
+
+    /// A window which asks the user to grant a plugin the scopes it requests
+    ConsentWindow(ConsentWindow),
 }
 
 impl DesktopWindow<AppWindow, WindowMessage, Message> for AppWindow {
@@ -50,6 +55,7 @@ impl DesktopWindow<AppWindow, WindowMessage, Message> for AppWindow {
             AppWindow::WorkspaceWindow(window) => window.title(),
             AppWindow::EditorWindow(window) => window.title(),
             AppWindow::SettingsWindow => "Settings Window".to_string(),
+            AppWindow::ConsentWindow(window) => window.title(),
             _ => "ILLEGITIMATE CHILD WINDOW".to_string()
         }
     }
@@ -71,6 +77,7 @@ impl DesktopWindow<AppWindow, WindowMessage, Message> for AppWindow {
             AppWindow::WorkspaceWindow(window) => window.update(message.kind.into()),
             AppWindow::EditorWindow(window) => window.update(message.kind.into()),
             AppWindow::SettingsWindow => Task::none(),
+            AppWindow::ConsentWindow(window) => window.update(message.kind.into()),
             _ => Task::none()
         }
     }
@@ -80,6 +87,7 @@ impl DesktopWindow<AppWindow, WindowMessage, Message> for AppWindow {
             AppWindow::SplashWindow(window) => window.view(),
             AppWindow::WorkspaceWindow(window) => window.view(),
             AppWindow::EditorWindow(window) => window.view(),
+            AppWindow::ConsentWindow(window) => window.view(),
             kind => container(text(format!("Not Implemented {:?}", kind))).into()
         }
     }
@@ -88,6 +96,7 @@ impl DesktopWindow<AppWindow, WindowMessage, Message> for AppWindow {
         match self {
             AppWindow::SplashWindow(window) => window.close(),
             AppWindow::EditorWindow(window) => window.close(),
+            AppWindow::ConsentWindow(window) => window.close(),
             _ => Task::none(),
         }
     }
@@ -12,7 +12,12 @@ use url::Url;
 use crate::runtime::{Message, GLOBAL_STATE};
 use crate::runtime::workers::jobs::build_tree::build_tree;
 use crate::runtime::workers::jobs::cache_assets::cache_assets;
+use crate::runtime::workers::jobs::collect_garbage::collect_garbage;
+use crate::runtime::workers::jobs::import_asset::import_asset;
+use crate::runtime::workers::jobs::index_tasks::index_tasks;
 use crate::runtime::workers::jobs::pre_render::pre_render;
+use crate::runtime::workers::jobs::toggle_task::toggle_task;
+use crate::runtime::workers::jobs::move_section::move_section;
 
 pub type JobResult<T> = Result<T, JobError>;
 
@@ -62,7 +67,12 @@ impl Worker {
         let maybe_jobs = match job.clone().kind {
             JobType::BuildTree(workspace, path, source, pre_render) => build_tree(job, self, source, workspace, path, pre_render).await,
             JobType::PreRender(workspace, path, source) => pre_render(job, self, workspace, source, path).await,
-            JobType::CacheAsset(workspace, path, url, source, buffer) => cache_assets(job, self, source, workspace, path, url, buffer).await,
+            JobType::CacheAsset(workspace, path, url, source, buffer, bypass) => cache_assets(job, self, source, workspace, path, url, buffer, bypass).await,
+            JobType::CollectGarbage(workspace, path) => collect_garbage(job, self, workspace, path).await,
+            JobType::ImportAsset(workspace, note, file, offset, content, source) => import_asset(job, self, source, workspace, note, file, offset, content).await,
+            JobType::IndexTasks(workspace, path, source) => index_tasks(job, self, source, workspace, path).await,
+            JobType::ToggleTask(workspace, note, offset, done, source) => toggle_task(job, self, source, workspace, note, offset, done).await,
+            JobType::MoveSection(workspace, note, from, to, source) => move_section(job, self, source, workspace, note, from, to).await,
         };
 
         if let Some(jobs) = maybe_jobs {
@@ -124,8 +134,50 @@ pub enum JobType {
     /// - Url -> The url to use when locating the asset. This allows for local files, cloud files, and internal assets too
     /// - Id -> The window ID that the asset should be broadcast to when completed
     /// - String -> A formatted string containing the buffer ID this asset should be assigned to.
+    /// - bool -> Whether to skip the workspace remote data strategy for this fetch (used by "load once").
     /// > Note: The assets assigned to a specific buffer ID are not accessible to plugins or other buffers, this is called "enclaving"
-    CacheAsset(String, PathBuf, Url, Id, String),
+    CacheAsset(String, PathBuf, Url, Id, String, bool),
+
+    /// Requests that a worker remove any cached assets which are no longer referenced by a note in the workspace
+    /// **Params**
+    /// - String -> The workspace ID being worked on.
+    /// - PathBuf -> The path of the workspace root.
+    CollectGarbage(String, PathBuf),
+
+    /// Requests that a worker import a local image into the workspace and reference it from a note
+    /// **Params**
+    /// - String -> The workspace ID being worked on.
+    /// - PathBuf -> The path of the note the image should be inserted into.
+    /// - PathBuf -> The path of the image being imported.
+    /// - usize -> The byte offset within the note to insert the image at.
+    /// - String -> The content ID of the note the offset was taken from.
+    /// - Id -> The window ID that the updated buffer should be broadcast to when completed.
+    ImportAsset(String, PathBuf, PathBuf, usize, String, Id),
+
+    /// Requests that a worker index the task list items of notes into the workspace database
+    /// **Params**
+    /// - String -> The workspace ID being worked on.
+    /// - PathBuf -> The note to index, or a directory to index every note within.
+    /// - Id -> The window ID that should be notified when the index has been updated.
+    IndexTasks(String, PathBuf, Id),
+
+    /// Requests that a worker check or uncheck a task within a note
+    /// **Params**
+    /// - String -> The workspace ID being worked on.
+    /// - PathBuf -> The path of the note containing the task.
+    /// - usize -> The byte offset of the task marker within the note.
+    /// - bool -> Whether the task is now done.
+    /// - Id -> The window ID that the updated buffer should be broadcast to when completed.
+    ToggleTask(String, PathBuf, usize, bool, Id),
+
+    /// Requests that a worker move a section of a note, along with the sections nested beneath it
+    /// **Params**
+    /// - String -> The workspace ID being worked on.
+    /// - PathBuf -> The path of the note containing the section.
+    /// - usize -> The index of the heading which begins the section being moved.
+    /// - usize -> The index of the heading which begins the section it is being moved to.
+    /// - Id -> The window ID that the updated buffer should be broadcast to when completed.
+    MoveSection(String, PathBuf, usize, usize, Id),
 }
 
 #[derive(Debug, Clone)]
@@ -1,45 +1,189 @@
 use std::path::PathBuf;
 use iced::futures::SinkExt;
 use iced_core::window::Id;
-use rusqlite::fallible_iterator::FallibleIterator;
 use url::Url;
+use crate::assets::{asset_id, asset_lock, cache_index, content_id, link_asset, sniff_mime, store_blob, workspace_file, workspace_key, AssetRequest};
+use crate::runtime::GLOBAL_STATE;
 use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
 use crate::runtime::workers::{Job, JobList, Worker};
-use crate::utils::cryptography::hashing::hash_str;
+use crate::storage::workspace::{asset_directory, open_database, read_setting, AssetCachingStrategy, RemoteDataStrategy};
 
-pub async fn cache_assets(_job: Job, worker: &mut Worker, window: Id, workspace: String, path: PathBuf, url: Url, buffer: String) -> Option<JobList> {
-    let hash = hash_str(format!("{}", url));
-    let mut root = path.clone();
-    root.pop();
+pub async fn cache_assets(_job: Job, worker: &mut Worker, window: Id, workspace: String, path: PathBuf, url: Url, buffer: String, bypass: bool) -> Option<JobList> {
+    let hash = asset_id(url.as_str());
 
-    let asset_dir = root.join(".assets");
+    let source = GLOBAL_STATE.lock().unwrap().workspaces.get(&workspace).cloned();
 
-    if !asset_dir.exists() {
-        std::fs::create_dir_all(&asset_dir).unwrap();
-    }
+    let Some(source) = source else {
+        worker.error(format!("Cannot cache asset for unknown workspace '{}'", workspace));
+        return None;
+    };
+
+    let root = PathBuf::from(&source.disk_path);
+
+    let db = match open_database(&root) {
+        Ok(db) => db,
+        Err(err) => {
+            worker.error(format!("Failed to open workspace database: {}", err));
+            return None;
+        }
+    };
+
+    let strategy = read_setting::<AssetCachingStrategy>(&db, "assets.cache-strategy")
+        .map(|s| s.value)
+        .unwrap_or(AssetCachingStrategy::Blob);
+
+    let request = AssetRequest {
+        id: hash.clone(),
+        url: url.clone(),
+        workspace: workspace.clone(),
+        path,
+        window,
+        buffer,
+    };
+
+    // Local files are always cached in memory, remote assets only when the workspace chooses to
+    let memory = match url.scheme() {
+        "http" | "https" => matches!(strategy, AssetCachingStrategy::Memory),
+        "file" => true,
+        _ => false,
+    };
+
+    if memory {
+        // Assets still cached in memory are already rendered by the editor, so fetching them again would be wasted
+        let cached = cache_index(window).is_some_and(|index| index.lock().unwrap().lookup(&hash));
 
-    let asset_path = asset_dir.join(&hash);
+        if cached {
+            debug!("Asset '{}' is still cached in memory", hash);
+            return None;
+        }
+    }
 
     match url.scheme() {
         "http" | "https" => {
+            let fetch_remote = read_setting::<RemoteDataStrategy>(&db, "assets.fetch-remote")
+                .map(|s| s.value)
+                .unwrap_or_default();
+            let allowed_domains = read_setting::<Vec<String>>(&db, "assets.allowed-domains")
+                .map(|s| s.value)
+                .unwrap_or_default();
+
+            if !bypass && !fetch_remote.permits(&url, &allowed_domains) {
+                worker.warn(format!("Blocked remote asset '{}' ({:?})", url, fetch_remote));
+                worker.sender.send(EditorMessage::new(EditorMessageKind::AssetBlocked(request), window).into()).await.unwrap();
+                return None;
+            }
+
             let res = reqwest::get(&url.to_string()).await;
 
             if let Ok(res) = res {
-                let write = tokio::fs::write(asset_path, res.bytes().await.unwrap().as_ref()).await;
-                if let Err(err) = write {
-                    error!("Failed to cache asset");
-                    error!("{}", err);
+                let header_mime = res.headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|h| h.to_str().ok())
+                    .map(|h| h.split(';').next().unwrap_or(h).trim().to_string());
+
+                let bytes = match res.bytes().await {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        fail(worker, window, &hash, err.to_string()).await;
+                        return None;
+                    }
+                };
+
+                let mime = header_mime.unwrap_or_else(|| sniff_mime(&bytes).to_string());
+                let content = content_id(&bytes);
+
+                // Held until the asset is referenced, so that garbage collection cannot remove it in the meantime
+                let lock = asset_lock(&workspace);
+                let _guard = lock.read().await;
+
+                match strategy {
+                    AssetCachingStrategy::Blob => {
+                        let key = workspace_key(&workspace);
+                        if let Err(err) = store_blob(&db, &key, &content, &mime, &bytes) {
+                            error!("Failed to cache asset");
+                            error!("{:?}", err);
+                            return None;
+                        }
+                    }
+                    AssetCachingStrategy::Memory => {
+                        // Memory cached assets never touch the disk, they are handed straight to the editor
+                        worker.sender.send(EditorMessage::new(EditorMessageKind::AssetFetched(request, mime, bytes.to_vec()), window).into()).await.unwrap();
+                        return None;
+                    }
+                    AssetCachingStrategy::Disk => {
+                        let asset_dir = asset_directory(root);
+
+                        if !asset_dir.exists() {
+                            std::fs::create_dir_all(&asset_dir).unwrap();
+                        }
+
+                        let asset_path = asset_dir.join(&content);
+
+                        if asset_path.exists() {
+                            debug!("Asset content '{}' is already stored", content);
+                        } else if let Err(err) = tokio::fs::write(asset_path, bytes.as_ref()).await {
+                            error!("Failed to cache asset");
+                            error!("{}", err);
+                            return None;
+                        }
+                    }
                 }
-                worker.sender.send(EditorMessage::new(EditorMessageKind::Tick, window).into()).await.unwrap();
+
+                if let Err(err) = link_asset(&db, &hash, &content, &url) {
+                    error!("Failed to record asset reference");
+                    error!("{:?}", err);
+                    return None;
+                }
+
+                worker.sender.send(EditorMessage::new(EditorMessageKind::LoadAsset(hash), window).into()).await.unwrap();
             } else {
-                error!("Failed to get asset url: {}", url);
-                error!("{}", res.unwrap_err())
+                fail(worker, window, &hash, res.unwrap_err().to_string()).await;
             }
         },
+        "file" => {
+            // Local files are not copied into the workspace, they are read from disk again whenever they expire
+            let Ok(file) = url.to_file_path() else {
+                fail(worker, window, &hash, "The URL is not a valid file path").await;
+                return None;
+            };
+
+            // Notes may come from anywhere, so they can only read files outside of the workspace if it allows them to
+            let external = read_setting::<bool>(&db, "assets.allow-external-files")
+                .map(|s| s.value)
+                .unwrap_or(false);
+
+            let file = match workspace_file(&root, &file) {
+                Some(file) => file,
+                None if external => file,
+                None => {
+                    fail(worker, window, &hash, format!("{} is outside of the workspace", file.display())).await;
+                    return None;
+                }
+            };
+
+            match tokio::fs::read(&file).await {
+                Ok(bytes) => {
+                    let mime = sniff_mime(&bytes).to_string();
+                    worker.sender.send(EditorMessage::new(EditorMessageKind::AssetFetched(request, mime, bytes), window).into()).await.unwrap();
+                }
+                Err(err) => fail(worker, window, &hash, err.to_string()).await
+            }
+        }
+        "noot" => {
+            // Imported assets are already stored within the workspace, they only need loading
+            worker.sender.send(EditorMessage::new(EditorMessageKind::LoadAsset(hash), window).into()).await.unwrap();
+        }
         s => {
-            error!("Cannot cache asset. Reason: Unsupported URL scheme: {}", s);
+            fail(worker, window, &hash, format!("Unsupported URL scheme: {}", s)).await;
         }
     }
 
     None
-}
\ No newline at end of file
+}
+
+/// Notifies the editor that an asset could not be loaded, so that an error can be rendered in its place
+async fn fail(worker: &mut Worker, window: Id, id: &str, reason: impl Into<String>) {
+    let reason = reason.into();
+    worker.warn(format!("Failed to cache asset '{}': {}", id, reason));
+    worker.sender.send(EditorMessage::new(EditorMessageKind::AssetFailed(id.to_string(), reason), window).into()).await.unwrap();
+}
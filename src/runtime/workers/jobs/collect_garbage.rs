@@ -0,0 +1,130 @@
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use rusqlite::Connection;
+use walkdir::WalkDir;
+use crate::assets::{asset_id, asset_lock};
+use crate::runtime::workers::{Job, JobList, Worker};
+use crate::storage::workspace::{asset_directory, open_database};
+use crate::storage::workspace::buffer::Buffer;
+
+#[cfg(test)]
+mod tests;
+
+pub async fn collect_garbage(_job: Job, worker: &mut Worker, workspace: String, path: PathBuf) -> Option<JobList> {
+    let db = match open_database(&path) {
+        Ok(db) => db,
+        Err(err) => {
+            worker.error(format!("Failed to open workspace database: {}", err));
+            return None;
+        }
+    };
+
+    // Assets being stored are not referenced by a note yet, so collection waits until nothing is being stored
+    let lock = asset_lock(&workspace);
+    let _guard = lock.write().await;
+
+    // Find every asset which is still referenced by a note within the workspace
+    let mut referenced = BTreeSet::new();
+
+    for entry in WalkDir::new(&path).into_iter().filter_entry(|e| e.file_name() != ".noot" && e.file_name() != ".assets") {
+        match entry {
+            Ok(e) => {
+                if e.path().extension() != Some(OsStr::new("md")) {
+                    continue;
+                }
+
+                let content = match std::fs::read_to_string(e.path()) {
+                    Ok(content) => content,
+                    Err(err) => {
+                        // If a note cannot be read we cannot know what it references, so nothing is removed
+                        worker.error(format!("Aborting asset garbage collection, failed to read {}: {}", e.path().display(), err));
+                        return None;
+                    }
+                };
+
+                let buffer = Buffer::from_md(e.file_name().to_string_lossy().to_string(), workspace.clone(), format!("file://{}", e.path().display()), content);
+
+                for src in buffer.image_sources() {
+                    referenced.insert(asset_id(&src));
+                }
+            },
+            Err(err) => {
+                // A directory which cannot be read may hold notes referencing assets, so nothing is removed
+                worker.error(format!("Aborting asset garbage collection, failed to walk the workspace: {}", err));
+                return None;
+            }
+        }
+    }
+
+    let (removed, freed) = match sweep(&db, &path, &referenced) {
+        Ok(result) => result,
+        Err(err) => {
+            worker.error(format!("Failed to collect unreferenced assets: {}", err));
+            return None;
+        }
+    };
+
+    if removed > 0 {
+        worker.info(format!("Removed {} unreferenced assets, freeing {} bytes", removed, freed));
+    } else {
+        worker.info("No unreferenced assets found");
+    }
+
+    None
+}
+
+/// Removes any asset references which are not in `referenced`, followed by any stored
+/// content which no longer has a reference. Returns the number of assets removed and the bytes freed.
+fn sweep(db: &Connection, path: &Path, referenced: &BTreeSet<String>) -> rusqlite::Result<(u64, u64)> {
+    let refs = db.prepare("SELECT id FROM asset_refs")?
+        .query_map((), |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for id in refs.iter().filter(|id| !referenced.contains(*id)) {
+        db.execute("DELETE FROM asset_refs WHERE id = ?", [id])?;
+    }
+
+    let live = db.prepare("SELECT content FROM asset_refs")?
+        .query_map((), |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<BTreeSet<String>>>()?;
+
+    let mut removed = 0;
+    let mut freed = 0;
+
+    // Blob assets
+    let blobs = db.prepare("SELECT id, LENGTH(data) FROM assets")?
+        .query_map((), |r| Ok((r.get::<_, String>(0)?, r.get::<_, u64>(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, u64)>>>()?;
+
+    for (id, size) in blobs {
+        if !live.contains(&id) {
+            db.execute("DELETE FROM assets WHERE id = ?", [&id])?;
+            removed += 1;
+            freed += size;
+        }
+    }
+
+    // Disk assets
+    if let Ok(entries) = std::fs::read_dir(asset_directory(path.to_path_buf())) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if live.contains(&name) {
+                continue;
+            }
+
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+
+            match std::fs::remove_file(entry.path()) {
+                Ok(_) => {
+                    removed += 1;
+                    freed += size;
+                }
+                Err(err) => error!("Failed to remove asset {}: {}", entry.path().display(), err)
+            }
+        }
+    }
+
+    Ok((removed, freed))
+}
@@ -0,0 +1,130 @@
+//! Tests for deduplicating stored assets and sweeping away those which are no longer referenced.
+
+use std::sync::Arc;
+use std::time::Duration;
+use crossbeam_queue::ArrayQueue;
+use nanoid::nanoid;
+use url::Url;
+use crate::assets::{content_id, link_asset, load_blob, resolve_asset, store_blob};
+use crate::runtime::workers::JobType;
+use crate::utils::cryptography::encryption::generate_key;
+use super::*;
+
+/// Creates an empty workspace in a temporary directory
+fn workspace() -> (PathBuf, Connection) {
+    let root = std::env::temp_dir().join(format!("noot-assets-{}", nanoid!(5)));
+    std::fs::create_dir_all(root.join(".noot")).unwrap();
+    let db = open_database(&root).unwrap();
+    (root, db)
+}
+
+fn url(name: &str) -> Url {
+    Url::parse(&format!("https://example.com/{}", name)).unwrap()
+}
+
+fn count(db: &Connection, table: &str) -> u64 {
+    db.query_row(&format!("SELECT COUNT(*) FROM {}", table), (), |r| r.get(0)).unwrap()
+}
+
+#[test]
+fn identical_content_is_stored_once() {
+    let (_, db) = workspace();
+    let key = generate_key();
+    let content = content_id(b"image");
+
+    for name in ["a.png", "b.png"] {
+        store_blob(&db, &key, &content, "image/png", b"image").unwrap();
+        link_asset(&db, name, &content, &url(name)).unwrap();
+    }
+
+    assert_eq!(count(&db, "assets"), 1);
+    assert_eq!(count(&db, "asset_refs"), 2);
+    assert_eq!(resolve_asset(&db, "a.png"), resolve_asset(&db, "b.png"));
+    assert_eq!(load_blob(&db, &key, "b.png").unwrap().size, 5);
+}
+
+#[test]
+fn sweeps_unreferenced_blobs() {
+    let (root, db) = workspace();
+    let key = generate_key();
+
+    for name in ["kept", "removed"] {
+        let content = content_id(name.as_bytes());
+        store_blob(&db, &key, &content, "image/png", name.as_bytes()).unwrap();
+        link_asset(&db, name, &content, &url(name)).unwrap();
+    }
+
+    let referenced = BTreeSet::from(["kept".to_string()]);
+    let (removed, _) = sweep(&db, &root, &referenced).unwrap();
+
+    assert_eq!(removed, 1);
+    assert_eq!(count(&db, "assets"), 1);
+    assert!(load_blob(&db, &key, "kept").is_some());
+    assert!(resolve_asset(&db, "removed").is_none());
+}
+
+#[test]
+fn keeps_content_which_is_still_referenced_elsewhere() {
+    let (root, db) = workspace();
+    let key = generate_key();
+    let content = content_id(b"shared");
+    store_blob(&db, &key, &content, "image/png", b"shared").unwrap();
+    link_asset(&db, "a", &content, &url("a")).unwrap();
+    link_asset(&db, "b", &content, &url("b")).unwrap();
+
+    let referenced = BTreeSet::from(["b".to_string()]);
+    let (removed, _) = sweep(&db, &root, &referenced).unwrap();
+
+    assert_eq!(removed, 0);
+    assert_eq!(count(&db, "asset_refs"), 1);
+    assert!(load_blob(&db, &key, "b").is_some());
+}
+
+#[test]
+fn sweeps_unreferenced_files() {
+    let (root, db) = workspace();
+    let assets = asset_directory(root.clone());
+    std::fs::create_dir_all(&assets).unwrap();
+
+    for name in ["kept", "removed"] {
+        let content = content_id(name.as_bytes());
+        std::fs::write(assets.join(&content), name).unwrap();
+        link_asset(&db, name, &content, &url(name)).unwrap();
+    }
+    // Files which were never referenced at all are removed too
+    std::fs::write(assets.join("orphan"), "orphan").unwrap();
+
+    let referenced = BTreeSet::from(["kept".to_string()]);
+    let (removed, freed) = sweep(&db, &root, &referenced).unwrap();
+
+    assert_eq!((removed, freed), (2, 13));
+    assert!(assets.join(content_id(b"kept")).exists());
+    assert!(!assets.join(content_id(b"removed")).exists());
+}
+
+#[tokio::test]
+async fn collection_waits_for_assets_being_stored() {
+    let (root, db) = workspace();
+    let workspace = root.file_name().unwrap().to_string_lossy().to_string();
+    let key = generate_key();
+    let content = content_id(b"storing");
+    store_blob(&db, &key, &content, "image/png", b"storing").unwrap();
+
+    // The asset is stored but not yet referenced, as if a job were still storing it
+    let lock = asset_lock(&workspace);
+    let storing = lock.read().await;
+
+    let collection = tokio::spawn(async move {
+        let (sender, _receiver) = iced::futures::channel::mpsc::channel(8);
+        let mut worker = Worker::new(0, Arc::new(ArrayQueue::new(1)), sender);
+        let job = Job::new(JobType::CollectGarbage(workspace.clone(), root.clone()));
+        collect_garbage(job, &mut worker, workspace, root).await
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(count(&db, "assets"), 1);
+
+    drop(storing);
+    collection.await.unwrap();
+    assert_eq!(count(&db, "assets"), 0);
+}
@@ -0,0 +1,140 @@
+use std::path::PathBuf;
+use iced::futures::SinkExt;
+use iced_core::window::Id;
+use crate::assets::{asset_lock, content_id, link_asset, local_asset_url, store_blob, workspace_key};
+use crate::assets::import::{is_importable, paste_directory, prepare_image};
+use crate::runtime::GLOBAL_STATE;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::runtime::workers::{Job, JobList, Worker};
+use crate::storage::workspace::{asset_directory, open_database, read_setting, AssetCachingStrategy, ImageImportFormat};
+
+pub async fn import_asset(_job: Job, worker: &mut Worker, window: Id, workspace: String, note: PathBuf, file: PathBuf, offset: usize, expected: String) -> Option<JobList> {
+    if !is_importable(&file) {
+        worker.warn(format!("Cannot import {}, it is not a supported image", file.display()));
+        return None;
+    }
+
+    let source = GLOBAL_STATE.lock().unwrap().workspaces.get(&workspace).cloned();
+
+    let Some(source) = source else {
+        worker.error(format!("Cannot import asset for unknown workspace '{}'", workspace));
+        return None;
+    };
+
+    let root = PathBuf::from(&source.disk_path);
+
+    let db = match open_database(&root) {
+        Ok(db) => db,
+        Err(err) => {
+            worker.error(format!("Failed to open workspace database: {}", err));
+            return None;
+        }
+    };
+
+    let strategy = read_setting::<AssetCachingStrategy>(&db, "assets.cache-strategy")
+        .map(|s| s.value)
+        .unwrap_or(AssetCachingStrategy::Blob);
+    let max_width = read_setting::<u32>(&db, "assets.import.max-width")
+        .map(|s| s.value)
+        .unwrap_or(0);
+    let format = read_setting::<ImageImportFormat>(&db, "assets.import.format")
+        .map(|s| s.value)
+        .unwrap_or(ImageImportFormat::Original);
+
+    let bytes = match tokio::fs::read(&file).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            worker.error(format!("Failed to read {}: {}", file.display(), err));
+            return None;
+        }
+    };
+
+    // Images pasted from the clipboard were only saved so that they could be read here
+    if file.starts_with(paste_directory()) {
+        tokio::fs::remove_file(&file).await
+            .unwrap_or_else(|err| worker.warn(format!("Failed to remove pasted image {}: {}", file.display(), err)));
+    }
+
+    let (bytes, mime) = match prepare_image(bytes, max_width, format) {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            worker.error(format!("Failed to prepare {} for import: {:?}", file.display(), err));
+            return None;
+        }
+    };
+
+    let content = content_id(&bytes);
+
+    // Held until the note references the asset, so that garbage collection cannot remove it in the meantime
+    let lock = asset_lock(&workspace);
+    let _guard = lock.read().await;
+
+    match strategy {
+        AssetCachingStrategy::Blob => {
+            if let Err(err) = store_blob(&db, &workspace_key(&workspace), &content, &mime, &bytes) {
+                worker.error(format!("Failed to store imported asset: {:?}", err));
+                return None;
+            }
+        }
+        // Imported assets have nowhere to be fetched from again, so the memory strategy keeps them on disk
+        AssetCachingStrategy::Memory | AssetCachingStrategy::Disk => {
+            let asset_dir = asset_directory(root);
+
+            if !asset_dir.exists() {
+                std::fs::create_dir_all(&asset_dir).unwrap();
+            }
+
+            let asset_path = asset_dir.join(&content);
+
+            if asset_path.exists() {
+                debug!("Asset content '{}' is already stored", content);
+            } else if let Err(err) = tokio::fs::write(asset_path, &bytes).await {
+                worker.error(format!("Failed to store imported asset: {}", err));
+                return None;
+            }
+        }
+    }
+
+    let url = local_asset_url(&workspace, &content);
+
+    if let Err(err) = link_asset(&db, &content, &content, &url) {
+        worker.error(format!("Failed to record asset reference: {:?}", err));
+        return None;
+    }
+
+    let alt = file.file_stem()
+        .map(|stem| stem.to_string_lossy().replace(['[', ']'], ""))
+        .unwrap_or_default();
+
+    let mut text = match tokio::fs::read_to_string(&note).await {
+        Ok(text) => text,
+        Err(err) => {
+            worker.error(format!("Failed to read {}: {}", note.display(), err));
+            return None;
+        }
+    };
+
+    // The offset was taken from the note as the editor last read it, so it is only used if the note is unchanged
+    let unchanged = content_id(text.as_bytes()) == expected;
+
+    if unchanged && offset < text.len() && text.is_char_boundary(offset) {
+        text.insert_str(offset, &format!("\n\n![{}]({})", alt, url));
+    } else {
+        if !unchanged {
+            worker.warn(format!("{} has changed since the image was added, inserting it at the end instead", note.display()));
+        }
+        text.push_str(&format!("\n![{}]({})\n", alt, url));
+    }
+
+    if let Err(err) = tokio::fs::write(&note, text).await {
+        worker.error(format!("Failed to insert image into {}: {}", note.display(), err));
+        return None;
+    }
+
+    worker.info(format!("Imported {} into {}", file.display(), note.display()));
+
+    // Only the inserted image needs rendering, which the editor does as the note is already open
+    worker.sender.send(EditorMessage::new(EditorMessageKind::FileChanged(note), window).into()).await.unwrap();
+
+    None
+}
@@ -0,0 +1,68 @@
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use iced::futures::SinkExt;
+use iced_core::window::Id;
+use walkdir::WalkDir;
+use crate::runtime::GLOBAL_STATE;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::runtime::workers::{Job, JobList, Worker};
+use crate::storage::workspace::open_database;
+use crate::storage::workspace::tasks::{extract, index_note, retain_notes};
+
+/// Indexes the tasks of a single note, or of every note when given a directory
+pub async fn index_tasks(_job: Job, worker: &mut Worker, window: Id, workspace: String, path: PathBuf) -> Option<JobList> {
+    let source = GLOBAL_STATE.lock().unwrap().workspaces.get(&workspace).cloned();
+
+    let Some(source) = source else {
+        worker.error(format!("Cannot index tasks for unknown workspace '{}'", workspace));
+        return None;
+    };
+
+    let mut db = match open_database(&PathBuf::from(&source.disk_path)) {
+        Ok(db) => db,
+        Err(err) => {
+            worker.error(format!("Failed to open workspace database: {}", err));
+            return None;
+        }
+    };
+
+    let notes: Vec<PathBuf> = if path.is_dir() {
+        WalkDir::new(&path).into_iter()
+            .filter_entry(|e| e.file_name() != ".noot" && e.file_name() != ".assets")
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().extension() == Some(OsStr::new("md")))
+            .map(|e| e.into_path())
+            .collect()
+    } else {
+        vec![path.clone()]
+    };
+
+    let mut count = 0;
+
+    for note in &notes {
+        let content = match std::fs::read_to_string(note) {
+            Ok(content) => content,
+            Err(err) => {
+                worker.warn(format!("Skipping tasks in {}: {}", note.display(), err));
+                continue;
+            }
+        };
+
+        let tasks = extract(note, &content);
+        count += tasks.len();
+
+        if let Err(err) = index_note(&mut db, note, &tasks) {
+            worker.error(format!("Failed to index tasks in {}: {}", note.display(), err));
+        }
+    }
+
+    // A full index also forgets notes which have since been removed
+    if path.is_dir() && let Err(err) = retain_notes(&db, &notes) {
+        worker.error(format!("Failed to remove tasks of deleted notes: {}", err));
+    }
+
+    worker.info(format!("Indexed {} tasks across {} notes", count, notes.len()));
+    worker.sender.send(EditorMessage::new(EditorMessageKind::TasksIndexed, window).into()).await.unwrap();
+
+    None
+}
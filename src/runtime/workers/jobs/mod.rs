@@ -1,3 +1,8 @@
 pub mod build_tree;
 pub mod pre_render;
-pub mod cache_assets;
\ No newline at end of file
+pub mod cache_assets;
+pub mod collect_garbage;
+pub mod import_asset;
+pub mod index_tasks;
+pub mod toggle_task;
+pub mod move_section;
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+use iced::futures::SinkExt;
+use iced_core::window::Id;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::runtime::workers::{Job, JobList, JobType, Worker};
+use crate::storage::workspace::buffer::outline;
+
+/// Moves a section of a note to where another section is, writing the change back to the note
+pub async fn move_section(_job: Job, worker: &mut Worker, window: Id, workspace: String, note: PathBuf, from: usize, to: usize) -> Option<JobList> {
+    let content = match tokio::fs::read_to_string(&note).await {
+        Ok(content) => content,
+        Err(err) => {
+            worker.error(format!("Failed to read {}: {}", note.display(), err));
+            return None;
+        }
+    };
+
+    let Some(updated) = outline::move_section(&content, from, to) else {
+        // The note may have changed after it was rendered, so bring the buffer up to date rather than moving the wrong section
+        worker.warn(format!("Cannot move section {} to section {} in {}", from, to, note.display()));
+        worker.sender.send(EditorMessage::new(EditorMessageKind::FileChanged(note), window).into()).await.unwrap();
+        return None;
+    };
+
+    if let Err(err) = tokio::fs::write(&note, updated).await {
+        worker.error(format!("Failed to move section in {}: {}", note.display(), err));
+        return None;
+    }
+
+    worker.sender.send(EditorMessage::new(EditorMessageKind::FileChanged(note.clone()), window).into()).await.unwrap();
+
+    // Tasks are indexed by where they are within the note, which the move has changed
+    Some(vec![Job::new(JobType::IndexTasks(workspace, note, window))])
+}
@@ -15,14 +15,14 @@ pub async fn pre_render(_job: Job, worker: &mut Worker, workspace: String, windo
         let content = std::fs::read_to_string(path.clone()).unwrap();
 
 
-        let buffer = Buffer::from_md(path.file_name().unwrap_or(OsStr::new("?UNKNOWN_file?")).to_str().unwrap().to_string(), workspace.clone(), format!("file://{}", path.to_str().unwrap().to_string()), content);
+        let buffer = Buffer::render_md(path.file_name().unwrap_or(OsStr::new("?UNKNOWN_file?")).to_str().unwrap().to_string(), workspace.clone(), format!("file://{}", path.to_str().unwrap().to_string()), content);
 
         let images = find_image(buffer.id.clone(), &buffer.doc, worker, workspace, window, path);
 
 
 
         worker.info("pre-render completed");
-        worker.sender.send(EditorMessage::new(EditorMessageKind::BufferRendered(buffer), window).into()).await.unwrap();
+        worker.sender.send(EditorMessage::new(EditorMessageKind::BufferRendered(Box::new(buffer)), window).into()).await.unwrap();
         worker.info("Update triggered");
         Some(images)
     } else {
@@ -39,11 +39,20 @@ fn find_image(bid: String, els: &[ElWrapper], worker: &mut Worker, workspace: St
 
     for el in els {
         if el.name == "img" {
-            image_jobs.push(
-                Job::new(
-                    JobType::CacheAsset(workspace.clone(), path.clone(), Url::from_str(el.attributes.get("src").unwrap().clone().unwrap().as_str()).unwrap(), window, bid.clone())
-                )
-            )
+            // Images without a `cached-src` have a malformed source, and are rendered as an error instead
+            if !el.attributes.contains_key("cached-src") {
+                worker.warn(format!("Skipping image with invalid source in {}", path.display()));
+                continue;
+            }
+
+            match Url::from_str(el.attributes.get("src").cloned().flatten().unwrap_or_default().as_str()) {
+                Ok(url) => image_jobs.push(
+                    Job::new(
+                        JobType::CacheAsset(workspace.clone(), path.clone(), url, window, bid.clone(), false)
+                    )
+                ),
+                Err(e) => worker.warn(format!("Skipping image with invalid source in {}: {}", path.display(), e))
+            }
         } else if el.children.len() > 0 {
             image_jobs.append(&mut find_image(bid.clone(), &el.children, worker, workspace.clone(), window, path.clone()));
         }
@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+use iced::futures::SinkExt;
+use iced_core::window::Id;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::runtime::workers::{Job, JobList, JobType, Worker};
+use crate::storage::workspace::tasks::toggle;
+
+/// Writes a checked or unchecked task back to its note
+pub async fn toggle_task(_job: Job, worker: &mut Worker, window: Id, workspace: String, note: PathBuf, offset: usize, done: bool) -> Option<JobList> {
+    let content = match tokio::fs::read_to_string(&note).await {
+        Ok(content) => content,
+        Err(err) => {
+            worker.error(format!("Failed to read {}: {}", note.display(), err));
+            return None;
+        }
+    };
+
+    let Some(updated) = toggle(&content, offset, done) else {
+        // The note changed after it was rendered, so render it again rather than editing the wrong line
+        worker.warn(format!("No task found at offset {} in {}", offset, note.display()));
+        return Some(vec![Job::new(JobType::PreRender(workspace, note, window))]);
+    };
+
+    if let Err(err) = tokio::fs::write(&note, updated).await {
+        worker.error(format!("Failed to update task in {}: {}", note.display(), err));
+        return None;
+    }
+
+    // Only the edited list needs rendering again, which the editor does as the note is already open
+    worker.sender.send(EditorMessage::new(EditorMessageKind::FileChanged(note.clone()), window).into()).await.unwrap();
+
+    Some(vec![Job::new(JobType::IndexTasks(workspace, note, window))])
+}
@@ -63,7 +63,7 @@ impl ProcessStorageManager {
         let _ = pm.set_setting("appearance.theme.adaptive_variance", false);
         let _ = pm.set_setting("appearance.theme.adaptive_variant_day", "light".to_string());
         let _ = pm.set_setting("appearance.tts.enable", true);
-        let _ = pm.set_setting("appearance.tts.provider", "google".to_string());
+        let _ = pm.set_setting("appearance.tts.provider", "system".to_string());
         let _ = pm.set_setting("workspace.load_last", false);
         pm
     }
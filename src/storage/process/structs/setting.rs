@@ -17,7 +17,7 @@ impl <V:Encode + Decode<()> + Debug> Setting<V> {
     }
 
     pub fn store(&self, conn: &mut Connection) -> rusqlite::Result<usize> {
-        conn.execute("INSERT INTO settings (id, value) VALUES (?, ?)", (&self.key, bincode::encode_to_vec(&self.value, bincode::config::standard()).unwrap()))
+        conn.execute("INSERT INTO settings (id, value) VALUES (?1, ?2) ON CONFLICT (id) DO UPDATE SET value = ?2", (&self.key, bincode::encode_to_vec(&self.value, bincode::config::standard()).unwrap()))
     }
 }
 
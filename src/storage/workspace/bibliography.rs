@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use serde_json::Value;
+
+/// Files which are used as the bibliography of a note when its front matter does not name one
+const DEFAULT_FILES: [&str; 4] = ["references.bib", "references.json", "bibliography.bib", "bibliography.json"];
+
+pub type BibliographyResult<T> = Result<T, BibliographyError>;
+
+#[derive(Debug)]
+pub enum BibliographyError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The BibTeX could not be parsed, contains the line number and a description of the problem
+    Syntax(usize, String),
+    /// The file is neither BibTeX (`.bib`) nor CSL-JSON (`.json`)
+    Unsupported(PathBuf),
+}
+
+impl Display for BibliographyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BibliographyError::Io(e) => write!(f, "{}", e),
+            BibliographyError::Json(e) => write!(f, "Invalid CSL-JSON: {}", e),
+            BibliographyError::Syntax(line, message) => write!(f, "Line {}: {}", line, message),
+            BibliographyError::Unsupported(path) => write!(f, "Unsupported bibliography format '{}'", path.display()),
+        }
+    }
+}
+
+impl From<std::io::Error> for BibliographyError {
+    fn from(e: std::io::Error) -> Self {
+        BibliographyError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for BibliographyError {
+    fn from(e: serde_json::Error) -> Self {
+        BibliographyError::Json(e)
+    }
+}
+
+/// A single work which can be cited
+#[derive(Debug, Clone, Default)]
+pub struct Reference {
+    pub key: String,
+    /// Authors formatted as `Family, G.`
+    pub authors: Vec<String>,
+    pub year: Option<String>,
+    pub title: Option<String>,
+    /// The journal, book or publisher the work appeared in
+    pub container: Option<String>,
+    pub url: Option<String>,
+}
+
+impl Reference {
+    /// Formats the reference for the references section, eg: `Smith, J., & Doe, A. (2019). Title. Journal.`
+    /// The url is left out, so that it can be rendered as a link
+    pub fn format(&self) -> String {
+        let mut parts = vec![];
+
+        if !self.authors.is_empty() {
+            parts.push(join_authors(&self.authors));
+        }
+
+        parts.push(format!("({}).", self.year.clone().unwrap_or("n.d.".to_string())));
+
+        for part in [&self.title, &self.container].into_iter().flatten() {
+            parts.push(format!("{}.", part.trim_end_matches('.')));
+        }
+
+        parts.join(" ")
+    }
+
+    /// A short form used when reading a citation aloud, eg: "Smith and Doe, 2019"
+    pub fn short(&self) -> String {
+        let names: Vec<&str> = self.authors.iter().map(|a| a.split(',').next().unwrap_or(a).trim()).collect();
+        let authors = match names.as_slice() {
+            [] => self.title.clone().unwrap_or(self.key.clone()),
+            [one] => one.to_string(),
+            [one, two] => format!("{} and {}", one, two),
+            [one, ..] => format!("{} et al.", one),
+        };
+
+        match &self.year {
+            Some(year) => format!("{}, {}", authors, year),
+            None => authors,
+        }
+    }
+}
+
+fn join_authors(authors: &[String]) -> String {
+    match authors {
+        [one] => one.clone(),
+        [rest @ .., last] => format!("{}, & {}", rest.join(", "), last),
+        [] => String::new(),
+    }
+}
+
+/// Formats an author name as `Family, G.`, accepting both `Family, Given` and `Given Family`
+fn format_author(family: &str, given: &str) -> String {
+    let initials: Vec<String> = given.split(|c: char| c.is_whitespace() || c == '-')
+        .filter_map(|name| name.chars().next())
+        .map(|c| format!("{}.", c))
+        .collect();
+
+    if initials.is_empty() {
+        family.to_string()
+    } else {
+        format!("{}, {}", family, initials.join(" "))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Bibliography {
+    references: HashMap<String, Reference>,
+}
+
+impl Bibliography {
+    /// Loads a BibTeX or CSL-JSON bibliography, chosen by the file extension
+    pub fn load(path: &Path) -> BibliographyResult<Self> {
+        let source = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+            Some("bib") | Some("bibtex") => parse_bibtex(&source),
+            Some("json") => parse_csl_json(&source),
+            _ => Err(BibliographyError::Unsupported(path.to_path_buf())),
+        }
+    }
+
+    /// Finds the bibliography of a note, either named by the `bibliography` key of its front matter,
+    /// or one of the default files in the directory of the note or any parent within the workspace
+    pub fn find(note: &Path, metadata: &str) -> Option<PathBuf> {
+        let directory = note.parent()?;
+
+        let named = metadata.lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.trim() == "bibliography")
+            .map(|(_, value)| value.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+            .filter(|value| !value.is_empty());
+
+        if let Some(named) = named {
+            return Some(directory.join(named));
+        }
+
+        for dir in directory.ancestors() {
+            if let Some(file) = DEFAULT_FILES.iter().map(|f| dir.join(f)).find(|f| f.is_file()) {
+                return Some(file);
+            }
+
+            // The `.noot` directory marks the root of the workspace
+            if dir.join(".noot").is_dir() {
+                break;
+            }
+        }
+
+        None
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Reference> {
+        self.references.get(key)
+    }
+}
+
+/// Parses a CSL-JSON bibliography, as exported by Zotero and most reference managers
+fn parse_csl_json(source: &str) -> BibliographyResult<Bibliography> {
+    let items: Vec<Value> = serde_json::from_str(source)?;
+    let mut references = HashMap::new();
+
+    for item in items {
+        let Some(key) = item.get("id").and_then(|id| id.as_str().map(|s| s.to_string()).or(id.as_i64().map(|i| i.to_string()))) else {
+            continue;
+        };
+
+        let text = |field: &str| item.get(field).and_then(|v| v.as_str()).map(|s| s.to_string());
+
+        let authors = item.get("author").and_then(|a| a.as_array()).map(|authors| {
+            authors.iter().filter_map(|author| {
+                match (author.get("family").and_then(|f| f.as_str()), author.get("literal").and_then(|l| l.as_str())) {
+                    (Some(family), _) => Some(format_author(family, author.get("given").and_then(|g| g.as_str()).unwrap_or_default())),
+                    (None, Some(literal)) => Some(literal.to_string()),
+                    _ => None,
+                }
+            }).collect()
+        }).unwrap_or_default();
+
+        let year = item.pointer("/issued/date-parts/0/0")
+            .and_then(|y| y.as_i64().map(|y| y.to_string()).or(y.as_str().map(|y| y.to_string())))
+            .or(item.pointer("/issued/literal").and_then(|y| y.as_str()).map(|y| y.to_string()));
+
+        let url = text("DOI").map(|doi| format!("https://doi.org/{}", doi)).or(text("URL"));
+
+        references.insert(key.clone(), Reference {
+            key,
+            authors,
+            year,
+            title: text("title"),
+            container: text("container-title").or(text("publisher")),
+            url,
+        });
+    }
+
+    Ok(Bibliography { references })
+}
+
+/// Parses a BibTeX bibliography, `@string` macros and `@preamble` entries are ignored
+fn parse_bibtex(source: &str) -> BibliographyResult<Bibliography> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut references = HashMap::new();
+    let mut i = 0;
+
+    let line = |pos: usize| chars[..pos.min(chars.len())].iter().filter(|c| **c == '\n').count() + 1;
+
+    while i < chars.len() {
+        // Anything outside of an entry is a comment
+        if chars[i] != '@' {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        i += 1;
+        let kind_start = i;
+        while i < chars.len() && chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        let kind = chars[kind_start..i].iter().collect::<String>().to_lowercase();
+
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+
+        let close = match chars.get(i) {
+            Some('{') => '}',
+            Some('(') => ')',
+            _ => return Err(BibliographyError::Syntax(line(start), format!("Expected '{{' after '@{}'", kind))),
+        };
+        i += 1;
+
+        // Find the end of the entry, so that ignored entries can be skipped entirely
+        let body_start = i;
+        let mut depth = 0;
+        while i < chars.len() && !(depth == 0 && chars[i] == close) {
+            match chars[i] {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => {}
+            }
+            i += 1;
+        }
+
+        if i >= chars.len() {
+            return Err(BibliographyError::Syntax(line(start), format!("Unclosed '@{}' entry", kind)));
+        }
+
+        let body: String = chars[body_start..i].iter().collect();
+        i += 1;
+
+        if matches!(kind.as_str(), "comment" | "preamble" | "string") {
+            continue;
+        }
+
+        let (key, fields) = body.split_once(',').unwrap_or((&body, ""));
+        let key = key.trim().to_string();
+        let fields = parse_fields(fields).map_err(|e| BibliographyError::Syntax(line(start), format!("{} in '{}'", e, key)))?;
+
+        let authors = fields.get("author").or(fields.get("editor"))
+            .map(|names| {
+                names.split(" and ")
+                    .map(|name| name.trim())
+                    .filter(|name| !name.is_empty())
+                    .map(|name| match name.split_once(',') {
+                        Some((family, given)) => format_author(family.trim(), given.trim()),
+                        None => match name.rsplit_once(' ') {
+                            Some((given, family)) => format_author(family, given),
+                            None => name.to_string(),
+                        },
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let url = fields.get("doi").map(|doi| format!("https://doi.org/{}", doi)).or(fields.get("url").cloned());
+        let container = ["journal", "journaltitle", "booktitle", "publisher", "howpublished", "institution", "school"].iter()
+            .find_map(|field| fields.get(*field).cloned());
+
+        references.insert(key.clone(), Reference {
+            key,
+            authors,
+            year: fields.get("year").cloned().or(fields.get("date").map(|d| d.chars().take(4).collect())),
+            title: fields.get("title").cloned(),
+            container,
+            url,
+        });
+    }
+
+    Ok(Bibliography { references })
+}
+
+/// Parses the fields of a BibTeX entry, eg: `title = {A {Nested} Title}, year = 2019`
+fn parse_fields(source: &str) -> Result<HashMap<String, String>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut fields = HashMap::new();
+    let mut i = 0;
+
+    loop {
+        while i < chars.len() && (chars[i].is_whitespace() || chars[i] == ',') {
+            i += 1;
+        }
+
+        if i >= chars.len() {
+            return Ok(fields);
+        }
+
+        let name_start = i;
+        while i < chars.len() && chars[i] != '=' {
+            i += 1;
+        }
+        let name = chars[name_start..i].iter().collect::<String>().trim().to_lowercase();
+
+        if i >= chars.len() {
+            return Err(format!("Field '{}' has no value", name));
+        }
+        i += 1;
+
+        // Values may be concatenated with `#`, eg: `"Vol. " # 2`
+        let mut value = String::new();
+        loop {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+
+            match chars.get(i) {
+                Some('{') => {
+                    let mut depth = 0;
+                    let start = i + 1;
+                    while i < chars.len() {
+                        match chars[i] {
+                            '{' => depth += 1,
+                            '}' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                        i += 1;
+                    }
+                    if i >= chars.len() {
+                        return Err(format!("Unclosed brace in field '{}'", name));
+                    }
+                    value.extend(chars[start..i].iter().filter(|c| **c != '{' && **c != '}'));
+                    i += 1;
+                }
+                Some('"') => {
+                    let start = i + 1;
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        i += 1;
+                    }
+                    if i >= chars.len() {
+                        return Err(format!("Unclosed quote in field '{}'", name));
+                    }
+                    value.extend(chars[start..i].iter().filter(|c| **c != '{' && **c != '}'));
+                    i += 1;
+                }
+                _ => {
+                    // Numbers and macro names, macros are kept as written
+                    let start = i;
+                    while i < chars.len() && !matches!(chars[i], ',' | '#') && !chars[i].is_whitespace() {
+                        i += 1;
+                    }
+                    value.extend(chars[start..i].iter());
+                }
+            }
+
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+
+            if chars.get(i) == Some(&'#') {
+                i += 1;
+            } else {
+                break;
+            }
+        }
+
+        fields.insert(name, value.split_whitespace().collect::<Vec<&str>>().join(" "));
+    }
+}
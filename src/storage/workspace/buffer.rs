@@ -1,24 +1,50 @@
 use std::path::PathBuf;
 use std::collections::HashMap;
+use std::ops::Range;
+use iced::window::Id;
 use url::Url;
-use html_parser::{Dom, Node};
-use pulldown_cmark::{Parser, Options, Event};
+use regex::Regex;
 use iced::{color, Border, Length, Padding};
 use iced::border::Radius;
-use iced::widget::{row, column, span, container, horizontal_rule, mouse_area};
+use iced::widget::{row, column, span, text, checkbox, container, horizontal_rule, mouse_area, image, svg, tooltip, horizontal_space, vertical_space};
+use iced::widget::tooltip::Position;
+use material_icons::Icon;
+use rust_i18n::t;
 use iced::widget::text::Span;
 use iced_aw::{grid, grid_row};
-use iced_core::alignment::Horizontal;
+use iced_core::alignment::{Horizontal, Vertical};
 use iced_core::Font;
 use iced_core::font::{Family, Style, Weight};
 use iced_core::font::Weight::{Bold, Medium, Normal};
 use crate::consts::*;
+use crate::ui::{diagrams, math};
+use crate::storage::workspace::bibliography::Bibliography;
+use crate::assets::{asset_id, content_id, local_asset_url, resolve_source, AssetData, AssetManager, AssetPermission, AssetRequest};
 use crate::utils::components::widgets::rich_text;
 use crate::runtime::{Element, GLOBAL_STATE};
 use crate::runtime::Message;
+use crate::runtime::speech::export::Chapter;
 use crate::runtime::messaging::MessageKind;
+use crate::runtime::windows::editor::messaging::{EditorMessage, EditorMessageKind};
+use crate::utils::components::buttons::{button_with_icon, RichButton};
 use crate::utils::components::widgets::rich_text::Rich;
-use crate::utils::cryptography::hashing::hash_str;
+use crate::utils::components::widgets::measured::measured;
+use self::outline::{sections, Section};
+use self::viewport::{HeightCache, Viewport};
+
+pub mod viewport;
+pub mod outline;
+mod markdown;
+mod hooks;
+
+lazy_static!(
+    /// Pandoc style citations, eg: `[@smith2019]`, `[@smith2019, p. 4]` or `[@smith2019; @doe2020]`
+    static ref CITATION: Regex = Regex::new(r"\[(@[^\[\]]+)\]").unwrap();
+    static ref CITATION_ITEM: Regex = Regex::new(r"^@([\w:.#$%&+?<>~/-]+)(?:\s*,\s*(.+))?$").unwrap();
+);
+
+/// Bullets for unordered lists, cycling as lists are nested
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
 
 #[derive(Debug, Clone)]
 pub struct Buffer {
@@ -26,7 +52,22 @@ pub struct Buffer {
     pub name: String,
     pub url: Url,
     pub doc: Vec<ElWrapper>,
-    pub tts_segments: Vec<String>
+    pub tts_segments: Vec<String>,
+    pub heights: HeightCache,
+    workspace: String,
+    /// The markdown the document was parsed from
+    source: String,
+    /// The content ID of the note as it was last read or written, which tells whether the source has unsaved changes
+    /// and whether the note was changed outside of the app since
+    saved: String,
+    /// The range of the source each top level block was parsed from
+    ranges: Vec<Range<usize>>,
+    metadata: String,
+    front_matter: usize,
+    /// The top level block which was last clicked, which images dropped or pasted into the note are inserted after
+    cursor: Option<usize>,
+    /// Whether the blocks are changed by the hooks plugins have registered, which is only done for notes being viewed
+    hooks: bool,
     // assets: BTreeMap<String, Vec<u8>>
 }
 
@@ -37,82 +78,384 @@ pub enum Image {
 
 
 impl Buffer {
-    pub fn new<U: Into<String> + Clone + std::fmt::Debug>(name: String, workspace: String, url: U, content: String) -> Self {
-        let dom = Dom::parse(&content).unwrap();
-        let mut els = vec![];
-        let mut root_dir = PathBuf::from(url.clone().into());
-        root_dir.pop();
+    /// Parses a note as it is written, eg: to find what it references
+    pub fn from_md<U: Into<String>>(name: String, workspace: String, url: U, content: String) -> Self {
+        Self::parse(name, workspace, url, content, false)
+    }
 
-        for element in dom.children {
-            match element {
-                Node::Comment(_) => continue,
-                Node::Text(_) => els.push(ElWrapper::new(element, workspace.clone())),
-                Node::Element(_) => els.push(ElWrapper::new(element, workspace.clone()))
-            }
-        }
+    /// Parses a note to be viewed, letting plugins change how it is drawn
+    pub fn render_md<U: Into<String>>(name: String, workspace: String, url: U, content: String) -> Self {
+        Self::parse(name, workspace, url, content, true)
+    }
 
-        // let asset_base = PathBuf::from(wsm.source.disk_path).with_file_name("assets");
+    fn parse<U: Into<String>>(name: String, workspace: String, url: U, content: String, hooks: bool) -> Self {
+        let base = Url::parse(&url.into()).unwrap();
+        let parsed = markdown::parse(&content, 0, &workspace, &base, hooks);
 
-        Self {
+        let mut buffer = Self {
             id: nanoid!(5),
             name,
-            url: Url::parse(&url.into()).unwrap(),
-            doc: els,
-            tts_segments: vec![]
-            // assets: BTreeMap::new()
+            url: base,
+            doc: parsed.els,
+            tts_segments: vec![],
+            heights: HeightCache::default(),
+            workspace,
+            saved: content_id(content.as_bytes()),
+            source: content,
+            ranges: parsed.ranges,
+            metadata: parsed.metadata,
+            front_matter: parsed.front_matter,
+            cursor: None,
+            hooks,
+        };
+
+        buffer.number();
+        buffer.heights = HeightCache::new(&buffer.doc);
+        buffer
+    }
+
+    /// Returns whether the source has been changed within the app since the note was last read or written
+    pub fn is_dirty(&self) -> bool {
+        content_id(self.source.as_bytes()) != self.saved
+    }
+
+    /// Writes the markdown of the buffer back to the note it was opened from, returning the path of the note, or `None`
+    /// if there are no changes to save. A note which has been changed outside of the app since it was read is not
+    /// overwritten, as those changes would be lost
+    pub fn save(&mut self) -> std::io::Result<Option<PathBuf>> {
+        if !self.is_dirty() {
+            return Ok(None);
+        }
+
+        let path = self.url.to_file_path()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::Unsupported, format!("{} is not a file", self.url)))?;
+
+        if content_id(&std::fs::read(&path)?) != self.saved {
+            return Err(std::io::Error::other(format!("{} has been changed outside of the app since it was opened", path.display())));
+        }
+
+        std::fs::write(&path, &self.source)?;
+        self.saved = content_id(self.source.as_bytes());
+        Ok(Some(path))
+    }
+
+    /// Updates the document after the note has been edited, parsing only the top level blocks around the edit again.
+    /// Returns the indices of the blocks which were parsed
+    pub fn update(&mut self, content: String) -> Range<usize> {
+        // The content was read from the note, so the buffer now matches it
+        self.saved = content_id(content.as_bytes());
+        if content == self.source {
+            return 0..0;
+        }
+
+        if let Some(parsed) = self.reparse(&content) {
+            // The blocks after the edit may have moved, so their spoken text is prepared again when next needed
+            self.tts_segments.clear();
+            return parsed;
+        }
+
+        self.source = content;
+        self.render();
+
+        0..self.doc.len()
+    }
+
+    /// Parses the whole document again from its source, keeping the buffer id and the measured heights of its blocks
+    pub fn render(&mut self) {
+        let mut buffer = Self::parse(self.name.clone(), self.workspace.clone(), self.url.clone(), std::mem::take(&mut self.source), self.hooks);
+        buffer.id = self.id.clone();
+        buffer.saved = std::mem::take(&mut self.saved);
+        buffer.cursor = self.cursor.filter(|&index| index < buffer.doc.len());
+        buffer.heights.carry_over(&self.heights);
+        *self = buffer;
+    }
+
+    /// Parses the blocks touched by an edit, with a block either side as the edit may join them up.
+    /// Returns `None` if the edit could change how the rest of the note is parsed
+    fn reparse(&mut self, content: &str) -> Option<Range<usize>> {
+        // Definitions of links and footnotes can be referred to from anywhere within the note
+        if self.source.contains("]:") || content.contains("]:") || self.ranges.is_empty() {
+            return None;
+        }
+
+        let (prefix, suffix) = common_affixes(&self.source, content);
+        let changed = prefix..self.source.len() - suffix;
+        let delta = content.len() as isize - self.source.len() as isize;
+        let shift = |offset: usize| offset.checked_add_signed(delta).unwrap();
+
+        // The front matter names the bibliography, which every citation depends on
+        if changed.start < self.front_matter {
+            return None;
+        }
+
+        let touched_start = self.ranges.iter().position(|r| r.end >= changed.start).unwrap_or(self.ranges.len());
+        let touched_end = self.ranges.iter().rposition(|r| r.start <= changed.end).map_or(0, |i| i + 1);
+
+        let mut start = touched_start.min(touched_end).saturating_sub(1);
+        let mut end = (touched_start.max(touched_end) + 1).min(self.ranges.len());
+        while start > 0 && self.ranges[start - 1] == self.ranges[start] {
+            start -= 1;
+        }
+        while end < self.ranges.len() && self.ranges[end] == self.ranges[end - 1] {
+            end += 1;
+        }
+
+        let from = if start == 0 { self.front_matter } else { self.ranges[start].start.min(changed.start) };
+        let to = if end == self.ranges.len() { self.source.len() } else { self.ranges[end - 1].end.max(changed.end) };
+
+        let parsed = markdown::parse(&content[from..shift(to)], from, &self.workspace, &self.url, self.hooks);
+
+        // The blocks either side of the edit must still begin and end where they did
+        if start > 0 && parsed.ranges.first().map(|r| r.start) != Some(from) {
+            return None;
+        }
+        if end < self.ranges.len() && parsed.ranges.last() != Some(&(shift(self.ranges[end - 1].start)..shift(self.ranges[end - 1].end))) {
+            return None;
+        }
+
+        // The references section is generated again below
+        let blocks = self.ranges.len();
+        self.heights.splice(blocks..self.doc.len(), &[]);
+        self.doc.truncate(blocks);
+
+        let added = parsed.els.len();
+        self.heights.splice(start..end, &parsed.els);
+        self.doc.splice(start..end, parsed.els);
+        self.ranges.splice(start..end, parsed.ranges);
+
+        for range in &mut self.ranges[start + added..] {
+            *range = shift(range.start)..shift(range.end);
+        }
+        shift_tasks(&mut self.doc[start + added..], delta);
+
+        self.source = content.to_string();
+        self.number();
+
+        let blocks = self.ranges.len();
+        self.heights.splice(blocks..blocks, &self.doc[blocks..]);
+
+        Some(start..start + added)
+    }
+
+    /// Numbers the lists, footnotes, citations and `<details>` elements of the document, which depend on
+    /// what comes before them, and appends the references section
+    fn number(&mut self) {
+        index_headings(&mut self.doc);
+        number_lists(&mut self.doc, 0);
+        index_details(&mut self.doc, &mut 0);
+        number_footnotes(&mut self.doc);
+        self.resolve_citations(&self.metadata.clone());
+    }
+
+    /// Numbers each cited source in the order it first appears, and appends a references section
+    /// generated from the bibliography of the note
+    fn resolve_citations(&mut self, metadata: &str) {
+        let mut items = vec![];
+        find_mut(&mut self.doc, &|el| el.name == "cite-item", &mut items);
+
+        if items.is_empty() {
+            return;
+        }
+
+        let bibliography = self.url.to_file_path().ok()
+            .and_then(|note| Bibliography::find(&note, metadata))
+            .map(|path| Bibliography::load(&path).map_err(|e| format!("{}: {}", path.display(), e)))
+            .unwrap_or(Err(t!("windows.editor.citations.no-bibliography").to_string()));
+
+        let mut cited: Vec<String> = vec![];
+
+        for item in items {
+            let key = item.attribute("key");
+
+            match bibliography.as_ref().ok().and_then(|b| b.get(&key)) {
+                Some(reference) => {
+                    let number = match cited.iter().position(|k| *k == key) {
+                        Some(index) => index + 1,
+                        None => {
+                            cited.push(key.clone());
+                            cited.len()
+                        }
+                    };
+                    item.attributes.insert("number".to_string(), Some(number.to_string()));
+                    item.attributes.insert("short".to_string(), Some(reference.short()));
+                }
+                None => {
+                    item.attributes.insert("missing".to_string(), Some("true".to_string()));
+                }
+            }
+        }
+
+        let mut section = ElWrapper::synthetic("references", vec![("id", "references".to_string())], vec![]);
+
+        match &bibliography {
+            Ok(bibliography) => {
+                for (index, key) in cited.iter().enumerate() {
+                    let Some(reference) = bibliography.get(key) else { continue };
+                    let mut attributes = vec![
+                        ("id", format!("ref-{}", key)),
+                        ("number", (index + 1).to_string()),
+                        ("text", reference.format()),
+                    ];
+                    if let Some(url) = &reference.url {
+                        attributes.push(("url", url.clone()));
+                    }
+                    section.children.push(ElWrapper::synthetic("reference", attributes, vec![]));
+                }
+            }
+            Err(e) => {
+                section.attributes.insert("error".to_string(), Some(e.clone()));
+            }
         }
+
+        self.doc.push(section);
     }
 
-    pub fn from_md<U: Into<String> + std::fmt::Debug + Clone>(name: String, workspace: String, url: U, content: String) -> Self {
-        let mut options = Options::empty();
+    /// Returns the distance from the top of the document to the block containing the element with the given id
+    pub fn anchor_offset(&self, anchor: &str) -> Option<f32> {
+        fn contains(el: &ElWrapper, anchor: &str) -> bool {
+            el.attribute("id") == anchor || el.children.iter().any(|c| contains(c, anchor))
+        }
 
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_FOOTNOTES);
-        options.insert(Options::ENABLE_TASKLISTS);
-        options.insert(Options::ENABLE_SUBSCRIPT);
-        options.insert(Options::ENABLE_SUPERSCRIPT);
-        options.insert(Options::ENABLE_WIKILINKS);
-        options.insert(Options::ENABLE_GFM);
-        options.insert(Options::ENABLE_DEFINITION_LIST);
-        options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+        let index = self.doc.iter().position(|el| contains(el, anchor))?;
+        Some(self.heights.top(index))
+    }
 
+    /// Lists the headings of the note, for navigating between its sections
+    pub fn outline(&self) -> Vec<Section> {
+        sections(&self.source)
+    }
+
+    /// Places the cursor on a top level block
+    pub fn place_cursor(&mut self, index: usize) {
+        self.cursor = Some(index).filter(|&index| index < self.doc.len());
+    }
 
+    /// Returns the byte offset within the note where an image dropped or pasted into it is inserted, which is after
+    /// the block holding the cursor, or the end of the note if no block has been clicked
+    pub fn insertion_point(&self) -> usize {
+        self.cursor.and_then(|index| self.ranges.get(index)).map_or(self.source.len(), |range| range.end)
+    }
 
-        let parser = Parser::new_ext(&content, options);
+    /// Returns the content ID of the markdown the document was parsed from, which offsets within it are only valid for
+    pub fn source_id(&self) -> String {
+        content_id(self.source.as_bytes())
+    }
 
+    /// Returns the index of the top level block containing a byte offset within the note
+    pub fn block_at(&self, offset: usize) -> Option<usize> {
+        self.ranges.iter().position(|r| r.contains(&offset))
+    }
 
-        // Write to String buffer.
+    /// Opens or closes the `<details>` element with the given index
+    pub fn toggle_details(&mut self, index: usize) {
+        let mut found = vec![];
+        find_mut(&mut self.doc, &|el| el.name == "details" && el.attribute("index") == index.to_string(), &mut found);
 
-        let mut html_output = String::new();
+        for details in found {
+            if details.attributes.remove("open").is_none() {
+                details.attributes.insert("open".to_string(), None);
+            }
+        }
+    }
 
-        let mut highlighted: Vec<Event> = vec![];
+    /// Returns the `src` of every image referenced within the document
+    pub fn image_sources(&self) -> Vec<String> {
+        self.block_image_sources(0..self.doc.len())
+    }
 
-        for event in parser {
-            match event {
-                // TODO: Implement code highlighting
-                // Event::Start(Tag::CodeBlock(kind)) => {
-                //
-                // },
-                // Event::End(Tag::CodeBlock(_)) => {
-                //
-                // }
-                _ => highlighted.push(event),
+    /// Returns the `src` of every image referenced within some of the top level blocks of the document
+    pub fn block_image_sources(&self, blocks: Range<usize>) -> Vec<String> {
+        fn collect(els: &[ElWrapper], sources: &mut Vec<String>) {
+            for el in els {
+                if el.name == "img" {
+                    if let Some(Some(src)) = el.attributes.get("src") {
+                        sources.push(src.clone());
+                    }
+                } else {
+                    collect(&el.children, sources);
+                }
             }
         }
 
-        // highlight_with_theme(parser, "base16-ocean.dark").unwrap();
+        let mut sources = vec![];
+        collect(&self.doc[blocks], &mut sources);
+        sources
+    }
 
-        pulldown_cmark::html::push_html(&mut html_output, highlighted.into_iter());
+    /// Returns the text spoken for each top level block when the note is read aloud, preparing it if the note has changed
+    pub fn segments(&mut self, assets: &AssetManager) -> &[String] {
+        if self.tts_segments.len() != self.doc.len() {
+            let segments = self.doc.iter()
+                .map(|el| greedy_render(std::slice::from_ref(el), assets).1.join(" ").trim().to_string())
+                .collect();
+            self.tts_segments = segments;
+        }
 
-        Self::new(name, workspace, url, html_output)
+        &self.tts_segments
     }
 
-    pub fn view(&self) -> Element {
+    /// Groups the spoken text of the note into chapters which begin at each heading, for exporting it as audio.
+    /// Only the chosen sections of the outline are included, along with the sections nested beneath them, or the
+    /// whole note if none are chosen
+    pub fn chapters(&mut self, assets: &AssetManager, chosen: &[usize]) -> Vec<Chapter> {
+        let segments = self.segments(assets).to_vec();
+        let sections = self.outline();
+        let headings: Vec<usize> = sections.iter().filter_map(|s| self.block_at(s.range.start)).collect();
+
+        let included = |section: &Section| chosen.is_empty() || chosen.iter()
+            .filter_map(|&c| sections.get(c))
+            .any(|c| c.range.start <= section.range.start && section.range.end <= c.range.end);
+
+        // The generated references are only read when reading the whole note
+        let last = if chosen.is_empty() { self.doc.len() } else { self.ranges.len() };
+        let chapter = |title: String, start: usize| {
+            let end = headings.iter().copied().find(|&h| h > start).unwrap_or(last);
+            let text = segments[start..end].iter().filter(|s| !s.is_empty()).cloned().collect::<Vec<_>>().join("\n");
+            Chapter { title, text }
+        };
+
+        let mut chapters = vec![];
+        if chosen.is_empty() && headings.first() != Some(&0) {
+            chapters.push(chapter(self.name.clone(), 0));
+        }
+
+        for section in sections.iter().filter(|s| included(s)) {
+            if let Some(start) = self.block_at(section.range.start) {
+                chapters.push(chapter(section.text.clone(), start));
+            }
+        }
+
+        chapters
+    }
+
+    /// Renders the blocks of the document which are within the viewport, leaving space for the rest.
+    /// The block being read aloud, if any, is highlighted
+    pub fn view<'a>(&'a self, assets: &'a AssetManager, viewport: Viewport, reading: Option<usize>, window: Id) -> Element<'a> {
         let start = std::time::Instant::now();
-        let (v, _texts) = greedy_render(&self.doc);
+        let visible = self.heights.visible(viewport);
+
+        let blocks = self.doc[visible.start..visible.end].iter().enumerate().map(|(i, el)| {
+            let index = visible.start + i;
+            let (v, _texts) = greedy_render(std::slice::from_ref(el), assets);
+            let v = if reading == Some(index) {
+                container(v).width(Length::Fill).style(|_| container::Style {
+                    background: Some(color!(0xa30000, 0.15).into()),
+                    ..Default::default()
+                }).into()
+            } else {
+                v
+            };
+            let v = measured(v, index, move |height| EditorMessage::new(EditorMessageKind::BlockMeasured(index, height), window).into());
+            mouse_area(v).on_press(EditorMessage::new(EditorMessageKind::PlaceCursor(index), window).into()).into()
+        });
 
-        let view = container(
+        let v = column(
+            std::iter::once(vertical_space().height(visible.above).into())
+                .chain(blocks)
+                .chain(std::iter::once(vertical_space().height(visible.below).into()))
+        );
+
+        let view: Element = container(
             v
         )
         .padding(Padding {top:5., right: 20., bottom: 5., left: 5.})
@@ -120,9 +463,10 @@ impl Buffer {
         .into();
 
         let diff = start.elapsed().as_micros();
-        info!("Rendered document in {}μs", diff);
+        info!("Rendered {} of {} blocks in {}μs", visible.end - visible.start, self.doc.len(), diff);
 
-        view
+        // Elements within a note do not know which window they are in, so their messages are given it here
+        view.map(move |message: Message| message.from_window(window))
     }
 }
 
@@ -141,109 +485,359 @@ pub enum Render<'a> {
     /// Contains an element, and a boolean stating whether the widget can be inlined or not
     Element(Element<'a>, bool),
     /// Contains a rich text span
-    Span(Span<'a, Message>)
+    Span(Span<'a, Message>),
+    /// Contains several rich text spans, eg: text with nested formatting such as `<strong><em>`
+    Spans(Vec<Span<'a, Message>>)
 }
 
 
 impl ElWrapper {
-    pub fn new(el: Node, workspace: String) -> Self {
-
-        match el {
-            Node::Text(content) => {
-                Self {
-                    // id: None,
-                    name: "TEXT".to_string(),
-                    attributes: Default::default(),
-                    // classes: vec![],
-                    children: vec![],
-                    display_text: content.clone(),
-                }
-            },
-            Node::Element(element) => {
-                let mut children = vec![];
+    /// Prepares a complete element to be drawn, eg: rendering diagrams and resolving image sources
+    fn finish(mut self, workspace: &str, base: &Url, hooks: bool) -> Self {
+        // Plugins may claim code blocks by their language, eg: to draw custom callouts
+        if let Some(rendered) = code_source(&self).filter(|_| hooks).and_then(|(lang, source)| hooks::code_block(&lang, &source, workspace, base)) {
+            return rendered;
+        }
 
-                for child in element.children {
-                    children.push(Self::new(child, workspace.clone()))
-                }
+        if let Some((lang, source)) = diagram_source(&self) {
+            // Diagrams are rendered here, so that the work happens on a worker rather than in the view
+            self.name = "diagram".to_string();
+            self.attributes.insert("lang".to_string(), Some(lang.clone()));
 
-                let mut s = Self {
-                    // id: element.id.clone(),
-                    name: element.name.clone(),
-                    attributes: element.attributes.clone(),
-                    // classes: element.classes.clone(),
-                    children,
-                    display_text: String::new(),
-                };
+            match diagrams::render(&lang, &source) {
+                Ok(svg) => self.attributes.insert("svg".to_string(), Some(svg)),
+                Err(e) => self.attributes.insert("error".to_string(), Some(e.to_string())),
+            };
+        }
 
-                if s.name == "img" {
-                    let src = s.attributes.get("src").unwrap().clone().unwrap();
-                    let hash = hash_str(src);
-                    let uri = Url::parse(&format!("noot://{}/assets/{}", workspace, hash)).unwrap();
-                    s.attributes.insert("cached-src".to_string(), Some(uri.to_string()));
-                }
+        if let Some((tex, display)) = math_source(&self) {
+            // Equations are parsed here, and display equations laid out, so the view only has to draw them
+            self.name = "math".to_string();
+            self.attributes.insert("tex".to_string(), Some(tex.clone()));
 
-                s
+            if display {
+                self.attributes.insert("display".to_string(), Some("true".to_string()));
             }
-            _ => {
-                Self {
-                    // id: None,
-                    name: "INVALID".to_string(),
-                    attributes: Default::default(),
-                    // classes: vec![],
-                    children: vec![],
-                    display_text: String::new(),
+
+            match math::parse(&tex) {
+                Ok(node) => {
+                    self.attributes.insert("unicode".to_string(), Some(math::to_unicode(&node)));
+                    self.attributes.insert("speech".to_string(), Some(math::to_speech(&node)));
+                    if display {
+                        self.attributes.insert("svg".to_string(), Some(math::to_svg(&node)));
+                    }
                 }
+                Err(e) => {
+                    self.attributes.insert("error".to_string(), Some(e.to_string()));
+                }
+            }
+        }
+
+        if self.name == "img" {
+            let src = self.attributes.get("src").cloned().flatten().unwrap_or_default();
+
+            // Malformed sources are left without a `cached-src`, and render as an error instead
+            if let Some(resolved) = resolve_source(&src, base) {
+                let hash = asset_id(resolved.as_str());
+                let uri = local_asset_url(workspace, &hash);
+                self.attributes.insert("src".to_string(), Some(resolved.to_string()));
+                self.attributes.insert("cached-src".to_string(), Some(uri.to_string()));
             }
         }
+
+        self
+    }
+
+    /// Creates an element with the given attributes, eg: for a generated references section
+    fn synthetic(name: &str, attributes: Vec<(&str, String)>, children: Vec<ElWrapper>) -> Self {
+        Self {
+            name: name.to_string(),
+            attributes: attributes.into_iter().map(|(k, v)| (k.to_string(), Some(v))).collect(),
+            children,
+            display_text: String::new(),
+        }
     }
 
-    pub fn view(&self, section_text: Option<String>) -> (Render, String) {
+    fn text(content: &str) -> Self {
+        Self {
+            name: "TEXT".to_string(),
+            attributes: Default::default(),
+            children: vec![],
+            display_text: content.to_string(),
+        }
+    }
+
+    /// Returns the value of an attribute, or an empty string if it is not set
+    pub fn attribute(&self, name: &str) -> String {
+        self.attributes.get(name).cloned().flatten().unwrap_or_default()
+    }
+
+    /// Returns the index of the top level block this element is, if it is a heading which was indexed
+    fn block(&self) -> Option<usize> {
+        self.attribute("block").parse().ok()
+    }
+
+    pub fn view<'a>(&'a self, section_text: Option<String>, assets: &'a AssetManager) -> (Render<'a>, String) {
         match self.name.as_str() {
             "TEXT" =>
                 (Render::Span(a11_span(unescape_html_text(self.display_text.as_str()))), unescape_html_text(self.display_text.as_str())),
             "hr" => (Render::Element(horizontal_rule(2).into(), false), "".to_string()),
-            "h1" => (heading(greedy_text(&self.children), HEADER_SIZE_1, section_text.clone().unwrap_or(greedy_text(&self.children))), section_text.unwrap_or(greedy_text(&self.children))),
-            "h2" => (heading(greedy_text(&self.children), HEADER_SIZE_2, greedy_text(&self.children)), greedy_text(&self.children)),
-            "h3" => (heading(greedy_text(&self.children), HEADER_SIZE_3, greedy_text(&self.children)), greedy_text(&self.children)),
-            "h4" => (heading(greedy_text(&self.children), HEADER_SIZE_4, greedy_text(&self.children)), greedy_text(&self.children)),
-            "h5" => (heading(greedy_text(&self.children), HEADER_SIZE_5, greedy_text(&self.children)), greedy_text(&self.children)),
+            "h1" => (heading(greedy_text(&self.children), HEADER_SIZE_1, self.block()), section_text.unwrap_or(greedy_text(&self.children))),
+            "h2" => (heading(greedy_text(&self.children), HEADER_SIZE_2, self.block()), greedy_text(&self.children)),
+            "h3" => (heading(greedy_text(&self.children), HEADER_SIZE_3, self.block()), greedy_text(&self.children)),
+            "h4" => (heading(greedy_text(&self.children), HEADER_SIZE_4, self.block()), greedy_text(&self.children)),
+            "h5" => (heading(greedy_text(&self.children), HEADER_SIZE_5, self.block()), greedy_text(&self.children)),
+            "h6" => (heading(greedy_text(&self.children), HEADER_SIZE_6, self.block()), greedy_text(&self.children)),
+            "em" | "i" | "strong" | "b" | "del" | "s" | "strike" | "u" | "ins" | "sup" | "sub" | "kbd" | "mark" | "br" => {
+                let mut runs = vec![];
+
+                if inline_runs(std::slice::from_ref(self), InlineStyle::default(), None, &mut runs) {
+                    (Render::Spans(runs.iter().map(run_span).collect()), runs_text(&runs))
+                } else {
+                    // Formatting around content which cannot be drawn as text, such as an image, is dropped
+                    let (v, t) = greedy_render(&self.children, assets);
+                    (Render::Element(v, true), t.join(""))
+                }
+            }
+            "blockquote" => {
+                let (v, t) = greedy_render(&self.children, assets);
+                (
+                    Render::Element(
+                        container(v)
+                            .padding(Padding { top: 5., right: 5., bottom: 5., left: 15. })
+                            .width(Length::Fill)
+                            .style(|_| {
+                                container::Style {
+                                    background: Some(color!(0x2a2a2a, 0.3).into()),
+                                    border: Border {
+                                        color: color!(0x5a5a5a),
+                                        width: 1.,
+                                        radius: Radius::new(5)
+                                    },
+                                    .. Default::default()
+                                }
+                            })
+                            .into(),
+                        false
+                    ),
+                    t!("windows.editor.blocks.quote", text = t.join("")).to_string()
+                )
+            }
+            "dl" => {
+                let mut col: Vec<Element> = vec![];
+                let mut texts = vec![];
+
+                for child in &self.children {
+                    match child.name.as_str() {
+                        "dt" => {
+                            let (v, t) = styled(&child.children, InlineStyle { bold: true, ..Default::default() }, assets);
+                            col.push(v);
+                            texts.push(t);
+                        }
+                        "dd" => {
+                            let (v, t) = greedy_render(&child.children, assets);
+                            col.push(container(v).padding(Padding { left: 30., ..Padding::ZERO }).into());
+                            texts.push(t.join(""));
+                        }
+                        _ => {}
+                    }
+                }
+
+                (Render::Element(column(col).spacing(2.).into(), false), texts.join(". "))
+            }
+            "details" => {
+                let open = self.attributes.contains_key("open");
+                let (summary, body) = match self.children.split_first() {
+                    Some((first, rest)) if first.name == "summary" => (Some(first), rest),
+                    _ => (None, self.children.as_slice()),
+                };
+
+                let (title, title_text) = match summary {
+                    Some(summary) => styled(&summary.children, InlineStyle { bold: true, ..Default::default() }, assets),
+                    None => {
+                        let title = t!("windows.editor.blocks.details").to_string();
+                        (a11_text(title.clone()).into(), title)
+                    }
+                };
+
+                let mut header = mouse_area(row!(a11_text(if open { "▾" } else { "▸" }).width(20.), title));
+                if let Ok(index) = self.attribute("index").parse::<usize>() {
+                    header = header.on_release(Message::new(MessageKind::EditorInput(EditorMessageKind::ToggleDetails(index)), None));
+                }
+
+                if open {
+                    let (v, t) = greedy_render(body, assets);
+                    (
+                        Render::Element(column!(header, container(v).padding(Padding { left: 20., ..Padding::ZERO })).into(), false),
+                        format!("{}. {}", title_text, t.join(""))
+                    )
+                } else {
+                    (Render::Element(header.into(), false), t!("windows.editor.blocks.collapsed", summary = title_text).to_string())
+                }
+            }
             "p" => {
-                let (v, t) = greedy_render(&self.children);
+                let (v, t) = greedy_render(&self.children, assets);
                 (Render::Element(v, false), t.join(" "))
             }
             "div" => {
-                let (v, t) = greedy_render(&self.children);
+                let (v, t) = greedy_render(&self.children, assets);
                 (Render::Element(v, false), t.join(" "))
             },
             "pre" => {
                 (
-                    Render::Element(
-                        container(
-                            rich_text(
-                                self.children[0].children.iter().map(|c| {
-                                    a11_mono(unescape_html_text(c.display_text.as_str()))
-                                }).collect::<Vec<Span<Message>>>()
+                    Render::Element(code_block(&self.children[0]), false),
+                    greedy_text(&self.children)
+                )
+
+            }
+            "diagram" => {
+                match self.attributes.get("svg").cloned().flatten() {
+                    Some(diagram) => (
+                        Render::Element(svg(svg::Handle::from_memory(diagram.into_bytes())).width(Length::Shrink).height(Length::Shrink).into(), false),
+                        t!("windows.editor.diagrams.description").to_string()
+                    ),
+                    None => {
+                        // Diagrams which fail to render fall back to showing their source
+                        let reason = t!("windows.editor.diagrams.failed", reason = self.attributes.get("error").cloned().flatten().unwrap_or_default()).to_string();
+                        (
+                            Render::Element(column!(broken_image(reason.clone()), vertical_space().height(5.), code_block(&self.children[0])).into(), false),
+                            reason
+                        )
+                    }
+                }
+            }
+            "math" => {
+                let attribute = |name: &str| self.attributes.get(name).cloned().flatten();
+                let speech = attribute("speech").unwrap_or_default();
+
+                match (attribute("error"), attribute("display").is_some()) {
+                    (None, true) => (
+                        Render::Element(
+                            container(
+                                svg(svg::Handle::from_memory(attribute("svg").unwrap_or_default().into_bytes()))
+                                    .width(Length::Shrink)
+                                    .height(Length::Shrink)
                             )
+                            .center_x(Length::Fill)
+                            .into(),
+                            false
+                        ),
+                        speech
+                    ),
+                    (None, false) => (Render::Span(a11_math(attribute("unicode").unwrap_or_default())), speech),
+                    (Some(error), true) => {
+                        // Equations which fail to parse fall back to showing their source
+                        let reason = t!("windows.editor.math.failed", reason = error).to_string();
+                        (
+                            Render::Element(column!(broken_image(reason.clone()), vertical_space().height(5.), a11_text(attribute("tex").unwrap_or_default())).into(), false),
+                            reason
                         )
-                        .padding(5)
-                        .width(Length::Fill)
-                        .style(|_| {
-                            container::Style {
-                                background: Some(iced::Background::Color(color!(0x1a1a1a))),
-                                border: iced::Border {
-                                    color: color!(0x2a2a2a),
-                                    width: 2.,
-                                    radius: Radius::new(5)
-                                },
-                                .. Default::default()
-                            }
-                        })
-                        .into(),
+                    }
+                    (Some(error), false) => (
+                        Render::Span(a11_mono(attribute("tex").unwrap_or_default()).color(color!(0xff6b6b))),
+                        t!("windows.editor.math.failed", reason = error).to_string()
+                    ),
+                }
+            }
+            "footnote-ref" => {
+                let label = self.attribute("label");
+                (
+                    Render::Span(
+                        a11_span(format!("[{}]", label))
+                            .link(Message::new(MessageKind::LinkOpened(Some(format!("#{}", self.attribute("target")))), None))
+                            .color(color!(0x0000fa))
+                    ),
+                    t!("windows.editor.footnotes.reference", label = label).to_string()
+                )
+            }
+            "footnote" => {
+                let label = self.attribute("label");
+                let (v, t) = greedy_render(&self.children, assets);
+                (
+                    Render::Element(
+                        row!(
+                            // The label links back to where the footnote was referenced
+                            rich_text([
+                                a11_span(format!("{}. ↩", label))
+                                    .link(Message::new(MessageKind::LinkOpened(Some(format!("#{}", self.attribute("target")))), None))
+                                    .color(color!(0x0000fa))
+                            ]),
+                            horizontal_space().width(5.),
+                            v
+                        ).into(),
                         false
                     ),
-                    greedy_text(&self.children)
+                    t!("windows.editor.footnotes.definition", label = label, text = t.join(" ")).to_string()
+                )
+            }
+            "cite" => {
+                let mut labels = vec![];
+                let mut sources = vec![];
+                let mut missing = false;
+
+                for item in &self.children {
+                    let locator = item.attribute("locator");
+
+                    if item.attributes.contains_key("missing") {
+                        missing = true;
+                        labels.push(format!("?{}", item.attribute("key")));
+                        sources.push(t!("windows.editor.citations.missing", key = item.attribute("key")).to_string());
+                    } else if locator.is_empty() {
+                        labels.push(item.attribute("number"));
+                        sources.push(item.attribute("short"));
+                    } else {
+                        labels.push(format!("{}, {}", item.attribute("number"), locator));
+                        sources.push(format!("{}, {}", item.attribute("short"), locator));
+                    }
+                }
+
+                // Citations of several sources link to the first one which could be resolved
+                let target = self.children.iter()
+                    .find(|item| !item.attributes.contains_key("missing"))
+                    .map(|item| format!("#ref-{}", item.attribute("key")))
+                    .unwrap_or("#references".to_string());
+
+                (
+                    Render::Span(
+                        a11_span(format!("[{}]", labels.join("; ")))
+                            .link(Message::new(MessageKind::LinkOpened(Some(target)), None))
+                            .color(if missing { color!(0xff6b6b) } else { color!(0x0000fa) })
+                    ),
+                    t!("windows.editor.citations.cite", sources = sources.join("; ")).to_string()
                 )
+            }
+            "references" => {
+                let title = t!("windows.editor.citations.references").to_string();
+                let mut col: Vec<Element> = vec![a11_heading(title.clone(), HEADER_SIZE_4).into()];
+                let mut texts = vec![title];
+
+                if let Some(error) = self.attributes.get("error").cloned().flatten() {
+                    let reason = t!("windows.editor.citations.failed", reason = error).to_string();
+                    col.push(broken_image(reason.clone()));
+                    texts.push(reason);
+                }
+
+                for reference in &self.children {
+                    let mut spans = vec![a11_span(format!("{} ", reference.attribute("text")))];
+
+                    if let Some(url) = reference.attributes.get("url").cloned().flatten() {
+                        spans.push(
+                            a11_span(url.clone())
+                                .link(Message::new(MessageKind::LinkOpened(Some(url)), None))
+                                .color(color!(0x0000fa))
+                        );
+                    }
+
+                    col.push(
+                        row!(
+                            a11_text(format!("[{}]", reference.attribute("number"))).width(40.),
+                            rich_text(spans)
+                        ).into()
+                    );
+                    texts.push(reference.attribute("text"));
+                }
 
+                (Render::Element(column(col).spacing(5.).into(), false), texts.join(". "))
             }
             "code" => {
                 let mut padding = Padding::new(2.);
@@ -277,18 +871,48 @@ impl ElWrapper {
                     greedy_text(&self.children)
                 )
             },
-            "ul" => {
-                (
-                    Render::Element(
-                        column(
-                            self.children.iter().map(|c| {
-                                row!(a11_text(format!("- {}", greedy_text(&c.children)))).wrap().into()
-                            })
-                        ).into(),
-                        false
-                    ),
-                    greedy_text(&self.children)
-                )
+            "ul" | "ol" => {
+                let mut items: Vec<Element> = vec![];
+                let mut texts = vec![];
+
+                for item in self.children.iter().filter(|c| c.name == "li") {
+                    let marker = item.attribute("marker");
+
+                    match item.children.split_first().filter(|(first, _)| first.name == "input") {
+                        Some((input, rest)) => {
+                            // Nested lists sit below the checkbox, rather than within its label
+                            let label = rest.iter()
+                                .filter(|c| !is_list(c))
+                                .map(|c| greedy_text(std::slice::from_ref(c)))
+                                .collect::<String>()
+                                .trim()
+                                .to_string();
+
+                            texts.push(if input.attributes.contains_key("checked") {
+                                t!("windows.editor.tasks.done", task = label).to_string()
+                            } else {
+                                t!("windows.editor.tasks.open", task = label).to_string()
+                            });
+
+                            let mut col = vec![task_item(input, label)];
+                            for list in rest.iter().filter(|c| is_list(c)) {
+                                let (v, t) = list.view(None, assets);
+                                if let Render::Element(e, _) = v {
+                                    col.push(container(e).padding(Padding { left: 30., ..Padding::ZERO }).into());
+                                }
+                                texts.push(t);
+                            }
+                            items.push(column(col).into());
+                        }
+                        None => {
+                            let (v, t) = greedy_render(&item.children, assets);
+                            items.push(row!(a11_text(marker.clone()).width(30.), v).into());
+                            texts.push(if self.name == "ol" { format!("{} {}", marker, t.join("")) } else { t.join("") });
+                        }
+                    }
+                }
+
+                (Render::Element(column(items).into(), false), texts.join(" "))
             }
             "a" => {
                 let path_opt = self.attributes.get("href");
@@ -301,7 +925,7 @@ impl ElWrapper {
                     }
                 }
                 if self.children[0].name != "TEXT" {
-                    let (v, t) = greedy_render(&self.children);
+                    let (v, t) = greedy_render(&self.children, assets);
                     (
                         Render::Element(
                             mouse_area(v)
@@ -350,10 +974,11 @@ impl ElWrapper {
 
                             // Iterate through columns in the table head
                             for cell in &child.children[0].children {
-                                let output = cell.view(None).0;
+                                let output = cell.view(None, assets).0;
                                 match output {
                                     Render::Element(e, _) => row.push(e),
                                     Render::Span(s) => row.push(rich_text([s]).into()),
+                                    Render::Spans(s) => row.push(rich_text(s).into()),
                                 }
                             }
 
@@ -364,10 +989,11 @@ impl ElWrapper {
                             let mut row = Vec::<Element>::new();
                             for trow in &child.children {
                                 for cell in &trow.children {
-                                    let output = cell.view(None).0;
+                                    let output = cell.view(None, assets).0;
                                     match output {
                                         Render::Element(e, _) => row.push(e),
                                         Render::Span(s) => row.push(rich_text([s]).into()),
+                                        Render::Spans(s) => row.push(rich_text(s).into()),
                                     }
                                 }
                             }
@@ -403,11 +1029,11 @@ impl ElWrapper {
                     }
 
                     (
-                        Render::Element(greedy_render_aligned(&self.children, alignment).0, false),
+                        Render::Element(greedy_render_aligned(&self.children, alignment, assets).0, false),
                         greedy_text(&self.children)
                     )
                 } else {
-                    let (v, t) = greedy_render(&self.children);
+                    let (v, t) = greedy_render(&self.children, assets);
                     (
                         Render::Element(v, false),
                         t.join(" ")
@@ -415,36 +1041,31 @@ impl ElWrapper {
                 }
             },
             "img" => {
-                dbg!(&self.attributes);
-                // let maybe_src = self.attributes.get("src").unwrap();
-            //     if let Some(src) = maybe_src {
-            //         // let hash = hash_str(src);
-            //         // warn!("Image handling support is experimental - Asset '{}' -> '{}'", src, hash);
-            //         // Render::Element(Svg::new(Handle::from_path().height(Length::Shrink).width(Length::Shrink).into(), true));
-            //         let mut alt = self.attributes.get("alt").unwrap_or(&None).clone().unwrap_or("".to_string());
-            //         // let asset = format!()
-            //         // let can_render = std::fs::exists()
-            //
-            //
-            //         // if alt.len() > 0 {
-            //         //
-            //         // } else {
-            //         //
-            //         // }
-            //
-            //         // let maybe_buffer = reqwest::blocking::get(src);
-            //
-            // //         if let Ok(response) = maybe_buffer {
-            // //             Render::Element(Svg::new(Handle::from_memory(Cow::Owned(response.bytes().unwrap().to_vec()))).height(Length::Shrink).width(Length::Shrink).into(), true)
-            // //         } else {
-            //             (Render::Span(a11_span("Image not found")), alt)
-            // //         }
-            // //
-            // //
-            // //
-            //     } else {
-                    (Render::Span(a11_span("Image not found")), "Image not found".into())
-                // }
+                let alt = self.attributes.get("alt").cloned().flatten().unwrap_or_default();
+                let src = self.attributes.get("src").cloned().flatten().unwrap_or_default();
+                let Some(id) = self.attributes.get("cached-src")
+                    .cloned()
+                    .flatten()
+                    .and_then(|src| Url::parse(&src).ok())
+                    .and_then(|src| src.path_segments().and_then(|mut s| s.next_back().map(|id| id.to_string()))) else {
+                    let reason = t!("windows.editor.assets.invalid-source", src = src).to_string();
+                    return (Render::Element(broken_image(reason.clone()), false), reason);
+                };
+
+                if let Some(blocked) = assets.blocked(&id) {
+                    return (Render::Element(blocked_image(blocked), false), alt);
+                }
+
+                if let Some(reason) = assets.failed(&id) {
+                    let reason = t!("windows.editor.assets.failed", src = src, reason = reason).to_string();
+                    return (Render::Element(broken_image(reason.clone()), false), reason);
+                }
+
+                match assets.get(&id).map(|a| &a.data) {
+                    Some(AssetData::Image(handle)) => (Render::Element(image(handle.clone()).into(), true), alt),
+                    Some(AssetData::Svg(handle)) => (Render::Element(svg(handle.clone()).width(Length::Shrink).height(Length::Shrink).into(), true), alt),
+                    _ => (Render::Span(a11_span("Image not found")), "Image not found".into())
+                }
             }
             _ => {
                 // dbg!(&self);
@@ -458,20 +1079,470 @@ impl ElWrapper {
 }
 
 
+/// Renders a placeholder for a remote image which was blocked by the workspace remote data strategy
+fn blocked_image<'a>(blocked: &AssetRequest) -> Element<'a> {
+    let domain = blocked.domain();
+
+    container(
+        column!(
+            a11_text(t!("windows.editor.assets.blocked", domain = domain).to_string()),
+            vertical_space().height(5.),
+            row!(
+                button_with_icon(Icon::Image, t!("windows.editor.assets.load-once"))
+                    .on_press(EditorMessage::new(EditorMessageKind::AllowAsset(blocked.id.clone(), AssetPermission::Once), blocked.window).into()),
+                horizontal_space().width(5.),
+                button_with_icon(Icon::VerifiedUser, t!("windows.editor.assets.allow-domain"))
+                    .on_press(EditorMessage::new(EditorMessageKind::AllowAsset(blocked.id.clone(), AssetPermission::Domain), blocked.window).into()),
+            )
+        )
+    )
+    .padding(5)
+    .width(Length::Fill)
+    .style(|_| {
+        container::Style {
+            border: iced::Border {
+                color: color!(0x2a2a2a),
+                width: 2.,
+                radius: Radius::new(5)
+            },
+            .. Default::default()
+        }
+    })
+    .into()
+}
+
+
+/// Renders a task list item as a checkbox, which writes back to the note when toggled
+fn task_item<'a>(input: &ElWrapper, label: String) -> Element<'a> {
+    let checked = input.attributes.contains_key("checked");
+    let mut task = checkbox(label, checked).font(build_font(get_a11_font(), Normal));
+
+    // Tasks can only be toggled within notes on disk, as there is nowhere to write the change otherwise
+    let note = input.attribute("note");
+    if let (false, Ok(offset)) = (note.is_empty(), input.attribute("offset").parse::<usize>()) {
+        task = task.on_toggle(move |done| {
+            Message::new(MessageKind::EditorInput(EditorMessageKind::ToggleTask(PathBuf::from(&note), offset, done)), None)
+        });
+    }
+
+    task.into()
+}
+
+fn is_list(el: &ElWrapper) -> bool {
+    matches!(el.name.as_str(), "ul" | "ol")
+}
+
+/// Marks each top level heading with its index, so that reading aloud can start from it
+fn index_headings(els: &mut [ElWrapper]) {
+    for (index, el) in els.iter_mut().enumerate() {
+        if matches!(el.name.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6") {
+            el.attributes.insert("block".to_string(), Some(index.to_string()));
+        }
+    }
+}
+
+/// Numbers the items of ordered lists, and picks a bullet for unordered lists based on how deeply they are nested
+fn number_lists(els: &mut [ElWrapper], depth: usize) {
+    for el in els {
+        let nested = match el.name.as_str() {
+            "ol" => {
+                let count = el.children.iter().filter(|c| c.name == "li").count();
+                let reversed = el.attributes.contains_key("reversed");
+                let start = el.attribute("start").parse::<i64>().unwrap_or(if reversed { count as i64 } else { 1 });
+
+                for (i, item) in el.children.iter_mut().filter(|c| c.name == "li").enumerate() {
+                    let number = if reversed { start - i as i64 } else { start + i as i64 };
+                    item.attributes.insert("marker".to_string(), Some(format!("{}.", number)));
+                }
+                depth + 1
+            }
+            "ul" => {
+                for item in el.children.iter_mut().filter(|c| c.name == "li") {
+                    item.attributes.insert("marker".to_string(), Some(BULLETS[depth % BULLETS.len()].to_string()));
+                }
+                depth + 1
+            }
+            _ => depth,
+        };
+
+        // Text before a nested list ends with a line break, which would leave a gap above the list
+        if el.name == "li" {
+            for i in 1..el.children.len() {
+                if is_list(&el.children[i]) && el.children[i - 1].name == "TEXT" {
+                    el.children[i - 1].display_text = el.children[i - 1].display_text.trim_end().to_string();
+                }
+            }
+        }
+
+        number_lists(&mut el.children, nested);
+    }
+}
+
+/// Numbers each `<details>` element in document order, so that toggling one can refer to it
+fn index_details(els: &mut [ElWrapper], count: &mut usize) {
+    for el in els {
+        if el.name == "details" {
+            el.attributes.insert("index".to_string(), Some(count.to_string()));
+            *count += 1;
+        }
+
+        index_details(&mut el.children, count);
+    }
+}
+
+/// Numbers footnotes in the order they are first referred to or defined
+fn number_footnotes(els: &mut [ElWrapper]) {
+    fn number(els: &mut [ElWrapper], numbers: &mut Vec<String>) {
+        for el in els {
+            let key = match el.name.as_str() {
+                "footnote-ref" => el.attribute("target"),
+                "footnote" => el.attribute("id"),
+                _ => {
+                    number(&mut el.children, numbers);
+                    continue;
+                }
+            };
+
+            let label = match numbers.iter().position(|k| *k == key) {
+                Some(index) => index + 1,
+                None => {
+                    numbers.push(key);
+                    numbers.len()
+                }
+            };
+            el.attributes.insert("label".to_string(), Some(label.to_string()));
+
+            number(&mut el.children, numbers);
+        }
+    }
+
+    number(els, &mut vec![]);
+}
+
+/// Moves the offsets of the task checkboxes within the given elements, after an edit earlier in the note
+fn shift_tasks(els: &mut [ElWrapper], delta: isize) {
+    let mut inputs = vec![];
+    find_mut(els, &|el| el.name == "input" && el.attributes.contains_key("offset"), &mut inputs);
+
+    for input in inputs {
+        if let Some(offset) = input.attribute("offset").parse::<usize>().ok().and_then(|o| o.checked_add_signed(delta)) {
+            input.attributes.insert("offset".to_string(), Some(offset.to_string()));
+        }
+    }
+}
+
+/// Returns the length in bytes of the start and the end which two strings have in common, without overlapping
+fn common_affixes(a: &str, b: &str) -> (usize, usize) {
+    let mut prefix = a.bytes().zip(b.bytes()).take_while(|(x, y)| x == y).count();
+    while !a.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+
+    let limit = a.len().min(b.len()) - prefix;
+    let mut suffix = a.bytes().rev().zip(b.bytes().rev()).take(limit).take_while(|(x, y)| x == y).count();
+    while !a.is_char_boundary(a.len() - suffix) {
+        suffix -= 1;
+    }
+
+    (prefix, suffix)
+}
+
+/// Where inline text sits relative to the baseline
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Script {
+    #[default]
+    Normal,
+    Super,
+    Sub,
+}
+
+/// The formatting of a run of inline text, accumulated from the elements which enclose it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct InlineStyle {
+    bold: bool,
+    italic: bool,
+    strike: bool,
+    underline: bool,
+    code: bool,
+    kbd: bool,
+    mark: bool,
+    script: Script,
+}
+
+/// A run of text with a single style, which is drawn as one span
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    text: String,
+    style: InlineStyle,
+    link: Option<String>,
+}
+
+/// Flattens inline elements, such as `<em>` or `<kbd>`, into runs of styled text.
+/// Returns `false` if an element cannot be drawn as text, eg: an image
+fn inline_runs(elements: &[ElWrapper], style: InlineStyle, link: Option<&str>, runs: &mut Vec<Run>) -> bool {
+    for el in elements {
+        let mut inner = style;
+        let mut inner_link = link;
+
+        match el.name.as_str() {
+            "TEXT" | "br" | "math" => {
+                let (text, style) = match el.name.as_str() {
+                    "TEXT" => (unescape_html_text(&el.display_text), style),
+                    "br" => ("\n".to_string(), style),
+                    _ if el.attributes.contains_key("display") || el.attributes.contains_key("error") => return false,
+                    _ => (el.attribute("unicode"), InlineStyle { italic: true, ..style }),
+                };
+                runs.push(Run { text, style, link: link.map(|l| l.to_string()) });
+                continue;
+            }
+            "em" | "i" => inner.italic = true,
+            "strong" | "b" => inner.bold = true,
+            "del" | "s" | "strike" => inner.strike = true,
+            "u" | "ins" => inner.underline = true,
+            "code" => inner.code = true,
+            "kbd" => inner.kbd = true,
+            "mark" => inner.mark = true,
+            "sup" => inner.script = Script::Super,
+            "sub" => inner.script = Script::Sub,
+            "a" => inner_link = el.attributes.get("href").and_then(|h| h.as_deref()),
+            _ => return false,
+        }
+
+        if !inline_runs(&el.children, inner, inner_link, runs) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn runs_text(runs: &[Run]) -> String {
+    runs.iter().map(|r| r.text.as_str()).collect()
+}
+
+fn run_span<'a>(run: &Run) -> Span<'a, Message> {
+    let style = run.style;
+    let mut font = build_font(get_a11_font(), if style.bold { Bold } else { Normal });
+    if style.italic {
+        font.style = Style::Italic;
+    }
+
+    let (text, size) = match style.script {
+        Script::Normal => (run.text.clone(), None),
+        Script::Super => script_text(&run.text, math::superscript),
+        Script::Sub => script_text(&run.text, math::subscript),
+    };
+
+    let mut s = span(text)
+        .font(font)
+        .strikethrough(style.strike)
+        .underline(style.underline);
+
+    if let Some(size) = size {
+        s = s.size(size);
+    }
+
+    if style.code {
+        s = s.background(color!(0x1a1a1a))
+            .border(Border { color: color!(0x2a2a2a), width: 2.0, radius: Radius::new(5) })
+            .padding(Padding { top: 2., right: 3., bottom: 2., left: 3. });
+    }
+
+    if style.kbd {
+        s = s.background(color!(0x2a2a2a))
+            .border(Border { color: color!(0x5a5a5a), width: 1.0, radius: Radius::new(3) })
+            .padding(Padding { top: 1., right: 4., bottom: 1., left: 4. });
+    }
+
+    if style.mark {
+        s = s.background(color!(0xfff59d)).color(color!(0x1a1a1a));
+    }
+
+    if let Some(url) = &run.link {
+        s = s.link(Message::new(MessageKind::LinkOpened(Some(url.clone())), None)).color(color!(0x0000fa));
+    }
+
+    s
+}
+
+/// Converts text into unicode superscript or subscript characters, or keeps it and returns a smaller size
+/// if any character has no equivalent
+fn script_text(text: &str, convert: fn(char) -> Option<char>) -> (String, Option<f32>) {
+    match text.chars().map(convert).collect::<Option<String>>() {
+        Some(converted) => (converted, None),
+        None => (text.to_string(), Some(SCRIPT_SIZE)),
+    }
+}
+
+/// Renders elements as a single block of styled text where possible, otherwise rendering each element in turn
+fn styled<'a>(elements: &'a [ElWrapper], style: InlineStyle, assets: &'a AssetManager) -> (Element<'a>, String) {
+    let mut runs = vec![];
+
+    if inline_runs(elements, style, None, &mut runs) {
+        (rich_text(runs.iter().map(run_span).collect::<Vec<_>>()).into(), runs_text(&runs))
+    } else {
+        let (v, t) = greedy_render(elements, assets);
+        (v, t.join(""))
+    }
+}
+
+/// Collects every element matching `predicate`, without descending into the matches
+fn find_mut<'a>(els: &'a mut [ElWrapper], predicate: &dyn Fn(&ElWrapper) -> bool, found: &mut Vec<&'a mut ElWrapper>) {
+    for el in els {
+        if predicate(el) {
+            found.push(el);
+        } else {
+            find_mut(&mut el.children, predicate, found);
+        }
+    }
+}
+
+/// Returns the language and source of a `pre` element which contains a diagram, eg: a ```` ```mermaid ```` code block
+fn diagram_source(el: &ElWrapper) -> Option<(String, String)> {
+    code_source(el).filter(|(lang, _)| diagrams::is_diagram(lang))
+}
+
+/// Returns the language and source of a `pre` element which contains a fenced code block tagged with a language
+fn code_source(el: &ElWrapper) -> Option<(String, String)> {
+    if el.name != "pre" {
+        return None;
+    }
+
+    let code = el.children.first().filter(|c| c.name == "code")?;
+    let lang = code.attributes.get("class")
+        .cloned()
+        .flatten()?
+        .split_whitespace()
+        .find_map(|class| class.strip_prefix("language-").map(|l| l.to_string()))?;
+
+    let source = code.children.iter()
+        .map(|c| html_escape::decode_html_entities(&c.display_text).to_string())
+        .collect::<String>();
+
+    Some((lang, source))
+}
+
+/// Splits text into plain text and citations, eg: `see [@smith2019, p. 4]`
+fn split_citations(content: &str) -> Vec<ElWrapper> {
+    let mut els = vec![];
+    let mut last = 0;
+
+    for caps in CITATION.captures_iter(content) {
+        let whole = caps.get(0).unwrap();
+        let items: Option<Vec<ElWrapper>> = caps[1].split(';')
+            .map(|item| {
+                let item = html_escape::decode_html_entities(item.trim()).to_string();
+                let item = CITATION_ITEM.captures(&item)?;
+                let mut attributes = vec![("key", item[1].to_string())];
+                if let Some(locator) = item.get(2) {
+                    attributes.push(("locator", locator.as_str().trim().to_string()));
+                }
+                Some(ElWrapper::synthetic("cite-item", attributes, vec![]))
+            })
+            .collect();
+
+        // Anything which is not a well formed list of citations is left as text
+        let Some(items) = items else { continue };
+
+        if whole.start() > last {
+            els.push(ElWrapper::text(&content[last..whole.start()]));
+        }
+        els.push(ElWrapper::synthetic("cite", vec![], items));
+        last = whole.end();
+    }
+
+    if last < content.len() || els.is_empty() {
+        els.push(ElWrapper::text(&content[last..]));
+    }
+
+    els
+}
+
+/// Returns the TeX source of a math span and whether it is a display equation, eg: `$$x^2$$`
+fn math_source(el: &ElWrapper) -> Option<(String, bool)> {
+    if el.name != "span" {
+        return None;
+    }
+
+    let classes = el.attributes.get("class").cloned().flatten()?;
+    let mut classes = classes.split_whitespace();
+
+    if !classes.clone().any(|class| class == "math") {
+        return None;
+    }
+
+    let display = classes.any(|class| class == "math-display");
+    let tex = el.children.iter()
+        .map(|c| html_escape::decode_html_entities(&c.display_text).to_string())
+        .collect::<String>();
+
+    Some((tex, display))
+}
+
+/// Renders the contents of a `code` element as a preformatted block
+fn code_block<'a>(code: &ElWrapper) -> Element<'a> {
+    container(
+        rich_text(
+            code.children.iter().map(|c| {
+                a11_mono(unescape_html_text(c.display_text.as_str()))
+            }).collect::<Vec<Span<Message>>>()
+        )
+    )
+    .padding(5)
+    .width(Length::Fill)
+    .style(|_| {
+        container::Style {
+            background: Some(iced::Background::Color(color!(0x1a1a1a))),
+            border: iced::Border {
+                color: color!(0x2a2a2a),
+                width: 2.,
+                radius: Radius::new(5)
+            },
+            .. Default::default()
+        }
+    })
+    .into()
+}
+
+/// Renders a placeholder for an image which could not be loaded
+fn broken_image<'a>(reason: String) -> Element<'a> {
+    container(
+        row!(
+            iced::widget::text(material_icons::icon_to_char(Icon::BrokenImage)).font(FONT_ICON).size(18),
+            horizontal_space().width(5.),
+            a11_text(reason)
+        )
+    )
+    .padding(5)
+    .width(Length::Fill)
+    .style(|_| {
+        container::Style {
+            border: iced::Border {
+                color: color!(0xa30000),
+                width: 2.,
+                radius: Radius::new(5)
+            },
+            .. Default::default()
+        }
+    })
+    .into()
+}
+
+
 fn unescape_html_text(src: &str) -> String {
     let after: String = html_escape::decode_html_entities(src).into();
     after.replace("\t", " ")
 }
 
 
-fn greedy_render_aligned(elements: &[ElWrapper], align: Horizontal) -> (Element, Vec<String>) {
+fn greedy_render_aligned<'a>(elements: &'a [ElWrapper], align: Horizontal, assets: &'a AssetManager) -> (Element<'a>, Vec<String>) {
     let mut col = vec![];
     let mut spans = vec![];
     let mut inline_elems = vec![];
     let mut text_elems = vec![];
 
     for element in elements {
-        let (view, tts) = element.view(None);
+        let (view, tts) = element.view(None, assets);
         match view {
             Render::Element(e, inline) => {
                 col.push(rich_text(spans).align_x(align).into());
@@ -485,6 +1556,7 @@ fn greedy_render_aligned(elements: &[ElWrapper], align: Horizontal) -> (Element,
                 }
             }
             Render::Span(s) => spans.push(s.into()),
+            Render::Spans(s) => spans.extend(s),
         }
         text_elems.push(tts);
     }
@@ -501,7 +1573,7 @@ fn greedy_render_aligned(elements: &[ElWrapper], align: Horizontal) -> (Element,
     )
 }
 
-fn greedy_render(elements: &[ElWrapper]) -> (Element, Vec<String>) {
+fn greedy_render<'a>(elements: &'a [ElWrapper], assets: &'a AssetManager) -> (Element<'a>, Vec<String>) {
     let mut col = vec![];
     let mut spans = vec![];
     let mut inline_elems = vec![];
@@ -511,10 +1583,10 @@ fn greedy_render(elements: &[ElWrapper]) -> (Element, Vec<String>) {
     let mut index = 0;
 
     for element in elements {
-        let (view, tts) = element.view(None);
+        let (view, tts) = element.view(None, assets);
 
         if element.name == "h1" && col.len() > 0 {
-            let (v, _) = element.view(Some(text_since_h1));
+            let (v, _) = element.view(Some(text_since_h1), assets);
             col[last_header_index+1] = match v {
                 Render::Element(e, _) => e.into(),
                 _ => unreachable!(),
@@ -539,6 +1611,7 @@ fn greedy_render(elements: &[ElWrapper]) -> (Element, Vec<String>) {
                 }
             }
             Render::Span(s) => spans.push(s.into()),
+            Render::Spans(s) => spans.extend(s),
         }
         // dbg!(&text_since_h1, &last_header_index);
         index +=1;
@@ -556,24 +1629,37 @@ fn greedy_render(elements: &[ElWrapper]) -> (Element, Vec<String>) {
 }
 
 fn greedy_text(elements: &[ElWrapper]) -> String {
-    elements.iter().map(|c| unescape_html_text(c.display_text.as_str()))
-        .collect::<Vec<String>>()
-        .join(" ")
+    elements.iter()
+        .map(|c| match c.name.as_str() {
+            "TEXT" => unescape_html_text(c.display_text.as_str()),
+            "math" => c.attributes.get("unicode").cloned().flatten().unwrap_or_default(),
+            "br" => "\n".to_string(),
+            _ => greedy_text(&c.children),
+        })
+        .collect()
 }
 
 
-fn heading<'a>(text: String, size: f32, tts: String) -> Render<'a> {
-    // dbg!(&text, size, &tts);
+fn heading<'a>(title: String, size: f32, block: Option<usize>) -> Render<'a> {
+    let heading = a11_heading(title, size).width(Length::Fill);
+
+    // Only top level headings know where they are in the note, so nested ones cannot be listened to
+    let Some(block) = block else {
+        return Render::Element(heading.into(), false);
+    };
+
     Render::Element(
-        // row!(
-        //     tooltip(
-        //         RichButton::new_with_icon(Icon::PlayArrow, "").width(40.).on_press(Message::say(tts.clone())),
-        //         "Listen to this section",
-        //         Position::Top
-        //     ),
-        //     horizontal_space().width(10.),
-            a11_heading(text, size).width(Length::Fill).into(),
-        // ).width(Length::Fill).into(),
+        row!(
+            tooltip(
+                RichButton::new_with_icon(Icon::PlayArrow, "")
+                    .width(40.)
+                    .on_press(Message::new(MessageKind::EditorInput(EditorMessageKind::ReadFrom(block)), None)),
+                text(t!("windows.editor.read-aloud.section").to_string()),
+                Position::Top
+            ),
+            horizontal_space().width(10.),
+            heading,
+        ).width(Length::Fill).align_y(Vertical::Center).into(),
         false
     )
 }
@@ -593,6 +1679,14 @@ fn a11_span<'a, T: Into<String>>(t: T) -> Span<'a, Message> {
         .font(build_font(font_name, Normal))
 }
 
+/// Inline equations are set in italics, so they stand apart from the surrounding text
+fn a11_math<'a, T: Into<String>>(t: T) -> Span<'a, Message> {
+    let font_name = get_a11_font();
+
+    span(t.into())
+        .font(Font { style: Style::Italic, ..build_font(font_name, Normal) })
+}
+
 fn a11_text<'a, T: Into<String>>(t: T) -> Rich<'a, Message> {
     let font_name = get_a11_font();
 
@@ -628,4 +1722,9 @@ fn build_font(name: String, weight: Weight) -> Font {
         stretch: Default::default(),
         style: Style::Normal,
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod benches;
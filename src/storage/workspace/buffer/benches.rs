@@ -0,0 +1,65 @@
+//! Benchmarks comparing rendering the whole document against rendering only the blocks in view.
+//!
+//! These are ignored by default, run them with `cargo test --release benches -- --ignored --nocapture`.
+//! Rendering reads the appearance settings, so they need to run within a desktop session.
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+use super::*;
+
+const ITERATIONS: u32 = 5;
+
+/// Builds a meeting archive style note, with the given number of meetings
+fn archive(meetings: usize) -> String {
+    let mut note = String::new();
+
+    for i in 0..meetings {
+        let _ = write!(
+            note,
+            "## Meeting {i}\n\nAttendees: *Alex*, **Sam** and `ops`\n\n\
+            - [ ] Follow up on item {i} #work\n- [x] Send the notes\n  - Shared in the team channel\n\n\
+            > Decided to ship on time, see [the plan](https://example.com/{i})\n\n\
+            | Owner | Task |\n|---|---|\n| Alex | Review |\n| Sam | Deploy |\n\n"
+        );
+    }
+
+    note
+}
+
+fn time<T>(f: impl Fn() -> T) -> Duration {
+    // Warm up caches such as the font setting before timing
+    drop(f());
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        drop(f());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn compare(meetings: usize) {
+    let buffer = Buffer::from_md("bench".to_string(), "bench".to_string(), "noot://internal/bench", archive(meetings));
+    let assets = AssetManager::new();
+    let window = Id::unique();
+
+    let full = time(|| greedy_render(&buffer.doc, &assets).0);
+    let top = time(|| buffer.view(&assets, Viewport::default(), None, window));
+    let middle = time(|| buffer.view(&assets, Viewport { offset: buffer.heights.total() / 2., ..Viewport::default() }, None, window));
+
+    println!(
+        "{} blocks: full render {:?}, virtualized {:?} at the top and {:?} in the middle",
+        buffer.doc.len(), full, top, middle
+    );
+}
+
+#[test]
+#[ignore]
+fn render_small_document() {
+    compare(10);
+}
+
+#[test]
+#[ignore]
+fn render_large_document() {
+    compare(500);
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use log::Level;
+use mlua::{FromLua, IntoLua, Lua, Table, Value};
+use url::Url;
+use crate::plugins::{console, PluginError};
+use crate::plugins::runtime::limited;
+use crate::plugins::runtime::markdown::{RenderHook, RENDER_HOOKS};
+use super::ElWrapper;
+
+/// Elements which are drawn from their first child, so cannot be given to the view without one
+const PARENT_ELEMENTS: [&str; 3] = ["pre", "a", "thead"];
+
+impl IntoLua for ElWrapper {
+    /// Elements are given to plugins as `{ name, attributes, children }`, and text as `{ name = "TEXT", text }`.
+    /// Attributes without a value, such as `checked`, are `true`
+    fn into_lua(self, lua: &Lua) -> mlua::Result<Value> {
+        let table = lua.create_table()?;
+        table.set("name", self.name.as_str())?;
+        if self.name == "TEXT" {
+            table.set("text", html_escape::decode_html_entities(&self.display_text).to_string())?;
+        }
+
+        let attributes = lua.create_table()?;
+        for (key, value) in self.attributes {
+            match value {
+                Some(value) => attributes.set(key, value)?,
+                None => attributes.set(key, true)?,
+            }
+        }
+
+        table.set("attributes", attributes)?;
+        table.set("children", lua.create_sequence_from(self.children)?)?;
+        Ok(Value::Table(table))
+    }
+}
+
+impl FromLua for ElWrapper {
+    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+        let Value::Table(table) = value else {
+            return Err(mlua::Error::FromLuaConversionError {
+                from: value.type_name(),
+                to: "element".to_string(),
+                message: Some("expected a table with a name".to_string()),
+            });
+        };
+
+        let name: String = table.get("name")?;
+        if name == "TEXT" {
+            return Ok(ElWrapper::text(&html_escape::encode_text(&table.get::<String>("text")?)));
+        }
+
+        let mut attributes = HashMap::new();
+        if let Some(table) = table.get::<Option<Table>>("attributes")? {
+            for pair in table.pairs::<String, Value>() {
+                match pair? {
+                    (_, Value::Nil | Value::Boolean(false)) => {}
+                    (key, Value::Boolean(true)) => { attributes.insert(key, None); }
+                    (key, value) => { attributes.insert(key, Some(String::from_lua(value, lua)?)); }
+                }
+            }
+        }
+
+        let children: Vec<ElWrapper> = table.get::<Option<Vec<ElWrapper>>>("children")?.unwrap_or_default();
+        if children.is_empty() && PARENT_ELEMENTS.contains(&name.as_str()) {
+            return Err(mlua::Error::runtime(format!("'{}' elements must have children", name)));
+        }
+
+        Ok(ElWrapper { name, attributes, children, display_text: String::new() })
+    }
+}
+
+/// What a plugin draws in place of a code block, built from a list of text, image and table items
+struct Rendered(Vec<ElWrapper>);
+
+impl FromLua for Rendered {
+    fn from_lua(value: Value, lua: &Lua) -> mlua::Result<Self> {
+        let mut blocks = vec![];
+        let mut paragraph = vec![];
+
+        for item in Vec::<Table>::from_lua(value, lua)? {
+            let kind: String = item.get("type")?;
+            let block = match kind.as_str() {
+                "text" => {
+                    paragraph.push(text_item(&item)?);
+                    continue;
+                }
+                "image" => {
+                    let src: String = item.get("src")?;
+                    let alt = item.get::<Option<String>>("alt")?.unwrap_or_default();
+                    ElWrapper::synthetic("p", vec![], vec![ElWrapper::synthetic("img", vec![("src", src), ("alt", alt)], vec![])])
+                }
+                "table" => table_item(&item)?,
+                other => return Err(mlua::Error::runtime(format!("Unknown item type '{}', expected one of: text, image, table", other))),
+            };
+
+            if !paragraph.is_empty() {
+                blocks.push(ElWrapper::synthetic("p", vec![], std::mem::take(&mut paragraph)));
+            }
+            blocks.push(block);
+        }
+
+        if !paragraph.is_empty() {
+            blocks.push(ElWrapper::synthetic("p", vec![], paragraph));
+        }
+
+        Ok(Rendered(blocks))
+    }
+}
+
+/// Builds a run of text, wrapped in the elements for each style it is given
+fn text_item(item: &Table) -> mlua::Result<ElWrapper> {
+    let mut el = ElWrapper::text(&html_escape::encode_text(&item.get::<String>("text")?));
+
+    for (style, name) in [("code", "code"), ("strike", "del"), ("italic", "em"), ("bold", "strong")] {
+        if item.get::<Option<bool>>(style)?.unwrap_or(false) {
+            el = ElWrapper::synthetic(name, vec![], vec![el]);
+        }
+    }
+
+    if let Some(href) = item.get::<Option<String>>("link")? {
+        el = ElWrapper::synthetic("a", vec![("href", href)], vec![el]);
+    }
+
+    Ok(el)
+}
+
+/// Builds a table in the same shape as a markdown table, whose cells are either strings or lists of text items
+fn table_item(item: &Table) -> mlua::Result<ElWrapper> {
+    let row = |cells: Vec<Value>, name: &str| -> mlua::Result<ElWrapper> {
+        let cells = cells.into_iter().map(|cell| {
+            let children = match cell {
+                Value::Table(items) => items.sequence_values::<Table>().map(|item| text_item(&item?)).collect::<mlua::Result<_>>()?,
+                value => vec![ElWrapper::text(&html_escape::encode_text(&value.to_string()?))],
+            };
+            Ok(ElWrapper::synthetic(name, vec![], children))
+        }).collect::<mlua::Result<_>>()?;
+
+        Ok(ElWrapper::synthetic("tr", vec![], cells))
+    };
+
+    let mut sections = vec![];
+    if let Some(head) = item.get::<Option<Vec<Value>>>("head")? {
+        sections.push(ElWrapper::synthetic("thead", vec![], vec![row(head, "th")?]));
+    }
+
+    let rows = item.get::<Vec<Vec<Value>>>("rows")?.into_iter().map(|cells| row(cells, "td")).collect::<mlua::Result<_>>()?;
+    sections.push(ElWrapper::synthetic("tbody", vec![], rows));
+
+    Ok(ElWrapper::synthetic("table", vec![], sections))
+}
+
+/// Prepares a tree built by a plugin to be drawn, as its elements were not added by the parser
+fn finish_tree(mut el: ElWrapper, workspace: &str, base: &Url) -> ElWrapper {
+    el.children = el.children.into_iter().map(|child| finish_tree(child, workspace, base)).collect();
+    el.finish(workspace, base, true)
+}
+
+/// Runs the transforms plugins have registered over each top level block of a note.
+/// Blocks which a transform fails on are left as they were, and the error is shown in the developer console
+pub fn transform(els: Vec<ElWrapper>, workspace: &str, base: &Url) -> Vec<ElWrapper> {
+    let transforms = RENDER_HOOKS.lock().unwrap().transforms();
+    if transforms.is_empty() {
+        return els;
+    }
+
+    // The plugins which ran out of time, whose transforms are skipped for the rest of the note
+    let mut stopped: Vec<&str> = vec![];
+
+    els.into_iter().map(|mut el| {
+        for hook in &transforms {
+            if stopped.contains(&hook.plugin.as_str()) {
+                continue;
+            }
+
+            match limited(|| hook.function.call::<Option<ElWrapper>>(el.clone())) {
+                Ok(Some(transformed)) => el = finish_tree(transformed, workspace, base),
+                Ok(None) => {}
+                Err(e) => {
+                    if failed(hook, format!("Unable to transform '{}'", el.name), e) {
+                        stopped.push(&hook.plugin);
+                    }
+                }
+            }
+        }
+        el
+    }).collect()
+}
+
+/// Renders a code block with the plugin which claimed its language, returning `None` if no plugin claimed it, the
+/// plugin chose not to render it, or the plugin failed to
+pub fn code_block(lang: &str, source: &str, workspace: &str, base: &Url) -> Option<ElWrapper> {
+    let hook = RENDER_HOOKS.lock().unwrap().code_block(lang)?;
+
+    match limited(|| hook.function.call::<Option<Rendered>>((source, lang))) {
+        Ok(rendered) => rendered.map(|Rendered(blocks)| {
+            let el = ElWrapper::synthetic("div", vec![("class", format!("plugin-block language-{}", lang)), ("plugin", hook.plugin)], blocks);
+            finish_tree(el, workspace, base)
+        }),
+        Err(e) => {
+            failed(&hook, format!("Unable to render a '{}' code block", lang), e);
+            None
+        }
+    }
+}
+
+/// Shows why a hook failed in the developer console. The hooks of a plugin which ran out of time are removed, so that
+/// it cannot hold up rendering again, in which case `true` is returned
+fn failed(hook: &RenderHook, action: String, error: PluginError) -> bool {
+    console::push(&hook.plugin, Level::Error, format!("{}: {}", action, error));
+
+    let timed_out = matches!(error, PluginError::TimedOut);
+    if timed_out {
+        RENDER_HOOKS.lock().unwrap().remove_plugin(&hook.plugin);
+    }
+    timed_out
+}
@@ -0,0 +1,429 @@
+use std::ops::Range;
+use pulldown_cmark::{Alignment, CodeBlockKind, Event, LinkType, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use url::Url;
+use super::{hooks, split_citations, ElWrapper};
+
+lazy_static!(
+    /// A tag or comment within raw HTML, eg: `<kbd>`, `</details>`, `<br/>` or `<!-- hidden -->`
+    static ref HTML_TAG: Regex = Regex::new(r#"(?s)<!--.*?-->|<(/?)([a-zA-Z][a-zA-Z0-9-]*)((?:\s+[^\s"'>/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'=<>`]+))?)*)\s*(/?)>"#).unwrap();
+    static ref HTML_ATTRIBUTE: Regex = Regex::new(r#"([^\s"'>/=]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#).unwrap();
+);
+
+/// Elements which never have children, so are closed as soon as they are opened
+const VOID_ELEMENTS: [&str; 6] = ["br", "hr", "img", "input", "col", "wbr"];
+
+pub fn options() -> Options {
+    let mut options = Options::empty();
+
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_TASKLISTS);
+    options.insert(Options::ENABLE_SUBSCRIPT);
+    options.insert(Options::ENABLE_SUPERSCRIPT);
+    options.insert(Options::ENABLE_WIKILINKS);
+    options.insert(Options::ENABLE_GFM);
+    options.insert(Options::ENABLE_DEFINITION_LIST);
+    options.insert(Options::ENABLE_MATH);
+    options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
+
+    options
+}
+
+/// The top level blocks parsed from some markdown
+#[derive(Debug, Default)]
+pub struct Parsed {
+    pub els: Vec<ElWrapper>,
+    /// The range of the source each element was parsed from.
+    /// Elements parsed from the same block, such as the paragraphs of a raw HTML block, share a range
+    pub ranges: Vec<Range<usize>>,
+    pub metadata: String,
+    /// The end of the YAML front matter, or zero if the note has none
+    pub front_matter: usize,
+}
+
+/// An element which is still having children added to it
+struct Open {
+    el: ElWrapper,
+    /// Whether the element was opened by a raw HTML tag, rather than by the markdown
+    raw: bool,
+}
+
+/// Builds elements from the events of the markdown parser, keeping the same shapes as the equivalent HTML
+struct Builder<'a> {
+    workspace: &'a str,
+    base: &'a Url,
+    /// Whether the hooks plugins have registered are run over the elements
+    hooks: bool,
+    note: Option<String>,
+    /// The elements which have been opened, with the root of the document first
+    stack: Vec<Open>,
+    /// Text which has not been added to the innermost element yet, so that adjacent text is joined
+    text: String,
+    parsed: Parsed,
+    /// The start of the top level block currently being parsed
+    block: Option<usize>,
+    in_metadata: bool,
+    in_table_head: bool,
+    alignments: Vec<Alignment>,
+    cell: usize,
+    /// The raw HTML of the block being parsed, as an HTML block is split into an event per line
+    html: Option<String>,
+}
+
+/// Parses markdown into top level elements, `offset` being where the source starts within the note
+pub fn parse(source: &str, offset: usize, workspace: &str, base: &Url, hooks: bool) -> Parsed {
+    let mut builder = Builder {
+        workspace,
+        base,
+        hooks,
+        note: base.to_file_path().ok().map(|note| note.to_string_lossy().to_string()),
+        stack: vec![Open { el: ElWrapper::synthetic("ROOT", vec![], vec![]), raw: false }],
+        text: String::new(),
+        parsed: Parsed::default(),
+        block: None,
+        in_metadata: false,
+        in_table_head: false,
+        alignments: vec![],
+        cell: 0,
+        html: None,
+    };
+
+    let mut depth = 0;
+
+    for (event, range) in Parser::new_ext(source, options()).into_offset_iter() {
+        let range = range.start + offset..range.end + offset;
+
+        if builder.block.is_none() {
+            builder.block = Some(range.start);
+        }
+
+        match event {
+            Event::Start(tag) => {
+                depth += 1;
+                builder.start(tag, &range);
+            }
+            Event::End(tag) => {
+                depth -= 1;
+                builder.end(tag, &range);
+            }
+            event => builder.event(event, &range),
+        }
+
+        // Raw HTML elements may span several blocks, eg: a `<details>` around some paragraphs
+        if depth == 0 && builder.stack.len() == 1 {
+            builder.end_block(range.end);
+        }
+    }
+
+    while builder.stack.len() > 1 {
+        builder.close();
+    }
+    builder.end_block(offset + source.len());
+
+    // Plugins may change each block once it has been parsed, eg: to link ticket references
+    if hooks {
+        builder.parsed.els = hooks::transform(std::mem::take(&mut builder.parsed.els), workspace, base);
+    }
+    builder.parsed
+}
+
+impl Builder<'_> {
+    fn start(&mut self, tag: Tag, range: &Range<usize>) {
+        match tag {
+            Tag::Paragraph => self.open("p", vec![]),
+            Tag::Heading { level, id, classes, attrs } => {
+                let mut attributes = vec![];
+                if let Some(id) = id {
+                    attributes.push(("id", id.to_string()));
+                }
+                if !classes.is_empty() {
+                    attributes.push(("class", classes.join(" ")));
+                }
+
+                self.open(&level.to_string(), attributes);
+                for (key, value) in attrs {
+                    self.top().attributes.insert(key.to_string(), value.map(|v| v.to_string()));
+                }
+            }
+            Tag::BlockQuote(_) => self.open("blockquote", vec![]),
+            Tag::CodeBlock(kind) => {
+                self.open("pre", vec![]);
+                match kind {
+                    CodeBlockKind::Fenced(info) if !info.split(' ').next().unwrap_or_default().is_empty() => {
+                        self.open("code", vec![("class", format!("language-{}", info.split(' ').next().unwrap_or_default()))])
+                    }
+                    _ => self.open("code", vec![]),
+                }
+            }
+            Tag::HtmlBlock => self.html = Some(String::new()),
+            Tag::List(Some(1)) => self.open("ol", vec![]),
+            Tag::List(Some(start)) => self.open("ol", vec![("start", start.to_string())]),
+            Tag::List(None) => self.open("ul", vec![]),
+            Tag::Item => self.open("li", vec![]),
+            Tag::FootnoteDefinition(label) => self.open("footnote", vec![
+                ("id", format!("fn-{}", label)),
+                ("target", format!("fnref-{}", label)),
+            ]),
+            Tag::DefinitionList => self.open("dl", vec![]),
+            Tag::DefinitionListTitle => self.open("dt", vec![]),
+            Tag::DefinitionListDefinition => self.open("dd", vec![]),
+            Tag::Table(alignments) => {
+                self.alignments = alignments;
+                self.open("table", vec![]);
+            }
+            Tag::TableHead => {
+                self.in_table_head = true;
+                self.cell = 0;
+                self.open("thead", vec![]);
+                self.open("tr", vec![]);
+            }
+            Tag::TableRow => {
+                self.cell = 0;
+                self.open("tr", vec![]);
+            }
+            Tag::TableCell => {
+                let name = if self.in_table_head { "th" } else { "td" };
+                let align = match self.alignments.get(self.cell) {
+                    Some(Alignment::Left) => Some("left"),
+                    Some(Alignment::Center) => Some("center"),
+                    Some(Alignment::Right) => Some("right"),
+                    _ => None,
+                };
+
+                self.open(name, align.map(|align| ("style", format!("text-align: {}", align))).into_iter().collect());
+            }
+            Tag::Emphasis => self.open("em", vec![]),
+            Tag::Strong => self.open("strong", vec![]),
+            Tag::Strikethrough => self.open("del", vec![]),
+            Tag::Superscript => self.open("sup", vec![]),
+            Tag::Subscript => self.open("sub", vec![]),
+            Tag::Link { link_type, dest_url, title, .. } => {
+                let href = match link_type {
+                    LinkType::Email => format!("mailto:{}", dest_url),
+                    _ => dest_url.to_string(),
+                };
+                let mut attributes = vec![("href", href)];
+                if !title.is_empty() {
+                    attributes.push(("title", title.to_string()));
+                }
+                self.open("a", attributes);
+            }
+            Tag::Image { dest_url, title, .. } => {
+                let mut attributes = vec![("src", dest_url.to_string())];
+                if !title.is_empty() {
+                    attributes.push(("title", title.to_string()));
+                }
+                self.open("img", attributes);
+            }
+            Tag::MetadataBlock(_) => {
+                self.in_metadata = true;
+                self.parsed.front_matter = range.end;
+            }
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd, range: &Range<usize>) {
+        match tag {
+            TagEnd::HtmlBlock => {
+                let html = self.html.take().unwrap_or_default();
+                self.raw_html(&html);
+            }
+            TagEnd::MetadataBlock(_) => {
+                self.in_metadata = false;
+                self.parsed.front_matter = range.end;
+            }
+            TagEnd::CodeBlock => {
+                self.close();
+                self.close();
+            }
+            TagEnd::TableHead => {
+                self.close();
+                self.close();
+                self.in_table_head = false;
+                self.open("tbody", vec![]);
+            }
+            TagEnd::Table => {
+                self.close();
+                self.close();
+            }
+            TagEnd::TableCell => {
+                self.cell += 1;
+                self.close();
+            }
+            TagEnd::Image => {
+                // The description of an image is its alt text, rather than content of its own
+                self.flush();
+                let img = self.top();
+                let alt = super::greedy_text(&img.children);
+                img.children.clear();
+                img.attributes.insert("alt".to_string(), Some(alt));
+                self.close();
+            }
+            _ => self.close(),
+        }
+    }
+
+    fn event(&mut self, event: Event, range: &Range<usize>) {
+        match event {
+            Event::Text(text) if self.in_metadata => self.parsed.metadata.push_str(&text),
+            Event::Text(text) => self.text.push_str(&html_escape::encode_text(&text)),
+            Event::Code(code) => self.push(ElWrapper::synthetic("code", vec![], vec![ElWrapper::text(&html_escape::encode_text(&code))])),
+            Event::InlineMath(tex) => self.push(ElWrapper::synthetic("span", vec![("class", "math math-inline".to_string())], vec![ElWrapper::text(&html_escape::encode_text(&tex))])),
+            Event::DisplayMath(tex) => self.push(ElWrapper::synthetic("span", vec![("class", "math math-display".to_string())], vec![ElWrapper::text(&html_escape::encode_text(&tex))])),
+            Event::Html(html) => match &mut self.html {
+                Some(block) => block.push_str(&html),
+                None => self.raw_html(&html),
+            },
+            Event::InlineHtml(html) => self.raw_html(&html),
+            Event::FootnoteReference(label) => self.push(ElWrapper::synthetic("footnote-ref", vec![
+                ("id", format!("fnref-{}", label)),
+                ("target", format!("fn-{}", label)),
+            ], vec![])),
+            Event::SoftBreak => self.text.push('\n'),
+            Event::HardBreak => self.push(ElWrapper::synthetic("br", vec![], vec![])),
+            Event::Rule => self.push(ElWrapper::synthetic("hr", vec![], vec![])),
+            Event::TaskListMarker(checked) => {
+                let mut attributes = vec![
+                    ("type", "checkbox".to_string()),
+                    ("disabled", String::new()),
+                    ("offset", range.start.to_string()),
+                ];
+                if checked {
+                    attributes.push(("checked", String::new()));
+                }
+                // Records where the task came from, so that toggling it can be written back to the note
+                if let Some(note) = &self.note {
+                    attributes.push(("note", note.clone()));
+                }
+                self.push(ElWrapper::synthetic("input", attributes, vec![]));
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the tags of some raw HTML to the tree, with the text between them
+    fn raw_html(&mut self, html: &str) {
+        let mut last = 0;
+
+        for caps in HTML_TAG.captures_iter(html) {
+            let whole = caps.get(0).unwrap();
+            self.raw_text(&html[last..whole.start()]);
+            last = whole.end();
+
+            // Comments have no name
+            let Some(name) = caps.get(2) else { continue };
+            let name = name.as_str().to_lowercase();
+
+            if &caps[1] == "/" {
+                self.close_raw(&name);
+                continue;
+            }
+
+            let mut el = ElWrapper::synthetic(&name, vec![], vec![]);
+            for attribute in HTML_ATTRIBUTE.captures_iter(&caps[3]) {
+                let value = attribute.get(2).or(attribute.get(3)).or(attribute.get(4)).map(|v| v.as_str().to_string());
+                el.attributes.insert(attribute[1].to_lowercase(), value);
+            }
+
+            if VOID_ELEMENTS.contains(&name.as_str()) || &caps[4] == "/" {
+                self.push(el);
+            } else {
+                self.flush();
+                self.stack.push(Open { el, raw: true });
+            }
+        }
+
+        self.raw_text(&html[last..]);
+    }
+
+    /// Adds text from raw HTML, where text which is only whitespace is formatting rather than content
+    fn raw_text(&mut self, text: &str) {
+        if !text.trim().is_empty() {
+            self.text.push_str(text);
+        }
+    }
+
+    fn top(&mut self) -> &mut ElWrapper {
+        &mut self.stack.last_mut().unwrap().el
+    }
+
+    fn open(&mut self, name: &str, attributes: Vec<(&str, String)>) {
+        self.flush();
+        self.stack.push(Open { el: ElWrapper::synthetic(name, attributes, vec![]), raw: false });
+    }
+
+    /// Closes the innermost element opened by the markdown, along with any raw HTML left open within it
+    fn close(&mut self) {
+        self.flush();
+
+        while self.stack.len() > 1 {
+            let open = self.stack.pop().unwrap();
+            let raw = open.raw;
+            self.push(open.el);
+
+            if !raw {
+                break;
+            }
+        }
+    }
+
+    /// Closes a raw HTML element, unless it was never opened within the current markdown element
+    fn close_raw(&mut self, name: &str) {
+        let found = self.stack.iter().rev()
+            .take_while(|open| open.raw)
+            .any(|open| open.el.name == name);
+
+        if !found {
+            return;
+        }
+
+        self.flush();
+        while let Some(open) = self.stack.pop() {
+            let done = open.el.name == name;
+            self.push(open.el);
+
+            if done {
+                break;
+            }
+        }
+    }
+
+    /// Adds a complete element to the innermost open element
+    fn push(&mut self, el: ElWrapper) {
+        self.flush();
+        let el = el.finish(self.workspace, self.base, self.hooks);
+        self.top().children.push(el);
+    }
+
+    /// Adds the pending text to the innermost open element
+    fn flush(&mut self) {
+        if self.text.is_empty() {
+            return;
+        }
+
+        let text = std::mem::take(&mut self.text);
+        let top = self.top();
+
+        // Citations are plain text to the markdown parser, so they are picked out here
+        if matches!(top.name.as_str(), "code" | "pre") {
+            top.children.push(ElWrapper::text(&text));
+        } else {
+            top.children.extend(split_citations(&text));
+        }
+    }
+
+    /// Records the range of the elements added to the root since the last block ended
+    fn end_block(&mut self, end: usize) {
+        self.flush();
+
+        let root = &mut self.stack[0].el;
+        let added = root.children.len();
+
+        if let Some(start) = self.block.take() {
+            self.parsed.els.append(&mut root.children);
+            self.parsed.ranges.extend(std::iter::repeat_n(start..end, added));
+        }
+    }
+}
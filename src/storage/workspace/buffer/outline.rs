@@ -0,0 +1,92 @@
+use std::ops::Range;
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use super::markdown::options;
+
+/// A top level heading of a note, and the section of the note it begins
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub level: usize,
+    pub text: String,
+    /// The range of the note covered by the heading and everything beneath it,
+    /// up until the next heading of the same or a higher level
+    pub range: Range<usize>,
+}
+
+/// Lists the top level headings of a note, in the order they appear
+pub fn sections(content: &str) -> Vec<Section> {
+    let mut sections: Vec<Section> = vec![];
+    let mut heading: Option<Section> = None;
+    let mut depth = 0;
+
+    for (event, range) in Parser::new_ext(content, options()).into_offset_iter() {
+        match &event {
+            Event::Start(Tag::Heading { level, .. }) if depth == 0 => {
+                heading = Some(Section { level: *level as usize, text: String::new(), range: range.start..content.len() });
+            }
+            Event::End(TagEnd::Heading(_)) if depth == 1 => {
+                sections.extend(heading.take());
+            }
+            Event::Text(text) | Event::Code(text) | Event::InlineMath(text) => {
+                if let Some(heading) = &mut heading {
+                    heading.text.push_str(text);
+                }
+            }
+            _ => {}
+        }
+
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            _ => {}
+        }
+    }
+
+    // Each section runs until the next heading which is not nested beneath it
+    for i in 0..sections.len() {
+        if let Some(next) = sections[i + 1..].iter().find(|s| s.level <= sections[i].level) {
+            sections[i].range.end = next.range.start;
+        }
+    }
+
+    sections
+}
+
+/// Moves a section of a note, along with the sections nested beneath it, to where another section is.
+/// Moving a section down places it after the target, and moving it up places it before.
+/// Returns `None` if the section cannot be moved there, eg: onto a section nested beneath it
+pub fn move_section(content: &str, from: usize, to: usize) -> Option<String> {
+    let sections = sections(content);
+    let section = sections.get(from)?.range.clone();
+    let target = sections.get(to)?;
+
+    let insert_at = if target.range.start >= section.end {
+        target.range.end
+    } else if target.range.end <= section.start {
+        target.range.start
+    } else {
+        return None;
+    };
+
+    let mut moved = content[section.clone()].to_string();
+    if !moved.ends_with('\n') {
+        moved.push('\n');
+    }
+
+    let mut updated = String::with_capacity(content.len() + 1);
+    updated.push_str(&content[..section.start]);
+    updated.push_str(&content[section.end..]);
+
+    let insert_at = if insert_at > section.start { insert_at - section.len() } else { insert_at };
+
+    if insert_at < updated.len() {
+        updated.insert_str(insert_at, &moved);
+    } else {
+        // The last line of a note may not have a line break, which the moved section would be joined on to
+        if !updated.is_empty() && !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push_str(&moved);
+    }
+
+    Some(updated)
+}
@@ -0,0 +1,346 @@
+//! Snapshot tests for the markdown renderer.
+//!
+//! Each test renders some markdown and compares an outline of the resulting elements, and the styled
+//! text runs they are drawn with, against `snapshots/<name>.snap`.
+//! Set `NOOT_UPDATE_SNAPSHOTS=1` to write the snapshots again after an intended change.
+
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+use nanoid::nanoid;
+use super::*;
+
+fn outline(els: &[ElWrapper], depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+
+    for el in els {
+        let mut runs = vec![];
+
+        if inline_runs(std::slice::from_ref(el), InlineStyle::default(), None, &mut runs) {
+            for run in runs {
+                let style = run.style;
+                let flags = [
+                    (style.bold, "bold"), (style.italic, "italic"), (style.strike, "strike"), (style.underline, "underline"),
+                    (style.code, "code"), (style.kbd, "kbd"), (style.mark, "mark"),
+                    (style.script == Script::Super, "super"), (style.script == Script::Sub, "sub"),
+                ];
+                let mut line = format!("{}{:?}", indent, run.text);
+                for (_, flag) in flags.iter().filter(|(set, _)| *set) {
+                    let _ = write!(line, " {}", flag);
+                }
+                if let Some(link) = run.link {
+                    let _ = write!(line, " link={}", link);
+                }
+                let _ = writeln!(out, "{}", line);
+            }
+            continue;
+        }
+
+        let mut attributes: Vec<(&String, &Option<String>)> = el.attributes.iter().collect();
+        attributes.sort();
+
+        let _ = write!(out, "{}<{}", indent, el.name);
+        for (key, value) in attributes {
+            match value {
+                Some(value) => { let _ = write!(out, " {}={:?}", key, value); }
+                None => { let _ = write!(out, " {}", key); }
+            }
+        }
+        let _ = writeln!(out, ">");
+
+        outline(&el.children, depth + 1, out);
+    }
+}
+
+fn render(markdown: &str) -> Buffer {
+    Buffer::from_md("test".to_string(), "test".to_string(), "noot://internal/test", markdown.to_string())
+}
+
+fn assert_snapshot(name: &str, markdown: &str) {
+    let mut actual = String::new();
+    outline(&render(markdown).doc, 0, &mut actual);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/storage/workspace/buffer/snapshots")
+        .join(format!("{}.snap", name));
+
+    if std::env::var("NOOT_UPDATE_SNAPSHOTS").is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &actual).unwrap();
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing snapshot {}, set NOOT_UPDATE_SNAPSHOTS=1 to create it", path.display()));
+
+    assert_eq!(expected, actual, "Snapshot '{}' has changed", name);
+}
+
+#[test]
+fn headings() {
+    assert_snapshot("headings", "# One\n## Two\n### Three\n#### Four\n##### Five\n###### Six\n");
+}
+
+#[test]
+fn blockquote() {
+    assert_snapshot("blockquote", "> A quote with *emphasis*\n>\n> > And a nested quote\n");
+}
+
+#[test]
+fn emphasis() {
+    assert_snapshot("emphasis", "Some *em*, **strong**, ***both*** and ~~deleted~~ text, with <u>underline</u>\n");
+}
+
+#[test]
+fn scripts() {
+    assert_snapshot("scripts", "x<sup>2</sup> and H<sub>2</sub>O, with a ^note^ which has no unicode form\n");
+}
+
+#[test]
+fn inline_html() {
+    assert_snapshot("inline_html", "Press <kbd>Ctrl</kbd> <kbd>C</kbd> to copy <mark>this text</mark><br>on a new line\n");
+}
+
+#[test]
+fn inline_code_and_links() {
+    assert_snapshot("inline_code_and_links", "Run `cargo test` or read [the **docs**](https://example.com)\n");
+}
+
+#[test]
+fn ordered_lists() {
+    assert_snapshot("ordered_lists", "1. One\n2. Two\n\n---\n\n7. Seven\n8. Eight\n\n<ol reversed><li>Three</li><li>Two</li><li>One</li></ol>\n");
+}
+
+#[test]
+fn nested_lists() {
+    assert_snapshot("nested_lists", "- One\n  - Two\n    - Three\n      - Four\n- Five\n  1. Six\n  2. Seven\n");
+}
+
+#[test]
+fn task_lists() {
+    assert_snapshot("task_lists", "- [ ] Open\n- [x] Done\n  - [ ] Nested\n");
+}
+
+#[test]
+fn definition_lists() {
+    assert_snapshot("definition_lists", "Term\n: The *definition*\n\nAnother term\n: First definition\n: Second definition\n");
+}
+
+#[test]
+fn details() {
+    assert_snapshot("details", "<details>\n<summary>More <em>info</em></summary>\n\nHidden body\n\n</details>\n\n<details open>\n<summary>Open</summary>\n\nShown body\n\n</details>\n");
+}
+
+#[test]
+fn comments_are_skipped() {
+    assert_snapshot("comments", "Before <!-- hidden --> after\n");
+}
+
+#[test]
+fn toggle_details() {
+    let mut buffer = render("<details>\n<summary>One</summary>\n\nBody\n\n</details>\n\n<details open>\n<summary>Two</summary>\n\nBody\n\n</details>\n");
+    let open = |buffer: &Buffer| -> Vec<bool> {
+        buffer.doc.iter().filter(|el| el.name == "details").map(|el| el.attributes.contains_key("open")).collect()
+    };
+
+    assert_eq!(open(&buffer), vec![false, true]);
+
+    buffer.toggle_details(0);
+    buffer.toggle_details(1);
+    assert_eq!(open(&buffer), vec![true, false]);
+}
+
+#[test]
+fn script_text_falls_back_to_a_smaller_size() {
+    assert_eq!(script_text("2", math::superscript), ("²".to_string(), None));
+    assert_eq!(script_text("note", math::superscript), ("note".to_string(), Some(SCRIPT_SIZE)));
+}
+
+#[test]
+fn only_blocks_in_view_are_visible() {
+    let buffer = render(&"A paragraph\n\n".repeat(500));
+    let heights = &buffer.heights;
+    let visible = heights.visible(Viewport { offset: heights.total() / 2., height: 600. });
+
+    assert!(visible.start > 0 && visible.end < buffer.doc.len());
+    assert_eq!(visible.above, heights.top(visible.start));
+    assert_eq!(visible.above + (visible.start..visible.end).map(|i| heights.height(i)).sum::<f32>() + visible.below, heights.total());
+}
+
+#[test]
+fn measuring_blocks_above_the_viewport_shifts_it() {
+    let mut buffer = render(&"A paragraph\n\n".repeat(100));
+    let offset = buffer.heights.top(50);
+    buffer.heights.scrolled(Viewport { offset, height: 600. });
+
+    let height = buffer.heights.height(10);
+    assert_eq!(buffer.heights.measure(10, height + 15.), Some(15.));
+    assert_eq!(buffer.heights.measure(60, 100.), None);
+}
+
+/// Applies an edit to a buffer, checking the result against parsing the edited note from scratch
+fn assert_update(before: &str, after: &str) -> Range<usize> {
+    let mut buffer = render(before);
+    let parsed = buffer.update(after.to_string());
+
+    let (mut actual, mut expected) = (String::new(), String::new());
+    outline(&buffer.doc, 0, &mut actual);
+    outline(&render(after).doc, 0, &mut expected);
+
+    assert_eq!(expected, actual, "Updating {:?} to {:?}", before, after);
+    assert_eq!(buffer.heights.total(), render(after).heights.total());
+    parsed
+}
+
+#[test]
+fn only_edited_blocks_are_parsed_again() {
+    let note = "# Title\n\nOne\n\nTwo\n\nThree\n\nFour\n\n- [ ] Task\n";
+
+    assert_eq!(assert_update(note, &note.replace("Three", "Three and a bit")), 2..5);
+    assert_eq!(assert_update(note, &note.replace("One", "The first")), 0..3);
+    assert_eq!(assert_update(note, &format!("{}\nFive\n", note)), 4..7);
+}
+
+#[test]
+fn edits_which_join_blocks_are_parsed_again() {
+    let note = "One\n\nTwo\n\n- A\n\n- B\n\nThree\n";
+
+    assert_update(note, &note.replace("One\n\nTwo", "One\nTwo"));
+    assert_update(note, &note.replace("Two", "Two\n---"));
+    assert_update(note, &note.replace("- B\n", "- B\n- C\n"));
+    assert_update(note, &note.replace("\n\nThree", "\nThree"));
+}
+
+#[test]
+fn edits_which_change_the_rest_of_the_note_parse_it_all() {
+    let note = "One\n\n<details>\n<summary>Two</summary>\n\nThree\n\n</details>\n\nFour\n\nFive\n";
+
+    assert_eq!(assert_update(note, &note.replace("One", "```\nOne")).len(), 1);
+    assert_update(note, &note.replace("</details>", ""));
+    assert_update(note, &note.replace("Four", "[Four][4]\n\n[4]: https://example.com"));
+    assert_update(note, &note.replace("Three", "Three[^1]\n\n[^1]: A footnote"));
+}
+
+#[test]
+fn tasks_after_an_edit_keep_their_offsets() {
+    let note = "Intro\n\n- [ ] One\n\nMiddle\n\n- [x] Two\n";
+    assert_update(note, &note.replace("Intro", "A longer introduction"));
+    assert_update(note, &note.replace("Middle", ""));
+}
+
+#[test]
+fn outline_lists_top_level_headings() {
+    let note = "# One\n\nIntro\n\n## Two `code`\n\n> # Quoted\n\n## Three\n\n# Four\n";
+    let outline: Vec<(usize, String, &str)> = outline::sections(note).into_iter()
+        .map(|s| (s.level, s.text, &note[s.range]))
+        .collect();
+
+    assert_eq!(outline, vec![
+        (1, "One".to_string(), "# One\n\nIntro\n\n## Two `code`\n\n> # Quoted\n\n## Three\n\n"),
+        (2, "Two code".to_string(), "## Two `code`\n\n> # Quoted\n\n"),
+        (2, "Three".to_string(), "## Three\n\n"),
+        (1, "Four".to_string(), "# Four\n"),
+    ]);
+}
+
+#[test]
+fn moving_a_section_takes_its_subsections() {
+    let note = "# A\n\n## A.1\n\n# B\n\nText\n\n# C";
+
+    assert_eq!(outline::move_section(note, 0, 3).as_deref(), Some("# B\n\nText\n\n# C\n# A\n\n## A.1\n\n"));
+    assert_eq!(outline::move_section(note, 3, 0).as_deref(), Some("# C\n# A\n\n## A.1\n\n# B\n\nText\n\n"));
+    assert_eq!(outline::move_section(note, 2, 1).as_deref(), Some("# A\n\n# B\n\nText\n\n## A.1\n\n# C"));
+    assert_eq!(outline::move_section(note, 0, 1), None);
+    assert_eq!(outline::move_section(note, 0, 9), None);
+}
+
+#[test]
+fn headings_know_which_block_to_read_aloud_from() {
+    let mut buffer = render("# Title\n\nOne\n\n> ## Quoted\n\n## Two\n");
+    let blocks = |buffer: &Buffer| buffer.doc.iter().map(|el| el.block()).collect::<Vec<_>>();
+
+    assert_eq!(blocks(&buffer), [Some(0), None, None, Some(3)]);
+    assert_eq!(buffer.doc[2].children[0].block(), None);
+
+    buffer.update("# Title\n\nOne\n\nMore\n\n> ## Quoted\n\n## Two\n".to_string());
+    assert_eq!(blocks(&buffer), [Some(0), None, None, None, Some(4)]);
+}
+
+#[test]
+fn chapters_begin_at_each_heading() {
+    let mut buffer = render("Intro\n\n# One\n\nFirst\n\n## One.1\n\nNested\n\n# Two\n\nSecond\n");
+    // Speaking is drawn from how the blocks render, which needs the app settings, so it is filled in here instead
+    buffer.tts_segments = buffer.doc.iter().map(|el| greedy_text(std::slice::from_ref(el))).collect();
+    let assets = AssetManager::new();
+
+    let chapters: Vec<(String, String)> = buffer.chapters(&assets, &[]).into_iter().map(|c| (c.title, c.text)).collect();
+    assert_eq!(chapters, vec![
+        ("test".to_string(), "Intro".to_string()),
+        ("One".to_string(), "One\nFirst".to_string()),
+        ("One.1".to_string(), "One.1\nNested".to_string()),
+        ("Two".to_string(), "Two\nSecond".to_string()),
+    ]);
+
+    let chosen: Vec<String> = buffer.chapters(&assets, &[0]).into_iter().map(|c| c.title).collect();
+    assert_eq!(chosen, ["One", "One.1"]);
+}
+
+/// Opens a buffer for a note written to a temporary directory
+fn note(content: &str) -> (PathBuf, Buffer) {
+    let path = std::env::temp_dir().join(format!("noot-save-{}.md", nanoid!(5)));
+    std::fs::write(&path, content).unwrap();
+    let buffer = Buffer::from_md("note.md".to_string(), "workspace".to_string(), Url::from_file_path(&path).unwrap(), content.to_string());
+    (path, buffer)
+}
+
+#[test]
+fn saving_without_changes_leaves_the_note_alone() {
+    let (path, mut buffer) = note("# Loaded\n");
+    std::fs::write(&path, "# Changed elsewhere\n").unwrap();
+
+    assert!(!buffer.is_dirty());
+    assert_eq!(buffer.save().unwrap(), None);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Changed elsewhere\n");
+}
+
+#[test]
+fn saving_writes_changes_made_within_the_app() {
+    let (path, mut buffer) = note("# Loaded\n");
+    buffer.source = "# Edited\n".to_string();
+    buffer.render();
+    assert!(buffer.is_dirty());
+
+    assert_eq!(buffer.save().unwrap(), Some(path.clone()));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Edited\n");
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn saving_refuses_to_overwrite_changes_made_elsewhere() {
+    let (path, mut buffer) = note("# Loaded\n");
+    buffer.source = "# Edited\n".to_string();
+    std::fs::write(&path, "# Changed elsewhere\n").unwrap();
+
+    assert!(buffer.save().is_err());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "# Changed elsewhere\n");
+
+    // Once the buffer is reloaded from the note it matches it again
+    buffer.update("# Changed elsewhere\n".to_string());
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn images_are_inserted_after_the_block_holding_the_cursor() {
+    let note = "# Title\n\nOne\n\nTwo\n";
+    let mut buffer = render(note);
+    assert_eq!(buffer.insertion_point(), note.len());
+
+    buffer.place_cursor(1);
+    assert_eq!(note[..buffer.insertion_point()].trim_end(), "# Title\n\nOne");
+
+    // The cursor stays on its block when the note is rendered again, unless the block is gone
+    buffer.render();
+    assert_eq!(note[..buffer.insertion_point()].trim_end(), "# Title\n\nOne");
+    buffer.update("# Title\n".to_string());
+    assert_eq!(buffer.insertion_point(), "# Title\n".len());
+}
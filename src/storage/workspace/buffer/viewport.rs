@@ -0,0 +1,187 @@
+use std::ops::Range;
+use super::ElWrapper;
+
+/// Space drawn either side of the viewport, so that short scrolls do not reveal undrawn blocks
+const OVERSCAN: f32 = 600.;
+/// The estimated height of a line of text, for blocks which have not been drawn yet
+const LINE_HEIGHT: f32 = 22.;
+/// Roughly how many characters fit on a line of the editor
+const LINE_LENGTH: f32 = 90.;
+/// The estimated height of images, diagrams and display equations
+const MEDIA_HEIGHT: f32 = 200.;
+
+/// The visible area of a buffer, in pixels from the top of the document
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub offset: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        // Until the editor has been scrolled the real height is unknown, so assume a large window
+        Self { offset: 0., height: 1440. }
+    }
+}
+
+/// The blocks which need drawing for a viewport, and the space taken by those which do not
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Visible {
+    pub start: usize,
+    pub end: usize,
+    pub above: f32,
+    pub below: f32,
+}
+
+/// The heights of the top level blocks of a buffer, measured once drawn and estimated until then
+#[derive(Debug, Clone, Default)]
+pub struct HeightCache {
+    estimates: Vec<f32>,
+    measured: Vec<Option<f32>>,
+    /// The first block within the viewport, which the scroll position is kept steady against
+    anchor: usize,
+}
+
+impl HeightCache {
+    pub fn new(doc: &[ElWrapper]) -> Self {
+        let estimates: Vec<f32> = doc.iter().map(estimate).collect();
+
+        Self {
+            measured: vec![None; estimates.len()],
+            estimates,
+            anchor: 0,
+        }
+    }
+
+    /// Keeps the heights measured for an earlier render of the buffer, for each block which looks unchanged
+    pub fn carry_over(&mut self, previous: &HeightCache) {
+        for (i, estimate) in self.estimates.iter().enumerate() {
+            if previous.estimates.get(i) == Some(estimate) {
+                self.measured[i] = previous.measured[i];
+            }
+        }
+        self.anchor = previous.anchor.min(self.estimates.len().saturating_sub(1));
+    }
+
+    /// Replaces the heights of the blocks within `removed` with estimates for the blocks which took their place
+    pub fn splice(&mut self, removed: Range<usize>, added: &[ElWrapper]) {
+        let estimates: Vec<f32> = added.iter().map(estimate).collect();
+
+        self.measured.splice(removed.clone(), vec![None; estimates.len()]);
+        self.estimates.splice(removed, estimates);
+        self.anchor = self.anchor.min(self.len().saturating_sub(1));
+    }
+
+    /// Returns the first block within the viewport
+    pub fn anchor(&self) -> usize {
+        self.anchor
+    }
+
+    fn len(&self) -> usize {
+        self.estimates.len()
+    }
+
+    pub fn height(&self, index: usize) -> f32 {
+        self.measured[index].unwrap_or(self.estimates[index])
+    }
+
+    /// Returns the distance from the top of the document to a block
+    pub fn top(&self, index: usize) -> f32 {
+        (0..index.min(self.len())).map(|i| self.height(i)).sum()
+    }
+
+    pub fn total(&self) -> f32 {
+        self.top(self.len())
+    }
+
+    /// Records the drawn height of a block.
+    /// Returns how far the blocks in view have moved, if the block is above them
+    pub fn measure(&mut self, index: usize, height: f32) -> Option<f32> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let previous = self.height(index);
+        self.measured[index] = Some(height);
+
+        let shift = height - previous;
+        (index < self.anchor && shift.abs() >= 0.5).then_some(shift)
+    }
+
+    /// Moves the anchor to the first block within the viewport
+    pub fn scrolled(&mut self, viewport: Viewport) {
+        let mut y = 0.;
+
+        self.anchor = (0..self.len())
+            .find(|&i| {
+                y += self.height(i);
+                y > viewport.offset
+            })
+            .unwrap_or(self.len().saturating_sub(1));
+    }
+
+    /// Returns the range of blocks which overlap the viewport, including the overscan either side
+    pub fn visible(&self, viewport: Viewport) -> Visible {
+        let top = (viewport.offset - OVERSCAN).max(0.);
+        let bottom = viewport.offset + viewport.height + OVERSCAN;
+
+        let mut y = 0.;
+        let mut start = None;
+        let mut end = self.len();
+        let mut above = 0.;
+
+        for i in 0..self.len() {
+            let height = self.height(i);
+
+            if start.is_none() && y + height > top {
+                start = Some(i);
+                above = y;
+            }
+
+            if y >= bottom {
+                end = i;
+                break;
+            }
+
+            y += height;
+        }
+
+        let start = start.unwrap_or(self.len());
+        if start == self.len() {
+            above = y;
+        }
+
+        Visible {
+            start,
+            end: end.max(start),
+            above,
+            below: (self.total() - self.top(end.max(start))).max(0.),
+        }
+    }
+}
+
+/// Guesses the drawn height of a block from how much text it contains
+fn estimate(el: &ElWrapper) -> f32 {
+    fn count(el: &ElWrapper, chars: &mut usize, lines: &mut usize, media: &mut usize) {
+        match el.name.as_str() {
+            "img" | "diagram" => *media += 1,
+            "math" if el.attributes.contains_key("display") => *media += 1,
+            "br" | "li" | "tr" | "dt" | "dd" => *lines += 1,
+            _ => {}
+        }
+
+        // Generated elements, such as references, keep their text in an attribute
+        *chars += el.display_text.len() + el.attribute("text").len();
+        *lines += el.display_text.matches('\n').count();
+
+        for child in &el.children {
+            count(child, chars, lines, media);
+        }
+    }
+
+    let (mut chars, mut lines, mut media) = (0, 0, 0);
+    count(el, &mut chars, &mut lines, &mut media);
+
+    let lines = (chars as f32 / LINE_LENGTH).ceil().max(lines as f32).max(1.);
+    LINE_HEIGHT * (lines + 1.) + MEDIA_HEIGHT * media as f32
+}
@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::fs::create_dir_all;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, MutexGuard};
 use bincode::{Decode, Encode};
 use chrono::Local;
@@ -15,12 +15,16 @@ use crate::runtime::workers::{Job, JobType};
 use crate::storage::process::structs::setting::Setting;
 use crate::storage::process::structs::workspace::Workspace;
 use crate::utils::cryptography::hashing::hash_str;
-use crate::assets::{AssetManager};
+use crate::assets::{load_blob, resolve_asset, sniff_mime, workspace_key, Asset, AssetManager, AssetPermission, DEFAULT_MEMORY_CAPACITY, DEFAULT_MEMORY_TTL};
+use crate::plugins::DEFAULT_PLUGIN_DIRECTORY;
 use self::buffer::Buffer;
+use self::tasks::{TaskFilter, TaskItem};
 
 const WORKSPACE_SEED: &'static str = include_str!("../../../database/workspace.sql");
 
 pub mod buffer;
+pub mod bibliography;
+pub mod tasks;
 
 #[derive(Debug)]
 pub struct WorkspaceManager {
@@ -64,12 +68,68 @@ pub enum RemoteDataStrategy {
     None,
 
     /// Only fetch remote data from specified domains
+    /// The domains are stored in the `assets.allowed-domains` workspace setting
     AllowSpecific,
 
     /// Fetch all remote data, regardless of domain
     All
 }
 
+#[derive(Encode, Decode, Debug, Copy, Clone, PartialEq)]
+pub enum ImageImportFormat {
+    /// Keep imported images in the format they were provided in
+    Original,
+
+    /// Convert imported images to PNG
+    Png,
+
+    /// Convert imported images to JPEG, this will discard any transparency
+    Jpeg
+}
+
+impl RemoteDataStrategy {
+    /// Returns whether the given URL may be fetched under this strategy
+    pub fn permits(&self, url: &Url, allowed_domains: &[String]) -> bool {
+        match self {
+            RemoteDataStrategy::None => false,
+            RemoteDataStrategy::All => true,
+            RemoteDataStrategy::AllowSpecific => url.host_str()
+                .map(|host| domain_allowed(host, allowed_domains))
+                .unwrap_or(false),
+        }
+    }
+}
+
+impl Default for RemoteDataStrategy {
+    fn default() -> Self {
+        #[cfg(not(feature = "enterprise"))]
+        return RemoteDataStrategy::All;
+        #[cfg(feature = "enterprise")]
+        return RemoteDataStrategy::None;
+    }
+}
+
+/// Reads an asset stored within the `.assets` directory of a workspace
+fn read_disk_asset(id: &str, path: &Path) -> Option<Asset> {
+    match std::fs::read(path) {
+        Ok(bytes) => Some(Asset::from_bytes(id, sniff_mime(&bytes), bytes)),
+        Err(e) => {
+            error!("Failed to read asset '{}': {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Checks a host against an allowlist, where an entry of `example.com` also allows its subdomains
+pub fn domain_allowed(host: &str, allowed_domains: &[String]) -> bool {
+    let host = host.to_lowercase();
+
+    allowed_domains.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches("*.").to_lowercase();
+        !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+    })
+}
+
 
 
 
@@ -91,21 +151,26 @@ impl WorkspaceManager {
         if workspace_dir.exists() && workspace_dir.is_dir() {
             info!("Workspace dir already exists");
             // The workspace is a folder and does exist
-            let noot_dir = workspace_dir.join(".noot");
-            let connection = Connection::open(&noot_dir.join("workspace.db")).unwrap();
+            let connection = open_database(&workspace_dir).unwrap();
 
             temp_lock.store.update_workspace(&source.id, Local::now());
             let queue = temp_lock.queue.clone();
 
-            Ok(Self {
+            let mut mgr = Self {
                 db: connection,
                 source_window: None,
                 source,
-                assets: AssetManager::new(),
+                assets: AssetManager::new().with_queue(queue.clone()),
                 tree: Vec::new(),
                 buffers: Default::default(),
                 queue
-            })
+            };
+
+            let ttl = mgr.get_setting::<u64>("assets.memory.ttl").map(|s| s.value).unwrap_or(DEFAULT_MEMORY_TTL);
+            let capacity = mgr.get_setting::<u64>("assets.memory.capacity").map(|s| s.value).unwrap_or(DEFAULT_MEMORY_CAPACITY);
+            mgr.assets.configure(ttl, capacity);
+
+            Ok(mgr)
         } else if workspace_dir.exists() && !workspace_dir.is_dir() {
             // The workspace exists but is not a folder
             Err(WorkspaceError::WorkspaceInvalid(format!("The path '{}' is not a directory", workspace_dir.display())))
@@ -176,8 +241,15 @@ impl WorkspaceManager {
 
         mgr.set_setting("plugins.enable", false)
             .set_setting("plugins.allow-unpacked", false)
+            .set_setting("plugins.directory", DEFAULT_PLUGIN_DIRECTORY.to_string())
             .set_setting("assets.cache-strategy", AssetCachingStrategy::Blob)
-            .set_setting("assets.fetch-remote", RemoteDataStrategy::All);
+            .set_setting("assets.fetch-remote", RemoteDataStrategy::All)
+            .set_setting("assets.allowed-domains", Vec::<String>::new())
+            .set_setting("assets.allow-external-files", false)
+            .set_setting("assets.memory.ttl", DEFAULT_MEMORY_TTL)
+            .set_setting("assets.memory.capacity", DEFAULT_MEMORY_CAPACITY)
+            .set_setting("assets.import.max-width", 0u32)
+            .set_setting("assets.import.format", ImageImportFormat::Original);
 
 
         Ok(mgr)
@@ -189,6 +261,12 @@ impl WorkspaceManager {
         // TODO: Implement file indexing
         self.queue.push(Job::new(JobType::BuildTree(self.source.id.to_string(), PathBuf::from(&self.source.disk_path), self.source_window.unwrap(), true))).unwrap();
 
+        // Clean up any cached assets which are no longer referenced by a note
+        self.queue.push(Job::new(JobType::CollectGarbage(self.source.id.to_string(), PathBuf::from(&self.source.disk_path)))).unwrap();
+
+        // Build the task index used by the task dashboard
+        self.queue.push(Job::new(JobType::IndexTasks(self.source.id.to_string(), PathBuf::from(&self.source.disk_path), self.source_window.unwrap()))).unwrap();
+
 
         // TODO: Implement asset caching on workspace open
         // TODO: Implement buffer pre-rendering to improve performance
@@ -199,6 +277,7 @@ impl WorkspaceManager {
 
     pub fn set_window_id(&mut self, id: Id) {
         self.source_window = Some(id);
+        self.assets.attach(id);
     }
 
     pub fn open_buffer(&mut self, id: Url) -> WorkspaceResult<()> {
@@ -228,23 +307,207 @@ impl WorkspaceManager {
 
     }
 
+    /// Loads a cached asset into the asset manager so that it can be rendered,
+    /// reading from wherever the workspace caching strategy placed it.
+    pub fn load_asset(&mut self, id: String) {
+        let strategy = self.get_setting::<AssetCachingStrategy>("assets.cache-strategy")
+            .map(|s| s.value)
+            .unwrap_or(AssetCachingStrategy::Blob);
+
+        let asset = match strategy {
+            AssetCachingStrategy::Blob => load_blob(&self.db, &workspace_key(&self.source.id), &id),
+            AssetCachingStrategy::Memory => {
+                // Imported assets have nowhere to be fetched from again, so they are kept on disk
+                let content = resolve_asset(&self.db, &id).unwrap_or(id.clone());
+                let path = asset_directory(PathBuf::from(&self.source.disk_path)).join(content);
+
+                if path.exists() {
+                    read_disk_asset(&id, &path)
+                } else {
+                    warn!("Asset '{}' cannot be loaded, memory cached assets are delivered by the worker", id);
+                    None
+                }
+            }
+            AssetCachingStrategy::Disk => {
+                let content = resolve_asset(&self.db, &id).unwrap_or(id.clone());
+                let path = asset_directory(PathBuf::from(&self.source.disk_path)).join(content);
+                read_disk_asset(&id, &path)
+            }
+        };
+
+        if let Some(asset) = asset {
+            info!("Loaded asset '{}' ({})", id, asset.kind);
+            self.assets.insert(id, asset);
+        } else {
+            self.assets.fail(id, "The asset could not be found in the workspace");
+        }
+    }
+
+    /// Queues a job to import a local image into the workspace, inserting a reference to it at a byte offset within
+    /// the note. The offset is only used while the note still has the content ID `source`, otherwise the reference is
+    /// inserted at the end of the note
+    pub fn import_asset(&mut self, note: PathBuf, file: PathBuf, offset: usize, source: String) {
+        let Some(window) = self.source_window else {
+            error!("Cannot import asset '{}' before the workspace has a window", file.display());
+            return;
+        };
+
+        let job = Job::new(JobType::ImportAsset(self.source.id.clone(), note, file.clone(), offset, source, window));
+
+        if self.queue.push(job).is_err() {
+            error!("Failed to queue import for asset '{}'", file.display());
+        }
+    }
+
+    /// Queues a job to check or uncheck the task at `offset` within a note
+    pub fn toggle_task(&mut self, note: PathBuf, offset: usize, done: bool) {
+        let Some(window) = self.source_window else {
+            error!("Cannot update tasks in '{}' before the workspace has a window", note.display());
+            return;
+        };
+
+        let job = Job::new(JobType::ToggleTask(self.source.id.clone(), note.clone(), offset, done, window));
+
+        if self.queue.push(job).is_err() {
+            error!("Failed to queue task update for '{}'", note.display());
+        }
+    }
+
+    /// Queues a job to move a section of a note to where another section is
+    pub fn move_section(&mut self, note: PathBuf, from: usize, to: usize) {
+        let Some(window) = self.source_window else {
+            error!("Cannot move sections in '{}' before the workspace has a window", note.display());
+            return;
+        };
+
+        let job = Job::new(JobType::MoveSection(self.source.id.clone(), note.clone(), from, to, window));
+
+        if self.queue.push(job).is_err() {
+            error!("Failed to queue section move for '{}'", note.display());
+        }
+    }
+
+    /// Updates the open buffers of a note after it has changed, parsing only the blocks which were edited
+    pub fn reload_buffer(&mut self, note: &Path) {
+        let content = match std::fs::read_to_string(note) {
+            Ok(content) => content,
+            Err(err) => {
+                error!("Failed to reload '{}': {}", note.display(), err);
+                return;
+            }
+        };
+
+        let mut jobs = vec![];
+
+        for buffer in self.buffers.values_mut().filter(|b| b.url.to_file_path().is_ok_and(|path| path == note)) {
+            let parsed = buffer.update(content.clone());
+            info!("Parsed {} of {} blocks in '{}' again", parsed.len(), buffer.doc.len(), buffer.name);
+
+            // Images added by the edit have not been fetched yet
+            if let Some(window) = self.source_window {
+                for src in buffer.block_image_sources(parsed).iter().filter_map(|src| Url::parse(src).ok()) {
+                    jobs.push(Job::new(JobType::CacheAsset(self.source.id.clone(), note.to_path_buf(), src, window, buffer.id.clone(), false)));
+                }
+            }
+        }
+
+        for job in jobs {
+            if self.queue.push(job).is_err() {
+                error!("Failed to queue images for '{}'", note.display());
+            }
+        }
+    }
+
+    /// Renders every open buffer again from its source, eg: after a plugin changed how notes are rendered
+    pub fn render_buffers(&mut self) {
+        for buffer in self.buffers.values_mut() {
+            buffer.render();
+        }
+    }
+
+    /// Returns the open tasks within the workspace which match a filter
+    pub fn tasks(&self, filter: &TaskFilter) -> Vec<TaskItem> {
+        tasks::query(&self.db, filter).unwrap_or_else(|err| {
+            error!("Failed to query tasks: {}", err);
+            vec![]
+        })
+    }
+
+    /// Returns every tag used by an open task within the workspace
+    pub fn task_tags(&self) -> Vec<String> {
+        tasks::tags(&self.db).unwrap_or_else(|err| {
+            error!("Failed to query task tags: {}", err);
+            vec![]
+        })
+    }
+
+    /// Purges expired memory cached assets and queues jobs to fetch them again
+    pub fn refresh_assets(&mut self) {
+        for request in self.assets.purge_expired() {
+            if self.queue.push(request.job(false)).is_err() {
+                error!("Failed to queue refresh for asset '{}'", request.id);
+            }
+        }
+    }
+
+    /// Adds a domain to the workspace allowlist.
+    /// If remote data is disabled entirely, the workspace is switched to only allow listed domains.
+    pub fn allow_domain(&mut self, domain: impl Into<String>) {
+        let domain = domain.into();
+        let mut allowed = self.get_setting::<Vec<String>>("assets.allowed-domains")
+            .map(|s| s.value)
+            .unwrap_or_default();
+
+        if !allowed.contains(&domain) {
+            info!("Adding '{}' to the remote data allowlist", domain);
+            allowed.push(domain);
+        }
+
+        let strategy = self.get_setting::<RemoteDataStrategy>("assets.fetch-remote")
+            .map(|s| s.value)
+            .unwrap_or_default();
+
+        if let RemoteDataStrategy::None = strategy {
+            self.set_setting("assets.fetch-remote", RemoteDataStrategy::AllowSpecific);
+        }
+
+        self.set_setting("assets.allowed-domains", allowed);
+    }
+
+    /// Handles the user's response to a blocked asset by re-queueing the fetch
+    pub fn allow_asset(&mut self, id: String, permission: AssetPermission) {
+        let Some(blocked) = self.assets.unblock(&id) else {
+            warn!("Asset '{}' is not blocked", id);
+            return;
+        };
+
+        let mut jobs = vec![];
+
+        match permission {
+            AssetPermission::Once => jobs.push(blocked.job(true)),
+            AssetPermission::Domain => {
+                let domain = blocked.domain();
+                self.allow_domain(domain.clone());
+                jobs.push(blocked.job(false));
+                jobs.extend(self.assets.unblock_domain(&domain).iter().map(|b| b.job(false)));
+            }
+        }
+
+        for job in jobs {
+            if self.queue.push(job).is_err() {
+                error!("Failed to queue asset job for '{}'", id);
+            }
+        }
+    }
+
     fn open_buffer_from_bytes(&self, bytes: &[u8], url: String, name: String) -> WorkspaceResult<Buffer> {
-        let b = Buffer::from_md(name, self.source.id.clone(), url, String::from_utf8_lossy(bytes).to_string());
+        let b = Buffer::render_md(name, self.source.id.clone(), url, String::from_utf8_lossy(bytes).to_string());
 
         Ok(b)
     }
 
     pub fn get_setting<T: Encode + Decode<()> + Debug>(&mut self, key: impl Into<String>) -> Option<Setting<T>> {
-        let k = key.into();
-        let mut stmt = self.db.prepare("SELECT * FROM settings WHERE id = ?").unwrap();
-        let outcome = stmt.query_row([&k], |r| Ok(Some(Setting::from(r))));
-
-        if let Ok(outcome) = outcome {
-            outcome
-        } else {
-            error!("Setting didnt exist: '{}'", k);
-            None
-        }
+        read_setting(&self.db, key)
     }
 
     pub fn set_setting<T: Encode + Decode<()> + Debug>(&mut self, key: impl Into<String>, value: T) -> &mut Self {
@@ -312,6 +575,33 @@ impl WorkspaceManager {
 // }
 
 
+/// Opens the `workspace.db` database stored within the `.noot` directory of a workspace,
+/// creating any tables which were added since the workspace was created.
+pub fn open_database(workspace_directory: &Path) -> rusqlite::Result<Connection> {
+    let db = Connection::open(workspace_directory.join(".noot").join("workspace.db"))?;
+    db.execute_batch(WORKSPACE_SEED)?;
+    Ok(db)
+}
+
+/// Returns the directory used to store assets when the `Disk` caching strategy is in use
+pub fn asset_directory(workspace_directory: PathBuf) -> PathBuf {
+    workspace_directory.join(".assets")
+}
+
+/// Reads a setting from a workspace database
+pub fn read_setting<T: Encode + Decode<()> + Debug>(db: &Connection, key: impl Into<String>) -> Option<Setting<T>> {
+    let k = key.into();
+    let mut stmt = db.prepare("SELECT * FROM settings WHERE id = ?").unwrap();
+    let outcome = stmt.query_row([&k], |r| Ok(Some(Setting::from(r))));
+
+    if let Ok(outcome) = outcome {
+        outcome
+    } else {
+        error!("Setting didnt exist: '{}'", k);
+        None
+    }
+}
+
 pub fn render_directory(path: String, workspace_directory: PathBuf) -> PathBuf {
     let mut workspace_dir = PathBuf::from(workspace_directory);
 
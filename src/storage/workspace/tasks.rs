@@ -0,0 +1,233 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, Local, NaiveDate};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use rusqlite::{params, Connection};
+use rust_i18n::t;
+
+lazy_static!(
+    static ref DUE: Regex = Regex::new(r"@due\((\d{4}-\d{2}-\d{2})\)").unwrap();
+    static ref TAG: Regex = Regex::new(r"(?:^|\s)#([\w/-]+)").unwrap();
+);
+
+#[cfg(test)]
+mod tests;
+
+/// A task list item, eg: `- [ ] Write the report #work @due(2026-10-20)`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskItem {
+    pub note: PathBuf,
+    /// The byte offset of the `[ ]` marker within the note, used to write changes back
+    pub offset: usize,
+    pub line: usize,
+    /// The text of the task, without its due date
+    pub text: String,
+    pub done: bool,
+    pub due: Option<NaiveDate>,
+    pub tags: Vec<String>,
+}
+
+impl TaskItem {
+    pub fn overdue(&self) -> bool {
+        !self.done && self.due.is_some_and(|due| due < Local::now().date_naive())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DueFilter {
+    #[default]
+    Any,
+    Overdue,
+    Today,
+    ThisWeek,
+    Undated,
+}
+
+impl DueFilter {
+    pub const ALL: [DueFilter; 5] = [DueFilter::Any, DueFilter::Overdue, DueFilter::Today, DueFilter::ThisWeek, DueFilter::Undated];
+}
+
+impl Display for DueFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            DueFilter::Any => t!("windows.editor.tasks.due.any"),
+            DueFilter::Overdue => t!("windows.editor.tasks.due.overdue"),
+            DueFilter::Today => t!("windows.editor.tasks.due.today"),
+            DueFilter::ThisWeek => t!("windows.editor.tasks.due.week"),
+            DueFilter::Undated => t!("windows.editor.tasks.due.undated"),
+        };
+        write!(f, "{}", label)
+    }
+}
+
+/// Narrows the open tasks shown in the task dashboard
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TaskFilter {
+    /// Matches any part of the path of the note
+    pub note: String,
+    pub tag: Option<String>,
+    pub due: DueFilter,
+}
+
+/// Finds every task list item within a note
+pub fn extract(note: &Path, content: &str) -> Vec<TaskItem> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut tasks = vec![];
+    // Each open list item, which holds a task if it starts with a marker
+    let mut items: Vec<Option<TaskItem>> = vec![];
+
+    for (event, range) in Parser::new_ext(content, options).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Item) => items.push(None),
+            Event::TaskListMarker(done) => {
+                if let Some(item) = items.last_mut() {
+                    *item = Some(TaskItem {
+                        note: note.to_path_buf(),
+                        offset: range.start,
+                        line: content[..range.start].matches('\n').count() + 1,
+                        text: String::new(),
+                        done,
+                        due: None,
+                        tags: vec![],
+                    });
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(Some(task)) = items.last_mut() {
+                    task.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(Some(task)) = items.last_mut() {
+                    task.text.push(' ');
+                }
+            }
+            Event::End(TagEnd::Item) => {
+                if let Some(Some(mut task)) = items.pop() {
+                    task.due = DUE.captures(&task.text).and_then(|c| NaiveDate::parse_from_str(&c[1], "%Y-%m-%d").ok());
+                    task.tags = TAG.captures_iter(&task.text).map(|c| c[1].to_string()).collect();
+                    task.text = DUE.replace_all(&task.text, "").split_whitespace().collect::<Vec<&str>>().join(" ");
+                    tasks.push(task);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    tasks.sort_by_key(|t| t.offset);
+    tasks
+}
+
+/// Flips the task marker at `offset`, returning the updated note.
+/// Returns `None` if there is no marker at the offset, eg: when the note has changed since it was rendered
+pub fn toggle(content: &str, offset: usize, done: bool) -> Option<String> {
+    let marker = content.get(offset..offset + 3)?;
+
+    if !matches!(marker, "[ ]" | "[x]" | "[X]") {
+        return None;
+    }
+
+    let mut updated = content.to_string();
+    updated.replace_range(offset..offset + 3, if done { "[x]" } else { "[ ]" });
+    Some(updated)
+}
+
+/// Replaces the indexed tasks of a note
+pub fn index_note(db: &mut Connection, note: &Path, tasks: &[TaskItem]) -> rusqlite::Result<()> {
+    let path = note.to_string_lossy();
+    let tx = db.transaction()?;
+    tx.execute("DELETE FROM tasks WHERE note = ?", [&path])?;
+
+    for task in tasks {
+        // Tags are stored with surrounding spaces, so that a single tag can be matched with LIKE
+        let tags = if task.tags.is_empty() { String::new() } else { format!(" {} ", task.tags.join(" ")) };
+        tx.execute(
+            "INSERT INTO tasks (note, offset, line, text, done, due, tags) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            params![path, task.offset as i64, task.line as i64, task.text, task.done, task.due.map(|d| d.to_string()), tags],
+        )?;
+    }
+
+    tx.commit()
+}
+
+/// Removes the tasks of any note which is not in `notes`, eg: because it was deleted
+pub fn retain_notes(db: &Connection, notes: &[PathBuf]) -> rusqlite::Result<()> {
+    let indexed = db.prepare("SELECT DISTINCT note FROM tasks")?
+        .query_map((), |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    for note in indexed.iter().filter(|n| !notes.iter().any(|p| p.to_string_lossy() == n.as_str())) {
+        db.execute("DELETE FROM tasks WHERE note = ?", [note])?;
+    }
+
+    Ok(())
+}
+
+/// Returns the open tasks matching a filter, soonest due first
+pub fn query(db: &Connection, filter: &TaskFilter) -> rusqlite::Result<Vec<TaskItem>> {
+    let today = Local::now().date_naive();
+    let week_end = today + chrono::Duration::days(6 - today.weekday().num_days_from_monday() as i64);
+
+    let due = match filter.due {
+        DueFilter::Any => "1",
+        DueFilter::Overdue => "due IS NOT NULL AND due < :today",
+        DueFilter::Today => "due = :today",
+        DueFilter::ThisWeek => "due IS NOT NULL AND due <= :week",
+        DueFilter::Undated => "due IS NULL",
+    };
+
+    let sql = format!(
+        "SELECT note, offset, line, text, done, due, tags FROM tasks \
+         WHERE done = 0 AND note LIKE :note ESCAPE '\\' AND tags LIKE :tag ESCAPE '\\' AND {} \
+         ORDER BY due IS NULL, due, note, offset",
+        due
+    );
+
+    let mut statement = db.prepare(&sql)?;
+    let note = format!("%{}%", escape_like(&filter.note));
+    let tag = filter.tag.as_ref().map(|t| format!("% {} %", escape_like(t))).unwrap_or("%".to_string());
+    let today = today.to_string();
+    let week = week_end.to_string();
+
+    let mut parameters: Vec<(&str, &dyn rusqlite::ToSql)> = vec![(":note", &note), (":tag", &tag)];
+    if sql.contains(":today") {
+        parameters.push((":today", &today));
+    }
+    if sql.contains(":week") {
+        parameters.push((":week", &week));
+    }
+
+    let tasks = statement.query_map(parameters.as_slice(), |r| {
+        Ok(TaskItem {
+            note: PathBuf::from(r.get::<_, String>(0)?),
+            offset: r.get::<_, i64>(1)? as usize,
+            line: r.get::<_, i64>(2)? as usize,
+            text: r.get(3)?,
+            done: r.get(4)?,
+            due: r.get::<_, Option<String>>(5)?.and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok()),
+            tags: r.get::<_, String>(6)?.split_whitespace().map(|t| t.to_string()).collect(),
+        })
+    })?;
+
+    tasks.collect()
+}
+
+/// Escapes the characters which `LIKE` treats as wildcards, so that they only match themselves
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Returns every tag used by an open task
+pub fn tags(db: &Connection) -> rusqlite::Result<Vec<String>> {
+    let rows = db.prepare("SELECT tags FROM tasks WHERE done = 0 AND tags != ''")?
+        .query_map((), |r| r.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+    let mut tags: Vec<String> = rows.iter().flat_map(|t| t.split_whitespace().map(|t| t.to_string())).collect();
+    tags.sort();
+    tags.dedup();
+    Ok(tags)
+}
@@ -0,0 +1,120 @@
+//! Tests for finding, toggling, indexing and querying the task list items within notes.
+
+use nanoid::nanoid;
+use crate::storage::workspace::open_database;
+use super::*;
+
+/// Opens the database of an empty workspace in a temporary directory
+fn database() -> Connection {
+    let root = std::env::temp_dir().join(format!("noot-tasks-{}", nanoid!(5)));
+    std::fs::create_dir_all(root.join(".noot")).unwrap();
+    open_database(&root).unwrap()
+}
+
+fn filter(note: &str, tag: Option<&str>) -> TaskFilter {
+    TaskFilter { note: note.to_string(), tag: tag.map(|t| t.to_string()), due: DueFilter::Any }
+}
+
+fn texts(tasks: &[TaskItem]) -> Vec<&str> {
+    tasks.iter().map(|t| t.text.as_str()).collect()
+}
+
+#[test]
+fn extracts_tasks_with_their_due_dates_and_tags() {
+    let note = Path::new("todo.md");
+    let tasks = extract(note, "# Todo\n\n- [ ] Write the report #work @due(2026-10-20)\n- [x] Buy milk\n- Not a task\n");
+
+    assert_eq!(tasks.len(), 2);
+    assert_eq!(tasks[0].text, "Write the report #work");
+    assert_eq!(tasks[0].line, 3);
+    assert!(!tasks[0].done);
+    assert_eq!(tasks[0].due, NaiveDate::from_ymd_opt(2026, 10, 20));
+    assert_eq!(tasks[0].tags, vec!["work"]);
+    assert_eq!(tasks[1].text, "Buy milk");
+    assert!(tasks[1].done);
+    assert_eq!(tasks[1].due, None);
+}
+
+#[test]
+fn extracted_offsets_point_at_the_marker() {
+    let content = "Intro\n\n- [ ] First\n  - [x] Nested\n";
+    let tasks = extract(Path::new("todo.md"), content);
+
+    assert_eq!(texts(&tasks), vec!["First", "Nested"]);
+    for task in &tasks {
+        assert!(matches!(&content[task.offset..task.offset + 3], "[ ]" | "[x]"));
+    }
+}
+
+#[test]
+fn toggle_flips_the_marker() {
+    let content = "- [ ] First\n- [x] Second\n";
+    let tasks = extract(Path::new("todo.md"), content);
+
+    let done = toggle(content, tasks[0].offset, true).unwrap();
+    assert_eq!(done, "- [x] First\n- [x] Second\n");
+
+    let open = toggle(&done, tasks[1].offset, false).unwrap();
+    assert_eq!(open, "- [x] First\n- [ ] Second\n");
+}
+
+#[test]
+fn toggle_refuses_offsets_without_a_marker() {
+    let content = "- [ ] First\n";
+    assert_eq!(toggle(content, 0, true), None);
+    assert_eq!(toggle(content, content.len(), true), None);
+}
+
+#[test]
+fn indexing_a_note_replaces_its_tasks() {
+    let mut db = database();
+    let note = Path::new("/notes/todo.md");
+
+    index_note(&mut db, note, &extract(note, "- [ ] First\n- [ ] Second\n")).unwrap();
+    index_note(&mut db, note, &extract(note, "- [ ] Third\n")).unwrap();
+
+    let tasks = query(&db, &TaskFilter::default()).unwrap();
+    assert_eq!(texts(&tasks), vec!["Third"]);
+    assert_eq!(tasks[0].note, note);
+}
+
+#[test]
+fn query_skips_finished_tasks_and_orders_by_due_date() {
+    let mut db = database();
+    let note = Path::new("/notes/todo.md");
+    let content = "- [ ] Undated\n- [x] Finished @due(2026-01-01)\n- [ ] Later @due(2026-12-01)\n- [ ] Sooner @due(2026-11-01)\n";
+    index_note(&mut db, note, &extract(note, content)).unwrap();
+
+    let tasks = query(&db, &TaskFilter::default()).unwrap();
+    assert_eq!(texts(&tasks), vec!["Sooner", "Later", "Undated"]);
+
+    let undated = query(&db, &TaskFilter { due: DueFilter::Undated, ..TaskFilter::default() }).unwrap();
+    assert_eq!(texts(&undated), vec!["Undated"]);
+}
+
+#[test]
+fn query_filters_by_note_and_tag() {
+    let mut db = database();
+    let work = Path::new("/notes/work.md");
+    let home = Path::new("/notes/home.md");
+    index_note(&mut db, work, &extract(work, "- [ ] Report #work\n- [ ] Review #work/code\n")).unwrap();
+    index_note(&mut db, home, &extract(home, "- [ ] Dishes #chores\n")).unwrap();
+
+    assert_eq!(texts(&query(&db, &filter("home", None)).unwrap()), vec!["Dishes #chores"]);
+    assert_eq!(texts(&query(&db, &filter("", Some("work"))).unwrap()), vec!["Report #work"]);
+    assert_eq!(texts(&query(&db, &filter("home", Some("work"))).unwrap()), Vec::<&str>::new());
+}
+
+#[test]
+fn query_matches_wildcards_literally() {
+    let mut db = database();
+    let plain = Path::new("/notes/plan.md");
+    let underscored = Path::new("/notes/my_plan.md");
+    index_note(&mut db, plain, &extract(plain, "- [ ] Plain #a-b\n")).unwrap();
+    index_note(&mut db, underscored, &extract(underscored, "- [ ] Underscored #a_b\n")).unwrap();
+
+    assert_eq!(texts(&query(&db, &filter("_", None)).unwrap()), vec!["Underscored #a_b"]);
+    assert_eq!(texts(&query(&db, &filter("%", None)).unwrap()), Vec::<&str>::new());
+    assert_eq!(texts(&query(&db, &filter("", Some("a_b"))).unwrap()), vec!["Underscored #a_b"]);
+    assert_eq!(texts(&query(&db, &filter("", Some("%"))).unwrap()), Vec::<&str>::new());
+}
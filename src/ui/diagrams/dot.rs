@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use super::{DiagramError, DiagramResult};
+use super::graph::{Direction, Edge, EdgeStyle, Graph, NodeShape};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Id(String),
+    Symbol(&'static str),
+}
+
+/// Parses a graphviz `graph` or `digraph` into a graph
+pub fn parse(source: &str) -> DiagramResult<Graph> {
+    let tokens = tokenize(source)?;
+    let mut parser = Parser { tokens, pos: 0, directed: true };
+
+    // Header: [strict] (graph | digraph) [id] {
+    if parser.peek_id().is_some_and(|id| id.eq_ignore_ascii_case("strict")) {
+        parser.pos += 1;
+    }
+
+    match parser.next_id().map(|id| id.to_lowercase()) {
+        Some(kind) if kind == "digraph" => parser.directed = true,
+        Some(kind) if kind == "graph" => parser.directed = false,
+        _ => return Err(parser.error("Expected 'graph' or 'digraph'")),
+    }
+
+    if parser.peek_id().is_some() {
+        parser.pos += 1;
+    }
+
+    parser.expect("{")?;
+
+    let mut graph = Graph::new(Direction::TopDown);
+    graph.default_shape = Some(NodeShape::Ellipse);
+    parser.statements(&mut graph)?;
+
+    if graph.is_empty() {
+        return Err(DiagramError::Empty);
+    }
+
+    Ok(graph)
+}
+
+/// Splits the source into identifiers, quoted strings and symbols, tracking the line of each token
+fn tokenize(source: &str) -> DiagramResult<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        match c {
+            '\n' => {
+                line += 1;
+                i += 1;
+            }
+            c if c.is_whitespace() => i += 1,
+            '#' => while i < chars.len() && chars[i] != '\n' { i += 1 },
+            '/' if chars.get(i + 1) == Some(&'/') => while i < chars.len() && chars[i] != '\n' { i += 1 },
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    if chars[i] == '\\' && chars.get(i + 1) == Some(&'"') {
+                        i += 1;
+                    }
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    text.push(chars[i]);
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(DiagramError::Syntax(line, "Unclosed string".to_string()));
+                }
+                i += 1;
+                tokens.push((line, Token::Id(text)));
+            }
+            '-' if matches!(chars.get(i + 1), Some('>') | Some('-')) => {
+                tokens.push((line, Token::Symbol(if chars[i + 1] == '>' { "->" } else { "--" })));
+                i += 2;
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => {
+                let symbol = match c {
+                    '{' => "{",
+                    '}' => "}",
+                    '[' => "[",
+                    ']' => "]",
+                    '=' => "=",
+                    ';' => ";",
+                    ',' => ",",
+                    _ => ":",
+                };
+                tokens.push((line, Token::Symbol(symbol)));
+                i += 1;
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' => {
+                let start = i;
+                // A leading dash is only valid for negative numbers
+                if c == '-' {
+                    i += 1;
+                }
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                    i += 1;
+                }
+                tokens.push((line, Token::Id(chars[start..i].iter().collect())));
+            }
+            c => return Err(DiagramError::Syntax(line, format!("Unexpected character '{}'", c))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    directed: bool,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> DiagramError {
+        let line = self.tokens.get(self.pos).or(self.tokens.last()).map(|(l, _)| *l).unwrap_or(1);
+        DiagramError::Syntax(line, message.to_string())
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn peek_id(&self) -> Option<&String> {
+        match self.peek() {
+            Some(Token::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    fn next_id(&mut self) -> Option<String> {
+        let id = self.peek_id().cloned();
+        if id.is_some() {
+            self.pos += 1;
+        }
+        id
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> DiagramResult<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", symbol)))
+        }
+    }
+
+    /// Parses statements until the closing brace of the current graph or subgraph
+    fn statements(&mut self, graph: &mut Graph) -> DiagramResult<()> {
+        loop {
+            if self.eat("}") {
+                return Ok(());
+            }
+
+            if self.eat(";") {
+                continue;
+            }
+
+            let Some(id) = self.next_id() else {
+                if self.eat("{") {
+                    // Anonymous subgraphs are flattened into the parent graph
+                    self.statements(graph)?;
+                    continue;
+                }
+                return Err(self.error("Expected a statement"));
+            };
+
+            match id.to_lowercase().as_str() {
+                "subgraph" => {
+                    if self.peek_id().is_some() {
+                        self.pos += 1;
+                    }
+                    self.expect("{")?;
+                    self.statements(graph)?;
+                    continue;
+                }
+                "graph" if self.peek() == Some(&Token::Symbol("[")) => {
+                    let attributes = self.attributes()?;
+                    apply_graph_attributes(graph, &attributes);
+                    continue;
+                }
+                "node" if self.peek() == Some(&Token::Symbol("[")) => {
+                    let attributes = self.attributes()?;
+                    if let Some(shape) = attributes.get("shape").and_then(|s| shape(s)) {
+                        graph.default_shape = Some(shape);
+                    }
+                    continue;
+                }
+                "edge" if self.peek() == Some(&Token::Symbol("[")) => {
+                    // Edge defaults are not supported, only per edge attributes
+                    self.attributes()?;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Graph attribute, eg: `rankdir=LR`
+            if self.eat("=") {
+                let value = self.next_id().ok_or(self.error("Expected a value"))?;
+                apply_graph_attributes(graph, &HashMap::from([(id.to_lowercase(), value)]));
+                continue;
+            }
+
+            let mut chain = vec![id];
+            while self.eat("->") || self.eat("--") {
+                chain.push(self.next_id().ok_or(self.error("Expected a node after edge"))?);
+            }
+
+            let attributes = if self.peek() == Some(&Token::Symbol("[")) { self.attributes()? } else { HashMap::new() };
+
+            if chain.len() == 1 {
+                graph.node(
+                    &chain[0],
+                    attributes.get("label").cloned(),
+                    attributes.get("shape").and_then(|s| shape(s)),
+                );
+            } else {
+                let style = match attributes.get("style").map(|s| s.as_str()) {
+                    Some("dashed") | Some("dotted") => EdgeStyle::Dotted,
+                    Some("bold") => EdgeStyle::Thick,
+                    _ => EdgeStyle::Solid,
+                };
+                let arrow = self.directed && attributes.get("dir").map(|d| d != "none").unwrap_or(true);
+
+                for pair in chain.windows(2) {
+                    graph.edge(Edge {
+                        from: pair[0].clone(),
+                        to: pair[1].clone(),
+                        label: attributes.get("label").cloned(),
+                        style,
+                        arrow,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Parses an attribute list, eg: `[label="Start", shape=box]`
+    fn attributes(&mut self) -> DiagramResult<HashMap<String, String>> {
+        let mut attributes = HashMap::new();
+
+        while self.eat("[") {
+            loop {
+                if self.eat("]") {
+                    break;
+                }
+                if self.eat(",") || self.eat(";") {
+                    continue;
+                }
+
+                let key = self.next_id().ok_or(self.error("Expected an attribute name"))?;
+                self.expect("=")?;
+                let value = self.next_id().ok_or(self.error("Expected an attribute value"))?;
+                attributes.insert(key.to_lowercase(), value);
+            }
+        }
+
+        Ok(attributes)
+    }
+}
+
+fn apply_graph_attributes(graph: &mut Graph, attributes: &HashMap<String, String>) {
+    if let Some(direction) = attributes.get("rankdir").and_then(|d| Direction::parse(d)) {
+        graph.direction = direction;
+    }
+}
+
+fn shape(name: &str) -> Option<NodeShape> {
+    match name.to_lowercase().as_str() {
+        "box" | "rect" | "rectangle" | "square" | "plaintext" | "plain" | "none" => Some(NodeShape::Rect),
+        "ellipse" | "oval" => Some(NodeShape::Ellipse),
+        "circle" | "doublecircle" | "point" => Some(NodeShape::Circle),
+        "diamond" => Some(NodeShape::Diamond),
+        "box3d" | "component" | "note" | "tab" | "folder" | "cylinder" => Some(NodeShape::Rect),
+        "rounded" | "mrecord" => Some(NodeShape::Rounded),
+        _ => None,
+    }
+}
@@ -0,0 +1,248 @@
+use super::{DiagramError, DiagramResult};
+use super::graph::{Direction, Edge, EdgeStyle, Graph, NodeShape};
+
+/// Statements which only affect styling or grouping, these are skipped rather than rejected
+const IGNORED: [&str; 8] = ["classDef", "class", "style", "click", "linkStyle", "subgraph", "end", "direction"];
+
+/// Parses a mermaid `flowchart` (or `graph`) into a graph
+pub fn parse(source: &str) -> DiagramResult<Graph> {
+    let mut lines = source.lines().enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with("%%"));
+
+    let (_, header) = lines.next().ok_or(DiagramError::Empty)?;
+    let direction = header.split_whitespace().nth(1)
+        .map(|d| Direction::parse(d.trim_end_matches(';')).unwrap_or_default())
+        .unwrap_or_default();
+
+    let mut graph = Graph::new(direction);
+
+    for (number, line) in lines {
+        for statement in line.split(';').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let keyword = statement.split_whitespace().next().unwrap_or_default();
+
+            if IGNORED.contains(&keyword) {
+                continue;
+            }
+
+            parse_statement(&mut graph, statement).map_err(|e| DiagramError::Syntax(number, e))?;
+        }
+    }
+
+    if graph.is_empty() {
+        return Err(DiagramError::Empty);
+    }
+
+    Ok(graph)
+}
+
+/// Parses a chain of nodes and links, eg: `A[Start] --> B{Choice} -->|Yes| C & D`
+fn parse_statement(graph: &mut Graph, statement: &str) -> Result<(), String> {
+    let mut cursor = Cursor { chars: statement.chars().collect(), pos: 0 };
+    let mut previous = parse_group(graph, &mut cursor)?;
+
+    loop {
+        cursor.skip_whitespace();
+
+        if cursor.done() {
+            return Ok(());
+        }
+
+        let (style, arrow, mut label) = parse_link(&mut cursor)?;
+
+        cursor.skip_whitespace();
+        if cursor.peek() == Some('|') {
+            cursor.pos += 1;
+            label = Some(cursor.take_until('|').ok_or("Unclosed link label")?);
+        }
+
+        cursor.skip_whitespace();
+        let next = parse_group(graph, &mut cursor)?;
+
+        for from in &previous {
+            for to in &next {
+                graph.edge(Edge {
+                    from: from.clone(),
+                    to: to.clone(),
+                    label: label.clone().filter(|l| !l.trim().is_empty()).map(|l| l.trim().to_string()),
+                    style,
+                    arrow,
+                });
+            }
+        }
+
+        previous = next;
+    }
+}
+
+/// Parses one or more nodes joined with `&`
+fn parse_group(graph: &mut Graph, cursor: &mut Cursor) -> Result<Vec<String>, String> {
+    let mut nodes = vec![parse_node(graph, cursor)?];
+
+    loop {
+        cursor.skip_whitespace();
+        if cursor.peek() != Some('&') {
+            return Ok(nodes);
+        }
+        cursor.pos += 1;
+        cursor.skip_whitespace();
+        nodes.push(parse_node(graph, cursor)?);
+    }
+}
+
+fn parse_node(graph: &mut Graph, cursor: &mut Cursor) -> Result<String, String> {
+    let start = cursor.pos;
+    while cursor.peek().is_some_and(|c| c.is_alphanumeric() || c == '_') {
+        cursor.pos += 1;
+    }
+
+    if cursor.pos == start {
+        return Err(format!("Expected a node at column {}", start + 1));
+    }
+
+    let id: String = cursor.chars[start..cursor.pos].iter().collect();
+
+    // Longer delimiters must be checked before the shorter ones they begin with
+    let shapes = [
+        ("((", "))", NodeShape::Circle),
+        ("([", "])", NodeShape::Rounded),
+        ("[[", "]]", NodeShape::Rect),
+        ("[(", ")]", NodeShape::Rect),
+        ("{{", "}}", NodeShape::Diamond),
+        ("(", ")", NodeShape::Rounded),
+        ("[", "]", NodeShape::Rect),
+        ("{", "}", NodeShape::Diamond),
+        (">", "]", NodeShape::Rect),
+    ];
+
+    for (open, close, shape) in shapes {
+        if cursor.starts_with(open) {
+            cursor.pos += open.chars().count();
+            let label = cursor.take_until_str(close).ok_or(format!("Unclosed node '{}'", id))?;
+            let label = label.trim().trim_matches('"').to_string();
+            graph.node(&id, Some(label), Some(shape));
+            return Ok(id);
+        }
+    }
+
+    graph.node(&id, None, None);
+    Ok(id)
+}
+
+/// Parses a link such as `-->`, `-.->`, `==>`, `---` or `-- text -->`
+fn parse_link(cursor: &mut Cursor) -> Result<(EdgeStyle, bool, Option<String>), String> {
+    let start = cursor.pos;
+
+    if cursor.peek() == Some('<') {
+        cursor.pos += 1;
+    }
+
+    while cursor.peek().is_some_and(|c| matches!(c, '-' | '=' | '.')) {
+        cursor.pos += 1;
+    }
+
+    let stroke: String = cursor.chars[start..cursor.pos].iter().collect();
+    let stroke = stroke.trim_start_matches('<');
+
+    if stroke.len() < 2 {
+        return Err(format!("Expected a link at column {}", start + 1));
+    }
+
+    let style = if stroke.contains('.') {
+        EdgeStyle::Dotted
+    } else if stroke.contains('=') {
+        EdgeStyle::Thick
+    } else {
+        EdgeStyle::Solid
+    };
+
+    // Links with inline text, eg: `A -- text --> B`
+    if matches!(stroke, "--" | "==" | "-.") && cursor.peek().is_some_and(|c| c.is_whitespace()) {
+        let endings: &[&str] = match stroke {
+            "--" => &["-->", "---", "--x", "--o"],
+            "==" => &["==>", "===", "==x", "==o"],
+            _ => &[".->", ".-"],
+        };
+
+        let rest: String = cursor.chars[cursor.pos..].iter().collect();
+        let (offset, ending) = endings.iter()
+            .filter_map(|e| rest.find(e).map(|i| (i, *e)))
+            .min_by_key(|(i, _)| *i)
+            .ok_or("Unclosed link text")?;
+
+        let label = rest[..offset].trim().to_string();
+        cursor.pos += rest[..offset + ending.len()].chars().count();
+
+        // Links may be lengthened with extra dashes, eg: `-- text ---->`
+        while cursor.peek().is_some_and(|c| matches!(c, '-' | '=' | '.')) {
+            cursor.pos += 1;
+        }
+
+        let arrow = ending.ends_with('>') || cursor.peek() == Some('>');
+        if cursor.peek() == Some('>') {
+            cursor.pos += 1;
+        }
+
+        return Ok((style, arrow, Some(label)));
+    }
+
+    let arrow = match cursor.peek() {
+        Some('>') | Some('x') | Some('o') => {
+            cursor.pos += 1;
+            true
+        }
+        _ => false,
+    };
+
+    Ok((style, arrow, None))
+}
+
+struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Cursor {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn starts_with(&self, pattern: &str) -> bool {
+        pattern.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    /// Consumes characters up to and including `end`, returning the text before it
+    fn take_until(&mut self, end: char) -> Option<String> {
+        let offset = self.chars[self.pos..].iter().position(|&c| c == end)?;
+        let text = self.chars[self.pos..self.pos + offset].iter().collect();
+        self.pos += offset + 1;
+        Some(text)
+    }
+
+    /// Consumes characters up to and including `end`, returning the text before it
+    fn take_until_str(&mut self, end: &str) -> Option<String> {
+        let start = self.pos;
+
+        while !self.done() {
+            if self.starts_with(end) {
+                let text = self.chars[start..self.pos].iter().collect();
+                self.pos += end.chars().count();
+                return Some(text);
+            }
+            self.pos += 1;
+        }
+
+        self.pos = start;
+        None
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use super::{svg_document, text_height, text_width, write_label, write_text, COLOR_FILL, COLOR_STROKE};
+
+const NODE_PADDING: f32 = 12.;
+const NODE_MIN_WIDTH: f32 = 60.;
+const NODE_GAP: f32 = 30.;
+const RANK_GAP: f32 = 60.;
+const MARGIN: f32 = 10.;
+/// Extra space given to the sides of a graph with curved edges, so the curves are not clipped
+const CURVE_ROOM: f32 = 25.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Direction {
+    #[default]
+    TopDown,
+    BottomUp,
+    LeftRight,
+    RightLeft,
+}
+
+impl Direction {
+    /// Parses a mermaid or dot direction, eg: `TD` or `LR`
+    pub fn parse(direction: &str) -> Option<Direction> {
+        match direction.to_uppercase().as_str() {
+            "TD" | "TB" => Some(Direction::TopDown),
+            "BT" => Some(Direction::BottomUp),
+            "LR" => Some(Direction::LeftRight),
+            "RL" => Some(Direction::RightLeft),
+            _ => None,
+        }
+    }
+
+    fn horizontal(&self) -> bool {
+        matches!(self, Direction::LeftRight | Direction::RightLeft)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeShape {
+    Rect,
+    Rounded,
+    Ellipse,
+    Circle,
+    Diamond,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeStyle {
+    Solid,
+    Dotted,
+    Thick,
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub id: String,
+    pub label: String,
+    pub shape: NodeShape,
+}
+
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub label: Option<String>,
+    pub style: EdgeStyle,
+    pub arrow: bool,
+}
+
+/// A directed graph which is drawn using a simple layered layout
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    pub direction: Direction,
+    pub default_shape: Option<NodeShape>,
+    pub(super) nodes: Vec<Node>,
+    pub(super) edges: Vec<Edge>,
+}
+
+/// The position and size of a node once laid out, `x` and `y` refer to its centre
+#[derive(Debug, Clone, Copy)]
+struct Placed {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    shape: NodeShape,
+}
+
+impl Graph {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            ..Default::default()
+        }
+    }
+
+    /// Adds a node, or updates the label and shape of a node which already exists
+    pub fn node(&mut self, id: &str, label: Option<String>, shape: Option<NodeShape>) {
+        if let Some(node) = self.nodes.iter_mut().find(|n| n.id == id) {
+            if let Some(label) = label {
+                node.label = label;
+            }
+            if let Some(shape) = shape {
+                node.shape = shape;
+            }
+            return;
+        }
+
+        self.nodes.push(Node {
+            id: id.to_string(),
+            label: label.unwrap_or(id.to_string()),
+            shape: shape.or(self.default_shape).unwrap_or(NodeShape::Rect),
+        });
+    }
+
+    /// Adds an edge, creating either of its nodes if they do not exist yet
+    pub fn edge(&mut self, edge: Edge) {
+        self.node(&edge.from.clone(), None, None);
+        self.node(&edge.to.clone(), None, None);
+        self.edges.push(edge);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Assigns each node a rank, so that edges point from lower ranks to higher ranks where possible
+    fn ranks(&self, index: &HashMap<&str, usize>) -> Vec<usize> {
+        let count = self.nodes.len();
+        let mut outgoing = vec![vec![]; count];
+
+        for edge in &self.edges {
+            outgoing[index[edge.from.as_str()]].push(index[edge.to.as_str()]);
+        }
+
+        // Edges which would form a cycle are ignored when ranking
+        let mut forward = vec![vec![]; count];
+        let mut state = vec![0u8; count];
+
+        fn visit(node: usize, outgoing: &[Vec<usize>], forward: &mut [Vec<usize>], state: &mut [u8]) {
+            state[node] = 1;
+            for &next in &outgoing[node] {
+                match state[next] {
+                    0 => {
+                        forward[node].push(next);
+                        visit(next, outgoing, forward, state);
+                    }
+                    2 => forward[node].push(next),
+                    _ => {}
+                }
+            }
+            state[node] = 2;
+        }
+
+        for node in 0..count {
+            if state[node] == 0 {
+                visit(node, &outgoing, &mut forward, &mut state);
+            }
+        }
+
+        // Longest path ranking over the remaining acyclic edges
+        let mut incoming = vec![0usize; count];
+        for targets in &forward {
+            for &t in targets {
+                incoming[t] += 1;
+            }
+        }
+
+        let mut ranks = vec![0usize; count];
+        let mut ready: Vec<usize> = (0..count).filter(|&n| incoming[n] == 0).collect();
+
+        while let Some(node) = ready.pop() {
+            for &next in &forward[node] {
+                ranks[next] = ranks[next].max(ranks[node] + 1);
+                incoming[next] -= 1;
+                if incoming[next] == 0 {
+                    ready.push(next);
+                }
+            }
+        }
+
+        ranks
+    }
+
+    fn size(node: &Node) -> (f32, f32) {
+        let width = (text_width(&node.label) + NODE_PADDING * 2.).max(NODE_MIN_WIDTH);
+        let height = text_height(&node.label) + NODE_PADDING * 2.;
+
+        match node.shape {
+            NodeShape::Diamond => (width * 1.5, height * 1.5),
+            NodeShape::Circle => (width.max(height), width.max(height)),
+            NodeShape::Ellipse => (width * 1.2, height * 1.2),
+            _ => (width, height),
+        }
+    }
+
+    fn layout(&self) -> (Vec<Placed>, Vec<usize>, f32, f32) {
+        let index: HashMap<&str, usize> = self.nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+        let ranks = self.ranks(&index);
+        let rank_count = ranks.iter().max().map(|r| r + 1).unwrap_or(0);
+
+        let mut layers: Vec<Vec<usize>> = vec![vec![]; rank_count];
+        for (node, &rank) in ranks.iter().enumerate() {
+            layers[rank].push(node);
+        }
+
+        // Order each layer by the average position of its parents to reduce crossings
+        for rank in 1..rank_count {
+            let previous: HashMap<usize, usize> = layers[rank - 1].iter().enumerate().map(|(pos, &n)| (n, pos)).collect();
+            let mut keyed: Vec<(f32, usize)> = layers[rank].iter().enumerate().map(|(pos, &node)| {
+                let parents: Vec<usize> = self.edges.iter()
+                    .filter(|e| index[e.to.as_str()] == node)
+                    .filter_map(|e| previous.get(&index[e.from.as_str()]).copied())
+                    .collect();
+
+                if parents.is_empty() {
+                    (pos as f32, node)
+                } else {
+                    (parents.iter().sum::<usize>() as f32 / parents.len() as f32, node)
+                }
+            }).collect();
+
+            keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            layers[rank] = keyed.into_iter().map(|(_, n)| n).collect();
+        }
+
+        let sizes: Vec<(f32, f32)> = self.nodes.iter().map(Self::size).collect();
+        let horizontal = self.direction.horizontal();
+
+        // "Along" is the axis that ranks advance on, "across" is the axis nodes within a rank are spread on
+        let along = |(w, h): (f32, f32)| if horizontal { w } else { h };
+        let across = |(w, h): (f32, f32)| if horizontal { h } else { w };
+
+        let layer_depth: Vec<f32> = layers.iter()
+            .map(|l| l.iter().map(|&n| along(sizes[n])).fold(0., f32::max))
+            .collect();
+        let layer_breadth: Vec<f32> = layers.iter()
+            .map(|l| l.iter().map(|&n| across(sizes[n])).sum::<f32>() + NODE_GAP * (l.len().max(1) - 1) as f32)
+            .collect();
+        let breadth = layer_breadth.iter().copied().fold(0., f32::max);
+
+        let mut placed = vec![Placed { x: 0., y: 0., width: 0., height: 0., shape: NodeShape::Rect }; self.nodes.len()];
+        let mut offset = MARGIN;
+
+        for (rank, layer) in layers.iter().enumerate() {
+            let centre = offset + layer_depth[rank] / 2.;
+            let mut cursor = MARGIN + (breadth - layer_breadth[rank]) / 2.;
+
+            for &node in layer {
+                let size = sizes[node];
+                let middle = cursor + across(size) / 2.;
+                let (x, y) = if horizontal { (centre, middle) } else { (middle, centre) };
+
+                placed[node] = Placed { x, y, width: size.0, height: size.1, shape: self.nodes[node].shape };
+                cursor += across(size) + NODE_GAP;
+            }
+
+            offset += layer_depth[rank] + RANK_GAP;
+        }
+
+        let depth = (offset - RANK_GAP).max(MARGIN) + MARGIN;
+        let (width, height) = if horizontal { (depth, breadth + MARGIN * 2.) } else { (breadth + MARGIN * 2., depth) };
+
+        // Reversed directions are mirrored once laid out
+        for p in placed.iter_mut() {
+            match self.direction {
+                Direction::BottomUp => p.y = height - p.y,
+                Direction::RightLeft => p.x = width - p.x,
+                _ => {}
+            }
+        }
+
+        (placed, ranks, width, height)
+    }
+
+    /// Renders the graph into an SVG document
+    pub fn to_svg(&self) -> String {
+        let (placed, ranks, width, height) = self.layout();
+        let index: HashMap<&str, usize> = self.nodes.iter().enumerate().map(|(i, n)| (n.id.as_str(), i)).collect();
+        let mut body = String::new();
+
+        for edge in &self.edges {
+            let (from_index, to_index) = (index[edge.from.as_str()], index[edge.to.as_str()]);
+            let (from, to) = (placed[from_index], placed[to_index]);
+
+            let stroke = match edge.style {
+                EdgeStyle::Solid => r#"stroke-width="1.5""#,
+                EdgeStyle::Dotted => r#"stroke-width="1.5" stroke-dasharray="4 3""#,
+                EdgeStyle::Thick => r#"stroke-width="3""#,
+            };
+            let marker = if edge.arrow { r#" marker-end="url(#arrow)""# } else { "" };
+
+            if from_index == to_index {
+                // Self referencing edges are drawn as a loop on the right of the node
+                let (x, y) = (from.x + from.width / 2., from.y);
+                let _ = write!(
+                    body,
+                    r#"<path d="M{:.1},{:.1} C{:.1},{:.1} {:.1},{:.1} {:.1},{:.1}" fill="none" stroke="{}" {}{}/>"#,
+                    x, y - 8., x + 40., y - 30., x + 40., y + 30., x, y + 8., COLOR_STROKE, stroke, marker
+                );
+
+                if let Some(label) = &edge.label {
+                    write_label(&mut body, x + 40., y, label);
+                }
+                continue;
+            }
+
+            // Edges between neighbouring ranks are straight, others are curved so they do not run through other edges
+            let span = ranks[to_index] as f32 - ranks[from_index] as f32;
+            let (mx, my) = ((from.x + to.x) / 2., (from.y + to.y) / 2.);
+            let (cx, cy) = if span == 1. {
+                (mx, my)
+            } else {
+                let (dx, dy) = (to.x - from.x, to.y - from.y);
+                let length = (dx * dx + dy * dy).sqrt().max(1.);
+                let bend = if span < 1. { -40. } else { 40. };
+                (mx - dy / length * bend, my + dx / length * bend)
+            };
+
+            let (x1, y1) = boundary(&from, cx, cy);
+            let (x2, y2) = boundary(&to, cx, cy);
+
+            let _ = write!(
+                body,
+                r#"<path d="M{:.1},{:.1} Q{:.1},{:.1} {:.1},{:.1}" fill="none" stroke="{}" {}{}/>"#,
+                x1, y1, cx, cy, x2, y2, COLOR_STROKE, stroke, marker
+            );
+
+            if let Some(label) = &edge.label {
+                write_label(&mut body, (x1 + x2) / 4. + cx / 2., (y1 + y2) / 4. + cy / 2., label);
+            }
+        }
+
+        let curved = self.edges.iter().any(|e| {
+            let (from, to) = (index[e.from.as_str()], index[e.to.as_str()]);
+            from != to && ranks[to] != ranks[from] + 1
+        });
+
+        for (node, p) in self.nodes.iter().zip(placed.iter()) {
+            let (left, top) = (p.x - p.width / 2., p.y - p.height / 2.);
+            let style = format!(r#"fill="{}" stroke="{}" stroke-width="1.5""#, COLOR_FILL, COLOR_STROKE);
+
+            let _ = match p.shape {
+                NodeShape::Rect => write!(body, r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" {}/>"#, left, top, p.width, p.height, style),
+                NodeShape::Rounded => write!(body, r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="{:.1}" {}/>"#, left, top, p.width, p.height, p.height / 2., style),
+                NodeShape::Ellipse | NodeShape::Circle => write!(body, r#"<ellipse cx="{:.1}" cy="{:.1}" rx="{:.1}" ry="{:.1}" {}/>"#, p.x, p.y, p.width / 2., p.height / 2., style),
+                NodeShape::Diamond => write!(
+                    body,
+                    r#"<polygon points="{:.1},{:.1} {:.1},{:.1} {:.1},{:.1} {:.1},{:.1}" {}/>"#,
+                    p.x, top, left + p.width, p.y, p.x, top + p.height, left, p.y, style
+                ),
+            };
+
+            write_text(&mut body, p.x, p.y, &node.label, "middle");
+        }
+
+        if curved {
+            let (dx, dy) = if self.direction.horizontal() { (0., CURVE_ROOM) } else { (CURVE_ROOM, 0.) };
+            let body = format!(r#"<g transform="translate({:.1},{:.1})">{}</g>"#, dx, dy, body);
+            svg_document(width + dx * 2., height + dy * 2., &body)
+        } else {
+            svg_document(width, height, &body)
+        }
+    }
+}
+
+/// Finds the point on the outline of a node which lies on the line towards `(x, y)`
+fn boundary(node: &Placed, x: f32, y: f32) -> (f32, f32) {
+    let (dx, dy) = (x - node.x, y - node.y);
+    let (hw, hh) = (node.width / 2., node.height / 2.);
+
+    if dx == 0. && dy == 0. {
+        return (node.x, node.y);
+    }
+
+    let t = match node.shape {
+        NodeShape::Rect | NodeShape::Rounded => {
+            let tx = if dx == 0. { f32::INFINITY } else { hw / dx.abs() };
+            let ty = if dy == 0. { f32::INFINITY } else { hh / dy.abs() };
+            tx.min(ty)
+        }
+        NodeShape::Diamond => 1. / (dx.abs() / hw + dy.abs() / hh),
+        NodeShape::Ellipse | NodeShape::Circle => 1. / ((dx / hw).powi(2) + (dy / hh).powi(2)).sqrt(),
+    };
+
+    (node.x + dx * t, node.y + dy * t)
+}
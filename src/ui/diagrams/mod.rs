@@ -0,0 +1,128 @@
+use std::fmt::{Display, Formatter, Write};
+
+pub mod graph;
+mod flowchart;
+mod sequence;
+mod dot;
+
+#[cfg(test)]
+mod tests;
+
+pub type DiagramResult<T> = Result<T, DiagramError>;
+
+#[derive(Debug, Clone)]
+pub enum DiagramError {
+    /// The diagram has no content to render
+    Empty,
+    /// The kind of diagram is not one we know how to render
+    Unsupported(String),
+    /// The diagram could not be parsed, contains the line number and a description of the problem
+    Syntax(usize, String),
+}
+
+impl Display for DiagramError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagramError::Empty => write!(f, "The diagram is empty"),
+            DiagramError::Unsupported(kind) => write!(f, "Unsupported diagram type '{}'", kind),
+            DiagramError::Syntax(line, message) => write!(f, "Line {}: {}", line, message),
+        }
+    }
+}
+
+// Diagram styling, chosen to match the code block colours used by the editor
+pub(crate) const FONT_SIZE: f32 = 14.;
+/// The generic `sans-serif` family is mapped to Arial by the SVG renderer, so common fonts are listed first
+pub(crate) const FONT_FAMILY: &str = "Roboto, 'DejaVu Sans', 'Noto Sans', 'Segoe UI', Helvetica, Arial, sans-serif";
+pub(crate) const LINE_HEIGHT: f32 = 18.;
+pub(crate) const COLOR_FILL: &str = "#1a1a1a";
+pub(crate) const COLOR_STROKE: &str = "#8a8a8a";
+pub(crate) const COLOR_TEXT: &str = "#e0e0e0";
+pub(crate) const COLOR_LABEL: &str = "#2a2a2a";
+
+/// Returns whether a fenced code block with the given language should be rendered as a diagram
+pub fn is_diagram(lang: &str) -> bool {
+    matches!(lang, "mermaid" | "dot" | "graphviz")
+}
+
+/// Renders the source of a diagram code block into an SVG document
+pub fn render(lang: &str, source: &str) -> DiagramResult<String> {
+    match lang {
+        "mermaid" => render_mermaid(source),
+        "dot" | "graphviz" => dot::parse(source).map(|g| g.to_svg()),
+        other => Err(DiagramError::Unsupported(other.to_string())),
+    }
+}
+
+fn render_mermaid(source: &str) -> DiagramResult<String> {
+    let header = source.lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty() && !l.starts_with("%%"))
+        .ok_or(DiagramError::Empty)?;
+
+    match header.split_whitespace().next().unwrap_or_default() {
+        "flowchart" | "graph" => flowchart::parse(source).map(|g| g.to_svg()),
+        "sequenceDiagram" => sequence::render(source),
+        other => Err(DiagramError::Unsupported(other.to_string())),
+    }
+}
+
+/// Splits a label into lines, mermaid uses `<br>` tags for line breaks
+pub(crate) fn label_lines(label: &str) -> Vec<&str> {
+    label.split("<br/>")
+        .flat_map(|l| l.split("<br>"))
+        .flat_map(|l| l.split("\\n"))
+        .collect()
+}
+
+/// Estimates the rendered width of a label, as the SVG is laid out before any font is loaded
+pub(crate) fn text_width(label: &str) -> f32 {
+    label_lines(label).iter()
+        .map(|l| l.chars().count() as f32 * FONT_SIZE * 0.6)
+        .fold(0., f32::max)
+}
+
+pub(crate) fn text_height(label: &str) -> f32 {
+    label_lines(label).len() as f32 * LINE_HEIGHT
+}
+
+/// Writes a (possibly multi-line) label centred vertically on `y`
+pub(crate) fn write_text(out: &mut String, x: f32, y: f32, label: &str, anchor: &str) {
+    let lines = label_lines(label);
+    let top = y - (lines.len() as f32 - 1.) * LINE_HEIGHT / 2.;
+
+    for (i, line) in lines.iter().enumerate() {
+        let _ = write!(
+            out,
+            r#"<text x="{:.1}" y="{:.1}" text-anchor="{}" font-family="{}" font-size="{}" fill="{}">{}</text>"#,
+            x, top + i as f32 * LINE_HEIGHT + FONT_SIZE * 0.35, anchor, FONT_FAMILY, FONT_SIZE, COLOR_TEXT, html_escape::encode_text(line.trim())
+        );
+    }
+}
+
+/// Writes a label with a background, so that it remains readable when drawn over a line
+pub(crate) fn write_label(out: &mut String, x: f32, y: f32, label: &str) {
+    let width = text_width(label) + 8.;
+    let height = text_height(label) + 4.;
+
+    let _ = write!(
+        out,
+        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="3" fill="{}"/>"#,
+        x - width / 2., y - height / 2., width, height, COLOR_LABEL
+    );
+    write_text(out, x, y, label, "middle");
+}
+
+/// Wraps the body of a diagram in an SVG document, including the arrow heads used by edges
+pub(crate) fn svg_document(width: f32, height: f32, body: &str) -> String {
+    format!(
+        concat!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}">"#,
+            r#"<defs>"#,
+            r#"<marker id="arrow" viewBox="0 0 10 10" refX="9" refY="5" markerWidth="8" markerHeight="8" orient="auto-start-reverse"><path d="M0,0 L10,5 L0,10 z" fill="{s}"/></marker>"#,
+            r#"<marker id="cross" viewBox="0 0 10 10" refX="5" refY="5" markerWidth="8" markerHeight="8" orient="auto"><path d="M1,1 L9,9 M9,1 L1,9" stroke="{s}" stroke-width="2"/></marker>"#,
+            r#"</defs>{b}</svg>"#
+        ),
+        w = width, h = height, s = COLOR_STROKE, b = body
+    )
+}
@@ -0,0 +1,258 @@
+use std::fmt::Write;
+use regex::Regex;
+use super::{svg_document, text_height, text_width, write_label, write_text, DiagramError, DiagramResult, COLOR_FILL, COLOR_LABEL, COLOR_STROKE};
+
+const PARTICIPANT_PADDING: f32 = 12.;
+const PARTICIPANT_MIN_WIDTH: f32 = 100.;
+const PARTICIPANT_GAP: f32 = 40.;
+const ROW_HEIGHT: f32 = 40.;
+const MARGIN: f32 = 10.;
+
+lazy_static!(
+    static ref PARTICIPANT: Regex = Regex::new(r"^(participant|actor)\s+(.+?)(?:\s+as\s+(.+))?$").unwrap();
+    static ref MESSAGE: Regex = Regex::new(r"^(.+?)\s*(-->>|->>|--x|-x|--\)|-\)|-->|->)\s*[+-]?\s*(.+?)\s*:\s*(.*)$").unwrap();
+    static ref NOTE: Regex = Regex::new(r"(?i)^note\s+(left of|right of|over)\s+([^:]+?)\s*:\s*(.*)$").unwrap();
+    static ref BLOCK: Regex = Regex::new(r"^(loop|alt|opt|par|critical|break|rect)\b\s*(.*)$").unwrap();
+    static ref DIVIDER: Regex = Regex::new(r"^(else|and|option)\b\s*(.*)$").unwrap();
+);
+
+#[derive(Debug, Clone)]
+struct Participant {
+    id: String,
+    label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Head {
+    None,
+    Arrow,
+    Cross,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Message { from: usize, to: usize, text: String, dashed: bool, head: Head },
+    Note { first: usize, last: usize, placement: String, text: String },
+    BlockStart { kind: String, text: String },
+    Divider { text: String },
+    BlockEnd,
+}
+
+/// Parses and renders a mermaid `sequenceDiagram`
+pub fn render(source: &str) -> DiagramResult<String> {
+    let mut participants: Vec<Participant> = vec![];
+    let mut steps = vec![];
+    let mut autonumber = false;
+    let mut depth = 0usize;
+
+    let lines = source.lines().enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty() && !l.starts_with("%%"))
+        .skip(1);
+
+    fn participant(participants: &mut Vec<Participant>, id: &str) -> usize {
+        let id = id.trim();
+        participants.iter().position(|p| p.id == id).unwrap_or_else(|| {
+            participants.push(Participant { id: id.to_string(), label: id.to_string() });
+            participants.len() - 1
+        })
+    }
+
+    for (number, line) in lines {
+        if line == "autonumber" {
+            autonumber = true;
+        } else if line.starts_with("activate ") || line.starts_with("deactivate ") || line.starts_with("title ") {
+            // Activations and titles are not drawn
+        } else if let Some(caps) = PARTICIPANT.captures(line) {
+            let index = participant(&mut participants, &caps[2]);
+            if let Some(label) = caps.get(3) {
+                participants[index].label = label.as_str().trim().to_string();
+            }
+        } else if let Some(caps) = NOTE.captures(line) {
+            let mut ids = caps[2].split(',').map(|id| participant(&mut participants, id));
+            let first = ids.next().unwrap_or_default();
+            let last = ids.next().unwrap_or(first);
+            steps.push(Step::Note { first: first.min(last), last: first.max(last), placement: caps[1].to_lowercase(), text: caps[3].to_string() });
+        } else if let Some(caps) = BLOCK.captures(line) {
+            depth += 1;
+            steps.push(Step::BlockStart { kind: caps[1].to_string(), text: caps[2].to_string() });
+        } else if let Some(caps) = DIVIDER.captures(line) {
+            if depth == 0 {
+                return Err(DiagramError::Syntax(number, format!("'{}' used outside of a block", &caps[1])));
+            }
+            steps.push(Step::Divider { text: caps[2].to_string() });
+        } else if line == "end" {
+            if depth == 0 {
+                return Err(DiagramError::Syntax(number, "'end' without a matching block".to_string()));
+            }
+            depth -= 1;
+            steps.push(Step::BlockEnd);
+        } else if let Some(caps) = MESSAGE.captures(line) {
+            let from = participant(&mut participants, &caps[1]);
+            let to = participant(&mut participants, &caps[3]);
+            let arrow = &caps[2];
+
+            steps.push(Step::Message {
+                from,
+                to,
+                text: caps[4].to_string(),
+                dashed: arrow.starts_with("--"),
+                head: match arrow.trim_start_matches('-') {
+                    ">" => Head::None,
+                    "x" => Head::Cross,
+                    _ => Head::Arrow,
+                },
+            });
+        } else {
+            return Err(DiagramError::Syntax(number, format!("Unrecognised statement '{}'", line)));
+        }
+    }
+
+    if depth > 0 {
+        return Err(DiagramError::Syntax(source.lines().count(), "A block is missing its 'end'".to_string()));
+    }
+
+    if participants.is_empty() {
+        return Err(DiagramError::Empty);
+    }
+
+    Ok(draw(&participants, &steps, autonumber))
+}
+
+fn draw(participants: &[Participant], steps: &[Step], autonumber: bool) -> String {
+    let widths: Vec<f32> = participants.iter()
+        .map(|p| (text_width(&p.label) + PARTICIPANT_PADDING * 2.).max(PARTICIPANT_MIN_WIDTH))
+        .collect();
+    let header = participants.iter().map(|p| text_height(&p.label)).fold(0., f32::max) + PARTICIPANT_PADDING * 2.;
+
+    // Space participants so that the messages between neighbours fit
+    let mut gaps: Vec<f32> = widths.windows(2).map(|w| (w[0] + w[1]) / 2. + PARTICIPANT_GAP).collect();
+    let neighbours = steps.iter().filter_map(|step| match step {
+        Step::Message { from, to, text, .. } if from.abs_diff(*to) == 1 => Some(((*from).min(*to), text)),
+        _ => None,
+    });
+    for (left, text) in neighbours {
+        gaps[left] = gaps[left].max(text_width(text) + PARTICIPANT_GAP);
+    }
+
+    let mut centres = vec![MARGIN + widths[0] / 2.];
+    for gap in &gaps {
+        centres.push(centres.last().unwrap() + gap);
+    }
+
+    let width = centres.last().unwrap() + widths.last().unwrap() / 2. + MARGIN + 40.;
+    let mut body = String::new();
+    let mut y = MARGIN + header + ROW_HEIGHT / 2.;
+    let mut blocks: Vec<(f32, String)> = vec![];
+    let mut frames = String::new();
+    let mut count = 0;
+
+    for step in steps {
+        match step {
+            Step::Message { from, to, text, dashed, head } => {
+                count += 1;
+                let text = if autonumber { format!("{}. {}", count, text) } else { text.clone() };
+                let dash = if *dashed { r#" stroke-dasharray="4 3""# } else { "" };
+                let marker = match head {
+                    Head::None => "",
+                    Head::Arrow => r#" marker-end="url(#arrow)""#,
+                    Head::Cross => r#" marker-end="url(#cross)""#,
+                };
+
+                if from == to {
+                    let x = centres[*from];
+                    write_text(&mut body, x + 8., y - 8., &text, "start");
+                    let _ = write!(
+                        body,
+                        r#"<path d="M{:.1},{:.1} h30 v20 h-30" fill="none" stroke="{}" stroke-width="1.5"{}{}/>"#,
+                        x, y, COLOR_STROKE, dash, marker
+                    );
+                    y += ROW_HEIGHT + 20.;
+                } else {
+                    let (x1, x2) = (centres[*from], centres[*to]);
+                    write_text(&mut body, (x1 + x2) / 2., y - 10., &text, "middle");
+                    let _ = write!(
+                        body,
+                        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="1.5"{}{}/>"#,
+                        x1, y + 6., x2, y + 6., COLOR_STROKE, dash, marker
+                    );
+                    y += ROW_HEIGHT;
+                }
+            }
+            Step::Note { first, last, placement, text } => {
+                let note_width = text_width(text) + 16.;
+                let note_height = text_height(text) + 8.;
+                let (left, right) = match placement.as_str() {
+                    "left of" => (centres[*first] - note_width - 10., centres[*first] - 10.),
+                    "right of" => (centres[*first] + 10., centres[*first] + note_width + 10.),
+                    _ => {
+                        let middle = (centres[*first] + centres[*last]) / 2.;
+                        let span = (centres[*last] - centres[*first] + 40.).max(note_width);
+                        (middle - span / 2., middle + span / 2.)
+                    }
+                };
+
+                let _ = write!(
+                    body,
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" stroke="{}"/>"#,
+                    left, y - note_height / 2., right - left, note_height, COLOR_LABEL, COLOR_STROKE
+                );
+                write_text(&mut body, (left + right) / 2., y, text, "middle");
+                y += note_height.max(ROW_HEIGHT - 10.) + 10.;
+            }
+            Step::BlockStart { kind, text } => {
+                blocks.push((y - ROW_HEIGHT / 2. + 5., format!("{} {}", kind, text).trim().to_string()));
+                y += 20.;
+            }
+            Step::Divider { text } => {
+                let _ = write!(
+                    frames,
+                    r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-dasharray="4 3"/>"#,
+                    MARGIN, y - ROW_HEIGHT / 2., width - MARGIN, y - ROW_HEIGHT / 2., COLOR_STROKE
+                );
+                if !text.is_empty() {
+                    write_label(&mut frames, width / 2., y - ROW_HEIGHT / 2., &format!("[{}]", text));
+                }
+                y += 10.;
+            }
+            Step::BlockEnd => {
+                if let Some((top, label)) = blocks.pop() {
+                    // Nested blocks are inset so that their borders remain visible
+                    let inset = blocks.len() as f32 * 6.;
+                    let bottom = y - ROW_HEIGHT / 2. + 5.;
+                    let _ = write!(
+                        frames,
+                        r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="none" stroke="{}"/>"#,
+                        MARGIN / 2. + inset, top, width - MARGIN - inset * 2., bottom - top, COLOR_STROKE
+                    );
+                    write_label(&mut frames, MARGIN / 2. + inset + text_width(&label) / 2. + 8., top + 10., &label);
+                    y += 10.;
+                }
+            }
+        }
+    }
+
+    let footer_top = y - ROW_HEIGHT / 2. + 10.;
+    let height = footer_top + header + MARGIN;
+    let mut heads = String::new();
+
+    for (i, p) in participants.iter().enumerate() {
+        let x = centres[i];
+        let _ = write!(
+            heads,
+            r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-dasharray="2 2"/>"#,
+            x, MARGIN + header, x, footer_top, COLOR_STROKE
+        );
+
+        for top in [MARGIN, footer_top] {
+            let _ = write!(
+                heads,
+                r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" rx="3" fill="{}" stroke="{}" stroke-width="1.5"/>"#,
+                x - widths[i] / 2., top, widths[i], header, COLOR_FILL, COLOR_STROKE
+            );
+            write_text(&mut heads, x, top + header / 2., &p.label, "middle");
+        }
+    }
+
+    svg_document(width, height, &format!("{}{}{}", heads, frames, body))
+}
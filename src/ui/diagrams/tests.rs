@@ -0,0 +1,164 @@
+//! Tests for the diagram parsers, checking the nodes and edges they extract and that malformed diagrams are rejected.
+
+use super::*;
+use super::graph::{EdgeStyle, Graph, NodeShape};
+
+fn edges(graph: &Graph) -> Vec<(&str, &str, Option<&str>)> {
+    graph.edges.iter().map(|e| (e.from.as_str(), e.to.as_str(), e.label.as_deref())).collect()
+}
+
+fn node<'a>(graph: &'a Graph, id: &str) -> &'a graph::Node {
+    graph.nodes.iter().find(|n| n.id == id).unwrap_or_else(|| panic!("No node '{}'", id))
+}
+
+fn syntax_error(result: DiagramResult<impl std::fmt::Debug>) -> usize {
+    match result {
+        Err(DiagramError::Syntax(line, _)) => line,
+        other => panic!("Expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn flowchart_nodes_and_shapes() {
+    let graph = flowchart::parse("flowchart LR\n  A[Start] --> B{Choice}\n  B --> C((End))\n  D(Rounded)").unwrap();
+
+    assert_eq!(graph.direction, graph::Direction::LeftRight);
+    assert_eq!(graph.nodes.len(), 4);
+    assert_eq!((node(&graph, "A").label.as_str(), node(&graph, "A").shape), ("Start", NodeShape::Rect));
+    assert_eq!(node(&graph, "B").shape, NodeShape::Diamond);
+    assert_eq!(node(&graph, "C").shape, NodeShape::Circle);
+    assert_eq!(node(&graph, "D").shape, NodeShape::Rounded);
+    // Directions are case insensitive
+    assert_eq!(graph::Direction::parse("td"), Some(graph::Direction::TopDown));
+}
+
+#[test]
+fn flowchart_edges_and_labels() {
+    let graph = flowchart::parse("graph TD\nA -->|Yes| B\nA -- No --> C\nB -.-> D\nC ==> D\nD --- E").unwrap();
+
+    assert_eq!(edges(&graph), [
+        ("A", "B", Some("Yes")),
+        ("A", "C", Some("No")),
+        ("B", "D", None),
+        ("C", "D", None),
+        ("D", "E", None),
+    ]);
+    assert_eq!(graph.edges[2].style, EdgeStyle::Dotted);
+    assert_eq!(graph.edges[3].style, EdgeStyle::Thick);
+    assert!(graph.edges[0].arrow);
+    assert!(!graph.edges[4].arrow);
+    // Nodes which are only referenced are labelled with their id
+    assert_eq!(node(&graph, "E").label, "E");
+}
+
+#[test]
+fn flowchart_chains_and_groups() {
+    let graph = flowchart::parse("flowchart TD\nA --> B & C --> D; E").unwrap();
+
+    assert_eq!(edges(&graph), [("A", "B", None), ("A", "C", None), ("B", "D", None), ("C", "D", None)]);
+    assert_eq!(graph.nodes.len(), 5);
+}
+
+#[test]
+fn flowchart_skips_styling() {
+    let graph = flowchart::parse("flowchart TD\n%% A comment\nsubgraph One\nA --> B\nend\nclassDef red fill:#f00\nclass A red").unwrap();
+    assert_eq!(edges(&graph), [("A", "B", None)]);
+}
+
+#[test]
+fn flowchart_rejects_malformed_input() {
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\nA -->")), 2);
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\nA[Start --> B")), 2);
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\nA --> B\nA -->|Yes B")), 3);
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\nA -- text B")), 2);
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\n--> B")), 2);
+    assert_eq!(syntax_error(flowchart::parse("flowchart TD\nA - B")), 2);
+    assert!(matches!(flowchart::parse("flowchart TD"), Err(DiagramError::Empty)));
+    assert!(matches!(flowchart::parse(""), Err(DiagramError::Empty)));
+}
+
+#[test]
+fn dot_nodes_edges_and_attributes() {
+    let source = r#"
+        digraph G {
+            rankdir=LR;
+            node [shape=box];
+            start [label="Start here"];
+            start -> middle -> end [label="next", style=dashed];
+            end [shape=diamond]
+        }
+    "#;
+    let graph = dot::parse(source).unwrap();
+
+    assert_eq!(graph.direction, graph::Direction::LeftRight);
+    assert_eq!(node(&graph, "start").label, "Start here");
+    assert_eq!(node(&graph, "start").shape, NodeShape::Rect);
+    assert_eq!(node(&graph, "end").shape, NodeShape::Diamond);
+    assert_eq!(edges(&graph), [("start", "middle", Some("next")), ("middle", "end", Some("next"))]);
+    assert!(graph.edges.iter().all(|e| e.style == EdgeStyle::Dotted && e.arrow));
+}
+
+#[test]
+fn dot_undirected_graphs_and_subgraphs() {
+    let source = "strict graph {\n  a -- b\n  subgraph cluster { c -- d }\n  { e }\n  // comment\n  /* block\n comment */\n}";
+    let graph = dot::parse(source).unwrap();
+
+    assert_eq!(edges(&graph), [("a", "b", None), ("c", "d", None)]);
+    assert!(graph.edges.iter().all(|e| !e.arrow));
+    // Nodes default to ellipses, as in graphviz
+    assert_eq!(node(&graph, "e").shape, NodeShape::Ellipse);
+}
+
+#[test]
+fn dot_rejects_malformed_input() {
+    assert_eq!(syntax_error(dot::parse("flowchart { a }")), 1);
+    assert_eq!(syntax_error(dot::parse("digraph {\n  a -> \n}")), 3);
+    assert_eq!(syntax_error(dot::parse("digraph {\n  a [label=\"open]\n}")), 3);
+    assert_eq!(syntax_error(dot::parse("digraph {\n  a [label]\n}")), 2);
+    assert_eq!(syntax_error(dot::parse("digraph {\n  a @ b\n}")), 2);
+    assert_eq!(syntax_error(dot::parse("digraph {\n  a -> b")), 2);
+    assert_eq!(syntax_error(dot::parse("digraph a -> b")), 1);
+    assert_eq!(syntax_error(dot::parse("")), 1);
+    assert!(matches!(dot::parse("digraph {}"), Err(DiagramError::Empty)));
+}
+
+#[test]
+fn sequence_participants_and_messages() {
+    let svg = sequence::render("sequenceDiagram\nparticipant A as Alice\nA->>Bob: Hello\nBob-->>A: Hi\nNote over A,Bob: Friends\nloop Every day\nA-xBob: Bye\nend").unwrap();
+
+    for label in ["Alice", "Bob", "Hello", "Hi", "Friends", "loop Every day", "Bye"] {
+        assert!(svg.contains(&format!(">{}</text>", label)), "'{}' was not drawn", label);
+    }
+    assert!(!svg.contains(">A</text>"));
+}
+
+#[test]
+fn sequence_rejects_malformed_input() {
+    assert_eq!(syntax_error(sequence::render("sequenceDiagram\nA->>B: Hi\nend")), 3);
+    assert_eq!(syntax_error(sequence::render("sequenceDiagram\nelse")), 2);
+    assert_eq!(syntax_error(sequence::render("sequenceDiagram\nloop\nA->>B: Hi")), 3);
+    assert_eq!(syntax_error(sequence::render("sequenceDiagram\nA says hi")), 2);
+    assert!(matches!(sequence::render("sequenceDiagram"), Err(DiagramError::Empty)));
+}
+
+#[test]
+fn render_picks_the_parser() {
+    assert!(render("mermaid", "graph TD\nA --> B").unwrap().starts_with("<svg"));
+    assert!(render("graphviz", "digraph { a -> b }").unwrap().starts_with("<svg"));
+    assert!(matches!(render("mermaid", "pie\n\"A\": 1"), Err(DiagramError::Unsupported(kind)) if kind == "pie"));
+    assert!(matches!(render("plantuml", "@startuml"), Err(DiagramError::Unsupported(_))));
+    assert!(matches!(render("mermaid", "%% only a comment"), Err(DiagramError::Empty)));
+}
+
+#[test]
+fn lays_out_cycles_and_self_loops() {
+    let svg = render("mermaid", "graph LR\nA --> B --> C --> A\nC --> C").unwrap();
+    assert_eq!(svg.matches("<text").count(), 3);
+    assert!(!svg.contains("NaN"));
+}
+
+#[test]
+fn escapes_labels() {
+    let svg = render("mermaid", "graph TD\nA[\"a < b & c\"]").unwrap();
+    assert!(svg.contains("a &lt; b &amp; c"));
+}
@@ -0,0 +1,316 @@
+use std::fmt::Write;
+use crate::ui::diagrams::COLOR_TEXT;
+use super::MathNode;
+
+const FONT_SIZE: f32 = 20.;
+/// Math fonts are preferred, falling back to common serif fonts which cover the symbols we use
+const FONT_FAMILY: &str = "'Latin Modern Math', 'STIX Two Math', 'Cambria Math', 'DejaVu Serif', 'Noto Serif', 'Times New Roman', serif";
+const MARGIN: f32 = 4.;
+/// Scripts are drawn smaller than their base, but never smaller than this
+const MIN_SIZE: f32 = 9.;
+
+/// A laid out node, positioned with its origin on the baseline at the left edge
+struct MathBox {
+    width: f32,
+    ascent: f32,
+    descent: f32,
+    body: String,
+}
+
+impl MathBox {
+    fn empty() -> Self {
+        Self { width: 0., ascent: 0., descent: 0., body: String::new() }
+    }
+
+    /// Draws the box into `out` with its origin at the given position
+    fn place(&self, out: &mut String, x: f32, y: f32) {
+        if !self.body.is_empty() {
+            let _ = write!(out, r#"<g transform="translate({:.1},{:.1})">{}</g>"#, x, y, self.body);
+        }
+    }
+}
+
+/// Renders an expression into an SVG document, used for display math
+pub fn to_svg(node: &MathNode) -> String {
+    let math = layout(node, FONT_SIZE);
+    let width = math.width + MARGIN * 2.;
+    let height = math.ascent + math.descent + MARGIN * 2.;
+    let mut body = String::new();
+    math.place(&mut body, MARGIN, MARGIN + math.ascent);
+
+    format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}" height="{h:.0}" viewBox="0 0 {w:.0} {h:.0}">{b}</svg>"#,
+        w = width.ceil(), h = height.ceil(), b = body
+    )
+}
+
+/// The height of the fraction bar and the centre of stretched delimiters above the baseline
+fn axis(size: f32) -> f32 {
+    size * 0.28
+}
+
+/// Estimates the width of a run of text, as the SVG is laid out before any font is loaded
+fn text_width(text: &str, size: f32) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '\'' | '′' | '|' | '(' | ')' | '[' | ']' => 0.32,
+            'm' | 'w' | 'M' | 'W' => 0.8,
+            c if c.is_ascii_uppercase() => 0.68,
+            c if c.is_ascii_digit() => 0.5,
+            c if c.is_alphabetic() => 0.52,
+            ' ' => 0.3,
+            _ => 0.7,
+        })
+        .sum::<f32>() * size
+}
+
+fn text(text: &str, size: f32, italic: bool) -> MathBox {
+    let style = if italic { r#" font-style="italic""# } else { "" };
+
+    MathBox {
+        width: text_width(text, size),
+        ascent: size * 0.72,
+        descent: size * 0.22,
+        body: format!(
+            r#"<text x="0" y="0" font-family="{}" font-size="{:.1}"{} fill="{}">{}</text>"#,
+            FONT_FAMILY, size, style, COLOR_TEXT, html_escape::encode_text(text)
+        ),
+    }
+}
+
+fn layout(node: &MathNode, size: f32) -> MathBox {
+    match node {
+        MathNode::Row(nodes) => row(nodes.iter().map(|n| layout(n, size)).collect()),
+        MathNode::Ident(s) => text(s, size, true),
+        MathNode::Number(s) | MathNode::Text(s) => text(s, size, false),
+        MathNode::Function(name) => {
+            let mut function = text(name, size, false);
+            function.width += size * 0.25;
+            function
+        }
+        MathNode::Operator(s, spaced) => {
+            let mut operator = text(s, size, false);
+            if *spaced {
+                let space = size * 0.25;
+                let mut body = String::new();
+                operator.place(&mut body, space, 0.);
+                operator.body = body;
+                operator.width += space * 2.;
+            }
+            operator
+        }
+        MathNode::LargeOp(s) => large_op(s, size),
+        MathNode::Frac(num, den) => fraction(num, den, size),
+        MathNode::Sqrt(index, body) => radical(index.as_deref(), body, size),
+        MathNode::Scripts { base, sub, sup } => {
+            let limits = matches!(base.as_ref(), MathNode::LargeOp(s) if !s.contains('∫'))
+                || matches!(base.as_ref(), MathNode::Function(f) if matches!(f.as_str(), "lim" | "max" | "min"));
+
+            if limits {
+                limits_layout(base, sub.as_deref(), sup.as_deref(), size)
+            } else {
+                scripts(base, sub.as_deref(), sup.as_deref(), size)
+            }
+        }
+        MathNode::Fenced(open, inner, close) => fenced(open, inner, close, size),
+        MathNode::Accent(accent, base) => {
+            let base = layout(base, size);
+            let mark = text(accent, size * 0.8, false);
+            let mut body = String::new();
+            base.place(&mut body, 0., 0.);
+            mark.place(&mut body, (base.width - mark.width) / 2., -base.ascent + size * 0.1);
+
+            MathBox { width: base.width.max(mark.width), ascent: base.ascent + size * 0.3, descent: base.descent, body }
+        }
+        MathNode::Space(width) => MathBox { width: width * size, ..MathBox::empty() },
+    }
+}
+
+fn row(boxes: Vec<MathBox>) -> MathBox {
+    let mut out = MathBox::empty();
+
+    for b in boxes {
+        b.place(&mut out.body, out.width, 0.);
+        out.width += b.width;
+        out.ascent = out.ascent.max(b.ascent);
+        out.descent = out.descent.max(b.descent);
+    }
+
+    out
+}
+
+fn script_size(size: f32) -> f32 {
+    (size * 0.7).max(MIN_SIZE)
+}
+
+fn fraction(num: &MathNode, den: &MathNode, size: f32) -> MathBox {
+    let inner = (size * 0.85).max(MIN_SIZE);
+    let num = layout(num, inner);
+    let den = layout(den, inner);
+    let gap = size * 0.15;
+    let padding = size * 0.1;
+    let width = num.width.max(den.width) + padding * 2.;
+    let axis = axis(size);
+
+    let mut body = String::new();
+    num.place(&mut body, (width - num.width) / 2., -axis - gap - num.descent);
+    den.place(&mut body, (width - den.width) / 2., -axis + gap + den.ascent);
+    let _ = write!(
+        body,
+        r#"<line x1="{:.1}" y1="{:.1}" x2="{:.1}" y2="{:.1}" stroke="{}" stroke-width="1.2"/>"#,
+        padding / 2., -axis, width - padding / 2., -axis, COLOR_TEXT
+    );
+
+    MathBox {
+        width,
+        ascent: axis + gap + num.descent + num.ascent,
+        descent: (den.ascent + den.descent + gap - axis).max(0.),
+        body,
+    }
+}
+
+fn radical(index: Option<&MathNode>, body: &MathNode, size: f32) -> MathBox {
+    let inner = layout(body, size);
+    let gap = size * 0.12;
+    let top = inner.ascent + gap;
+    let bottom = inner.descent;
+    let sign = size * 0.55;
+
+    // The index sits above the short tick of the radical sign
+    let index = index.map(|i| layout(i, (size * 0.5).max(MIN_SIZE)));
+    let offset = index.as_ref().map(|i| (i.width - sign * 0.4).max(0.)).unwrap_or_default();
+
+    let mut out = String::new();
+    if let Some(index) = &index {
+        index.place(&mut out, 0., -top * 0.45);
+    }
+
+    let _ = write!(
+        out,
+        r#"<path d="M{:.1},{:.1} L{:.1},{:.1} L{:.1},{:.1} L{:.1},{:.1} H{:.1}" fill="none" stroke="{}" stroke-width="1.2" stroke-linejoin="round"/>"#,
+        offset, -top * 0.4,
+        offset + sign * 0.25, -top * 0.5,
+        offset + sign * 0.55, bottom,
+        offset + sign, -top,
+        offset + sign + inner.width + size * 0.1,
+        COLOR_TEXT
+    );
+    inner.place(&mut out, offset + sign + size * 0.05, 0.);
+
+    MathBox {
+        width: offset + sign + inner.width + size * 0.15,
+        ascent: (top + size * 0.08).max(index.map(|i| top * 0.45 + i.ascent).unwrap_or_default()),
+        descent: bottom + size * 0.05,
+        body: out,
+    }
+}
+
+fn large_op(symbol: &str, size: f32) -> MathBox {
+    let large = size * 1.6;
+    let mut glyph = text(symbol, large, false);
+    let mut body = String::new();
+
+    // Centre the enlarged glyph on the maths axis rather than sitting it on the baseline
+    let shift = large * 0.25 - axis(size);
+    glyph.place(&mut body, size * 0.05, shift);
+
+    glyph.width = glyph.width.min(large * 0.8) + size * 0.2;
+    glyph.ascent = large * 0.72 - shift;
+    glyph.descent = large * 0.22 + shift;
+    glyph.body = body;
+    glyph
+}
+
+fn limits_layout(base: &MathNode, sub: Option<&MathNode>, sup: Option<&MathNode>, size: f32) -> MathBox {
+    let base = layout(base, size);
+    let small = script_size(size);
+    let sub = sub.map(|s| layout(s, small));
+    let sup = sup.map(|s| layout(s, small));
+    let gap = size * 0.1;
+
+    let width = [Some(base.width), sub.as_ref().map(|s| s.width), sup.as_ref().map(|s| s.width)]
+        .into_iter()
+        .flatten()
+        .fold(0., f32::max);
+
+    let mut body = String::new();
+    base.place(&mut body, (width - base.width) / 2., 0.);
+    let mut ascent = base.ascent;
+    let mut descent = base.descent;
+
+    if let Some(sup) = sup {
+        sup.place(&mut body, (width - sup.width) / 2., -base.ascent - gap - sup.descent);
+        ascent += gap + sup.ascent + sup.descent;
+    }
+
+    if let Some(sub) = sub {
+        sub.place(&mut body, (width - sub.width) / 2., base.descent + gap + sub.ascent);
+        descent += gap + sub.ascent + sub.descent;
+    }
+
+    MathBox { width: width + size * 0.15, ascent, descent, body }
+}
+
+fn scripts(base: &MathNode, sub: Option<&MathNode>, sup: Option<&MathNode>, size: f32) -> MathBox {
+    let base = layout(base, size);
+    let small = script_size(size);
+    let sub = sub.map(|s| layout(s, small));
+    let sup = sup.map(|s| layout(s, small));
+
+    let mut body = String::new();
+    base.place(&mut body, 0., 0.);
+    let x = base.width + size * 0.05;
+    let mut width = x;
+    let mut ascent = base.ascent;
+    let mut descent = base.descent;
+
+    if let Some(sup) = sup {
+        // Raise the script with its base, so that it clears tall bases such as fractions
+        let raise = (base.ascent - small * 0.4).max(size * 0.38);
+        sup.place(&mut body, x, -raise);
+        width = width.max(x + sup.width);
+        ascent = ascent.max(raise + sup.ascent);
+    }
+
+    if let Some(sub) = sub {
+        let lower = (base.descent + small * 0.2).max(size * 0.22);
+        sub.place(&mut body, x, lower);
+        width = width.max(x + sub.width);
+        descent = descent.max(lower + sub.descent);
+    }
+
+    MathBox { width: width + size * 0.05, ascent, descent, body }
+}
+
+/// Draws delimiters stretched to the height of their contents
+fn fenced(open: &str, inner: &MathNode, close: &str, size: f32) -> MathBox {
+    let inner = layout(inner, size);
+    let axis = axis(size);
+    let padding = size * 0.1;
+
+    // Delimiters are symmetric around the axis, so they stretch to the larger side of the contents
+    let half = (inner.ascent - axis).max(inner.descent + axis).max(size * 0.5) + padding;
+    let scale = (half * 2. / size).max(1.);
+
+    let delimiter = |out: &mut String, symbol: &str, x: f32| -> f32 {
+        if symbol.is_empty() {
+            return 0.;
+        }
+
+        let glyph = text(symbol, size, false);
+        let _ = write!(
+            out,
+            r#"<g transform="translate({:.1},{:.1}) scale(1,{:.2})"><text x="0" y="{:.1}" font-family="{}" font-size="{:.1}" fill="{}">{}</text></g>"#,
+            x, -axis, scale, size * 0.25, FONT_FAMILY, size, COLOR_TEXT, html_escape::encode_text(symbol)
+        );
+        glyph.width
+    };
+
+    let mut body = String::new();
+    let mut width = delimiter(&mut body, open, 0.);
+    inner.place(&mut body, width + padding / 2., 0.);
+    width += inner.width + padding;
+    width += delimiter(&mut body, close, width);
+
+    MathBox { width, ascent: half + axis, descent: half - axis, body }
+}
@@ -0,0 +1,469 @@
+use std::fmt::{Display, Formatter};
+
+mod symbols;
+mod layout;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::symbols::{subscript, superscript};
+pub use self::layout::to_svg;
+
+pub type MathResult<T> = Result<T, MathError>;
+
+#[derive(Debug, Clone)]
+pub enum MathError {
+    /// A command which is not part of the supported subset of LaTeX
+    UnknownCommand(String),
+    /// A group or environment was opened and never closed
+    Unclosed(&'static str),
+    /// A command was missing one of its arguments
+    MissingArgument(String),
+    /// A closing brace or `\right` appeared without a matching opening
+    Unexpected(String),
+}
+
+impl Display for MathError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MathError::UnknownCommand(command) => write!(f, "Unknown command '\\{}'", command),
+            MathError::Unclosed(what) => write!(f, "Missing closing {}", what),
+            MathError::MissingArgument(command) => write!(f, "'{}' is missing an argument", command),
+            MathError::Unexpected(token) => write!(f, "Unexpected '{}'", token),
+        }
+    }
+}
+
+/// A parsed LaTeX expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathNode {
+    Row(Vec<MathNode>),
+    /// A variable, drawn in italics
+    Ident(String),
+    Number(String),
+    /// An operator, relation or punctuation mark, contains the symbol and whether it should be spaced
+    Operator(String, bool),
+    /// Upright text, from `\text{}` or `\mathrm{}`
+    Text(String),
+    /// A named function such as `\sin` or `\log`
+    Function(String),
+    /// A large operator such as `\sum` or `\int`, whose scripts are drawn as limits
+    LargeOp(String),
+    Frac(Box<MathNode>, Box<MathNode>),
+    Sqrt(Option<Box<MathNode>>, Box<MathNode>),
+    Scripts {
+        base: Box<MathNode>,
+        sub: Option<Box<MathNode>>,
+        sup: Option<Box<MathNode>>,
+    },
+    /// A group surrounded by stretchy delimiters, from `\left( ... \right)`
+    Fenced(String, Box<MathNode>, String),
+    /// An accent drawn above a node, eg: `\hat{x}`
+    Accent(String, Box<MathNode>),
+    /// Horizontal space, measured in ems
+    Space(f32),
+}
+
+/// Parses a LaTeX math expression
+pub fn parse(source: &str) -> MathResult<MathNode> {
+    let mut parser = Parser { chars: source.chars().collect(), pos: 0 };
+    let row = parser.row()?;
+
+    match parser.peek() {
+        None => Ok(row),
+        Some('}') => Err(MathError::Unexpected("}".to_string())),
+        Some(c) => Err(MathError::Unexpected(c.to_string())),
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses nodes until the end of the current group
+    fn row(&mut self) -> MathResult<MathNode> {
+        let mut nodes = vec![];
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                None | Some('}') => break,
+                Some('\\') if self.command_ahead("right") => break,
+                _ => {}
+            }
+
+            let atom = match self.atom()? {
+                // A sign at the start of an expression or after another operator is unary, eg: `-b`
+                MathNode::Operator(op, true) if matches!(op.as_str(), "−" | "+" | "±" | "∓")
+                    && matches!(nodes.last(), None | Some(MathNode::Operator(..))) => MathNode::Operator(op, false),
+                atom => atom,
+            };
+            nodes.push(self.scripts(atom)?);
+        }
+
+        Ok(if nodes.len() == 1 { nodes.pop().unwrap() } else { MathNode::Row(nodes) })
+    }
+
+    fn command_ahead(&self, name: &str) -> bool {
+        let end = self.pos + 1 + name.chars().count();
+        self.chars.get(self.pos + 1..end).is_some_and(|c| c.iter().copied().eq(name.chars()))
+            && !self.chars.get(end).is_some_and(|c| c.is_ascii_alphabetic())
+    }
+
+    /// Attaches any `^` and `_` scripts which follow a node
+    fn scripts(&mut self, base: MathNode) -> MathResult<MathNode> {
+        let mut sub = None;
+        let mut sup = None;
+
+        loop {
+            self.skip_whitespace();
+
+            match self.peek() {
+                Some('^') => {
+                    self.pos += 1;
+                    sup = Some(Box::new(self.argument("^")?));
+                }
+                Some('_') => {
+                    self.pos += 1;
+                    sub = Some(Box::new(self.argument("_")?));
+                }
+                Some('\'') => {
+                    // Primes are superscripts, eg: f'(x)
+                    self.pos += 1;
+                    sup = Some(Box::new(MathNode::Operator("′".to_string(), false)));
+                }
+                _ => break,
+            }
+        }
+
+        if sub.is_none() && sup.is_none() {
+            Ok(base)
+        } else {
+            Ok(MathNode::Scripts { base: Box::new(base), sub, sup })
+        }
+    }
+
+    /// Parses a single argument, either a braced group or a single token
+    fn argument(&mut self, command: &str) -> MathResult<MathNode> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            None | Some('}') => Err(MathError::MissingArgument(command.to_string())),
+            Some('{') => self.group(),
+            // A bare argument is a single character, so `x^10` is `x` to the power of `1` followed by `0`
+            Some(c) if c.is_ascii_digit() => {
+                self.pos += 1;
+                Ok(MathNode::Number(c.to_string()))
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn group(&mut self) -> MathResult<MathNode> {
+        self.pos += 1;
+        let row = self.row()?;
+
+        if self.peek() != Some('}') {
+            return Err(MathError::Unclosed("brace"));
+        }
+
+        self.pos += 1;
+        Ok(row)
+    }
+
+    /// Reads the raw text of a braced group, used by `\text{}` where the content is not math
+    fn raw_group(&mut self, command: &str) -> MathResult<String> {
+        self.skip_whitespace();
+
+        if self.peek() != Some('{') {
+            return Err(MathError::MissingArgument(command.to_string()));
+        }
+
+        let start = self.pos + 1;
+        let mut depth = 0;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.pos += 1;
+                        return Ok(self.chars[start..self.pos - 1].iter().collect());
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+
+        Err(MathError::Unclosed("brace"))
+    }
+
+    fn command_name(&mut self) -> String {
+        self.pos += 1;
+        let start = self.pos;
+
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+
+        // Single character commands, eg: `\,` or `\{`
+        if self.pos == start && self.peek().is_some() {
+            self.pos += 1;
+        }
+
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Reads the delimiter following `\left` or `\right`
+    fn delimiter(&mut self, command: &str) -> MathResult<String> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('\\') => {
+                let name = self.command_name();
+                match name.as_str() {
+                    "{" | "}" | "|" => Ok(if name == "|" { "‖".to_string() } else { name }),
+                    "langle" => Ok("⟨".to_string()),
+                    "rangle" => Ok("⟩".to_string()),
+                    "lfloor" => Ok("⌊".to_string()),
+                    "rfloor" => Ok("⌋".to_string()),
+                    "lceil" => Ok("⌈".to_string()),
+                    "rceil" => Ok("⌉".to_string()),
+                    _ => Err(MathError::UnknownCommand(name)),
+                }
+            }
+            Some('.') => {
+                self.pos += 1;
+                Ok(String::new())
+            }
+            Some(c) => {
+                self.pos += 1;
+                Ok(c.to_string())
+            }
+            None => Err(MathError::MissingArgument(command.to_string())),
+        }
+    }
+
+    fn atom(&mut self) -> MathResult<MathNode> {
+        let Some(c) = self.peek() else {
+            return Err(MathError::Unclosed("group"));
+        };
+
+        match c {
+            '{' => self.group(),
+            '\\' => self.command(),
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                Ok(MathNode::Number(self.chars[start..self.pos].iter().collect()))
+            }
+            c if c.is_alphabetic() => {
+                self.pos += 1;
+                Ok(MathNode::Ident(c.to_string()))
+            }
+            '^' | '_' => Ok(MathNode::Row(vec![])),
+            c => {
+                self.pos += 1;
+                let spaced = matches!(c, '+' | '-' | '=' | '<' | '>' | '*');
+                let symbol = match c {
+                    '-' => "−".to_string(),
+                    '*' => "∗".to_string(),
+                    c => c.to_string(),
+                };
+                Ok(MathNode::Operator(symbol, spaced))
+            }
+        }
+    }
+
+    fn command(&mut self) -> MathResult<MathNode> {
+        let name = self.command_name();
+
+        match name.as_str() {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.argument(&name)?;
+                let denominator = self.argument(&name)?;
+                Ok(MathNode::Frac(Box::new(numerator), Box::new(denominator)))
+            }
+            "sqrt" => {
+                self.skip_whitespace();
+                let index = if self.peek() == Some('[') {
+                    self.pos += 1;
+                    let start = self.pos;
+                    while self.peek().is_some_and(|c| c != ']') {
+                        self.pos += 1;
+                    }
+                    if self.peek().is_none() {
+                        return Err(MathError::Unclosed("bracket"));
+                    }
+                    let index: String = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    Some(Box::new(parse(&index)?))
+                } else {
+                    None
+                };
+                Ok(MathNode::Sqrt(index, Box::new(self.argument("sqrt")?)))
+            }
+            "text" | "textrm" | "mathrm" | "textit" | "textbf" | "mathbf" | "operatorname" | "mbox" => {
+                let text = self.raw_group(&name)?;
+                Ok(if name == "operatorname" { MathNode::Function(text) } else { MathNode::Text(text) })
+            }
+            "left" => {
+                let open = self.delimiter("left")?;
+                let inner = self.row()?;
+                if !self.command_ahead("right") {
+                    return Err(MathError::Unclosed("\\right"));
+                }
+                self.command_name();
+                let close = self.delimiter("right")?;
+                Ok(MathNode::Fenced(open, Box::new(inner), close))
+            }
+            "right" => Err(MathError::Unexpected("\\right".to_string())),
+            "," | ":" | ">" => Ok(MathNode::Space(0.2)),
+            ";" => Ok(MathNode::Space(0.3)),
+            "!" => Ok(MathNode::Space(0.)),
+            " " | "quad" => Ok(MathNode::Space(1.)),
+            "qquad" => Ok(MathNode::Space(2.)),
+            "\\" => Ok(MathNode::Space(1.)),
+            "{" | "}" | "%" | "$" | "#" | "&" | "_" | "|" => Ok(MathNode::Operator(if name == "|" { "‖".to_string() } else { name }, false)),
+            _ => {
+                if let Some(accent) = symbols::accent(&name) {
+                    let base = self.argument(&name)?;
+                    return Ok(MathNode::Accent(accent.to_string(), Box::new(base)));
+                }
+
+                if symbols::is_function(&name) {
+                    return Ok(MathNode::Function(name));
+                }
+
+                match symbols::lookup(&name) {
+                    Some(symbols::Symbol::Letter(c)) => Ok(MathNode::Ident(c.to_string())),
+                    Some(symbols::Symbol::Operator(c, spaced)) => Ok(MathNode::Operator(c.to_string(), spaced)),
+                    Some(symbols::Symbol::Large(c)) => Ok(MathNode::LargeOp(c.to_string())),
+                    None => Err(MathError::UnknownCommand(name)),
+                }
+            }
+        }
+    }
+}
+
+/// Converts an expression into plain text using unicode symbols, so that it can flow within a paragraph
+pub fn to_unicode(node: &MathNode) -> String {
+    match node {
+        MathNode::Row(nodes) => nodes.iter().map(to_unicode).collect(),
+        MathNode::Ident(s) | MathNode::Number(s) | MathNode::Text(s) | MathNode::LargeOp(s) => s.clone(),
+        MathNode::Function(name) => format!("{} ", name),
+        MathNode::Operator(s, true) => format!(" {} ", s),
+        MathNode::Operator(s, false) => s.clone(),
+        MathNode::Frac(num, den) => format!("{}/{}", wrap(num), wrap(den)),
+        MathNode::Sqrt(index, body) => {
+            let root = match index.as_deref().map(to_unicode).as_deref() {
+                None => "√".to_string(),
+                Some("3") => "∛".to_string(),
+                Some("4") => "∜".to_string(),
+                Some(other) => format!("{}√", script(other, symbols::superscript).unwrap_or(format!("({})", other))),
+            };
+            format!("{}{}", root, wrap(body))
+        }
+        MathNode::Scripts { base, sub, sup } => {
+            let mut out = to_unicode(base).trim_end().to_string();
+            if let Some(sub) = sub {
+                let text = to_unicode(sub);
+                out += &script(&text, symbols::subscript).unwrap_or(format!("_({})", text));
+            }
+            if let Some(sup) = sup {
+                let text = to_unicode(sup);
+                out += &script(&text, symbols::superscript).unwrap_or(format!("^({})", text));
+            }
+            out
+        }
+        MathNode::Fenced(open, inner, close) => format!("{}{}{}", open, to_unicode(inner), close),
+        MathNode::Accent(accent, base) => format!("{}{}", to_unicode(base), symbols::combining(accent)),
+        MathNode::Space(width) => if *width > 0. { " ".to_string() } else { String::new() },
+    }
+}
+
+/// Wraps compound expressions in brackets, so that `\frac{a+b}{c}` reads as `(a + b)/c`
+fn wrap(node: &MathNode) -> String {
+    let text = to_unicode(node);
+
+    match node {
+        MathNode::Ident(_) | MathNode::Number(_) | MathNode::Fenced(..) => text,
+        MathNode::Scripts { .. } if !text.contains(' ') => text,
+        _ => format!("({})", text.trim()),
+    }
+}
+
+/// Maps every character of a script to its unicode superscript or subscript form, if they all have one
+fn script(text: &str, map: fn(char) -> Option<char>) -> Option<String> {
+    text.chars().filter(|c| !c.is_whitespace()).map(map).collect()
+}
+
+/// Converts an expression into a form which can be read aloud, eg: `x^2` becomes "x squared"
+pub fn to_speech(node: &MathNode) -> String {
+    speak(node).trim_end_matches(',').to_string()
+}
+
+fn speak(node: &MathNode) -> String {
+    let speech = match node {
+        MathNode::Row(nodes) => nodes.iter().map(speak).collect::<Vec<String>>().join(" "),
+        MathNode::Ident(s) => symbols::speak(s),
+        MathNode::Number(s) | MathNode::Text(s) => s.clone(),
+        MathNode::Function(name) => symbols::speak_function(name),
+        MathNode::Operator(s, _) => symbols::speak(s),
+        MathNode::LargeOp(s) => format!("the {}", symbols::speak(s)),
+        MathNode::Frac(num, den) => format!("the fraction {} over {},", speak(num), speak(den)),
+        MathNode::Sqrt(None, body) => format!("the square root of {},", speak(body)),
+        MathNode::Sqrt(Some(index), body) => match to_unicode(index).as_str() {
+            "3" => format!("the cube root of {},", speak(body)),
+            _ => format!("the root {} of {},", speak(index), speak(body)),
+        },
+        MathNode::Scripts { base, sub, sup } => {
+            let mut out = speak(base);
+            let limit = matches!(base.as_ref(), MathNode::Function(f) if f == "lim");
+            let limits = limit || matches!(base.as_ref(), MathNode::LargeOp(_));
+
+            if let Some(sub) = sub {
+                out += &match (limit, limits) {
+                    (true, _) => format!(" as {}", speak(sub)),
+                    (false, true) => format!(" from {}", speak(sub)),
+                    _ => format!(" sub {}", speak(sub)),
+                };
+            }
+
+            if let Some(sup) = sup {
+                out += &match (limits, to_unicode(sup).as_str()) {
+                    (true, _) => format!(" to {} of", speak(sup)),
+                    (false, "2") => " squared".to_string(),
+                    (false, "3") => " cubed".to_string(),
+                    (false, "′") => " prime".to_string(),
+                    _ => format!(" to the power of {},", speak(sup)),
+                };
+            } else if limits && sub.is_some() {
+                out += " of";
+            }
+            out
+        }
+        MathNode::Fenced(open, inner, close) => format!("{} {} {}", symbols::speak(open), speak(inner), symbols::speak(close)),
+        MathNode::Accent(accent, base) => format!("{} {}", speak(base), symbols::speak_accent(accent)),
+        MathNode::Space(_) => String::new(),
+    };
+
+    speech.split_whitespace().collect::<Vec<&str>>().join(" ").replace(" ,", ",").replace(",,", ",")
+}
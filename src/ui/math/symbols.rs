@@ -0,0 +1,157 @@
+/// A symbol produced by a LaTeX command, how each is read aloud is looked up by `speak`
+#[derive(Debug, Clone, Copy)]
+pub enum Symbol {
+    Letter(char),
+    /// An operator or relation, and whether it is surrounded by space
+    Operator(char, bool),
+    /// A large operator which takes limits, eg: `\sum`
+    Large(char),
+}
+
+const LETTERS: [(&str, char, &str); 43] = [
+    ("alpha", 'α', "alpha"), ("beta", 'β', "beta"), ("gamma", 'γ', "gamma"), ("delta", 'δ', "delta"),
+    ("epsilon", 'ϵ', "epsilon"), ("varepsilon", 'ε', "epsilon"), ("zeta", 'ζ', "zeta"), ("eta", 'η', "eta"),
+    ("theta", 'θ', "theta"), ("vartheta", 'ϑ', "theta"), ("iota", 'ι', "iota"), ("kappa", 'κ', "kappa"),
+    ("lambda", 'λ', "lambda"), ("mu", 'μ', "mu"), ("nu", 'ν', "nu"), ("xi", 'ξ', "xi"),
+    ("pi", 'π', "pi"), ("varpi", 'ϖ', "pi"), ("rho", 'ρ', "rho"), ("varrho", 'ϱ', "rho"),
+    ("sigma", 'σ', "sigma"), ("varsigma", 'ς', "sigma"), ("tau", 'τ', "tau"), ("upsilon", 'υ', "upsilon"),
+    ("phi", 'ϕ', "phi"), ("varphi", 'φ', "phi"), ("chi", 'χ', "chi"), ("psi", 'ψ', "psi"),
+    ("omega", 'ω', "omega"), ("Gamma", 'Γ', "capital gamma"), ("Delta", 'Δ', "capital delta"), ("Theta", 'Θ', "capital theta"),
+    ("Lambda", 'Λ', "capital lambda"), ("Xi", 'Ξ', "capital xi"), ("Pi", 'Π', "capital pi"), ("Sigma", 'Σ', "capital sigma"),
+    ("Upsilon", 'Υ', "capital upsilon"), ("Phi", 'Φ', "capital phi"), ("Psi", 'Ψ', "capital psi"), ("Omega", 'Ω', "capital omega"),
+    ("ell", 'ℓ', "ell"), ("hbar", 'ℏ', "h bar"), ("partial", '∂', "partial"),
+];
+
+const OPERATORS: [(&str, char, &str, bool); 52] = [
+    ("times", '×', "times", true), ("cdot", '⋅', "times", true), ("div", '÷', "divided by", true),
+    ("pm", '±', "plus or minus", true), ("mp", '∓', "minus or plus", true), ("ast", '∗', "times", true),
+    ("leq", '≤', "is less than or equal to", true), ("le", '≤', "is less than or equal to", true),
+    ("geq", '≥', "is greater than or equal to", true), ("ge", '≥', "is greater than or equal to", true),
+    ("neq", '≠', "is not equal to", true), ("ne", '≠', "is not equal to", true),
+    ("approx", '≈', "is approximately", true), ("equiv", '≡', "is equivalent to", true),
+    ("sim", '∼', "is similar to", true), ("simeq", '≃', "is similar to", true), ("cong", '≅', "is congruent to", true),
+    ("propto", '∝', "is proportional to", true), ("ll", '≪', "is much less than", true), ("gg", '≫', "is much greater than", true),
+    ("in", '∈', "in", true), ("notin", '∉', "not in", true), ("ni", '∋', "contains", true),
+    ("subset", '⊂', "is a subset of", true), ("subseteq", '⊆', "is a subset of or equal to", true),
+    ("supset", '⊃', "is a superset of", true), ("supseteq", '⊇', "is a superset of or equal to", true),
+    ("cup", '∪', "union", true), ("cap", '∩', "intersection", true), ("setminus", '∖', "minus", true),
+    ("wedge", '∧', "and", true), ("land", '∧', "and", true), ("vee", '∨', "or", true), ("lor", '∨', "or", true),
+    ("to", '→', "tends to", true), ("rightarrow", '→', "tends to", true), ("leftarrow", '←', "from", true),
+    ("mapsto", '↦', "maps to", true), ("Rightarrow", '⇒', "implies", true), ("implies", '⇒', "implies", true),
+    ("Leftarrow", '⇐', "is implied by", true), ("Leftrightarrow", '⇔', "if and only if", true), ("iff", '⇔', "if and only if", true),
+    ("infty", '∞', "infinity", false), ("nabla", '∇', "nabla", false), ("forall", '∀', "for all", false),
+    ("exists", '∃', "there exists", false), ("emptyset", '∅', "the empty set", false), ("neg", '¬', "not", false),
+    ("cdots", '⋯', "dot dot dot", false), ("ldots", '…', "dot dot dot", false), ("dots", '…', "dot dot dot", false),
+];
+
+const LARGE: [(&str, char, &str); 9] = [
+    ("sum", '∑', "sum"), ("prod", '∏', "product"), ("coprod", '∐', "coproduct"),
+    ("int", '∫', "integral"), ("iint", '∬', "double integral"), ("iiint", '∭', "triple integral"), ("oint", '∮', "contour integral"),
+    ("bigcup", '⋃', "union"), ("bigcap", '⋂', "intersection"),
+];
+
+const FUNCTIONS: [&str; 22] = [
+    "sin", "cos", "tan", "sec", "csc", "cot", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd",
+];
+
+/// Accents, the symbol drawn above the base and how it is read aloud
+const ACCENTS: [(&str, &str, char, &str); 7] = [
+    ("hat", "^", '\u{0302}', "hat"), ("bar", "¯", '\u{0304}', "bar"), ("overline", "¯", '\u{0305}', "bar"),
+    ("vec", "→", '\u{20D7}', "vector"), ("dot", "˙", '\u{0307}', "dot"), ("ddot", "¨", '\u{0308}', "double dot"),
+    ("tilde", "~", '\u{0303}', "tilde"),
+];
+
+pub fn lookup(name: &str) -> Option<Symbol> {
+    LETTERS.iter().find(|(n, ..)| *n == name).map(|(_, c, _)| Symbol::Letter(*c))
+        .or_else(|| OPERATORS.iter().find(|(n, ..)| *n == name).map(|(_, c, _, spaced)| Symbol::Operator(*c, *spaced)))
+        .or_else(|| LARGE.iter().find(|(n, ..)| *n == name).map(|(_, c, _)| Symbol::Large(*c)))
+}
+
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name)
+}
+
+/// Returns the symbol drawn above the base for an accent command
+pub fn accent(name: &str) -> Option<&'static str> {
+    ACCENTS.iter().find(|(n, ..)| *n == name).map(|(_, symbol, ..)| *symbol)
+}
+
+/// Returns the combining character for an accent, used when the accent is written inline
+pub fn combining(accent: &str) -> char {
+    ACCENTS.iter().find(|(_, symbol, ..)| *symbol == accent).map(|(_, _, c, _)| *c).unwrap_or('\u{0302}')
+}
+
+pub fn speak_accent(accent: &str) -> &'static str {
+    ACCENTS.iter().find(|(_, symbol, ..)| *symbol == accent).map(|(.., speech)| *speech).unwrap_or_default()
+}
+
+/// Reads aloud a single symbol, falling back to the symbol itself
+pub fn speak(symbol: &str) -> String {
+    let mut chars = symbol.chars();
+    let (Some(c), None) = (chars.next(), chars.next()) else {
+        return symbol.to_string();
+    };
+
+    let speech = match c {
+        '+' => "plus",
+        '−' => "minus",
+        '=' => "equals",
+        '<' => "is less than",
+        '>' => "is greater than",
+        '/' => "over",
+        '!' => "factorial",
+        '′' => "prime",
+        '∗' => "times",
+        '(' | '[' | '{' => "open bracket",
+        ')' | ']' | '}' => "close bracket",
+        '|' => "bar",
+        _ => {
+            return LETTERS.iter().find(|(_, l, _)| *l == c).map(|(.., s)| *s)
+                .or_else(|| OPERATORS.iter().find(|(_, o, ..)| *o == c).map(|(_, _, s, _)| *s))
+                .or_else(|| LARGE.iter().find(|(_, l, _)| *l == c).map(|(.., s)| *s))
+                .unwrap_or(symbol)
+                .to_string()
+        }
+    };
+
+    speech.to_string()
+}
+
+/// Reads aloud a named function, eg: `\ln` is read as "the natural log of"
+pub fn speak_function(name: &str) -> String {
+    match name {
+        "sin" => "sine of".to_string(),
+        "cos" => "cosine of".to_string(),
+        "tan" => "tangent of".to_string(),
+        "log" => "log of".to_string(),
+        "ln" => "the natural log of".to_string(),
+        "exp" => "the exponential of".to_string(),
+        "lim" => "the limit".to_string(),
+        "max" => "the maximum of".to_string(),
+        "min" => "the minimum of".to_string(),
+        "det" => "the determinant of".to_string(),
+        other => other.to_string(),
+    }
+}
+
+pub fn superscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '⁰', '1' => '¹', '2' => '²', '3' => '³', '4' => '⁴',
+        '5' => '⁵', '6' => '⁶', '7' => '⁷', '8' => '⁸', '9' => '⁹',
+        '+' => '⁺', '−' | '-' => '⁻', '=' => '⁼', '(' => '⁽', ')' => '⁾',
+        'n' => 'ⁿ', 'i' => 'ⁱ', 'x' => 'ˣ', 'y' => 'ʸ', 'a' => 'ᵃ', 'b' => 'ᵇ', 'k' => 'ᵏ', 'T' => 'ᵀ',
+        '′' => '′', '∗' => '*',
+        _ => return None,
+    })
+}
+
+pub fn subscript(c: char) -> Option<char> {
+    Some(match c {
+        '0' => '₀', '1' => '₁', '2' => '₂', '3' => '₃', '4' => '₄',
+        '5' => '₅', '6' => '₆', '7' => '₇', '8' => '₈', '9' => '₉',
+        '+' => '₊', '−' | '-' => '₋', '=' => '₌', '(' => '₍', ')' => '₎',
+        'a' => 'ₐ', 'e' => 'ₑ', 'i' => 'ᵢ', 'j' => 'ⱼ', 'k' => 'ₖ', 'n' => 'ₙ', 'o' => 'ₒ', 'x' => 'ₓ', 't' => 'ₜ',
+        _ => return None,
+    })
+}
@@ -0,0 +1,137 @@
+//! Tests for parsing LaTeX, and for the unicode, speech and SVG forms it is rendered into.
+
+use super::*;
+use super::MathNode::*;
+
+fn ident(s: &str) -> Box<MathNode> {
+    Box::new(Ident(s.to_string()))
+}
+
+fn number(s: &str) -> Box<MathNode> {
+    Box::new(Number(s.to_string()))
+}
+
+/// Reads the size of an SVG document from its `width` and `height` attributes
+fn size(svg: &str) -> (f32, f32) {
+    let attribute = |name: &str| {
+        let start = svg.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+        svg[start..].split('"').next().unwrap().parse::<f32>().unwrap()
+    };
+    (attribute("width"), attribute("height"))
+}
+
+#[test]
+fn parses_fractions() {
+    assert_eq!(parse(r"\frac{a}{b}").unwrap(), Frac(ident("a"), ident("b")));
+    assert_eq!(parse(r"\dfrac12").unwrap(), Frac(number("1"), number("2")));
+    assert_eq!(
+        parse(r"\frac{a+b}{c}").unwrap(),
+        Frac(Box::new(Row(vec![Ident("a".into()), Operator("+".into(), true), Ident("b".into())])), ident("c"))
+    );
+}
+
+#[test]
+fn parses_scripts() {
+    assert_eq!(parse("x^2").unwrap(), Scripts { base: ident("x"), sub: None, sup: Some(number("2")) });
+    assert_eq!(parse("x_i^2").unwrap(), Scripts { base: ident("x"), sub: Some(ident("i")), sup: Some(number("2")) });
+    assert_eq!(parse("x^{2}_{i}").unwrap(), parse("x_i^2").unwrap());
+    // A bare script is a single character
+    assert_eq!(parse("x^10").unwrap(), Row(vec![Scripts { base: ident("x"), sub: None, sup: Some(number("1")) }, Number("0".into())]));
+    assert_eq!(parse("f'").unwrap(), Scripts { base: ident("f"), sub: None, sup: Some(Box::new(Operator("′".into(), false))) });
+    assert!(matches!(parse(r"\sum_{i=1}^n").unwrap(), Scripts { base, .. } if *base == LargeOp("∑".into())));
+}
+
+#[test]
+fn parses_commands() {
+    assert_eq!(parse(r"\alpha").unwrap(), Ident("α".into()));
+    assert_eq!(parse(r"\sin").unwrap(), Function("sin".into()));
+    assert_eq!(parse(r"\text{if } x").unwrap(), Row(vec![Text("if ".into()), Ident("x".into())]));
+    assert_eq!(parse(r"\sqrt[3]{x}").unwrap(), Sqrt(Some(number("3")), ident("x")));
+    assert_eq!(parse(r"\hat x").unwrap(), Accent("^".into(), ident("x")));
+    assert_eq!(parse(r"\left( x \right]").unwrap(), Fenced("(".into(), ident("x"), "]".into()));
+    // A leading minus is a sign rather than an operation
+    assert_eq!(parse("-b").unwrap(), Row(vec![Operator("−".into(), false), Ident("b".into())]));
+}
+
+#[test]
+fn rejects_unknown_commands() {
+    assert!(matches!(parse(r"\foo"), Err(MathError::UnknownCommand(name)) if name == "foo"));
+    assert!(matches!(parse(r"x + \frac{\nope}{2}"), Err(MathError::UnknownCommand(name)) if name == "nope"));
+    assert!(matches!(parse(r"\left\foo x \right)"), Err(MathError::UnknownCommand(_))));
+}
+
+#[test]
+fn rejects_unbalanced_braces() {
+    assert!(matches!(parse(r"\frac{a}{b"), Err(MathError::Unclosed("brace"))));
+    assert!(matches!(parse("{x"), Err(MathError::Unclosed("brace"))));
+    assert!(matches!(parse(r"\text{x"), Err(MathError::Unclosed("brace"))));
+    assert!(matches!(parse("x}"), Err(MathError::Unexpected(token)) if token == "}"));
+    assert!(matches!(parse(r"\sqrt[3{x}"), Err(MathError::Unclosed("bracket"))));
+    assert!(matches!(parse(r"\left( x"), Err(MathError::Unclosed("\\right"))));
+    assert!(matches!(parse(r"x \right)"), Err(MathError::Unexpected(_))));
+}
+
+#[test]
+fn rejects_missing_arguments() {
+    assert!(matches!(parse(r"\frac{a}"), Err(MathError::MissingArgument(command)) if command == "frac"));
+    assert!(matches!(parse("x^"), Err(MathError::MissingArgument(command)) if command == "^"));
+    assert!(matches!(parse(r"{x_}"), Err(MathError::MissingArgument(_))));
+    assert!(matches!(parse(r"\text"), Err(MathError::MissingArgument(_))));
+}
+
+#[test]
+fn converts_to_unicode() {
+    let unicode = |source: &str| to_unicode(&parse(source).unwrap());
+
+    assert_eq!(unicode(r"x^2 + y_1"), "x² + y₁");
+    assert_eq!(unicode(r"\frac{a+b}{c}"), "(a + b)/c");
+    assert_eq!(unicode(r"\sqrt{x}"), "√x");
+    assert_eq!(unicode(r"\sqrt[3]{x}"), "∛x");
+    assert_eq!(unicode(r"x^{n+1}"), "xⁿ⁺¹");
+    assert_eq!(unicode(r"x^{\alpha}"), "x^(α)");
+}
+
+#[test]
+fn converts_to_speech() {
+    let speech = |source: &str| to_speech(&parse(source).unwrap());
+
+    assert_eq!(speech("x^2"), "x squared");
+    assert_eq!(speech(r"\frac{1}{2}"), "the fraction 1 over 2");
+    assert_eq!(speech(r"\sqrt{x}"), "the square root of x");
+    assert_eq!(speech("x_i"), "x sub i");
+}
+
+#[test]
+fn lays_out_fractions_taller_than_their_parts() {
+    let (_, line) = size(&to_svg(&parse("a").unwrap()));
+    let svg = to_svg(&parse(r"\frac{a}{b}").unwrap());
+    let (_, fraction) = size(&svg);
+
+    assert!(fraction > line * 1.5, "{} is not taller than {}", fraction, line);
+    assert!(svg.contains("<line"));
+}
+
+#[test]
+fn lays_out_scripts_smaller_than_their_base() {
+    let svg = to_svg(&parse("x^2").unwrap());
+    let sizes: Vec<f32> = svg.match_indices("font-size=\"")
+        .map(|(i, _)| svg[i + 11..].split('"').next().unwrap().parse().unwrap())
+        .collect();
+
+    assert_eq!(sizes.len(), 2);
+    assert!(sizes[1] < sizes[0]);
+    assert!(size(&svg).0 > size(&to_svg(&parse("x").unwrap())).0);
+}
+
+#[test]
+fn lays_out_every_node() {
+    let svg = to_svg(&parse(r"\sum_{i=1}^{n} \int_0^1 \left( \hat{x}_i \cdot \sqrt[3]{\frac{a}{b}} \right) \, \text{d}x \quad \lim_{x \to 0}").unwrap());
+
+    assert!(svg.starts_with("<svg"));
+    assert!(!svg.contains("NaN") && !svg.contains("inf"));
+}
+
+#[test]
+fn escapes_text() {
+    assert!(to_svg(&parse(r"a < b").unwrap()).contains("&lt;"));
+}
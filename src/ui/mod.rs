@@ -1 +1,3 @@
-pub mod theme;
\ No newline at end of file
+pub mod theme;
+pub mod diagrams;
+pub mod math;
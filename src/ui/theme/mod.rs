@@ -2,35 +2,37 @@ use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use lightningcss::{
-    bundler::{Bundler, FileProvider},
+    bundler::{Bundler, FileProvider, SourceProvider},
     stylesheet::ParserOptions
 };
-use lightningcss::declaration::DeclarationBlock;
 use lightningcss::printer::PrinterOptions;
-use lightningcss::selector::Selector;
 use lightningcss::stylesheet::StyleSheet;
-use cssparser::Parser;
+use crate::config::appearance::ThemeVariant;
 
 #[derive(Debug)]
-pub struct ThemeManager<'t> {
-    pub active: &'t str,
-    pub themes: BTreeMap<&'t str, Theme<'t>>
+pub struct ThemeManager {
+    pub active: String,
+    pub themes: BTreeMap<String, Theme>,
+    /// The theme the user chose, which becomes active once it is registered, eg: by a plugin which loads later
+    preferred: Option<String>
 }
 
-impl <'t> ThemeManager<'t> {
-    pub fn new() -> ThemeManager<'t> {
+impl ThemeManager {
+    pub fn new() -> ThemeManager {
         let mut tm = ThemeManager {
             active:  match dark_light::detect().unwrap_or(dark_light::Mode::Light) {
                 dark_light::Mode::Dark => "Noot Dark",
                 dark_light::Mode::Light => "Noot Light",
                 dark_light::Mode::Unspecified => "Noot Light",
-            },
-            themes: BTreeMap::new()
+            }.to_string(),
+            themes: BTreeMap::new(),
+            preferred: None
         };
 
         // Load default built in themes
-        let day = Theme::from_bytes("Noot Dark", include_bytes!("../../../themes/compiled/light.css"));
+        let mut day = Theme::from_bytes("Noot Light", include_bytes!("../../../themes/compiled/light.css"));
         let night = Theme::from_bytes("Noot Dark", include_bytes!("../../../themes/compiled/dark.css"));
+        day.variant = ThemeVariant::Light;
 
         tm.add_theme(day);
         tm.add_theme(night);
@@ -38,46 +40,97 @@ impl <'t> ThemeManager<'t> {
         tm
     }
 
-    pub fn add_theme(&mut self, theme: Theme<'t>) {
+    pub fn add_theme(&mut self, theme: Theme) {
         info!("Importing theme '{}'", theme.name);
-        self.themes.insert(theme.name, theme);
+        if self.preferred.as_deref() == Some(theme.name.as_str()) {
+            self.active = theme.name.clone();
+        }
+
+        self.themes.insert(theme.name.clone(), theme);
+    }
+
+    /// Removes the themes a plugin registered, eg: before it is reloaded.
+    /// If the active theme is removed, the built in theme of the same variant is used until it is registered again
+    pub fn remove_plugin_themes(&mut self, plugin: &str) {
+        let variant = self.variant();
+        self.themes.retain(|_, theme| theme.plugin.as_deref() != Some(plugin));
+
+        if !self.themes.contains_key(&self.active) {
+            self.active = match variant {
+                ThemeVariant::Light => "Noot Light",
+                _ => "Noot Dark",
+            }.to_string();
+        }
+    }
+
+    /// Lists the names of every theme which can be chosen
+    pub fn names(&self) -> Vec<String> {
+        self.themes.keys().map(|name| name.to_string()).collect()
+    }
+
+    /// Chooses the theme to use, which is remembered so that it is used as soon as it is registered if it is not yet
+    pub fn select(&mut self, name: &str) {
+        self.preferred = Some(name.to_string());
+        if self.themes.contains_key(name) {
+            self.active = name.to_string();
+        }
+    }
+
+    /// Returns whether the active theme is light or dark
+    pub fn variant(&self) -> ThemeVariant {
+        self.themes.get(&self.active).map_or(ThemeVariant::Dark, |theme| theme.variant.clone())
     }
 }
 
 #[derive(Debug)]
-pub struct Theme<'t> {
-    name: &'t str,
-    internal: StyleSheet<'t, 't>
+pub struct Theme {
+    name: String,
+    pub variant: ThemeVariant,
+    /// The plugin which registered the theme, if it is not built in
+    pub plugin: Option<String>
 }
 
 
 
-impl Theme<'_> {
+impl Theme {
     pub fn from_css(name: &str, file: PathBuf) -> Theme {
-        Theme {
-            name,
-            internal: StyleSheet::parse(Bundler::new(&FileProvider::new(), None, ParserOptions::default()).bundle(file.as_path()).unwrap().to_css(PrinterOptions::default()).unwrap().code.leak(), ParserOptions::default()).unwrap(),
-        }
+        Self::bundle(name, ThemeVariant::Dark, &FileProvider::new(), file.as_path()).unwrap()
     }
 
-    pub fn from_bytes<'a>(name: &'a str, data: &'a [u8]) -> Theme<'a> {
+    /// Bundles a theme and every file it `@import`s into a single stylesheet, reading them from `files`
+    pub fn bundle<P: SourceProvider>(name: &str, variant: ThemeVariant, files: &P, entry: &Path) -> Result<Theme, String> {
+        let css = Bundler::new(files, None, ParserOptions::default())
+            .bundle(entry)
+            .map_err(|e| e.to_string())?
+            .to_css(PrinterOptions::default())
+            .map_err(|e| e.to_string())?
+            .code;
+
+        // Checked here, so that a theme which cannot be parsed is never registered
+        StyleSheet::parse(&css, ParserOptions::default()).map_err(|e| e.to_string())?;
+
+        Ok(Theme {
+            name: name.to_string(),
+            variant,
+            plugin: None,
+        })
+    }
+
+    pub fn from_bytes(name: &str, data: &[u8]) -> Theme {
+        StyleSheet::parse(&String::from_utf8_lossy(data), ParserOptions::default()).unwrap();
+
         Theme {
-            name,
-            internal: StyleSheet::parse(String::from_utf8_lossy(data).to_string().leak(), ParserOptions::default()).unwrap(),
+            name: name.to_string(),
+            variant: ThemeVariant::Dark,
+            plugin: None,
         }
     }
 
     pub fn name(&self) -> &str {
-        self.name
-    }
-
-    pub fn evaluate<S: Into<String>>(&self, selector: S) {
-        let p = Parser::new()
-        let selector = Selector::parse(&Parser::new(), selector).unwrap();
-        dbg!(&self.internal.rules);
+        &self.name
     }
 }
 
 lazy_static!(
-    pub static ref THEMES: Mutex<ThemeManager<'static>> = Mutex::new(ThemeManager::new());
+    pub static ref THEMES: Mutex<ThemeManager> = Mutex::new(ThemeManager::new());
 );
\ No newline at end of file
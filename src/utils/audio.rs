@@ -0,0 +1,278 @@
+use std::fmt::{Display, Formatter};
+use std::io::{self, Write};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+use crate::consts::APP_NAME;
+
+/// How many samples are stored in each FLAC frame of an Ogg file
+const BLOCK_SIZE: usize = 4096;
+/// Identifies the single logical stream within exported Ogg files
+const OGG_SERIAL: u32 = 0x6e6f6f74;
+
+/// The file formats which audio can be exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+    #[default]
+    Wav,
+    /// Lossless FLAC audio within an Ogg container, as there is no Vorbis encoder available
+    Ogg,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 2] = [AudioFormat::Wav, AudioFormat::Ogg];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Ogg => "ogg",
+        }
+    }
+}
+
+impl Display for AudioFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AudioFormat::Wav => write!(f, "WAV"),
+            AudioFormat::Ogg => write!(f, "Ogg"),
+        }
+    }
+}
+
+/// Mono audio, with samples between -1 and 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+/// Marks where a chapter of some audio begins, eg: at a heading of a note
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub title: String,
+    /// The index of the first sample of the chapter
+    pub sample: usize,
+}
+
+impl Audio {
+    pub fn new(sample_rate: u32) -> Self {
+        Self { samples: vec![], sample_rate }
+    }
+
+    /// Returns how long the audio lasts, in seconds
+    pub fn duration(&self) -> f32 {
+        self.samples.len() as f32 / self.sample_rate as f32
+    }
+
+    /// Adds some audio on to the end, converting its sample rate to match if needed
+    pub fn append(&mut self, other: &Audio) {
+        if other.sample_rate == self.sample_rate {
+            self.samples.extend_from_slice(&other.samples);
+            return;
+        }
+
+        // Voices generate speech at a single rate, so simple linear interpolation is enough here
+        let ratio = other.sample_rate as f64 / self.sample_rate as f64;
+        let length = (other.samples.len() as f64 / ratio) as usize;
+        self.samples.extend((0..length).map(|i| {
+            let position = i as f64 * ratio;
+            let index = position as usize;
+            let next = other.samples.get(index + 1).unwrap_or(&other.samples[index]);
+            let fraction = (position - index as f64) as f32;
+            other.samples[index] * (1. - fraction) + next * fraction
+        }));
+    }
+
+    /// Returns the samples as signed 16 bit integers, which both exported formats are written with
+    fn pcm(&self) -> impl Iterator<Item = i16> + '_ {
+        self.samples.iter().map(|s| (s.clamp(-1., 1.) * i16::MAX as f32) as i16)
+    }
+
+    /// Writes the audio as a 16 bit WAV file.
+    /// The markers are written as cue points, labelled with the chapter titles
+    pub fn write_wav<W: Write>(&self, mut out: W, markers: &[Marker]) -> io::Result<()> {
+        let mut format = vec![];
+        format.extend(1u16.to_le_bytes()); // PCM
+        format.extend(1u16.to_le_bytes()); // Mono
+        format.extend(self.sample_rate.to_le_bytes());
+        format.extend((self.sample_rate * 2).to_le_bytes());
+        format.extend(2u16.to_le_bytes());
+        format.extend(16u16.to_le_bytes());
+
+        let data: Vec<u8> = self.pcm().flat_map(|s| s.to_le_bytes()).collect();
+
+        let mut cues = vec![];
+        cues.extend((markers.len() as u32).to_le_bytes());
+        for (id, marker) in markers.iter().enumerate() {
+            cues.extend((id as u32 + 1).to_le_bytes());
+            cues.extend((marker.sample as u32).to_le_bytes());
+            cues.extend(b"data");
+            cues.extend(0u32.to_le_bytes());
+            cues.extend(0u32.to_le_bytes());
+            cues.extend((marker.sample as u32).to_le_bytes());
+        }
+
+        let mut labels = b"adtl".to_vec();
+        for (id, marker) in markers.iter().enumerate() {
+            let mut label = (id as u32 + 1).to_le_bytes().to_vec();
+            label.extend(marker.title.as_bytes());
+            label.push(0);
+            chunk(&mut labels, b"labl", &label);
+        }
+
+        let mut riff = b"WAVE".to_vec();
+        chunk(&mut riff, b"fmt ", &format);
+        chunk(&mut riff, b"data", &data);
+        if !markers.is_empty() {
+            chunk(&mut riff, b"cue ", &cues);
+            chunk(&mut riff, b"LIST", &labels);
+        }
+
+        let mut file = vec![];
+        chunk(&mut file, b"RIFF", &riff);
+        out.write_all(&file)
+    }
+
+    /// Writes the audio as an Ogg FLAC file.
+    /// The markers are written as `CHAPTERxxx` comments, which most players show as chapters
+    pub fn write_ogg<W: Write>(&self, out: W, title: &str, markers: &[Marker]) -> io::Result<()> {
+        let mut writer = PacketWriter::new(out);
+        let total = self.samples.len();
+
+        let mut info = vec![];
+        info.extend((BLOCK_SIZE as u16).to_be_bytes());
+        info.extend((BLOCK_SIZE as u16).to_be_bytes());
+        info.extend([0; 6]); // The frame sizes are unknown
+        // 20 bits of sample rate, 3 bits of channels less one, 5 bits of bits per sample less one, then 36 bits of samples
+        let packed = (self.sample_rate as u64) << 44 | 15 << 36 | (total as u64 & 0xf_ffff_ffff);
+        info.extend(packed.to_be_bytes());
+        info.extend([0; 16]); // The MD5 signature is optional
+
+        let mut header = vec![0x7f];
+        header.extend(b"FLAC");
+        header.extend([1, 0]);
+        header.extend(1u16.to_be_bytes()); // The comments follow in a header packet of their own
+        header.extend(b"fLaC");
+        metadata_block(&mut header, 0, false, &info);
+        writer.write_packet(header.into_boxed_slice(), OGG_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+        let mut comments = vec![format!("TITLE={}", title)];
+        for (i, marker) in markers.iter().enumerate() {
+            let millis = (marker.sample as u64 * 1000) / self.sample_rate as u64;
+            comments.push(format!(
+                "CHAPTER{:03}={:02}:{:02}:{:02}.{:03}",
+                i + 1, millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000
+            ));
+            comments.push(format!("CHAPTER{:03}NAME={}", i + 1, marker.title));
+        }
+
+        let mut body = vec![];
+        vorbis_string(&mut body, APP_NAME);
+        body.extend((comments.len() as u32).to_le_bytes());
+        for comment in &comments {
+            vorbis_string(&mut body, comment);
+        }
+
+        let mut packet = vec![];
+        metadata_block(&mut packet, 4, true, &body);
+        let end = if total == 0 { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::EndPage };
+        writer.write_packet(packet.into_boxed_slice(), OGG_SERIAL, end, 0)?;
+
+        let pcm: Vec<i16> = self.pcm().collect();
+        for (number, block) in pcm.chunks(BLOCK_SIZE).enumerate() {
+            let written = (number * BLOCK_SIZE + block.len()) as u64;
+            let end = if written as usize == total { PacketWriteEndInfo::EndStream } else { PacketWriteEndInfo::NormalPacket };
+            writer.write_packet(flac_frame(number as u32, block).into_boxed_slice(), OGG_SERIAL, end, written)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends a RIFF chunk, padded to an even length
+fn chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend(id);
+    out.extend((data.len() as u32).to_le_bytes());
+    out.extend(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+/// Appends a FLAC metadata block
+fn metadata_block(out: &mut Vec<u8>, kind: u8, last: bool, data: &[u8]) {
+    out.push(if last { 0x80 | kind } else { kind });
+    out.extend(&(data.len() as u32).to_be_bytes()[1..]);
+    out.extend(data);
+}
+
+/// Appends a string prefixed with its length, as used by Vorbis comments
+fn vorbis_string(out: &mut Vec<u8>, s: &str) {
+    out.extend((s.len() as u32).to_le_bytes());
+    out.extend(s.as_bytes());
+}
+
+/// Encodes a block of mono 16 bit samples as a FLAC frame.
+/// The samples are stored verbatim, which keeps the encoder simple at the cost of compression
+fn flac_frame(number: u32, samples: &[i16]) -> Vec<u8> {
+    // Sync code with a fixed block size, then a block size stored at the end of the header, the sample rate from the
+    // stream info, a single channel and 16 bit samples
+    let mut frame = vec![0xff, 0xf8, 0x70, 0x08];
+    utf8_number(&mut frame, number);
+    frame.extend((samples.len() as u16 - 1).to_be_bytes());
+    frame.push(crc8(&frame));
+
+    // A verbatim subframe
+    frame.push(0x02);
+    for sample in samples {
+        frame.extend(sample.to_be_bytes());
+    }
+
+    let crc = crc16(&frame);
+    frame.extend(crc.to_be_bytes());
+    frame
+}
+
+/// Appends a number in the variable length encoding FLAC frame headers use, which is the same as UTF-8
+fn utf8_number(out: &mut Vec<u8>, number: u32) {
+    if number < 0x80 {
+        out.push(number as u8);
+        return;
+    }
+
+    let mut continuation = vec![];
+    let mut rest = number;
+    // Each continuation byte holds six bits, and the first byte holds fewer bits the more bytes follow it
+    while rest >= 1 << (6 - continuation.len()) {
+        continuation.push(0x80 | (rest & 0x3f) as u8);
+        rest >>= 6;
+    }
+
+    let count = continuation.len() + 1;
+    let prefix = !(0xffu8 >> count);
+    out.push(prefix | rest as u8);
+    out.extend(continuation.iter().rev());
+}
+
+/// The CRC-8 of FLAC frame headers, with the polynomial x^8 + x^2 + x + 1
+pub fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |mut crc, byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+/// The CRC-16 of FLAC frames, with the polynomial x^16 + x^15 + x^2 + 1
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |mut crc, byte| {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+        }
+        crc
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,96 @@
+//! Tests for the audio encoders, which read the written files back.
+
+use std::io::Cursor;
+use hound::WavReader;
+use ogg::reading::PacketReader;
+use super::*;
+
+fn tone() -> Audio {
+    Audio {
+        samples: (0..10_000).map(|i| (i as f32 / 10.).sin() * 0.5).collect(),
+        sample_rate: 4000,
+    }
+}
+
+fn markers() -> Vec<Marker> {
+    vec![
+        Marker { title: "Introduction".to_string(), sample: 0 },
+        Marker { title: "Design".to_string(), sample: 6000 },
+    ]
+}
+
+#[test]
+fn checksums_match_the_flac_specification() {
+    assert_eq!(crc8(b"123456789"), 0xf4);
+    assert_eq!(crc16(b"123456789"), 0xfee8);
+}
+
+#[test]
+fn frame_numbers_are_encoded_like_utf8() {
+    for number in [0, 0x7f, 0x80, 0x7ff, 0x800, 0xffff, 0x10000, 0x10ffff] {
+        let mut encoded = vec![];
+        utf8_number(&mut encoded, number);
+        assert_eq!(encoded, char::from_u32(number).unwrap().to_string().into_bytes(), "{:x}", number);
+    }
+}
+
+#[test]
+fn appended_audio_is_resampled() {
+    let mut audio = Audio::new(8000);
+    audio.append(&tone());
+
+    assert_eq!(audio.samples.len(), 20_000);
+    assert_eq!(audio.duration(), tone().duration());
+}
+
+#[test]
+fn wav_files_have_cue_points_at_chapters() {
+    let mut file = vec![];
+    tone().write_wav(&mut file, &markers()).unwrap();
+
+    let reader = WavReader::new(Cursor::new(&file)).unwrap();
+    assert_eq!(reader.spec().sample_rate, 4000);
+    assert_eq!(reader.duration(), 10_000);
+
+    let cues = file.windows(4).position(|w| w == b"cue ").unwrap();
+    assert_eq!(&file[cues + 8..cues + 12], 2u32.to_le_bytes());
+    assert_eq!(&file[cues + 40..cues + 44], 6000u32.to_le_bytes());
+    assert!(file.windows(7).any(|w| w == b"Design\0"));
+}
+
+#[test]
+fn ogg_files_have_chapter_comments_and_valid_frames() {
+    let mut file = vec![];
+    tone().write_ogg(&mut file, "Notes", &markers()).unwrap();
+
+    let mut reader = PacketReader::new(Cursor::new(file));
+    let header = reader.read_packet().unwrap().unwrap();
+    assert_eq!(&header.data[..5], b"\x7fFLAC");
+    assert_eq!(&header.data[9..13], b"fLaC");
+
+    let comments = reader.read_packet().unwrap().unwrap();
+    let comments = String::from_utf8_lossy(&comments.data);
+    assert!(comments.contains("TITLE=Notes"));
+    assert!(comments.contains("CHAPTER001=00:00:00.000"));
+    assert!(comments.contains("CHAPTER002=00:00:01.500"));
+    assert!(comments.contains("CHAPTER002NAME=Design"));
+
+    let mut samples = 0;
+    while let Some(frame) = reader.read_packet().unwrap() {
+        let data = &frame.data;
+        assert_eq!(&data[..2], [0xff, 0xf8]);
+        assert_eq!(crc16(data), 0, "The frame checksum should cancel out");
+
+        // The frame numbers here are below 128, so take a single byte
+        let header = 5;
+        let block = u16::from_be_bytes([data[header], data[header + 1]]) as usize + 1;
+        assert_eq!(crc8(&data[..header + 2]), data[header + 2]);
+        assert_eq!(data.len(), header + 3 + 1 + block * 2 + 2);
+
+        samples += block;
+        if frame.last_in_stream() {
+            assert_eq!(frame.absgp_page(), 10_000);
+        }
+    }
+    assert_eq!(samples, 10_000);
+}
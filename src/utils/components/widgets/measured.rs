@@ -0,0 +1,193 @@
+use iced::{Element, Length};
+use iced_core::{event, layout, mouse, overlay, renderer, Clipboard, Event, Layout, Rectangle, Shell, Size, Vector, Widget};
+use iced_core::widget::{tree, Operation, Tree};
+
+/// Wraps an element and reports its height whenever it is laid out at a different size,
+/// used to learn the real height of blocks which were only estimated before they were drawn
+pub struct Measured<'a, Message, Theme = iced::Theme, Renderer = iced::Renderer> {
+    content: Element<'a, Message, Theme, Renderer>,
+    /// Identifies the measured content, so that a widget state left behind by different content is not reused
+    key: usize,
+    on_measure: Box<dyn Fn(f32) -> Message + 'a>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct State {
+    key: usize,
+    height: f32,
+    reported: Option<f32>,
+}
+
+impl<'a, Message, Theme, Renderer> Measured<'a, Message, Theme, Renderer> {
+    pub fn new(content: impl Into<Element<'a, Message, Theme, Renderer>>, key: usize, on_measure: impl Fn(f32) -> Message + 'a) -> Self {
+        Self {
+            content: content.into(),
+            key,
+            on_measure: Box::new(on_measure),
+        }
+    }
+}
+
+pub fn measured<'a, Message, Theme, Renderer>(
+    content: impl Into<Element<'a, Message, Theme, Renderer>>,
+    key: usize,
+    on_measure: impl Fn(f32) -> Message + 'a,
+) -> Measured<'a, Message, Theme, Renderer> {
+    Measured::new(content, key, on_measure)
+}
+
+impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer>
+for Measured<'a, Message, Theme, Renderer>
+where
+    Renderer: iced_core::Renderer,
+{
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn layout(
+        &self,
+        tree: &mut Tree,
+        renderer: &Renderer,
+        limits: &layout::Limits,
+    ) -> layout::Node {
+        let content = self.content.as_widget().layout(&mut tree.children[0], renderer, limits);
+        tree.state.downcast_mut::<State>().height = content.size().height;
+
+        layout::Node::with_children(content.size(), vec![content])
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+        );
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State { key: self.key, ..State::default() })
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        let state = tree.state.downcast_mut::<State>();
+        if state.key != self.key {
+            *state = State { key: self.key, ..State::default() };
+        }
+
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation,
+    ) {
+        self.content.as_widget().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        viewport: &Rectangle,
+    ) -> event::Status {
+        // Every frame is preceded by a redraw request, which is the first chance to report a new layout
+        let state = tree.state.downcast_mut::<State>();
+        if state.reported != Some(state.height) {
+            state.reported = Some(state.height);
+            shell.publish((self.on_measure)(state.height));
+        }
+
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, Message, Theme, Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            translation,
+        )
+    }
+}
+
+impl<'a, Message, Theme, Renderer> From<Measured<'a, Message, Theme, Renderer>> for Element<'a, Message, Theme, Renderer>
+where
+    Message: 'a,
+    Theme: 'a,
+    Renderer: iced_core::Renderer + 'a,
+{
+    fn from(measured: Measured<'a, Message, Theme, Renderer>) -> Self {
+        Element::new(measured)
+    }
+}
@@ -2,6 +2,7 @@ use iced_core::widget::text::Catalog;
 
 pub mod status_bar;
 pub mod rich_text;
+pub mod measured;
 
 // pub mod editor;
 
@@ -0,0 +1,54 @@
+use crypto::{ChaCha20Poly1305, KeyInit, Nonce};
+use crypto::aead::{Aead, AeadCore, OsRng};
+
+/// The length (in bytes) of the nonce which is prepended to every encrypted payload.
+const NONCE_LENGTH: usize = 12;
+
+pub type EncryptionResult<T> = Result<T, EncryptionError>;
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The key was not 32 bytes long, eg: because the stored key is corrupt
+    InvalidKey,
+    /// The payload was too short to contain a nonce
+    Truncated,
+    /// The cipher rejected the payload, either because the key is wrong or the data was tampered with
+    Cipher(crypto::aead::Error),
+}
+
+/// Generates a new random key suitable for use with [`encrypt`] and [`decrypt`]
+pub fn generate_key() -> Vec<u8> {
+    ChaCha20Poly1305::generate_key(&mut OsRng).to_vec()
+}
+
+fn cipher(key: &[u8]) -> EncryptionResult<ChaCha20Poly1305> {
+    ChaCha20Poly1305::new_from_slice(key).map_err(|_| EncryptionError::InvalidKey)
+}
+
+/// Encrypts the given bytes, returning the nonce followed by the ciphertext
+pub fn encrypt(key: &[u8], data: &[u8]) -> EncryptionResult<Vec<u8>> {
+    let cipher = cipher(key)?;
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher.encrypt(&nonce, data).map_err(EncryptionError::Cipher)?;
+
+    let mut output = nonce.to_vec();
+    output.extend(ciphertext);
+
+    Ok(output)
+}
+
+/// Decrypts a payload previously produced by [`encrypt`]
+pub fn decrypt(key: &[u8], data: &[u8]) -> EncryptionResult<Vec<u8>> {
+    if data.len() < NONCE_LENGTH {
+        return Err(EncryptionError::Truncated);
+    }
+
+    let cipher = cipher(key)?;
+    let (nonce, ciphertext) = data.split_at(NONCE_LENGTH);
+
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext).map_err(EncryptionError::Cipher)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,46 @@
+//! Tests for encrypting and decrypting blob assets.
+
+use super::*;
+
+#[test]
+fn round_trips() {
+    let key = generate_key();
+    let data = b"A note about noot".to_vec();
+
+    let encrypted = encrypt(&key, &data).unwrap();
+    assert_ne!(encrypted[NONCE_LENGTH..], data[..]);
+    assert_eq!(decrypt(&key, &encrypted).unwrap(), data);
+}
+
+#[test]
+fn uses_a_new_nonce_every_time() {
+    let key = generate_key();
+    assert_ne!(encrypt(&key, b"data").unwrap(), encrypt(&key, b"data").unwrap());
+}
+
+#[test]
+fn rejects_tampered_payloads() {
+    let key = generate_key();
+    let mut encrypted = encrypt(&key, b"A note about noot").unwrap();
+    let last = encrypted.len() - 1;
+    encrypted[last] ^= 1;
+
+    assert!(matches!(decrypt(&key, &encrypted), Err(EncryptionError::Cipher(_))));
+}
+
+#[test]
+fn rejects_the_wrong_key() {
+    let encrypted = encrypt(&generate_key(), b"data").unwrap();
+    assert!(matches!(decrypt(&generate_key(), &encrypted), Err(EncryptionError::Cipher(_))));
+}
+
+#[test]
+fn rejects_truncated_payloads() {
+    assert!(matches!(decrypt(&generate_key(), &[0; NONCE_LENGTH - 1]), Err(EncryptionError::Truncated)));
+}
+
+#[test]
+fn rejects_keys_of_the_wrong_length() {
+    assert!(matches!(encrypt(&[0; 16], b"data"), Err(EncryptionError::InvalidKey)));
+    assert!(matches!(decrypt(&[0; 33], &[0; 64]), Err(EncryptionError::InvalidKey)));
+}
@@ -7,3 +7,11 @@ pub fn hash_str<S: Into<String>>(text: S) -> String {
 
     format!("{:x}", hasher.finalize())
 }
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    let mut hasher = Sha512::new();
+
+    hasher.update(bytes);
+
+    format!("{:x}", hasher.finalize())
+}
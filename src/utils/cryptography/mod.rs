@@ -1 +1,2 @@
 pub mod hashing;
+pub mod encryption;
@@ -1,3 +1,4 @@
 pub mod time;
 pub mod components;
 pub mod cryptography;
+pub mod audio;
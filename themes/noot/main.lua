@@ -1,3 +1,3 @@
 info("This is coming from the noot theme plugin")
 
---noot.register_theme("Molten Core", "Dark", "css/molten-core.css");
\ No newline at end of file
+noot.register_theme("Molten Core", "Dark", "css/molten-core.css");